@@ -0,0 +1,690 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use crypto_market_type::MarketType;
+use crypto_msg_type::MessageType;
+use crypto_rest_client::{
+    fetch_candlestick_page, BinanceInverseRestClient, BinanceLinearRestClient,
+    BinanceSpotRestClient, BitmexRestClient,
+};
+use log::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    msg::Message,
+    sink::SinkSender,
+    utils::rate_limit::{get_request_weight, Endpoint},
+};
+
+// Binance requires less than 1 hour between startTime and endTime of aggTrades
+const BINANCE_WINDOW: u64 = 3600 * 1000;
+// Max number of trades per page of BitMEX
+const BITMEX_PAGE_SIZE: usize = 1000;
+// The base time to back off after a failed request
+const BACKOFF_TIME: Duration = Duration::from_millis(500);
+
+fn checkpoint_dir() -> PathBuf {
+    if let Ok(data_dir) = std::env::var("DATA_DIR") {
+        PathBuf::from(data_dir).join("checkpoints")
+    } else {
+        std::env::temp_dir().join("checkpoints")
+    }
+}
+
+// Returns `default` if the checkpoint doesn't exist.
+fn load_checkpoint<T: DeserializeOwned>(path: &PathBuf, default: T) -> io::Result<T> {
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(default),
+        Err(err) => Err(err),
+    }
+}
+
+// Writes to a temporary file first, so that the checkpoint is never half written.
+fn save_checkpoint<T: Serialize>(path: &PathBuf, checkpoint: &T) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(serde_json::to_string(checkpoint)?.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)
+}
+
+/// Progress of a backfill, saved after every page.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Checkpoint {
+    /// Trades before this time have been sent, in milliseconds.
+    cursor: u64,
+    /// ID of the last sent trade, for exchanges with sequential trade IDs.
+    last_id: Option<u64>,
+    /// IDs of sent trades at `cursor`, for exchanges which paginate by time.
+    ids_at_cursor: Vec<String>,
+    /// Number of trades sent.
+    count: u64,
+}
+
+impl Checkpoint {
+    fn path(
+        exchange: &str,
+        market_type: MarketType,
+        symbol: &str,
+        start: u64,
+        end: u64,
+    ) -> PathBuf {
+        checkpoint_dir().join(format!(
+            "backfill.{}.{}.{}.{}-{}.json",
+            exchange, market_type, symbol, start, end
+        ))
+    }
+
+    fn load(path: &PathBuf, start: u64) -> io::Result<Self> {
+        load_checkpoint(
+            path,
+            Checkpoint {
+                cursor: start,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn save(&self, path: &PathBuf) -> io::Result<()> {
+        save_checkpoint(path, self)
+    }
+
+    // Whether a trade has been sent already.
+    fn contains(&self, trade: &Trade) -> bool {
+        match (trade.seq_id, self.last_id) {
+            (Some(seq_id), Some(last_id)) => seq_id <= last_id,
+            (Some(_), None) => false,
+            (None, _) => {
+                trade.timestamp < self.cursor
+                    || (trade.timestamp == self.cursor && self.ids_at_cursor.contains(&trade.id))
+            }
+        }
+    }
+
+    fn update(&mut self, trade: &Trade) {
+        if trade.timestamp > self.cursor {
+            self.cursor = trade.timestamp;
+            self.ids_at_cursor.clear();
+        }
+        match trade.seq_id {
+            Some(seq_id) => self.last_id = Some(seq_id),
+            None => self.ids_at_cursor.push(trade.id.clone()),
+        }
+        self.count += 1;
+    }
+
+    /// Moves past a page without new trades, returns false if there are no more trades.
+    fn skip_page(&mut self, exchange: &str, page_size: usize) -> bool {
+        match exchange {
+            // no trades within the window
+            "binance" if self.last_id.is_none() => {
+                self.cursor += BINANCE_WINDOW;
+                true
+            }
+            // a full page of trades at the same millisecond
+            "bitmex" if page_size >= BITMEX_PAGE_SIZE => {
+                self.cursor += 1;
+                self.ids_at_cursor.clear();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Progress of a candlestick backfill, saved after every page.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct CandlestickCheckpoint {
+    /// Candlesticks which begin before this time have been sent, in seconds.
+    cursor: u64,
+    /// Number of candlesticks sent.
+    count: u64,
+}
+
+impl CandlestickCheckpoint {
+    fn path(
+        exchange: &str,
+        market_type: MarketType,
+        symbol: &str,
+        interval: usize,
+        start: u64,
+        end: u64,
+    ) -> PathBuf {
+        checkpoint_dir().join(format!(
+            "backfill.{}.{}.{}.candlestick_{}.{}-{}.json",
+            exchange, market_type, symbol, interval, start, end
+        ))
+    }
+}
+
+// A trade in the shape of the websocket message of the live crawler.
+struct Trade {
+    id: String,
+    // sequential trade ID
+    seq_id: Option<u64>,
+    timestamp: u64,
+    json: String,
+}
+
+fn invalid_trade(exchange: &str, raw_trade: &Value) -> crypto_rest_client::Error {
+    crypto_rest_client::Error::new(format!("Invalid {} trade {}", exchange, raw_trade))
+}
+
+fn unsupported(exchange: &str, market_type: MarketType) -> crypto_rest_client::Error {
+    crypto_rest_client::Error::new(format!(
+        "{} {} does NOT support trade backfill",
+        exchange, market_type
+    ))
+}
+
+// Whether `fetch_page()` supports a market.
+fn is_supported(exchange: &str, market_type: MarketType) -> bool {
+    match exchange {
+        "binance" => matches!(
+            market_type,
+            MarketType::Spot
+                | MarketType::InverseSwap
+                | MarketType::InverseFuture
+                | MarketType::LinearSwap
+                | MarketType::LinearFuture
+        ),
+        "bitmex" => true,
+        _ => false,
+    }
+}
+
+fn fetch_binance_page(
+    market_type: MarketType,
+    symbol: &str,
+    checkpoint: &Checkpoint,
+    end: u64,
+) -> Result<Vec<Trade>, crypto_rest_client::Error> {
+    let (from_id, start_time, end_time) = match checkpoint.last_id {
+        Some(last_id) => (Some(last_id + 1), None, None),
+        None => (
+            None,
+            Some(checkpoint.cursor),
+            Some(end.min(checkpoint.cursor + BINANCE_WINDOW - 1)),
+        ),
+    };
+    let text = match market_type {
+        MarketType::Spot => {
+            BinanceSpotRestClient::fetch_agg_trades(symbol, from_id, start_time, end_time)
+        }
+        MarketType::InverseSwap | MarketType::InverseFuture => {
+            BinanceInverseRestClient::fetch_agg_trades(symbol, from_id, start_time, end_time)
+        }
+        MarketType::LinearSwap | MarketType::LinearFuture => {
+            BinanceLinearRestClient::fetch_agg_trades(symbol, from_id, start_time, end_time)
+        }
+        _ => Err(unsupported("binance", market_type)),
+    }?;
+    parse_binance_trades(symbol, &text)
+}
+
+// Converts aggTrades to aggTrade websocket messages.
+fn parse_binance_trades(symbol: &str, text: &str) -> Result<Vec<Trade>, crypto_rest_client::Error> {
+    let stream = format!("{}@aggTrade", symbol.to_lowercase());
+    serde_json::from_str::<Vec<Value>>(text)?
+        .into_iter()
+        .map(|mut raw_trade| {
+            let (seq_id, timestamp) = match (raw_trade["a"].as_u64(), raw_trade["T"].as_u64()) {
+                (Some(seq_id), Some(timestamp)) => (seq_id, timestamp),
+                _ => return Err(invalid_trade("binance", &raw_trade)),
+            };
+            if let Some(obj) = raw_trade.as_object_mut() {
+                obj.insert("e".to_string(), json!("aggTrade"));
+                obj.insert("E".to_string(), json!(timestamp));
+                obj.insert("s".to_string(), json!(symbol));
+            }
+            Ok(Trade {
+                id: seq_id.to_string(),
+                seq_id: Some(seq_id),
+                timestamp,
+                json: json!({ "stream": stream, "data": raw_trade }).to_string(),
+            })
+        })
+        .collect()
+}
+
+fn fetch_bitmex_page(
+    symbol: &str,
+    checkpoint: &Checkpoint,
+) -> Result<Vec<Trade>, crypto_rest_client::Error> {
+    let start_time: DateTime<Utc> = (UNIX_EPOCH + Duration::from_millis(checkpoint.cursor)).into();
+    let text = BitmexRestClient::fetch_trades(
+        symbol,
+        Some(start_time.to_rfc3339_opts(SecondsFormat::Millis, true)),
+    )?;
+    parse_bitmex_trades(&text)
+}
+
+// Converts trades to insert messages of the trade table.
+fn parse_bitmex_trades(text: &str) -> Result<Vec<Trade>, crypto_rest_client::Error> {
+    serde_json::from_str::<Vec<Value>>(text)?
+        .into_iter()
+        .map(|raw_trade| {
+            let id = raw_trade["trdMatchID"].as_str().map(|x| x.to_string());
+            let timestamp = raw_trade["timestamp"]
+                .as_str()
+                .and_then(|x| DateTime::parse_from_rfc3339(x).ok())
+                .map(|x| x.timestamp_millis() as u64);
+            match (id, timestamp) {
+                (Some(id), Some(timestamp)) => Ok(Trade {
+                    id,
+                    seq_id: None,
+                    timestamp,
+                    json: json!({ "table": "trade", "action": "insert", "data": [raw_trade] })
+                        .to_string(),
+                }),
+                _ => Err(invalid_trade("bitmex", &raw_trade)),
+            }
+        })
+        .collect()
+}
+
+fn fetch_page(
+    exchange: &str,
+    market_type: MarketType,
+    symbol: &str,
+    checkpoint: &Checkpoint,
+    end: u64,
+) -> Result<Vec<Trade>, crypto_rest_client::Error> {
+    match exchange {
+        "binance" => fetch_binance_page(market_type, symbol, checkpoint, end),
+        "bitmex" => fetch_bitmex_page(symbol, checkpoint),
+        _ => Err(unsupported(exchange, market_type)),
+    }
+}
+
+// Calls `fetch` within rate limits until it succeeds or `REST_RETRY_COUNT` is reached.
+fn fetch_retry<T, F>(
+    exchange: &str,
+    market_type: MarketType,
+    symbol: &str,
+    msg_type: MessageType,
+    mut fetch: F,
+) -> Result<T, String>
+where
+    F: FnMut() -> Result<T, crypto_rest_client::Error>,
+{
+    let retry_count = std::env::var("REST_RETRY_COUNT")
+        .unwrap_or_else(|_| "5".to_string())
+        .parse::<u32>()
+        .unwrap();
+    let endpoint = Endpoint::rest(exchange, market_type);
+    let weight = get_request_weight(exchange, market_type, msg_type);
+    let mut backoff_factor = 1;
+    for i in 0..retry_count {
+        endpoint.wait(weight);
+        match fetch() {
+            Ok(page) => return Ok(page),
+            Err(err) => {
+                if i == retry_count - 1 {
+                    return Err(format!(
+                        "Failed to backfill {} {} {} after retrying {} times, error: {}",
+                        exchange, market_type, symbol, retry_count, err
+                    ));
+                }
+                warn!(
+                    "The {}th time, {} {} {} {}",
+                    i, exchange, market_type, symbol, err
+                );
+                match err.retry_after {
+                    Some(retry_after) => endpoint.penalize(retry_after),
+                    None => std::thread::sleep(BACKOFF_TIME * backoff_factor),
+                }
+                backoff_factor *= 2;
+            }
+        }
+    }
+    Err(format!(
+        "Failed to backfill {} {} {}",
+        exchange, market_type, symbol
+    ))
+}
+
+/// Backfill historical trades of a symbol within `[start, end]`, in Unix milliseconds.
+///
+/// Pages of the RESTful API are fetched within rate limits, and every trade is sent as a
+/// `MessageType::Trade` message in the same shape as the live websocket messages, so that
+/// `crypto_msg_parser::parse_trade()` parses both.
+///
+/// Progress is saved to a checkpoint file under `$DATA_DIR/checkpoints` after every page, so
+/// a backfill which failed or was interrupted resumes where it stopped when called again with
+/// the same arguments, without sending duplicated trades. The checkpoint is removed when
+/// the backfill completes.
+///
+/// Supported exchanges are binance (spot, inverse and linear markets) and bitmex.
+///
+/// Returns the number of trades sent, including those sent before resuming.
+pub fn backfill_trades(
+    exchange: &str,
+    market_type: MarketType,
+    symbol: &str,
+    start: u64,
+    end: u64,
+    tx: impl Into<SinkSender>,
+) -> Result<u64, String> {
+    if !is_supported(exchange, market_type) {
+        return Err(unsupported(exchange, market_type).to_string());
+    }
+    let tx = tx.into();
+    let path = Checkpoint::path(exchange, market_type, symbol, start, end);
+    let mut checkpoint = Checkpoint::load(&path, start)
+        .map_err(|err| format!("Failed to load {}, error: {}", path.display(), err))?;
+    if checkpoint.count > 0 {
+        info!(
+            "Resuming the backfill of {} {} {} from {} after {} trades",
+            exchange, market_type, symbol, checkpoint.cursor, checkpoint.count
+        );
+    }
+
+    let mut finished = false;
+    while !finished && checkpoint.cursor <= end {
+        let trades = fetch_retry(exchange, market_type, symbol, MessageType::Trade, || {
+            fetch_page(exchange, market_type, symbol, &checkpoint, end)
+        })?;
+        let page_size = trades.len();
+        let mut num_new = 0;
+        for trade in trades {
+            if trade.timestamp > end {
+                finished = true;
+                break;
+            }
+            if trade.timestamp < start || checkpoint.contains(&trade) {
+                continue;
+            }
+            let message = Message::new_with_symbol(
+                exchange.to_string(),
+                market_type,
+                MessageType::Trade,
+                symbol.to_string(),
+                trade.json.clone(),
+            );
+            if tx.send(message).is_err() {
+                checkpoint
+                    .save(&path)
+                    .map_err(|err| format!("Failed to save {}, error: {}", path.display(), err))?;
+                return Err("The receiver was dropped".to_string());
+            }
+            checkpoint.update(&trade);
+            num_new += 1;
+        }
+        if num_new == 0 && !finished && !checkpoint.skip_page(exchange, page_size) {
+            finished = true;
+        }
+        checkpoint
+            .save(&path)
+            .map_err(|err| format!("Failed to save {}, error: {}", path.display(), err))?;
+    }
+    let _ = fs::remove_file(&path);
+    Ok(checkpoint.count)
+}
+
+/// Backfill historical candlesticks of a symbol which begin within `[start, end)`, in Unix
+/// milliseconds.
+///
+/// `interval` is in seconds, see `crypto_rest_client::fetch_candlesticks()` for supported
+/// exchanges and intervals, others are rejected before any request is sent. Pages are fetched
+/// within rate limits, and every candlestick is sent as a `MessageType::Candlestick` message of
+/// a JSON `CandlestickMsg`, in ascending order of `begin_time`.
+///
+/// Progress is saved to a checkpoint file under `$DATA_DIR/checkpoints` after every page, so
+/// a backfill which failed or was interrupted resumes where it stopped when called again with
+/// the same arguments, without sending duplicated candlesticks. The checkpoint is removed when
+/// the backfill completes.
+///
+/// Returns the number of candlesticks sent, including those sent before resuming.
+pub fn backfill_candlesticks(
+    exchange: &str,
+    market_type: MarketType,
+    symbol: &str,
+    interval: usize,
+    start: u64,
+    end: u64,
+    tx: impl Into<SinkSender>,
+) -> Result<u64, String> {
+    crypto_rest_client::check_candlesticks(exchange, market_type, interval)
+        .map_err(|err| err.to_string())?;
+    let tx = tx.into();
+    let path = CandlestickCheckpoint::path(exchange, market_type, symbol, interval, start, end);
+    // The RESTful APIs and begin_time are in seconds
+    let (start, end) = (start.div_ceil(1000), end.div_ceil(1000));
+    let mut checkpoint = load_checkpoint(
+        &path,
+        CandlestickCheckpoint {
+            cursor: start,
+            count: 0,
+        },
+    )
+    .map_err(|err| format!("Failed to load {}, error: {}", path.display(), err))?;
+    if checkpoint.count > 0 {
+        info!(
+            "Resuming the backfill of {} {} {} from {} after {} candlesticks",
+            exchange, market_type, symbol, checkpoint.cursor, checkpoint.count
+        );
+    }
+
+    while checkpoint.cursor < end {
+        let cursor = checkpoint.cursor;
+        let (candlesticks, next) = fetch_retry(
+            exchange,
+            market_type,
+            symbol,
+            MessageType::Candlestick,
+            || fetch_candlestick_page(exchange, market_type, symbol, interval, cursor, end),
+        )?;
+        for candlestick in candlesticks {
+            let begin_time = candlestick.begin_time as u64;
+            let message = Message::new_with_symbol(
+                exchange.to_string(),
+                market_type,
+                MessageType::Candlestick,
+                symbol.to_string(),
+                serde_json::to_string(&candlestick).unwrap(),
+            );
+            if tx.send(message).is_err() {
+                save_checkpoint(&path, &checkpoint)
+                    .map_err(|err| format!("Failed to save {}, error: {}", path.display(), err))?;
+                return Err("The receiver was dropped".to_string());
+            }
+            checkpoint.cursor = begin_time + 1;
+            checkpoint.count += 1;
+        }
+        checkpoint.cursor = next;
+        save_checkpoint(&path, &checkpoint)
+            .map_err(|err| format!("Failed to save {}, error: {}", path.display(), err))?;
+    }
+    let _ = fs::remove_file(&path);
+    Ok(checkpoint.count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        backfill_candlesticks, backfill_trades, load_checkpoint, parse_binance_trades,
+        parse_bitmex_trades, save_checkpoint, CandlestickCheckpoint, Checkpoint, Trade,
+        BINANCE_WINDOW, BITMEX_PAGE_SIZE,
+    };
+    use crypto_market_type::MarketType;
+
+    fn trade(id: &str, seq_id: Option<u64>, timestamp: u64) -> Trade {
+        Trade {
+            id: id.to_string(),
+            seq_id,
+            timestamp,
+            json: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_dedup_by_seq_id() {
+        let mut checkpoint = Checkpoint {
+            cursor: 1000,
+            ..Default::default()
+        };
+        let first = trade("7", Some(7), 1000);
+        assert!(!checkpoint.contains(&first));
+        checkpoint.update(&first);
+        assert!(checkpoint.contains(&first));
+        assert!(checkpoint.contains(&trade("6", Some(6), 1000)));
+        assert!(!checkpoint.contains(&trade("8", Some(8), 1000)));
+        assert_eq!(Some(7), checkpoint.last_id);
+        assert!(checkpoint.ids_at_cursor.is_empty());
+    }
+
+    #[test]
+    fn test_dedup_by_time() {
+        let mut checkpoint = Checkpoint {
+            cursor: 1000,
+            ..Default::default()
+        };
+        checkpoint.update(&trade("a", None, 1000));
+        checkpoint.update(&trade("b", None, 1001));
+        assert_eq!(1001, checkpoint.cursor);
+        assert_eq!(vec!["b".to_string()], checkpoint.ids_at_cursor);
+
+        // the next page starts at the cursor, inclusive
+        assert!(checkpoint.contains(&trade("a", None, 1000)));
+        assert!(checkpoint.contains(&trade("b", None, 1001)));
+        assert!(!checkpoint.contains(&trade("c", None, 1001)));
+        assert_eq!(2, checkpoint.count);
+    }
+
+    #[test]
+    fn test_skip_page() {
+        let mut checkpoint = Checkpoint {
+            cursor: 1000,
+            ..Default::default()
+        };
+        assert!(checkpoint.skip_page("binance", 0));
+        assert_eq!(1000 + BINANCE_WINDOW, checkpoint.cursor);
+        checkpoint.last_id = Some(1);
+        assert!(!checkpoint.skip_page("binance", 0));
+
+        assert!(!checkpoint.skip_page("bitmex", 10));
+        assert!(checkpoint.skip_page("bitmex", BITMEX_PAGE_SIZE));
+        assert_eq!(1001 + BINANCE_WINDOW, checkpoint.cursor);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir()
+            .join(format!("backfill-{}", rand::random::<u64>()))
+            .join("checkpoint.json");
+        assert_eq!(1000, Checkpoint::load(&path, 1000).unwrap().cursor);
+
+        let mut checkpoint = Checkpoint {
+            cursor: 1000,
+            ..Default::default()
+        };
+        checkpoint.update(&trade("a", None, 1001));
+        checkpoint.save(&path).unwrap();
+        assert_eq!(checkpoint, Checkpoint::load(&path, 0).unwrap());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_candlestick_checkpoint() {
+        let path = CandlestickCheckpoint::path(
+            &format!("test{}", rand::random::<u64>()),
+            MarketType::Spot,
+            "BTCUSDT",
+            60,
+            1660003200,
+            1660012200,
+        );
+        assert!(path
+            .to_str()
+            .unwrap()
+            .ends_with(".spot.BTCUSDT.candlestick_60.1660003200-1660012200.json"));
+        let default = CandlestickCheckpoint {
+            cursor: 1660003200,
+            count: 0,
+        };
+        let checkpoint = load_checkpoint(&path, default).unwrap();
+        assert_eq!(1660003200, checkpoint.cursor);
+
+        let checkpoint = CandlestickCheckpoint {
+            cursor: 1660009200,
+            count: 100,
+        };
+        save_checkpoint(&path, &checkpoint).unwrap();
+        assert_eq!(
+            checkpoint,
+            load_checkpoint(&path, CandlestickCheckpoint::default()).unwrap()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let (tx, _rx) = std::sync::mpsc::channel();
+        assert!(backfill_trades("okx", MarketType::Spot, "BTC-USDT", 0, 1, tx.clone()).is_err());
+        assert!(backfill_trades(
+            "binance",
+            MarketType::EuropeanOption,
+            "BTC",
+            0,
+            1,
+            tx.clone()
+        )
+        .is_err());
+        assert!(backfill_candlesticks(
+            "bitmex",
+            MarketType::InverseSwap,
+            "XBTUSD",
+            60,
+            0,
+            1,
+            tx.clone()
+        )
+        .is_err());
+        assert!(
+            backfill_candlesticks("binance", MarketType::Spot, "BTCUSDT", 7, 0, 1, tx).is_err()
+        );
+    }
+
+    #[test]
+    fn test_binance_trades() {
+        let text = r#"[{"a":26129,"p":"23950.01","q":"0.5","f":27781,"l":27782,"T":1660000000000,"m":true,"M":true}]"#;
+        let trades = parse_binance_trades("BTCUSDT", text).unwrap();
+        assert_eq!(Some(26129), trades[0].seq_id);
+        assert_eq!(1660000000000, trades[0].timestamp);
+
+        let trade_msgs =
+            crypto_msg_parser::parse_trade("binance", MarketType::Spot, &trades[0].json).unwrap();
+        assert_eq!("BTCUSDT", trade_msgs[0].symbol);
+        assert_eq!("26129", trade_msgs[0].trade_id);
+        assert_eq!(23950.01, trade_msgs[0].price);
+        assert_eq!(1660000000000, trade_msgs[0].timestamp);
+    }
+
+    #[test]
+    fn test_bitmex_trades() {
+        let text = r#"[{"timestamp":"2022-08-08T23:06:40.123Z","symbol":"XBTUSD","side":"Buy","size":100,"price":23950.5,"tickDirection":"PlusTick","trdMatchID":"6f1f4b3a-0000-0000-0000-000000000000","grossValue":417528,"homeNotional":0.00417528,"foreignNotional":100}]"#;
+        let trades = parse_bitmex_trades(text).unwrap();
+        assert_eq!(None, trades[0].seq_id);
+        assert_eq!(1660000000123, trades[0].timestamp);
+
+        let trade_msgs =
+            crypto_msg_parser::parse_trade("bitmex", MarketType::InverseSwap, &trades[0].json)
+                .unwrap();
+        assert_eq!("XBTUSD", trade_msgs[0].symbol);
+        assert_eq!(trades[0].id, trade_msgs[0].trade_id);
+        assert_eq!(1660000000123, trade_msgs[0].timestamp);
+    }
+}
@@ -1,19 +1,30 @@
 use core::panic;
-use std::sync::mpsc::Sender;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{mpsc::Sender, Arc},
+};
 
-use crate::{crawlers::utils::crawl_event, msg::Message};
+use crate::{
+    crawlers::utils::crawl_event,
+    l2_sync::{BinanceL2Synchronizer, L2SyncEvent},
+    sink::SinkSender,
+    status::{report, StatusKind},
+    utils::rate_limit::{get_request_weight, Endpoint},
+};
 use crypto_market_type::MarketType;
 use crypto_msg_type::MessageType;
+use crypto_rest_client::fetch_l2_snapshot;
 use crypto_ws_client::*;
+use log::*;
 
-use super::utils::create_conversion_thread;
+use super::utils::{backoff, connect_with_retry, create_conversion_thread};
 
 const EXCHANGE_NAME: &str = "binance";
 
 pub(crate) async fn crawl_trade(
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     if market_type == MarketType::EuropeanOption
         && (symbols.is_none() || symbols.unwrap().is_empty())
@@ -30,7 +41,13 @@ pub(crate) async fn crawl_trade(
             ("TRADE_ALL".to_string(), "BTCUSDT_P".to_string()),
         ];
 
-        let ws_client = BinanceOptionWSClient::new(tx, None).await;
+        let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+            BinanceOptionWSClient::new(tx.clone(), None)
+        })
+        .await
+        else {
+            return;
+        };
         ws_client.subscribe(&topics).await;
         ws_client.run().await;
         ws_client.close();
@@ -39,11 +56,7 @@ pub(crate) async fn crawl_trade(
     }
 }
 
-pub(crate) async fn crawl_bbo(
-    market_type: MarketType,
-    symbols: Option<&[String]>,
-    tx: Sender<Message>,
-) {
+pub(crate) async fn crawl_bbo(market_type: MarketType, symbols: Option<&[String]>, tx: SinkSender) {
     if symbols.is_none() || symbols.unwrap().is_empty() {
         let tx =
             create_conversion_thread(EXCHANGE_NAME.to_string(), MessageType::BBO, market_type, tx);
@@ -51,19 +64,37 @@ pub(crate) async fn crawl_bbo(
             vec![r#"{"id":9527,"method":"SUBSCRIBE","params":["!bookTicker"]}"#.to_string()]; // All Book Tickers Stream
         match market_type {
             MarketType::Spot => {
-                let ws_client = BinanceSpotWSClient::new(tx, None).await;
+                let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+                    BinanceSpotWSClient::new(tx.clone(), None)
+                })
+                .await
+                else {
+                    return;
+                };
                 ws_client.send(&commands).await;
                 ws_client.run().await;
                 ws_client.close();
             }
             MarketType::InverseFuture | MarketType::InverseSwap => {
-                let ws_client = BinanceInverseWSClient::new(tx, None).await;
+                let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+                    BinanceInverseWSClient::new(tx.clone(), None)
+                })
+                .await
+                else {
+                    return;
+                };
                 ws_client.send(&commands).await;
                 ws_client.run().await;
                 ws_client.close();
             }
             MarketType::LinearFuture | MarketType::LinearSwap => {
-                let ws_client = BinanceLinearWSClient::new(tx, None).await;
+                let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+                    BinanceLinearWSClient::new(tx.clone(), None)
+                })
+                .await
+                else {
+                    return;
+                };
                 ws_client.send(&commands).await;
                 ws_client.run().await;
                 ws_client.close();
@@ -81,7 +112,7 @@ pub(crate) async fn crawl_bbo(
 pub(crate) async fn crawl_ticker(
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     if symbols.is_none() || symbols.unwrap().is_empty() {
         let tx = create_conversion_thread(
@@ -95,19 +126,37 @@ pub(crate) async fn crawl_ticker(
 
         match market_type {
             MarketType::Spot => {
-                let ws_client = BinanceSpotWSClient::new(tx, None).await;
+                let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+                    BinanceSpotWSClient::new(tx.clone(), None)
+                })
+                .await
+                else {
+                    return;
+                };
                 ws_client.send(&commands).await;
                 ws_client.run().await;
                 ws_client.close();
             }
             MarketType::InverseFuture | MarketType::InverseSwap => {
-                let ws_client = BinanceInverseWSClient::new(tx, None).await;
+                let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+                    BinanceInverseWSClient::new(tx.clone(), None)
+                })
+                .await
+                else {
+                    return;
+                };
                 ws_client.send(&commands).await;
                 ws_client.run().await;
                 ws_client.close();
             }
             MarketType::LinearFuture | MarketType::LinearSwap => {
-                let ws_client = BinanceLinearWSClient::new(tx, None).await;
+                let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+                    BinanceLinearWSClient::new(tx.clone(), None)
+                })
+                .await
+                else {
+                    return;
+                };
                 ws_client.send(&commands).await;
                 ws_client.run().await;
                 ws_client.close();
@@ -117,7 +166,13 @@ pub(crate) async fn crawl_ticker(
                     r#"{"id":9527,"method":"SUBSCRIBE","params":["BTCUSDT@TICKER_ALL"]}"#
                         .to_string(),
                 ];
-                let ws_client = BinanceLinearWSClient::new(tx, None).await;
+                let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+                    BinanceLinearWSClient::new(tx.clone(), None)
+                })
+                .await
+                else {
+                    return;
+                };
                 ws_client.send(&commands).await;
                 ws_client.run().await;
                 ws_client.close();
@@ -136,7 +191,7 @@ pub(crate) async fn crawl_ticker(
 pub(crate) async fn crawl_funding_rate(
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     let tx = create_conversion_thread(
         EXCHANGE_NAME.to_string(),
@@ -144,11 +199,22 @@ pub(crate) async fn crawl_funding_rate(
         market_type,
         tx,
     );
-    let ws_client: Box<dyn WSClient + Send + Sync> = match market_type {
-        MarketType::InverseSwap => Box::new(BinanceInverseWSClient::new(tx, None).await),
-        MarketType::LinearSwap => Box::new(BinanceLinearWSClient::new(tx, None).await),
+    let ws_client: Option<Box<dyn WSClient + Send + Sync>> = match market_type {
+        MarketType::InverseSwap => connect_with_retry(EXCHANGE_NAME, market_type, || {
+            BinanceInverseWSClient::new(tx.clone(), None)
+        })
+        .await
+        .map(|ws_client| Box::new(ws_client) as Box<dyn WSClient + Send + Sync>),
+        MarketType::LinearSwap => connect_with_retry(EXCHANGE_NAME, market_type, || {
+            BinanceLinearWSClient::new(tx.clone(), None)
+        })
+        .await
+        .map(|ws_client| Box::new(ws_client) as Box<dyn WSClient + Send + Sync>),
         _ => panic!("Binance {} does NOT have funding rates", market_type),
     };
+    let Some(ws_client) = ws_client else {
+        return;
+    };
 
     if symbols.is_none() || symbols.unwrap().is_empty() {
         let commands =
@@ -166,3 +232,164 @@ pub(crate) async fn crawl_funding_rate(
     ws_client.run().await;
     ws_client.close();
 }
+
+pub(crate) async fn crawl_l2_sync(
+    market_type: MarketType,
+    symbols: &[String],
+    tx: Sender<L2SyncEvent>,
+) {
+    if symbols.is_empty() {
+        report(
+            EXCHANGE_NAME,
+            market_type,
+            Some(MessageType::L2Event),
+            StatusKind::Error,
+            "symbols must not be empty".to_string(),
+        );
+        return;
+    }
+    let mut synchronizers = symbols
+        .iter()
+        .map(|symbol| {
+            (
+                symbol.to_string(),
+                BinanceL2Synchronizer::new(market_type, symbol),
+            )
+        })
+        .collect::<HashMap<String, BinanceL2Synchronizer>>();
+
+    let (ws_tx, mut ws_rx) = tokio::sync::mpsc::channel::<String>(1024);
+    let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+        crypto_ws_client::create_ws_client(
+            EXCHANGE_NAME,
+            market_type,
+            ws_tx.clone(),
+            WSClientOptions::default(),
+        )
+    })
+    .await
+    else {
+        return;
+    };
+    let ws_client: Arc<dyn WSClient + Send + Sync> = Arc::from(ws_client);
+    drop(ws_tx);
+    // diffs are buffered by synchronizers until their snapshots arrive
+    ws_client.subscribe_orderbook(symbols).await;
+    let handle = {
+        let ws_client = ws_client.clone();
+        tokio::task::spawn(async move { ws_client.run().await })
+    };
+
+    // Snapshots are fetched one by one in the order of requests
+    let endpoint = Endpoint::rest(EXCHANGE_NAME, market_type);
+    let (request_tx, mut request_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let (snapshot_tx, mut snapshot_rx) = tokio::sync::mpsc::unbounded_channel::<(
+        String,
+        Result<String, crypto_rest_client::Error>,
+    )>();
+    {
+        let endpoint = endpoint.clone();
+        let weight = get_request_weight(EXCHANGE_NAME, market_type, MessageType::L2Snapshot);
+        tokio::task::spawn(async move {
+            while let Some(symbol) = request_rx.recv().await {
+                endpoint.wait_async(weight).await;
+                let resp = {
+                    let symbol = symbol.clone();
+                    tokio::task::spawn_blocking(move || {
+                        fetch_l2_snapshot(EXCHANGE_NAME, market_type, &symbol, Some(0))
+                    })
+                    .await
+                    .unwrap_or_else(|err| Err(crypto_rest_client::Error::new(err.to_string())))
+                };
+                if snapshot_tx.send((symbol, resp)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    // failed symbols are requested again after backing off
+    let (retry_tx, mut retry_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+
+    // symbols whose snapshots have been requested
+    let mut pending = HashSet::<String>::new();
+    // symbol -> number of consecutive failures
+    let mut failures = HashMap::<String, u32>::new();
+    'outer: loop {
+        let (symbol, result) = tokio::select! {
+            msg = ws_rx.recv() => match msg {
+                Some(msg) => {
+                    let symbol = match crypto_msg_parser::extract_symbol(EXCHANGE_NAME, market_type, &msg) {
+                        Ok(symbol) if synchronizers.contains_key(&symbol) => symbol,
+                        _ => continue, // such as responses to subscriptions
+                    };
+                    let result = synchronizers.get_mut(&symbol).unwrap().on_update(&msg);
+                    (symbol, result)
+                }
+                None => break,
+            },
+            Some((symbol, resp)) = snapshot_rx.recv() => match resp {
+                Ok(json) => {
+                    pending.remove(&symbol);
+                    failures.remove(&symbol);
+                    let result = synchronizers.get_mut(&symbol).unwrap().on_snapshot(&json);
+                    (symbol, result)
+                }
+                Err(err) => {
+                    let backoff_factor = failures.entry(symbol.clone()).or_insert(0);
+                    *backoff_factor += 1;
+                    let backoff_time = backoff(&endpoint, &err, *backoff_factor);
+                    report(
+                        EXCHANGE_NAME,
+                        market_type,
+                        Some(MessageType::L2Snapshot),
+                        StatusKind::Error,
+                        format!(
+                            "Failed to fetch the snapshot of {}, error: {}, retry in {} milliseconds",
+                            symbol,
+                            err,
+                            backoff_time.as_millis()
+                        ),
+                    );
+                    // the symbol stays pending until it is re-queued
+                    let retry_tx = retry_tx.clone();
+                    tokio::task::spawn(async move {
+                        tokio::time::sleep(backoff_time).await;
+                        let _ = retry_tx.send(symbol);
+                    });
+                    continue;
+                }
+            },
+            Some(symbol) = retry_rx.recv() => {
+                pending.remove(&symbol);
+                (symbol, Ok(Vec::new()))
+            }
+        };
+        match result {
+            Ok(events) => {
+                for event in events {
+                    if let L2SyncEvent::Resync {
+                        symbol,
+                        expected,
+                        received,
+                    } = &event
+                    {
+                        warn!(
+                            "Gap in {} {} {}, expected {}, received {}, resyncing",
+                            EXCHANGE_NAME, market_type, symbol, expected, received
+                        );
+                    }
+                    if tx.send(event).is_err() {
+                        break 'outer; // break the loop if there is no receiver
+                    }
+                }
+            }
+            Err(err) => error!("{}", err),
+        }
+        if synchronizers[&symbol].needs_snapshot() && !pending.contains(&symbol) {
+            pending.insert(symbol.clone());
+            let _ = request_tx.send(symbol);
+        }
+    }
+    ws_client.close();
+    let _ = handle.await;
+}
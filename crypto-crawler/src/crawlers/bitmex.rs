@@ -1,16 +1,15 @@
 use super::{
     crawl_candlestick_ext, crawl_event,
-    utils::{check_args, fetch_symbols_retry},
+    utils::{check_args, connect_with_retry, fetch_symbols_retry},
 };
-use crate::{crawlers::utils::create_conversion_thread, msg::Message};
+use crate::{crawlers::utils::create_conversion_thread, sink::SinkSender};
 use crypto_market_type::MarketType;
 use crypto_msg_type::MessageType;
 use crypto_ws_client::*;
-use std::sync::mpsc::Sender;
 
 const EXCHANGE_NAME: &str = "bitmex";
 
-async fn crawl_all(msg_type: MessageType, tx: Sender<Message>) {
+async fn crawl_all(msg_type: MessageType, tx: SinkSender) {
     let tx = create_conversion_thread(EXCHANGE_NAME.to_string(), msg_type, MarketType::Unknown, tx);
 
     let channel: &str = match msg_type {
@@ -24,7 +23,13 @@ async fn crawl_all(msg_type: MessageType, tx: Sender<Message>) {
     };
     let commands = vec![format!(r#"{{"op":"subscribe","args":["{}"]}}"#, channel)];
 
-    let ws_client = BitmexWSClient::new(tx, None).await;
+    let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, MarketType::Unknown, || {
+        BitmexWSClient::new(tx.clone(), None)
+    })
+    .await
+    else {
+        return;
+    };
     ws_client.send(&commands).await;
     ws_client.run().await;
     ws_client.close();
@@ -33,7 +38,7 @@ async fn crawl_all(msg_type: MessageType, tx: Sender<Message>) {
 pub(crate) async fn crawl_trade(
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     if market_type == MarketType::Unknown {
         // crawl all symbols
@@ -46,7 +51,7 @@ pub(crate) async fn crawl_trade(
 pub(crate) async fn crawl_l2_event(
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     if market_type == MarketType::Unknown {
         // crawl all symbols
@@ -63,11 +68,7 @@ pub(crate) async fn crawl_l2_event(
     }
 }
 
-pub(crate) async fn crawl_bbo(
-    market_type: MarketType,
-    symbols: Option<&[String]>,
-    tx: Sender<Message>,
-) {
+pub(crate) async fn crawl_bbo(market_type: MarketType, symbols: Option<&[String]>, tx: SinkSender) {
     if market_type == MarketType::Unknown {
         // crawl all symbols
         crawl_all(MessageType::BBO, tx).await;
@@ -79,7 +80,7 @@ pub(crate) async fn crawl_bbo(
 pub(crate) async fn crawl_l2_topk(
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     if market_type == MarketType::Unknown {
         // crawl all symbols
@@ -93,7 +94,7 @@ pub(crate) async fn crawl_l2_topk(
 pub(crate) async fn crawl_funding_rate(
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     if market_type == MarketType::Unknown {
         // crawl all symbols
@@ -133,7 +134,13 @@ pub(crate) async fn crawl_funding_rate(
 
         match market_type {
             MarketType::InverseSwap | MarketType::QuantoSwap => {
-                let ws_client = BitmexWSClient::new(tx, None).await;
+                let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+                    BitmexWSClient::new(tx.clone(), None)
+                })
+                .await
+                else {
+                    return;
+                };
                 ws_client.subscribe(&topics).await;
                 ws_client.run().await;
                 ws_client.close();
@@ -146,7 +153,7 @@ pub(crate) async fn crawl_funding_rate(
 pub(crate) async fn crawl_candlestick(
     market_type: MarketType,
     symbol_interval_list: Option<&[(String, usize)]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     if market_type == MarketType::Unknown {
         let tx = create_conversion_thread(
@@ -161,7 +168,13 @@ pub(crate) async fn crawl_candlestick(
             r#"{"op":"subscribe","args":["tradeBin5m"]}"#.to_string(),
         ];
 
-        let ws_client = BitmexWSClient::new(tx, None).await;
+        let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+            BitmexWSClient::new(tx.clone(), None)
+        })
+        .await
+        else {
+            return;
+        };
         ws_client.send(&commands).await;
         ws_client.run().await;
         ws_client.close();
@@ -1,16 +1,18 @@
 use super::crawl_event;
-use crate::{crawlers::utils::create_conversion_thread, msg::Message};
+use crate::{
+    crawlers::utils::{connect_with_retry, create_conversion_thread},
+    sink::SinkSender,
+};
 use crypto_market_type::MarketType;
 use crypto_msg_type::MessageType;
 use crypto_ws_client::*;
-use std::sync::mpsc::Sender;
 
 const EXCHANGE_NAME: &str = "deribit";
 
 pub(crate) async fn crawl_trade(
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     if symbols.is_none() || symbols.unwrap().is_empty() {
         let tx = create_conversion_thread(
@@ -43,7 +45,13 @@ pub(crate) async fn crawl_trade(
             _ => panic!("Deribit does NOT have the {} market type", market_type),
         };
 
-        let ws_client = DeribitWSClient::new(tx, None).await;
+        let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+            DeribitWSClient::new(tx.clone(), None)
+        })
+        .await
+        else {
+            return;
+        };
         ws_client.subscribe(&topics).await;
         ws_client.run().await;
         ws_client.close();
@@ -1,12 +1,11 @@
-use super::utils::fetch_symbols_retry;
+use super::utils::{connect_with_retry, fetch_symbols_retry};
 use crate::{
     crawlers::{crawl_event, utils::create_conversion_thread},
-    msg::Message,
+    sink::SinkSender,
 };
 use crypto_market_type::MarketType;
 use crypto_msg_type::MessageType;
 use crypto_ws_client::*;
-use std::sync::mpsc::Sender;
 
 const EXCHANGE_NAME: &str = "huobi";
 
@@ -14,7 +13,7 @@ const EXCHANGE_NAME: &str = "huobi";
 pub(crate) async fn crawl_l2_event(
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     match market_type {
         MarketType::Spot => {
@@ -31,7 +30,13 @@ pub(crate) async fn crawl_l2_event(
             };
             // Huobi Spot market.$symbol.mbp.$levels must use wss://api.huobi.pro/feed
             // or wss://api-aws.huobi.pro/feed
-            let ws_client = HuobiSpotWSClient::new(tx, Some("wss://api.huobi.pro/feed")).await;
+            let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+                HuobiSpotWSClient::new(tx.clone(), Some("wss://api.huobi.pro/feed"))
+            })
+            .await
+            else {
+                return;
+            };
             ws_client.subscribe_orderbook(&symbols).await;
             ws_client.run().await;
             ws_client.close();
@@ -57,7 +62,7 @@ pub(crate) async fn crawl_l2_event(
 pub(crate) async fn crawl_funding_rate(
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     let tx = create_conversion_thread(
         EXCHANGE_NAME.to_string(),
@@ -78,19 +83,31 @@ pub(crate) async fn crawl_funding_rate(
 
     match market_type {
         MarketType::InverseSwap => {
-            let ws_client =
-                HuobiInverseSwapWSClient::new(tx, Some("wss://api.hbdm.com/swap-notification"))
-                    .await;
+            let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+                HuobiInverseSwapWSClient::new(
+                    tx.clone(),
+                    Some("wss://api.hbdm.com/swap-notification"),
+                )
+            })
+            .await
+            else {
+                return;
+            };
             ws_client.send(&commands).await;
             ws_client.run().await;
             ws_client.close();
         }
         MarketType::LinearSwap => {
-            let ws_client = HuobiLinearSwapWSClient::new(
-                tx,
-                Some("wss://api.hbdm.com/linear-swap-notification"),
-            )
-            .await;
+            let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+                HuobiLinearSwapWSClient::new(
+                    tx.clone(),
+                    Some("wss://api.hbdm.com/linear-swap-notification"),
+                )
+            })
+            .await
+            else {
+                return;
+            };
             ws_client.send(&commands).await;
             ws_client.run().await;
             ws_client.close();
@@ -1,25 +1,29 @@
-use crate::{crawlers::utils::create_conversion_thread, msg::Message};
+use crate::{
+    crawlers::utils::{connect_with_retry, create_conversion_thread},
+    sink::SinkSender,
+};
 use crypto_market_type::MarketType;
 use crypto_msg_type::MessageType;
 use crypto_ws_client::*;
-use std::sync::mpsc::Sender;
 
 use super::crawl_event;
 
 const EXCHANGE_NAME: &str = "kucoin";
 
-pub(crate) async fn crawl_bbo(
-    market_type: MarketType,
-    symbols: Option<&[String]>,
-    tx: Sender<Message>,
-) {
+pub(crate) async fn crawl_bbo(market_type: MarketType, symbols: Option<&[String]>, tx: SinkSender) {
     if market_type == MarketType::Spot && (symbols.is_none() || symbols.unwrap().is_empty()) {
         let tx =
             create_conversion_thread(EXCHANGE_NAME.to_string(), MessageType::BBO, market_type, tx);
 
         // https://docs.kucoin.com/#all-symbols-ticker
         let commands: Vec<String> = vec![r#"{"id":"crypto-ws-client","type":"subscribe","topic":"/market/ticker:all","privateChannel":false,"response":true}"#.to_string()];
-        let ws_client = KuCoinSpotWSClient::new(tx, None).await;
+        let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+            KuCoinSpotWSClient::new(tx.clone(), None)
+        })
+        .await
+        else {
+            return;
+        };
         ws_client.send(&commands).await;
         ws_client.run().await;
         ws_client.close();
@@ -0,0 +1,122 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{mpsc::Sender, Arc},
+};
+
+use crate::{
+    l2_sync::{L2SyncEvent, SequenceL2Synchronizer},
+    status::{report, StatusKind},
+};
+use crypto_market_type::MarketType;
+use crypto_msg_type::MessageType;
+use crypto_ws_client::*;
+
+use super::utils::connect_with_retry;
+
+// Number of updates buffered without a snapshot before reporting it
+const MAX_UPDATES_WITHOUT_SNAPSHOT: usize = 1024;
+
+/// Whether the incremental level2 websocket channel of a market sends a snapshot on
+/// subscription, which `crawl_l2_sync()` relies on.
+pub(crate) fn has_websocket_snapshots(exchange: &str, market_type: MarketType) -> bool {
+    match exchange {
+        // The spot channel sends no snapshot
+        "huobi" => market_type != MarketType::Spot,
+        "bitfinex" | "bitget" | "bithumb" | "bitmex" | "bybit" | "coinbase_pro" | "deribit"
+        | "dydx" | "ftx" | "gate" | "kraken" | "okx" => true,
+        _ => false,
+    }
+}
+
+/// Synchronizes level2 orderbooks from websocket snapshots and updates.
+///
+/// On a gap the symbol is unsubscribed and subscribed again, for a new snapshot.
+pub(crate) async fn crawl_l2_sync(
+    exchange: &str,
+    market_type: MarketType,
+    symbols: &[String],
+    tx: Sender<L2SyncEvent>,
+) {
+    if symbols.is_empty() {
+        report(
+            exchange,
+            market_type,
+            Some(MessageType::L2Event),
+            StatusKind::Error,
+            "symbols must not be empty".to_string(),
+        );
+        return;
+    }
+    let mut synchronizers = symbols
+        .iter()
+        .map(|symbol| (symbol.to_string(), SequenceL2Synchronizer::new(symbol)))
+        .collect::<HashMap<String, SequenceL2Synchronizer>>();
+
+    let (ws_tx, mut ws_rx) = tokio::sync::mpsc::channel::<String>(1024);
+    let Some(ws_client) = connect_with_retry(exchange, market_type, || {
+        crypto_ws_client::create_ws_client(
+            exchange,
+            market_type,
+            ws_tx.clone(),
+            WSClientOptions::default(),
+        )
+    })
+    .await
+    else {
+        return;
+    };
+    let ws_client: Arc<dyn WSClient + Send + Sync> = Arc::from(ws_client);
+    drop(ws_tx);
+    ws_client.subscribe_orderbook(symbols).await;
+    let handle = {
+        let ws_client = ws_client.clone();
+        tokio::task::spawn(async move { ws_client.run().await })
+    };
+
+    // symbols reported to have no snapshot
+    let mut no_snapshot = HashSet::<String>::new();
+    'outer: while let Some(msg) = ws_rx.recv().await {
+        let orderbooks = match crypto_msg_parser::parse_l2(exchange, market_type, &msg, None) {
+            Ok(orderbooks) => orderbooks,
+            Err(_) => continue, // such as responses to subscriptions
+        };
+        for orderbook in orderbooks {
+            let symbol = orderbook.symbol.clone();
+            let synchronizer = match synchronizers.get_mut(&symbol) {
+                Some(synchronizer) => synchronizer,
+                None => continue,
+            };
+            for event in synchronizer.on_message(orderbook) {
+                if let L2SyncEvent::Resync { symbol, .. } = &event {
+                    let topics = ws_client
+                        .topics()
+                        .into_iter()
+                        .filter(|(_, s)| s.eq_ignore_ascii_case(symbol))
+                        .collect::<Vec<(String, String)>>();
+                    ws_client.unsubscribe(&topics).await;
+                    ws_client.subscribe(&topics).await;
+                }
+                if tx.send(event).is_err() {
+                    break 'outer; // break the loop if there is no receiver
+                }
+            }
+            if synchronizer.needs_snapshot()
+                && synchronizer.num_buffered() >= MAX_UPDATES_WITHOUT_SNAPSHOT
+                && no_snapshot.insert(symbol.clone())
+            {
+                report(
+                    exchange,
+                    market_type,
+                    Some(MessageType::L2Event),
+                    StatusKind::Error,
+                    format!(
+                        "No snapshot of {} after {} updates, the websocket channel may not send snapshots",
+                        symbol, MAX_UPDATES_WITHOUT_SNAPSHOT
+                    ),
+                );
+            }
+        }
+    }
+    ws_client.close();
+    let _ = handle.await;
+}
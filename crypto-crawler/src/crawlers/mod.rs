@@ -6,10 +6,13 @@ pub(super) mod bitmex;
 pub(super) mod deribit;
 pub(super) mod huobi;
 pub(super) mod kucoin;
+pub(super) mod l2_sync;
 pub(super) mod okx;
+mod subscriptions;
 pub(super) mod zb;
 pub(super) mod zbg;
 
+pub use subscriptions::{get_crawler_topics, CrawlerTopics};
 pub use utils::fetch_symbols_retry;
 pub(super) use utils::{
     crawl_candlestick_ext, crawl_event, crawl_open_interest, crawl_snapshot,
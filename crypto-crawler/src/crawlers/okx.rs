@@ -1,9 +1,8 @@
-use super::utils::fetch_symbols_retry;
-use crate::{crawlers::utils::create_conversion_thread, msg::Message};
+use super::utils::{connect_with_retry, fetch_symbols_retry};
+use crate::{crawlers::utils::create_conversion_thread, sink::SinkSender};
 use crypto_market_type::MarketType;
 use crypto_msg_type::MessageType;
 use crypto_ws_client::*;
-use std::sync::mpsc::Sender;
 
 const EXCHANGE_NAME: &str = "okx";
 
@@ -11,7 +10,7 @@ const EXCHANGE_NAME: &str = "okx";
 pub(crate) async fn crawl_funding_rate(
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     let tx = create_conversion_thread(
         EXCHANGE_NAME.to_string(),
@@ -32,7 +31,13 @@ pub(crate) async fn crawl_funding_rate(
 
     match market_type {
         MarketType::InverseSwap | MarketType::LinearSwap => {
-            let ws_client = OkxWSClient::new(tx, None).await;
+            let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+                OkxWSClient::new(tx.clone(), None)
+            })
+            .await
+            else {
+                return;
+            };
             ws_client.subscribe(&topics).await;
             ws_client.run().await;
             ws_client.close();
@@ -49,7 +54,7 @@ pub(crate) async fn crawl_funding_rate(
 pub(crate) async fn crawl_open_interest(
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     let tx = create_conversion_thread(
         EXCHANGE_NAME.to_string(),
@@ -73,7 +78,13 @@ pub(crate) async fn crawl_open_interest(
         .collect();
 
     if market_type != MarketType::Spot {
-        let ws_client = OkxWSClient::new(tx, None).await;
+        let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+            OkxWSClient::new(tx.clone(), None)
+        })
+        .await
+        else {
+            return;
+        };
         ws_client.subscribe(&topics).await;
         ws_client.run().await;
         ws_client.close();
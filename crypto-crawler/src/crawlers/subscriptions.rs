@@ -0,0 +1,221 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crypto_market_type::MarketType;
+use crypto_msg_type::MessageType;
+use crypto_ws_client::WSClient;
+use log::*;
+use once_cell::sync::Lazy;
+
+type Client = Arc<dyn WSClient + Send + Sync>;
+
+/// Topics subscribed by a running crawler, returned by `get_crawler_topics()`.
+#[derive(Clone, Debug)]
+pub struct CrawlerTopics {
+    pub exchange: String,
+    pub market_type: MarketType,
+    pub msg_type: MessageType,
+    /// Topics of every connection of the crawler, indexed by connection.
+    pub connections: Vec<Vec<(String, String)>>,
+}
+
+impl CrawlerTopics {
+    /// Map from every topic to the index of the connection which owns it.
+    pub fn topic_map(&self) -> HashMap<(String, String), usize> {
+        self.connections
+            .iter()
+            .enumerate()
+            .flat_map(|(i, topics)| topics.iter().map(move |topic| (topic.clone(), i)))
+            .collect()
+    }
+
+    /// The number of topics of every connection.
+    pub fn topics_per_connection(&self) -> Vec<usize> {
+        self.connections.iter().map(|topics| topics.len()).collect()
+    }
+}
+
+// Connections of a crawler, which are shared with its symbol discovery.
+pub(super) struct CrawlerConnections {
+    exchange: String,
+    market_type: MarketType,
+    msg_type: MessageType,
+    // every connection with its number of subscriptions
+    clients: Mutex<Vec<(Client, usize)>>,
+}
+
+impl CrawlerConnections {
+    pub fn add(&self, ws_client: Client, num_subscriptions: usize) {
+        self.clients
+            .lock()
+            .unwrap()
+            .push((ws_client, num_subscriptions));
+    }
+
+    /// Records `n` more subscriptions of `ws_client`.
+    pub fn add_subscriptions(&self, ws_client: &Client, n: usize) {
+        self.update_num_subscriptions(ws_client, |num| *num += n);
+    }
+
+    /// The number of subscriptions of `ws_client`.
+    pub fn num_subscriptions(&self, ws_client: &Client) -> usize {
+        let clients = self.clients.lock().unwrap();
+        clients
+            .iter()
+            .find(|(c, _)| Arc::ptr_eq(c, ws_client))
+            .map_or(0, |(_, num)| *num)
+    }
+
+    fn update_num_subscriptions(&self, ws_client: &Client, f: impl FnOnce(&mut usize)) {
+        let mut clients = self.clients.lock().unwrap();
+        if let Some((_, num)) = clients.iter_mut().find(|(c, _)| Arc::ptr_eq(c, ws_client)) {
+            f(num);
+        }
+    }
+
+    /// Closes all connections, so that their run() loops return.
+    pub fn close_all(&self) {
+        for (ws_client, _) in self.clients.lock().unwrap().iter() {
+            ws_client.close();
+        }
+    }
+
+    pub fn snapshot(&self) -> CrawlerTopics {
+        CrawlerTopics {
+            exchange: self.exchange.clone(),
+            market_type: self.market_type,
+            msg_type: self.msg_type,
+            connections: self
+                .clients
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(ws_client, _)| ws_client.topics())
+                .collect(),
+        }
+    }
+
+    /// Unsubscribes all topics and candlesticks of `symbols` from the connections which own
+    /// them, and deducts them from the number of subscriptions of these connections.
+    pub async fn unsubscribe_symbols(&self, symbols: &[String]) {
+        let is_removed = |symbol: &str| symbols.iter().any(|s| s.eq_ignore_ascii_case(symbol));
+        let clients = self
+            .clients
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(ws_client, _)| ws_client.clone())
+            .collect::<Vec<Client>>();
+        for ws_client in clients {
+            let topics = ws_client
+                .topics()
+                .into_iter()
+                .filter(|(_, symbol)| is_removed(symbol))
+                .collect::<Vec<(String, String)>>();
+            if !topics.is_empty() {
+                info!(
+                    "{} {} {} unsubscribing {:?}",
+                    self.exchange, self.market_type, self.msg_type, topics
+                );
+                ws_client.unsubscribe(&topics).await;
+            }
+            let symbol_interval_list = ws_client
+                .candlesticks()
+                .into_iter()
+                .filter(|(symbol, _)| is_removed(symbol))
+                .collect::<Vec<(String, usize)>>();
+            if !symbol_interval_list.is_empty() {
+                info!(
+                    "{} {} {} unsubscribing {:?}",
+                    self.exchange, self.market_type, self.msg_type, symbol_interval_list
+                );
+                ws_client
+                    .unsubscribe_candlestick(&symbol_interval_list)
+                    .await;
+            }
+            let n = topics.len() + symbol_interval_list.len();
+            self.update_num_subscriptions(&ws_client, |num| *num = num.saturating_sub(n));
+        }
+    }
+}
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+static CRAWLERS: Lazy<Mutex<HashMap<usize, Arc<CrawlerConnections>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Registers connections of a crawler until the guard is dropped.
+pub(super) struct Registration {
+    id: usize,
+    pub connections: Arc<CrawlerConnections>,
+}
+
+impl Registration {
+    pub fn new(exchange: &str, market_type: MarketType, msg_type: MessageType) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let connections = Arc::new(CrawlerConnections {
+            exchange: exchange.to_string(),
+            market_type,
+            msg_type,
+            clients: Mutex::new(Vec::new()),
+        });
+        CRAWLERS.lock().unwrap().insert(id, connections.clone());
+        Registration { id, connections }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        CRAWLERS.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Topics of all running crawlers, in the order they were started.
+pub fn get_crawler_topics() -> Vec<CrawlerTopics> {
+    let mut crawlers = CRAWLERS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(id, connections)| (*id, connections.clone()))
+        .collect::<Vec<(usize, Arc<CrawlerConnections>)>>();
+    crawlers.sort_by_key(|(id, _)| *id);
+    crawlers
+        .into_iter()
+        .map(|(_, connections)| connections.snapshot())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CrawlerTopics;
+    use crypto_market_type::MarketType;
+    use crypto_msg_type::MessageType;
+
+    #[test]
+    fn test_topic_map() {
+        let topics = CrawlerTopics {
+            exchange: "binance".to_string(),
+            market_type: MarketType::Spot,
+            msg_type: MessageType::Trade,
+            connections: vec![
+                vec![
+                    ("aggTrade".to_string(), "BTCUSDT".to_string()),
+                    ("aggTrade".to_string(), "ETHUSDT".to_string()),
+                ],
+                vec![("aggTrade".to_string(), "BNBUSDT".to_string())],
+            ],
+        };
+        let topic_map = topics.topic_map();
+        assert_eq!(3, topic_map.len());
+        assert_eq!(
+            Some(&1),
+            topic_map.get(&("aggTrade".to_string(), "BNBUSDT".to_string()))
+        );
+        assert_eq!(vec![2, 1], topics.topics_per_connection());
+    }
+}
@@ -3,40 +3,35 @@ use std::{
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use crate::utils::{REST_LOCKS, WS_LOCKS};
+use crate::utils::rate_limit::{get_request_weight, Endpoint};
 use crypto_market_type::{get_market_types, MarketType};
 use crypto_markets::fetch_symbols;
 use crypto_rest_client::{fetch_l2_snapshot, fetch_l3_snapshot, fetch_open_interest};
 use crypto_ws_client::*;
 use log::*;
 
-use crate::{get_hot_spot_symbols, utils::cmc_rank::sort_by_cmc_rank, Message, MessageType};
+use crate::{
+    get_hot_spot_symbols, sink::SinkSender, utils::cmc_rank::sort_by_cmc_rank, Message, MessageType,
+};
+
+use crate::status::{report, StatusKind};
+
+use super::subscriptions::{CrawlerConnections, Registration};
 
 pub fn fetch_symbols_retry(exchange: &str, market_type: MarketType) -> Vec<String> {
     let retry_count = std::env::var("REST_RETRY_COUNT")
         .unwrap_or_else(|_| "5".to_string())
         .parse::<i64>()
         .unwrap();
-    let cooldown_time = get_cooldown_time_per_request(exchange, market_type);
-    let lock = REST_LOCKS
-        .get(exchange)
-        .unwrap()
-        .get(&market_type)
-        .unwrap()
-        .clone();
+    let endpoint = Endpoint::rest(exchange, market_type);
+    let weight = get_request_weight(exchange, market_type, MessageType::Other);
     let mut symbols = Vec::<String>::new();
     let mut backoff_factor = 1;
     for i in 0..retry_count {
-        let mut lock_ = lock.lock().unwrap();
-        if !lock_.owns_lock() {
-            lock_.lock().unwrap();
-        }
+        endpoint.wait(weight);
         match fetch_symbols(exchange, market_type) {
             Ok(list) => {
                 symbols = list;
-                if lock_.owns_lock() {
-                    lock_.unlock().unwrap();
-                }
                 break;
             }
             Err(err) => {
@@ -48,12 +43,7 @@ pub fn fetch_symbols_retry(exchange: &str, market_type: MarketType) -> Vec<Strin
                 }
             }
         }
-        // Cooldown after each request, and make all other processes wait
-        // on the lock to avoid parallel requests, thus avoid 429 error
-        std::thread::sleep(cooldown_time * backoff_factor);
-        if lock_.owns_lock() {
-            lock_.unlock().unwrap();
-        }
+        std::thread::sleep(BACKOFF_TIME * backoff_factor);
     }
     symbols
 }
@@ -81,30 +71,8 @@ pub(super) fn check_args(exchange: &str, market_type: MarketType, symbols: &[Str
     }
 }
 
-fn get_cooldown_time_per_request(exchange: &str, market_type: MarketType) -> Duration {
-    let millis = match exchange {
-        "binance" => 500,      // spot weitht 1200, contract weight 2400
-        "bitget" => 100,       // 20 requests per 2 seconds
-        "bithumb" => 8 * 10, // 135 requests per 1 second for public APIs, multiplied by 10 to reduce its frequency
-        "bitmex" => 2000, // 60 requests per minute on all routes (reduced to 30 when unauthenticated)
-        "bitstamp" => 75 * 10, // 8000 requests per 10 minutes, but bitstamp orderbook is too big, need to reduce its frequency
-        "bitz" => 34,          // no more than 30 times within 1 second
-        "bybit" => 20 * 10, // 50 requests per second continuously for 2 minutes, multiplied by 10 to reduce its frequency
-        "coinbase_pro" => 100, //  10 requests per second
-        "deribit" => 50,    // 20 requests per second
-        "dydx" => 100,      // 100 requests per 10 seconds
-        "gate" => 4,        // 300 read operations per IP per second
-        "huobi" => 2,       // 800 times/second for one IP
-        "kucoin" => match market_type {
-            MarketType::Spot => 300, // 3x to avoid 429
-            _ => 100,                // 30 times/3s
-        },
-        "mexc" => 100, // 20 times per 2 seconds
-        "okx" => 100,  // 20 requests per 2 seconds
-        _ => 100,
-    };
-    Duration::from_millis(millis)
-}
+/// The base time to back off after a failed request.
+const BACKOFF_TIME: Duration = Duration::from_millis(500);
 
 /// Crawl leve2 or level3 orderbook snapshots through RESTful APIs.
 pub(crate) fn crawl_snapshot(
@@ -112,7 +80,7 @@ pub(crate) fn crawl_snapshot(
     market_type: MarketType,
     msg_type: MessageType, // L2Snapshot or L3Snapshot
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     let is_empty = match symbols {
         Some(list) => {
@@ -126,14 +94,8 @@ pub(crate) fn crawl_snapshot(
         None => true,
     };
 
-    let cooldown_time = get_cooldown_time_per_request(exchange, market_type);
-
-    let lock = REST_LOCKS
-        .get(exchange)
-        .unwrap()
-        .get(&market_type)
-        .unwrap()
-        .clone();
+    let endpoint = Endpoint::rest(exchange, market_type);
+    let weight = get_request_weight(exchange, market_type, msg_type);
     'outer: loop {
         let mut real_symbols = if is_empty {
             if market_type == MarketType::Spot {
@@ -153,21 +115,12 @@ pub(crate) fn crawl_snapshot(
         // retry 5 times at most
         while index < real_symbols.len() && backoff_factor < 6 {
             let symbol = real_symbols[index].as_str();
-            let mut lock_ = lock.lock().unwrap();
-            if !lock_.owns_lock() {
-                lock_.lock().unwrap();
-            }
+            endpoint.wait(weight);
             let resp = match msg_type {
                 MessageType::L2Snapshot => fetch_l2_snapshot(exchange, market_type, symbol, None),
                 MessageType::L3Snapshot => fetch_l3_snapshot(exchange, market_type, symbol, None),
                 _ => panic!("msg_type must be L2Snapshot or L3Snapshot"),
             };
-            // Cooldown after each request, and make all other processes wait
-            // on the lock to avoid parallel requests, thus avoid 429 error
-            std::thread::sleep(cooldown_time);
-            if lock_.owns_lock() {
-                lock_.unlock().unwrap();
-            }
             match resp {
                 Ok(msg) => {
                     index += 1;
@@ -190,6 +143,7 @@ pub(crate) fn crawl_snapshot(
                         .duration_since(SystemTime::UNIX_EPOCH)
                         .unwrap()
                         .as_millis() as u64;
+                    let backoff_time = backoff(&endpoint, &err, backoff_factor);
                     warn!(
                         "{} {} {} {} {} {}, error: {}, back off for {} milliseconds",
                         current_timestamp,
@@ -199,68 +153,84 @@ pub(crate) fn crawl_snapshot(
                         market_type,
                         symbol,
                         err,
-                        (backoff_factor * cooldown_time).as_millis()
+                        backoff_time.as_millis()
                     );
-                    std::thread::sleep(backoff_factor * cooldown_time);
+                    std::thread::sleep(backoff_time);
                     success_count = 0;
                     backoff_factor += 1;
                 }
             }
         }
-        std::thread::sleep(cooldown_time * 2); // if real_symbols is empty, CPU will be 100% without this line
+        std::thread::sleep(BACKOFF_TIME); // if real_symbols is empty, CPU will be 100% without this line
     }
 }
 
-/// Crawl open interests of all trading symbols.
-pub(crate) fn crawl_open_interest(exchange: &str, market_type: MarketType, tx: Sender<Message>) {
-    let cooldown_time = get_cooldown_time_per_request(exchange, market_type);
+/// Returns how long to back off after a failed request.
+///
+/// If the server responded with `Retry-After`, all requests to the endpoint group are held off
+/// by the limiter instead.
+pub(super) fn backoff(
+    endpoint: &Endpoint,
+    err: &crypto_rest_client::Error,
+    backoff_factor: u32,
+) -> Duration {
+    match err.retry_after {
+        Some(retry_after) => {
+            endpoint.penalize(retry_after);
+            Duration::ZERO
+        }
+        None => BACKOFF_TIME * backoff_factor,
+    }
+}
 
-    let lock = REST_LOCKS
-        .get(exchange)
-        .unwrap()
-        .get(&market_type)
-        .unwrap()
-        .clone();
+/// Crawl open interests of all trading symbols.
+pub(crate) fn crawl_open_interest(exchange: &str, market_type: MarketType, tx: SinkSender) {
+    let endpoint = Endpoint::rest(exchange, market_type);
+    let weight = get_request_weight(exchange, market_type, MessageType::OpenInterest);
     'outer: loop {
         match exchange {
             "bitz" | "deribit" | "dydx" | "ftx" | "huobi" | "kucoin" | "okx" => {
-                let mut lock_ = lock.lock().unwrap();
-                if !lock_.owns_lock() {
-                    lock_.lock().unwrap();
-                }
+                endpoint.wait(weight);
                 let resp = fetch_open_interest(exchange, market_type, None);
-                if let Ok(json) = resp {
-                    if exchange == "deribit" {
-                        // A RESTful response of deribit open_interest contains four lines
-                        for x in json.trim().split('\n') {
+                match resp {
+                    Ok(json) => {
+                        if exchange == "deribit" {
+                            // A RESTful response of deribit open_interest contains four lines
+                            for x in json.trim().split('\n') {
+                                let message = Message::new(
+                                    exchange.to_string(),
+                                    market_type,
+                                    MessageType::OpenInterest,
+                                    x.to_string(),
+                                );
+                                if tx.send(message).is_err() {
+                                    break; // break the loop if there is no receiver
+                                }
+                            }
+                        } else {
                             let message = Message::new(
                                 exchange.to_string(),
                                 market_type,
                                 MessageType::OpenInterest,
-                                x.to_string(),
+                                json,
                             );
                             if tx.send(message).is_err() {
                                 break; // break the loop if there is no receiver
                             }
                         }
-                    } else {
-                        let message = Message::new(
-                            exchange.to_string(),
+                    }
+                    Err(err) => {
+                        let backoff_time = backoff(&endpoint, &err, 1);
+                        warn!(
+                            "{} {}, error: {}, back off for {} milliseconds",
+                            exchange,
                             market_type,
-                            MessageType::OpenInterest,
-                            json,
+                            err,
+                            backoff_time.as_millis()
                         );
-                        if tx.send(message).is_err() {
-                            break; // break the loop if there is no receiver
-                        }
+                        std::thread::sleep(backoff_time);
                     }
                 }
-                // Cooldown after each request, and make all other processes wait
-                // on the lock to avoid parallel requests, thus avoid 429 error
-                std::thread::sleep(cooldown_time);
-                if lock_.owns_lock() {
-                    lock_.unlock().unwrap();
-                }
             }
             "binance" | "bitget" | "bybit" | "gate" | "zbg" => {
                 let real_symbols = fetch_symbols_retry(exchange, market_type);
@@ -271,17 +241,8 @@ pub(crate) fn crawl_open_interest(exchange: &str, market_type: MarketType, tx: S
                 // retry 5 times at most
                 while index < real_symbols.len() && backoff_factor < 6 {
                     let symbol = real_symbols[index].as_str();
-                    let mut lock_ = lock.lock().unwrap();
-                    if !lock_.owns_lock() {
-                        lock_.lock().unwrap();
-                    }
+                    endpoint.wait(weight);
                     let resp = fetch_open_interest(exchange, market_type, Some(symbol));
-                    // Cooldown after each request, and make all other processes wait
-                    // on the lock to avoid parallel requests, thus avoid 429 error
-                    std::thread::sleep(cooldown_time);
-                    if lock_.owns_lock() {
-                        lock_.unlock().unwrap();
-                    }
                     match resp {
                         Ok(msg) => {
                             index += 1;
@@ -305,6 +266,7 @@ pub(crate) fn crawl_open_interest(exchange: &str, market_type: MarketType, tx: S
                                 .unwrap()
                                 .as_millis()
                                 as u64;
+                            let backoff_time = backoff(&endpoint, &err, backoff_factor);
                             warn!(
                                 "{} {} {} {} {} {}, error: {}, back off for {} milliseconds",
                                 current_timestamp,
@@ -314,9 +276,9 @@ pub(crate) fn crawl_open_interest(exchange: &str, market_type: MarketType, tx: S
                                 market_type,
                                 symbol,
                                 err,
-                                (backoff_factor * cooldown_time).as_millis()
+                                backoff_time.as_millis()
                             );
-                            std::thread::sleep(backoff_factor * cooldown_time);
+                            std::thread::sleep(backoff_time);
                             success_count = 0;
                             backoff_factor += 1;
                         }
@@ -325,7 +287,7 @@ pub(crate) fn crawl_open_interest(exchange: &str, market_type: MarketType, tx: S
             }
             _ => panic!("{} does NOT have open interest RESTful API", exchange),
         }
-        std::thread::sleep(cooldown_time * 2); // if real_symbols is empty, CPU will be 100% without this line
+        std::thread::sleep(BACKOFF_TIME); // if real_symbols is empty, CPU will be 100% without this line
     }
 }
 
@@ -350,185 +312,101 @@ async fn subscribe_with_lock(
     };
 }
 
-fn get_connection_interval_ms(exchange: &str, _market_type: MarketType) -> Option<u64> {
-    match exchange {
-        "bitfinex" => Some(3000), // you cannot open more than 20 connections per minute, see https://docs.bitfinex.com/docs/requirements-and-limitations#websocket-rate-limits
-        // "bitmex" => Some(9000), // 40 per hour
-        "bitz" => Some(100), // `cat crawler-trade-bitz-spot-error-12.log` has many "429 Too Many Requests"
-        "kucoin" => Some(2000), //  Connection Limit: 30 per minute, see https://docs.kucoin.com/#connection-times
-        "okx" => Some(1000), // Connection limit: 1 time per second, https://www.okx.com/docs-v5/en/#websocket-api-connect
-        _ => None,
+/// Retry until a websocket client is connected.
+///
+/// If the server responded with 429, wait for the `retry-after` seconds, otherwise back off exponentially.
+///
+/// Returns `None` if the exchange doesn't have the market type, after reporting it.
+pub(super) async fn connect_with_retry<T, F, Fut>(
+    exchange: &str,
+    market_type: MarketType,
+    connect: F,
+) -> Option<T>
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = Result<T, WsError>>,
+{
+    let mut backoff_factor = 1;
+    loop {
+        match connect().await {
+            Ok(ws_client) => return Some(ws_client),
+            Err(err @ WsError::UnsupportedMarket { .. }) => {
+                report(
+                    exchange,
+                    market_type,
+                    None,
+                    StatusKind::Error,
+                    err.to_string(),
+                );
+                return None;
+            }
+            Err(err) => {
+                let seconds = match err {
+                    WsError::RateLimited {
+                        retry_after: Some(seconds),
+                        ..
+                    } => {
+                        if let Some(endpoint) = Endpoint::ws(exchange, market_type) {
+                            endpoint.penalize(Duration::from_secs(seconds));
+                        }
+                        seconds + rand::random::<u64>() % 9 + 1 // add random seconds to avoid concurrent requests
+                    }
+                    _ => backoff_factor,
+                };
+                error!(
+                    "{} {} {}, retrying in {} seconds",
+                    exchange, market_type, err, seconds
+                );
+                tokio::time::sleep(Duration::from_secs(seconds)).await;
+                backoff_factor = std::cmp::min(backoff_factor * 2, 64);
+            }
+        }
     }
 }
 
-fn get_num_subscriptions_per_connection(exchange: &str, market_type: MarketType) -> usize {
-    match exchange {
-        // A single connection can listen to a maximum of 200 streams
-        "binance" => {
-            if market_type == MarketType::Spot {
-                // https://binance-docs.github.io/apidocs/spot/en/#websocket-limits
-                1024
-            } else {
-                // https://binance-docs.github.io/apidocs/futures/en/#websocket-market-streams
-                // https://binance-docs.github.io/apidocs/delivery/en/#websocket-market-streams
-                200
-            }
-        } // https://binance-docs.github.io/apidocs/futures/en/#websocket-market-streams
-        // All websocket connections have a limit of 30 subscriptions to public market data feed channels
-        "bitfinex" => 30, // https://docs.bitfinex.com/docs/ws-general#subscribe-to-channels
-        "kucoin" => 300, // Subscription limit for each connection: 300 topics, see https://docs.kucoin.com/#topic-subscription-limit
-        "okx" => 256,    // okx spot l2_event throws many ResetWithoutClosingHandshake errors
-        _ => usize::MAX, // usize::MAX means unlimited
-    }
+async fn try_create_ws_client_internal(
+    exchange: &str,
+    market_type: MarketType,
+    tx: Sender<String>,
+) -> Result<Arc<dyn WSClient + Send + Sync>, WsError> {
+    crypto_ws_client::create_ws_client(exchange, market_type, tx, WSClientOptions::default())
+        .await
+        .map(Arc::from)
 }
 
 async fn create_ws_client_internal(
     exchange: &str,
     market_type: MarketType,
     tx: Sender<String>,
-) -> Arc<dyn WSClient + Send + Sync> {
-    match exchange {
-        "binance" => match market_type {
-            MarketType::Spot => Arc::new(BinanceSpotWSClient::new(tx, None).await),
-            MarketType::InverseFuture | MarketType::InverseSwap => {
-                Arc::new(BinanceInverseWSClient::new(tx, None).await)
-            }
-            MarketType::LinearFuture | MarketType::LinearSwap => {
-                Arc::new(BinanceLinearWSClient::new(tx, None).await)
-            }
-            MarketType::EuropeanOption => Arc::new(BinanceOptionWSClient::new(tx, None).await),
-            _ => panic!("Binance does NOT have the {} market type", market_type),
-        },
-        "bitfinex" => Arc::new(BitfinexWSClient::new(tx, None).await),
-        "bitget" => match market_type {
-            MarketType::Spot => Arc::new(BitgetSpotWSClient::new(tx, None).await),
-            MarketType::InverseSwap | MarketType::LinearSwap => {
-                Arc::new(BitgetSwapWSClient::new(tx, None).await)
-            }
-            _ => panic!("Bitget does NOT have the {} market type", market_type),
-        },
-        "bithumb" => Arc::new(BithumbWSClient::new(tx, None).await),
-        "bitmex" => Arc::new(BitmexWSClient::new(tx, None).await),
-        "bitstamp" => Arc::new(BitstampWSClient::new(tx, None).await),
-        "bitz" => match market_type {
-            MarketType::Spot => Arc::new(BitzSpotWSClient::new(tx, None).await),
-            _ => panic!("Bitz does NOT have the {} market type", market_type),
-        },
-        "bybit" => match market_type {
-            MarketType::InverseFuture | MarketType::InverseSwap => {
-                Arc::new(BybitInverseWSClient::new(tx, None).await)
-            }
-            MarketType::LinearSwap => Arc::new(BybitLinearSwapWSClient::new(tx, None).await),
-            _ => panic!("Bybit does NOT have the {} market type", market_type),
-        },
-        "coinbase_pro" => Arc::new(CoinbaseProWSClient::new(tx, None).await),
-        "deribit" => Arc::new(DeribitWSClient::new(tx, None).await),
-        "dydx" => match market_type {
-            MarketType::LinearSwap => Arc::new(DydxSwapWSClient::new(tx, None).await),
-            _ => panic!("dYdX does NOT have the {} market type", market_type),
-        },
-        "ftx" => Arc::new(FtxWSClient::new(tx, None).await),
-        "gate" => match market_type {
-            MarketType::Spot => Arc::new(GateSpotWSClient::new(tx, None).await),
-            MarketType::InverseSwap => Arc::new(GateInverseSwapWSClient::new(tx, None).await),
-            MarketType::LinearSwap => Arc::new(GateLinearSwapWSClient::new(tx, None).await),
-            MarketType::InverseFuture => Arc::new(GateInverseFutureWSClient::new(tx, None).await),
-            MarketType::LinearFuture => Arc::new(GateLinearFutureWSClient::new(tx, None).await),
-            _ => panic!("Gate does NOT have the {} market type", market_type),
-        },
-        "huobi" => match market_type {
-            MarketType::Spot => Arc::new(HuobiSpotWSClient::new(tx, None).await),
-            MarketType::InverseFuture => Arc::new(HuobiFutureWSClient::new(tx, None).await),
-            MarketType::LinearSwap => Arc::new(HuobiLinearSwapWSClient::new(tx, None).await),
-            MarketType::InverseSwap => Arc::new(HuobiInverseSwapWSClient::new(tx, None).await),
-            MarketType::EuropeanOption => Arc::new(HuobiOptionWSClient::new(tx, None).await),
-            _ => panic!("Huobi does NOT have the {} market type", market_type),
-        },
-        "kraken" => match market_type {
-            MarketType::Spot => Arc::new(KrakenSpotWSClient::new(tx, None).await),
-            MarketType::InverseFuture | MarketType::InverseSwap => {
-                Arc::new(KrakenFuturesWSClient::new(tx, None).await)
-            }
-            _ => panic!("Kraken does NOT have the {} market type", market_type),
-        },
-        "kucoin" => match market_type {
-            MarketType::Spot => Arc::new(KuCoinSpotWSClient::new(tx, None).await),
-            MarketType::InverseSwap | MarketType::LinearSwap | MarketType::InverseFuture => {
-                Arc::new(KuCoinSwapWSClient::new(tx, None).await)
-            }
-            _ => panic!("KuCoin does NOT have the {} market type", market_type),
-        },
-        "mexc" => match market_type {
-            MarketType::Spot => Arc::new(MexcSpotWSClient::new(tx, None).await),
-            MarketType::LinearSwap | MarketType::InverseSwap => {
-                Arc::new(MexcSwapWSClient::new(tx, None).await)
-            }
-            _ => panic!("MEXC does NOT have the {} market type", market_type),
-        },
-        "okx" => Arc::new(OkxWSClient::new(tx, None).await),
-        "zb" => match market_type {
-            MarketType::Spot => Arc::new(ZbSpotWSClient::new(tx, None).await),
-            MarketType::LinearSwap => Arc::new(ZbSwapWSClient::new(tx, None).await),
-            _ => panic!("ZB does NOT have the {} market type", market_type),
-        },
-        "zbg" => match market_type {
-            MarketType::Spot => Arc::new(ZbgSpotWSClient::new(tx, None).await),
-            MarketType::InverseSwap | MarketType::LinearSwap => {
-                Arc::new(ZbgSwapWSClient::new(tx, None).await)
-            }
-            _ => panic!("ZBG does NOT have the {} market type", market_type),
-        },
-        _ => panic!("Unknown exchange {}", exchange),
-    }
+) -> Option<Arc<dyn WSClient + Send + Sync>> {
+    connect_with_retry(exchange, market_type, || {
+        try_create_ws_client_internal(exchange, market_type, tx.clone())
+    })
+    .await
 }
 
 async fn create_ws_client(
     exchange: &str,
     market_type: MarketType,
     msg_type: MessageType,
-    tx: Sender<Message>,
-) -> Arc<dyn WSClient + Send + Sync> {
+    tx: SinkSender,
+) -> Option<Arc<dyn WSClient + Send + Sync>> {
     let tx = create_conversion_thread(exchange.to_string(), msg_type, market_type, tx);
-    if let Some(interval) = get_connection_interval_ms(exchange, market_type) {
-        let lock = WS_LOCKS
-            .get(exchange)
-            .unwrap()
-            .get(&market_type)
-            .unwrap()
-            .clone();
-        let mut lock = lock.lock().await;
-        let mut i = 0;
-        while !lock.owns_lock() {
-            i += 1;
-            debug!(
-                "{} {} {} try_lock_with_pid() the {}th time",
-                exchange, market_type, msg_type, i
-            );
-            if lock.try_lock_with_pid().is_ok() {
-                break;
-            } else {
-                tokio::time::sleep(std::time::Duration::from_millis(
-                    rand::random::<u64>() % 90 + 11,
-                ))
-                .await; // give chances to other tasks
-            }
-        }
-        let ws_client = create_ws_client_internal(exchange, market_type, tx).await;
-        tokio::time::sleep(Duration::from_millis(interval)).await;
-        if lock.owns_lock() {
-            lock.unlock().unwrap();
-        }
-        ws_client
-    } else {
-        create_ws_client_internal(exchange, market_type, tx).await
+    if let Some(endpoint) = Endpoint::ws(exchange, market_type) {
+        debug!(
+            "{} {} {} waiting for the connection rate limit",
+            exchange, market_type, msg_type
+        );
+        endpoint.wait_async(1).await;
     }
+    create_ws_client_internal(exchange, market_type, tx).await
 }
 
 pub(crate) async fn create_ws_client_symbol(
     exchange: &str,
     market_type: MarketType,
     tx: Sender<String>,
-) -> Arc<dyn WSClient + Send + Sync> {
+) -> Option<Arc<dyn WSClient + Send + Sync>> {
     let tx = create_parser_thread(exchange.to_string(), market_type, tx);
     create_ws_client_internal(exchange, market_type, tx).await
 }
@@ -536,16 +414,20 @@ pub(crate) async fn create_ws_client_symbol(
 #[derive(Clone)]
 struct EmptyStruct {} // for stop channel
 
+// Changes of trading symbols found by symbol discovery
+enum SymbolChange {
+    Added(Vec<String>),
+    Removed(Vec<String>),
+}
+
 fn create_symbol_discovery_thread(
     exchange: String,
     market_type: MarketType,
     subscribed_symbols: Vec<String>,
     mut stop_ch_rx: tokio::sync::broadcast::Receiver<EmptyStruct>,
-    tx: tokio::sync::mpsc::Sender<Vec<String>>, // send out new symbols
+    tx: tokio::sync::mpsc::Sender<SymbolChange>, // send out new and removed symbols
 ) -> tokio::task::JoinHandle<()> {
-    let num_topics_per_connection = get_num_subscriptions_per_connection(&exchange, market_type);
     let mut subscribed_symbols = subscribed_symbols;
-    let mut num_subscribed_of_last_client = subscribed_symbols.len() % num_topics_per_connection;
     let mut hourly = tokio::time::interval(Duration::from_secs(3600));
     tokio::task::spawn(async move {
         loop {
@@ -567,17 +449,28 @@ fn create_symbol_discovery_thread(
 
                     if !new_symbols.is_empty() {
                         warn!("Found new symbols: {}", new_symbols.join(", "));
-                        if tx.send(new_symbols.clone()).await.is_err() {
+                        if tx.send(SymbolChange::Added(new_symbols.clone())).await.is_err() {
                             break; // break the loop if there is no receiver
                         }
-                        num_subscribed_of_last_client += new_symbols.len();
                         subscribed_symbols.append(&mut new_symbols);
                     }
-                    if num_subscribed_of_last_client >= num_topics_per_connection {
-                        panic!(
-                            "The last connection has subscribed {} topics, which is more than {}, restarting the process",
-                             num_subscribed_of_last_client, num_topics_per_connection,
-                        ); // pm2 will restart the whole process
+
+                    // an empty list means fetch_symbols_retry() failed
+                    let removed_symbols: Vec<String> = if latest_symbols.is_empty() {
+                        Vec::new()
+                    } else {
+                        subscribed_symbols
+                            .iter()
+                            .filter(|s| !latest_symbols.contains(s))
+                            .cloned()
+                            .collect()
+                    };
+                    if !removed_symbols.is_empty() {
+                        warn!("Found removed symbols: {}", removed_symbols.join(", "));
+                        subscribed_symbols.retain(|s| !removed_symbols.contains(s));
+                        if tx.send(SymbolChange::Removed(removed_symbols)).await.is_err() {
+                            break; // break the loop if there is no receiver
+                        }
                     }
                 }
             }
@@ -585,35 +478,152 @@ fn create_symbol_discovery_thread(
     })
 }
 
+// Splits `items` into the rest capacity of the last connection and chunks for new connections.
+fn split_by_capacity<T: Clone>(
+    items: &[T],
+    num_subscribed: usize,
+    num_topics_per_connection: usize,
+) -> (Vec<T>, Vec<Vec<T>>) {
+    let capacity = num_topics_per_connection.saturating_sub(num_subscribed);
+    let n = std::cmp::min(capacity, items.len());
+    let chunks = items[n..]
+        .chunks(num_topics_per_connection)
+        .map(|chunk| chunk.to_vec())
+        .collect();
+    (items[..n].to_vec(), chunks)
+}
+
+// A connection created by create_overflow_ws_client() and the handle of its run() loop
+type OverflowConnection = (Arc<dyn WSClient + Send + Sync>, tokio::task::JoinHandle<()>);
+
+// Creates a new connection once the last one is full, instead of restarting the process.
+//
+// The returned handle completes once the connection is closed.
+async fn create_overflow_ws_client(
+    exchange: &str,
+    market_type: MarketType,
+    msg_type: MessageType,
+    num_topics: usize,
+    tx: SinkSender,
+) -> Option<OverflowConnection> {
+    let ws_client = create_ws_client(exchange, market_type, msg_type, tx).await?;
+    let handle = {
+        let ws_client = ws_client.clone();
+        tokio::task::spawn(async move {
+            ws_client.run().await;
+            ws_client.close();
+        })
+    };
+    report(
+        exchange,
+        market_type,
+        Some(msg_type),
+        StatusKind::Recovered,
+        format!(
+            "The last connection is full, created a new connection for {} topics",
+            num_topics
+        ),
+    );
+    Some((ws_client, handle))
+}
+
+// Closes connections created by a symbol receiver thread and waits until they stop.
+async fn close_overflow_connections(overflow_connections: Vec<OverflowConnection>) {
+    for (ws_client, handle) in overflow_connections {
+        ws_client.close();
+        _ = handle.await;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn create_new_symbol_receiver_thread(
     exchange: String,
     msg_type: MessageType,
     market_type: MarketType,
-    mut symbols_rx: tokio::sync::mpsc::Receiver<Vec<String>>,
-    ws_client: Arc<dyn WSClient + Send + Sync>,
+    mut symbols_rx: tokio::sync::mpsc::Receiver<SymbolChange>,
+    ws_client: Arc<dyn WSClient + Send + Sync>, // subscribes new symbols
+    connections: Arc<CrawlerConnections>,       // unsubscribes removed symbols
+    tx: SinkSender,                             // for new connections
 ) -> tokio::task::JoinHandle<()> {
+    let num_topics_per_connection = get_max_topics_per_connection(&exchange, market_type);
     tokio::task::spawn(async move {
-        let exchange_clone = exchange;
-        while let Some(new_symbols) = symbols_rx.recv().await {
-            subscribe_with_lock(
-                exchange_clone.clone(),
-                market_type,
-                msg_type,
-                new_symbols,
-                ws_client.clone(),
-            )
-            .await;
+        let mut ws_client = ws_client;
+        let mut overflow_connections = Vec::new();
+        while let Some(change) = symbols_rx.recv().await {
+            match change {
+                SymbolChange::Added(new_symbols) => {
+                    let (head, chunks) = split_by_capacity(
+                        &new_symbols,
+                        connections.num_subscriptions(&ws_client),
+                        num_topics_per_connection,
+                    );
+                    if !head.is_empty() {
+                        connections.add_subscriptions(&ws_client, head.len());
+                        subscribe_with_lock(
+                            exchange.clone(),
+                            market_type,
+                            msg_type,
+                            head,
+                            ws_client.clone(),
+                        )
+                        .await;
+                    }
+                    for chunk in chunks {
+                        let Some(overflow_connection) = create_overflow_ws_client(
+                            &exchange,
+                            market_type,
+                            msg_type,
+                            chunk.len(),
+                            tx.clone(),
+                        )
+                        .await
+                        else {
+                            break;
+                        };
+                        ws_client = overflow_connection.0.clone();
+                        overflow_connections.push(overflow_connection);
+                        connections.add(ws_client.clone(), chunk.len());
+                        subscribe_with_lock(
+                            exchange.clone(),
+                            market_type,
+                            msg_type,
+                            chunk,
+                            ws_client.clone(),
+                        )
+                        .await;
+                    }
+                }
+                SymbolChange::Removed(removed_symbols) => {
+                    connections.unsubscribe_symbols(&removed_symbols).await
+                }
+            }
         }
+        close_overflow_connections(overflow_connections).await;
     })
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_new_symbol_receiver_thread_candlestick(
+    exchange: String,
+    market_type: MarketType,
     intervals: Vec<usize>,
-    mut rx: tokio::sync::mpsc::Receiver<Vec<String>>,
+    mut rx: tokio::sync::mpsc::Receiver<SymbolChange>,
     ws_client: Arc<dyn WSClient + Send + Sync>,
+    connections: Arc<CrawlerConnections>, // unsubscribes removed symbols
+    tx: SinkSender,                       // for new connections
 ) -> tokio::task::JoinHandle<()> {
+    let num_topics_per_connection = get_max_topics_per_connection(&exchange, market_type);
     tokio::task::spawn(async move {
-        while let Some(new_symbols) = rx.recv().await {
+        let mut ws_client = ws_client;
+        let mut overflow_connections = Vec::new();
+        while let Some(change) = rx.recv().await {
+            let new_symbols = match change {
+                SymbolChange::Added(new_symbols) => new_symbols,
+                SymbolChange::Removed(removed_symbols) => {
+                    connections.unsubscribe_symbols(&removed_symbols).await;
+                    continue;
+                }
+            };
             let new_symbol_interval_list = new_symbols
                 .iter()
                 .flat_map(|symbol| {
@@ -623,19 +633,43 @@ fn create_new_symbol_receiver_thread_candlestick(
                         .map(move |interval| (symbol.clone(), interval))
                 })
                 .collect::<Vec<(String, usize)>>();
-            ws_client
-                .subscribe_candlestick(&new_symbol_interval_list)
-                .await;
+            let (head, chunks) = split_by_capacity(
+                &new_symbol_interval_list,
+                connections.num_subscriptions(&ws_client),
+                num_topics_per_connection,
+            );
+            if !head.is_empty() {
+                connections.add_subscriptions(&ws_client, head.len());
+                ws_client.subscribe_candlestick(&head).await;
+            }
+            for chunk in chunks {
+                let Some(overflow_connection) = create_overflow_ws_client(
+                    &exchange,
+                    market_type,
+                    MessageType::Candlestick,
+                    chunk.len(),
+                    tx.clone(),
+                )
+                .await
+                else {
+                    break;
+                };
+                ws_client = overflow_connection.0.clone();
+                overflow_connections.push(overflow_connection);
+                connections.add(ws_client.clone(), chunk.len());
+                ws_client.subscribe_candlestick(&chunk).await;
+            }
         }
+        close_overflow_connections(overflow_connections).await;
     })
 }
 
-// create a thread to convert Sender<Message> Sender<String>
+// create a thread to convert SinkSender to Sender<String>
 pub(crate) fn create_conversion_thread(
     exchange: String,
     msg_type: MessageType,
     market_type: MarketType,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) -> Sender<String> {
     let (tx_raw, rx_raw) = std::sync::mpsc::channel();
     tokio::task::spawn_blocking(move || {
@@ -649,7 +683,37 @@ pub(crate) fn create_conversion_thread(
     tx_raw
 }
 
-// create a thread to call `crypto-msg-parser`
+// Parses a message of `msg_type` with `crypto-msg-parser` and serializes the result.
+fn parse_message(
+    exchange: &str,
+    market_type: MarketType,
+    msg_type: MessageType,
+    json: &str,
+) -> Result<String, String> {
+    let parsed = match msg_type {
+        MessageType::Trade => crypto_msg_parser::parse_trade(exchange, market_type, json)
+            .map(|trades| serde_json::to_string(&trades)),
+        MessageType::L2Event => {
+            let received_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64;
+            crypto_msg_parser::parse_l2(exchange, market_type, json, Some(received_at))
+                .map(|orderbooks| serde_json::to_string(&orderbooks))
+        }
+        _ => return Err(format!("unknown msg type {}, message: {}", msg_type, json)),
+    };
+    match parsed {
+        Ok(Ok(parsed)) => Ok(parsed),
+        Ok(Err(err)) => Err(format!("{} failed to serialize, error: {}", msg_type, err)),
+        Err(err) => Err(format!(
+            "{} failed to parse, error: {}, message: {}",
+            msg_type, err, json
+        )),
+    }
+}
+
+// create a thread to call `crypto-msg-parser`, messages which fail to parse are skipped
 fn create_parser_thread(
     exchange: String,
     market_type: MarketType,
@@ -659,33 +723,19 @@ fn create_parser_thread(
     std::thread::spawn(move || {
         for json in rx_raw {
             let msg_type = crypto_msg_parser::get_msg_type(&exchange, &json);
-            let parsed = match msg_type {
-                MessageType::Trade => serde_json::to_string(
-                    &crypto_msg_parser::parse_trade(&exchange, market_type, &json).unwrap(),
-                )
-                .unwrap(),
-                MessageType::L2Event => {
-                    let received_at = SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_millis()
-                        .try_into()
-                        .unwrap();
-                    serde_json::to_string(
-                        &crypto_msg_parser::parse_l2(
-                            &exchange,
-                            market_type,
-                            &json,
-                            Some(received_at),
-                        )
-                        .unwrap(),
-                    )
-                    .unwrap()
+            match parse_message(&exchange, market_type, msg_type, &json) {
+                Ok(parsed) => {
+                    if tx.send(parsed).is_err() {
+                        break; // break the loop if there is no receiver
+                    }
                 }
-                _ => panic!("unknown msg type {}", msg_type),
-            };
-            if tx.send(parsed).is_err() {
-                break; // break the loop if there is no receiver
+                Err(err) => report(
+                    &exchange,
+                    market_type,
+                    Some(msg_type),
+                    StatusKind::Error,
+                    err,
+                ),
             }
         }
     });
@@ -698,14 +748,14 @@ async fn crawl_event_one_chunk(
     msg_type: MessageType,
     ws_client: Option<Arc<dyn WSClient + Send + Sync>>,
     symbols: Vec<String>,
-    tx: Sender<Message>,
-) -> tokio::task::JoinHandle<()> {
-    let ws_client = if let Some(ws_client) = ws_client {
-        ws_client
-    } else {
-        let tx_clone = tx.clone();
-        create_ws_client(&exchange, market_type, msg_type, tx_clone).await
+    tx: SinkSender,
+    connections: Arc<CrawlerConnections>,
+) -> Option<tokio::task::JoinHandle<()>> {
+    let ws_client = match ws_client {
+        Some(ws_client) => ws_client,
+        None => create_ws_client(&exchange, market_type, msg_type, tx).await?,
     };
+    connections.add(ws_client.clone(), symbols.len());
 
     {
         // fire and forget
@@ -723,10 +773,10 @@ async fn crawl_event_one_chunk(
         });
     }
 
-    tokio::task::spawn(async move {
+    Some(tokio::task::spawn(async move {
         ws_client.run().await;
         ws_client.close();
-    })
+    }))
 }
 
 pub(crate) async fn crawl_event(
@@ -734,9 +784,9 @@ pub(crate) async fn crawl_event(
     msg_type: MessageType,
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
-    let num_topics_per_connection = get_num_subscriptions_per_connection(exchange, market_type);
+    let num_topics_per_connection = get_max_topics_per_connection(exchange, market_type);
     let is_empty = match symbols {
         Some(list) => {
             if list.is_empty() {
@@ -762,9 +812,11 @@ pub(crate) async fn crawl_event(
 
     // The stop channel is used by all tokio tasks
     let (stop_ch_tx, stop_ch_rx) = tokio::sync::broadcast::channel::<EmptyStruct>(1);
+    // Connections are inspectable by get_crawler_topics() until the crawler returns
+    let registration = Registration::new(exchange, market_type, msg_type);
 
-    // create a thread to discover new symbols
-    let (tx_symbols, rx_symbols) = tokio::sync::mpsc::channel::<Vec<String>>(4);
+    // create a thread to discover new and removed symbols
+    let (tx_symbols, rx_symbols) = tokio::sync::mpsc::channel::<SymbolChange>(4);
     let symbol_discovery_thread = if automatic_symbol_discovery {
         let thread = create_symbol_discovery_thread(
             exchange.to_string(),
@@ -778,28 +830,37 @@ pub(crate) async fn crawl_event(
         None
     };
 
-    // create a thread to convert Sender<String> to Sender<Message>
+    // subscribes new symbols and unsubscribes removed ones
+    let mut receiver_thread = None;
+    // create a thread to convert Sender<String> to SinkSender
     if real_symbols.len() <= num_topics_per_connection {
-        let ws_client = create_ws_client(exchange, market_type, msg_type, tx).await;
-        subscribe_with_lock(
-            exchange.to_string(),
-            market_type,
-            msg_type,
-            real_symbols,
-            ws_client.clone(),
-        )
-        .await;
-        if automatic_symbol_discovery {
-            create_new_symbol_receiver_thread(
+        if let Some(ws_client) = create_ws_client(exchange, market_type, msg_type, tx.clone()).await
+        {
+            registration
+                .connections
+                .add(ws_client.clone(), real_symbols.len());
+            subscribe_with_lock(
                 exchange.to_string(),
-                msg_type,
                 market_type,
-                rx_symbols,
+                msg_type,
+                real_symbols,
                 ws_client.clone(),
-            );
+            )
+            .await;
+            if automatic_symbol_discovery {
+                receiver_thread = Some(create_new_symbol_receiver_thread(
+                    exchange.to_string(),
+                    msg_type,
+                    market_type,
+                    rx_symbols,
+                    ws_client.clone(),
+                    registration.connections.clone(),
+                    tx,
+                ));
+            }
+            ws_client.run().await;
+            ws_client.close();
         }
-        ws_client.run().await;
-        ws_client.close();
     } else {
         // split to chunks
         let mut chunks: Vec<Vec<String>> = Vec::new();
@@ -822,12 +883,13 @@ pub(crate) async fn crawl_event(
         {
             let n = chunks.len();
             for (i, chunk) in chunks.into_iter().enumerate() {
-                last_ws_client = if i == (n - 1) {
-                    let tx_clone = tx.clone();
-                    Some(create_ws_client(exchange, market_type, msg_type, tx_clone).await)
-                } else {
-                    None
-                };
+                if i == (n - 1) {
+                    last_ws_client =
+                        create_ws_client(exchange, market_type, msg_type, tx.clone()).await;
+                    if last_ws_client.is_none() {
+                        break;
+                    }
+                }
                 let ret = crawl_event_one_chunk(
                     exchange.to_string(),
                     market_type,
@@ -835,23 +897,34 @@ pub(crate) async fn crawl_event(
                     last_ws_client.clone(),
                     chunk,
                     tx.clone(),
+                    registration.connections.clone(),
                 );
-                handles.push(ret.await);
+                let Some(handle) = ret.await else {
+                    break;
+                };
+                handles.push(handle);
             }
-            drop(tx);
         }
         if automatic_symbol_discovery && last_ws_client.is_some() {
-            create_new_symbol_receiver_thread(
+            receiver_thread = Some(create_new_symbol_receiver_thread(
                 exchange.to_string(),
                 msg_type,
                 market_type,
                 rx_symbols,
                 last_ws_client.unwrap(),
-            );
+                registration.connections.clone(),
+                tx,
+            ));
         }
         for handle in handles {
             if let Err(err) = handle.await {
-                panic!("{}", err); // TODO: use tokio::task::JoinSet or futures::stream::FuturesUnordered
+                report(
+                    exchange,
+                    market_type,
+                    Some(msg_type),
+                    StatusKind::Error,
+                    format!("A connection stopped abnormally, error: {}", err),
+                );
             }
         }
     };
@@ -859,6 +932,11 @@ pub(crate) async fn crawl_event(
     if let Some(thread) = symbol_discovery_thread {
         _ = thread.await;
     }
+    // stop connections which are still running, including overflow connections
+    registration.connections.close_all();
+    if let Some(thread) = receiver_thread {
+        _ = thread.await;
+    }
 }
 
 // from 1m to 5m
@@ -891,14 +969,14 @@ async fn crawl_candlestick_one_chunk(
     market_type: MarketType,
     ws_client: Option<Arc<dyn WSClient + Send + Sync>>,
     symbol_interval_list: Vec<(String, usize)>,
-    tx: Sender<Message>,
-) -> tokio::task::JoinHandle<()> {
-    let ws_client = if let Some(ws_client) = ws_client {
-        ws_client
-    } else {
-        let tx_clone = tx.clone();
-        create_ws_client(&exchange, market_type, MessageType::Candlestick, tx_clone).await
+    tx: SinkSender,
+    connections: Arc<CrawlerConnections>,
+) -> Option<tokio::task::JoinHandle<()>> {
+    let ws_client = match ws_client {
+        Some(ws_client) => ws_client,
+        None => create_ws_client(&exchange, market_type, MessageType::Candlestick, tx).await?,
     };
+    connections.add(ws_client.clone(), symbol_interval_list.len());
 
     {
         // fire and forget
@@ -910,19 +988,19 @@ async fn crawl_candlestick_one_chunk(
         });
     }
 
-    tokio::task::spawn(async move {
+    Some(tokio::task::spawn(async move {
         ws_client.run().await;
         ws_client.close();
-    })
+    }))
 }
 
 pub(crate) async fn crawl_candlestick_ext(
     exchange: &str,
     market_type: MarketType,
     symbol_interval_list: Option<&[(String, usize)]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
-    let num_topics_per_connection = get_num_subscriptions_per_connection(exchange, market_type);
+    let num_topics_per_connection = get_max_topics_per_connection(exchange, market_type);
     let is_empty = match symbol_interval_list {
         Some(list) => {
             if list.is_empty() {
@@ -958,13 +1036,17 @@ pub(crate) async fn crawl_candlestick_ext(
         return;
     }
     let real_symbols: Vec<String> = symbol_interval_list.iter().map(|t| t.0.clone()).collect();
-    let real_intervals: Vec<usize> = symbol_interval_list.iter().map(|t| t.1).collect();
+    let mut real_intervals: Vec<usize> = symbol_interval_list.iter().map(|t| t.1).collect();
+    real_intervals.sort_unstable();
+    real_intervals.dedup();
 
     // The stop channel is used by all tokio tasks
     let (stop_ch_tx, stop_ch_rx) = tokio::sync::broadcast::channel::<EmptyStruct>(1);
+    // Connections are inspectable by get_crawler_topics() until the crawler returns
+    let registration = Registration::new(exchange, market_type, MessageType::Candlestick);
 
-    // create a thread to discover new symbols
-    let (tx_symbols, rx_symbols) = tokio::sync::mpsc::channel::<Vec<String>>(4);
+    // create a thread to discover new and removed symbols
+    let (tx_symbols, rx_symbols) = tokio::sync::mpsc::channel::<SymbolChange>(4);
     let symbol_discovery_thread = if automatic_symbol_discovery {
         let thread = create_symbol_discovery_thread(
             exchange.to_string(),
@@ -978,18 +1060,30 @@ pub(crate) async fn crawl_candlestick_ext(
         None
     };
 
+    // subscribes new symbols and unsubscribes removed ones
+    let mut receiver_thread = None;
     if symbol_interval_list.len() <= num_topics_per_connection {
-        let ws_client = create_ws_client(exchange, market_type, MessageType::Candlestick, tx).await;
-        ws_client.subscribe_candlestick(&symbol_interval_list).await;
-        if automatic_symbol_discovery {
-            create_new_symbol_receiver_thread_candlestick(
-                real_intervals,
-                rx_symbols,
-                ws_client.clone(),
-            );
+        if let Some(ws_client) =
+            create_ws_client(exchange, market_type, MessageType::Candlestick, tx.clone()).await
+        {
+            registration
+                .connections
+                .add(ws_client.clone(), symbol_interval_list.len());
+            ws_client.subscribe_candlestick(&symbol_interval_list).await;
+            if automatic_symbol_discovery {
+                receiver_thread = Some(create_new_symbol_receiver_thread_candlestick(
+                    exchange.to_string(),
+                    market_type,
+                    real_intervals,
+                    rx_symbols,
+                    ws_client.clone(),
+                    registration.connections.clone(),
+                    tx,
+                ));
+            }
+            ws_client.run().await;
+            ws_client.close();
         }
-        ws_client.run().await;
-        ws_client.close();
     } else {
         // split to chunks
         let mut chunks: Vec<Vec<(String, usize)>> = Vec::new();
@@ -1014,36 +1108,52 @@ pub(crate) async fn crawl_candlestick_ext(
         {
             let n = chunks.len();
             for (i, chunk) in chunks.into_iter().enumerate() {
-                last_ws_client = if i == (n - 1) {
-                    let tx_clone = tx.clone();
-                    Some(
-                        create_ws_client(exchange, market_type, MessageType::Candlestick, tx_clone)
-                            .await,
+                if i == (n - 1) {
+                    last_ws_client = create_ws_client(
+                        exchange,
+                        market_type,
+                        MessageType::Candlestick,
+                        tx.clone(),
                     )
-                } else {
-                    None
-                };
+                    .await;
+                    if last_ws_client.is_none() {
+                        break;
+                    }
+                }
                 let ret = crawl_candlestick_one_chunk(
                     exchange.to_string(),
                     market_type,
                     last_ws_client.clone(),
                     chunk,
                     tx.clone(),
+                    registration.connections.clone(),
                 );
-                handles.push(ret.await);
+                let Some(handle) = ret.await else {
+                    break;
+                };
+                handles.push(handle);
             }
-            drop(tx);
         }
         if automatic_symbol_discovery && last_ws_client.is_some() {
-            create_new_symbol_receiver_thread_candlestick(
+            receiver_thread = Some(create_new_symbol_receiver_thread_candlestick(
+                exchange.to_string(),
+                market_type,
                 real_intervals,
                 rx_symbols,
                 last_ws_client.unwrap(),
-            );
+                registration.connections.clone(),
+                tx,
+            ));
         }
         for handle in handles {
             if let Err(err) = handle.await {
-                panic!("{}", err); // TODO: use tokio::task::JoinSet or futures::stream::FuturesUnordered
+                report(
+                    exchange,
+                    market_type,
+                    Some(MessageType::Candlestick),
+                    StatusKind::Error,
+                    format!("A connection stopped abnormally, error: {}", err),
+                );
             }
         }
     };
@@ -1051,4 +1161,30 @@ pub(crate) async fn crawl_candlestick_ext(
     if let Some(thread) = symbol_discovery_thread {
         _ = thread.await;
     }
+    // stop connections which are still running, including overflow connections
+    registration.connections.close_all();
+    if let Some(thread) = receiver_thread {
+        _ = thread.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_by_capacity;
+
+    #[test]
+    fn test_split_by_capacity() {
+        let items: Vec<usize> = (0..7).collect();
+        let (head, chunks) = split_by_capacity(&items, 8, 10);
+        assert_eq!(vec![0, 1], head);
+        assert_eq!(vec![vec![2, 3, 4, 5, 6]], chunks);
+
+        let (head, chunks) = split_by_capacity(&items, 10, 3);
+        assert!(head.is_empty());
+        assert_eq!(vec![vec![0, 1, 2], vec![3, 4, 5], vec![6]], chunks);
+
+        let (head, chunks) = split_by_capacity(&items, 0, 10);
+        assert_eq!(items, head);
+        assert!(chunks.is_empty());
+    }
 }
@@ -1,18 +1,16 @@
-use std::sync::mpsc::Sender;
-
-use crate::{crawlers::utils::crawl_event, msg::Message};
+use crate::{crawlers::utils::crawl_event, sink::SinkSender};
 use crypto_market_type::MarketType;
 use crypto_msg_type::MessageType;
 use crypto_ws_client::*;
 
-use super::utils::create_conversion_thread;
+use super::utils::{connect_with_retry, create_conversion_thread};
 
 const EXCHANGE_NAME: &str = "zb";
 
 pub(crate) async fn crawl_ticker(
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     if market_type == MarketType::LinearSwap && (symbols.is_none() || symbols.unwrap().is_empty()) {
         let tx = create_conversion_thread(
@@ -24,7 +22,13 @@ pub(crate) async fn crawl_ticker(
         let commands: Vec<String> =
             vec![r#"{"action": "subscribe","channel": "All.Ticker"}"#.to_string()];
 
-        let ws_client = ZbSwapWSClient::new(tx, None).await;
+        let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+            ZbSwapWSClient::new(tx.clone(), None)
+        })
+        .await
+        else {
+            return;
+        };
         ws_client.send(&commands).await;
         ws_client.run().await;
         ws_client.close();
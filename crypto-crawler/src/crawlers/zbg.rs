@@ -1,18 +1,16 @@
-use std::sync::mpsc::Sender;
-
-use crate::{crawlers::utils::crawl_event, msg::Message};
+use crate::{crawlers::utils::crawl_event, sink::SinkSender};
 use crypto_market_type::MarketType;
 use crypto_msg_type::MessageType;
 use crypto_ws_client::*;
 
-use super::utils::create_conversion_thread;
+use super::utils::{connect_with_retry, create_conversion_thread};
 
 const EXCHANGE_NAME: &str = "zbg";
 
 pub(crate) async fn crawl_ticker(
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: SinkSender,
 ) {
     if symbols.is_none() || symbols.unwrap().is_empty() {
         if market_type == MarketType::Spot {
@@ -25,7 +23,13 @@ pub(crate) async fn crawl_ticker(
             let commands: Vec<String> =
                 vec![r#"{"action":"ADD", "dataType":"ALL_TRADE_STATISTIC_24H"}"#.to_string()];
 
-            let ws_client = ZbgSpotWSClient::new(tx, None).await;
+            let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+                ZbgSpotWSClient::new(tx.clone(), None)
+            })
+            .await
+            else {
+                return;
+            };
             ws_client.send(&commands).await;
             ws_client.run().await;
             ws_client.close();
@@ -39,7 +43,13 @@ pub(crate) async fn crawl_ticker(
             let commands: Vec<String> =
                 vec![r#"{"action":"sub", "topic":"future_all_indicator"}"#.to_string()];
 
-            let ws_client = ZbgSwapWSClient::new(tx, None).await;
+            let Some(ws_client) = connect_with_retry(EXCHANGE_NAME, market_type, || {
+                ZbgSwapWSClient::new(tx.clone(), None)
+            })
+            .await
+            else {
+                return;
+            };
             ws_client.send(&commands).await;
             ws_client.run().await;
             ws_client.close();
@@ -0,0 +1,280 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crypto_market_type::MarketType;
+use crypto_message::{Order, OrderBookMsg};
+use crypto_msg_type::MessageType;
+
+use crate::{
+    l2_sync::L2SyncEvent,
+    sink::SinkSender,
+    status::{report, StatusKind},
+    Message,
+};
+
+/// Options of `crawl_l2_book()`.
+#[derive(Clone, Debug)]
+pub struct L2BookOptions {
+    /// How often to send the order book of every synchronized symbol.
+    pub interval: Duration,
+    /// Send only the best K levels of each side as `MessageType::L2TopK`, or the full order
+    /// book as `MessageType::L2Snapshot` if None.
+    pub top_k: Option<usize>,
+}
+
+impl Default for L2BookOptions {
+    fn default() -> Self {
+        L2BookOptions {
+            interval: Duration::from_secs(1),
+            top_k: None,
+        }
+    }
+}
+
+// Prices are positive, so the order of their bits is the order of their values
+fn price_key(price: f64) -> u64 {
+    price.to_bits()
+}
+
+/// A local level2 order book of one symbol.
+pub struct L2Book {
+    exchange: String,
+    market_type: MarketType,
+    symbol: String,
+    pair: String,
+    timestamp: i64,
+    seq_id: Option<u64>,
+    asks: BTreeMap<u64, Order>,
+    bids: BTreeMap<u64, Order>,
+}
+
+impl L2Book {
+    /// Creates an order book from a snapshot.
+    pub fn new(snapshot: &OrderBookMsg) -> Self {
+        let mut book = L2Book {
+            exchange: snapshot.exchange.clone(),
+            market_type: snapshot.market_type,
+            symbol: snapshot.symbol.clone(),
+            pair: snapshot.pair.clone(),
+            timestamp: snapshot.timestamp,
+            seq_id: snapshot.seq_id,
+            asks: BTreeMap::new(),
+            bids: BTreeMap::new(),
+        };
+        book.apply(snapshot);
+        book
+    }
+
+    /// Applies a snapshot, which replaces all levels, or an update, in which a level with
+    /// zero quantity is removed.
+    pub fn apply(&mut self, msg: &OrderBookMsg) {
+        if msg.snapshot {
+            self.asks.clear();
+            self.bids.clear();
+        }
+        for (levels, orders) in [(&mut self.asks, &msg.asks), (&mut self.bids, &msg.bids)] {
+            for order in orders {
+                if order.quantity_base == 0.0 {
+                    levels.remove(&price_key(order.price));
+                } else {
+                    levels.insert(price_key(order.price), *order);
+                }
+            }
+        }
+        self.timestamp = msg.timestamp;
+        self.seq_id = msg.seq_id.or(self.seq_id);
+    }
+
+    /// The lowest ask.
+    pub fn best_ask(&self) -> Option<&Order> {
+        self.asks.values().next()
+    }
+
+    /// The highest bid.
+    pub fn best_bid(&self) -> Option<&Order> {
+        self.bids.values().next_back()
+    }
+
+    /// Returns the order book as a snapshot, or the best `top_k` levels of each side.
+    pub fn to_msg(&self, top_k: Option<usize>) -> OrderBookMsg {
+        let depth = top_k.unwrap_or(usize::MAX);
+        OrderBookMsg {
+            exchange: self.exchange.clone(),
+            market_type: self.market_type,
+            symbol: self.symbol.clone(),
+            pair: self.pair.clone(),
+            msg_type: if top_k.is_some() {
+                MessageType::L2TopK
+            } else {
+                MessageType::L2Snapshot
+            },
+            timestamp: self.timestamp,
+            snapshot: true,
+            asks: self.asks.values().take(depth).copied().collect(),
+            bids: self.bids.values().rev().take(depth).copied().collect(),
+            seq_id: self.seq_id,
+            prev_seq_id: None,
+            json: String::new(),
+        }
+    }
+}
+
+/// Maintains order books from synchronizer events and sends them every `options.interval`,
+/// serialized as JSON `OrderBookMsg`.
+///
+/// Order books are discarded on gaps and sent again once resynchronized.
+pub(crate) fn spawn_book_thread(
+    exchange: &str,
+    market_type: MarketType,
+    options: L2BookOptions,
+    rx: Receiver<L2SyncEvent>,
+    tx: SinkSender,
+) -> JoinHandle<()> {
+    let exchange = exchange.to_string();
+    std::thread::spawn(move || {
+        let mut books = HashMap::<String, L2Book>::new();
+        let mut resyncing = HashSet::<String>::new();
+        let mut next_time = Instant::now() + options.interval;
+        loop {
+            let timeout = next_time.saturating_duration_since(Instant::now());
+            match rx.recv_timeout(timeout) {
+                Ok(L2SyncEvent::Snapshot(snapshot)) => {
+                    if resyncing.remove(&snapshot.symbol) {
+                        report(
+                            &exchange,
+                            market_type,
+                            Some(MessageType::L2Event),
+                            StatusKind::Recovered,
+                            format!("Resynchronized the order book of {}", snapshot.symbol),
+                        );
+                    }
+                    books.insert(snapshot.symbol.clone(), L2Book::new(&snapshot));
+                }
+                Ok(L2SyncEvent::Update(update)) => {
+                    if let Some(book) = books.get_mut(&update.symbol) {
+                        book.apply(&update);
+                    }
+                }
+                Ok(L2SyncEvent::Resync {
+                    symbol,
+                    expected,
+                    received,
+                }) => {
+                    report(
+                        &exchange,
+                        market_type,
+                        Some(MessageType::L2Event),
+                        StatusKind::Error,
+                        format!(
+                            "Gap in the order book of {}, expected {}, received {}, resyncing",
+                            symbol, expected, received
+                        ),
+                    );
+                    books.remove(&symbol);
+                    resyncing.insert(symbol);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if Instant::now() >= next_time {
+                next_time = Instant::now() + options.interval;
+                for book in books.values() {
+                    let orderbook = book.to_msg(options.top_k);
+                    let message = Message::new_with_symbol(
+                        exchange.clone(),
+                        market_type,
+                        orderbook.msg_type,
+                        orderbook.symbol.clone(),
+                        serde_json::to_string(&orderbook).unwrap(),
+                    );
+                    if tx.send(message).is_err() {
+                        return; // no receiver
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto_market_type::MarketType;
+    use crypto_message::{Order, OrderBookMsg};
+    use crypto_msg_type::MessageType;
+
+    use super::L2Book;
+
+    fn order(price: f64, quantity: f64) -> Order {
+        Order {
+            price,
+            quantity_base: quantity,
+            quantity_quote: price * quantity,
+            quantity_contract: None,
+        }
+    }
+
+    fn orderbook(snapshot: bool, asks: Vec<Order>, bids: Vec<Order>) -> OrderBookMsg {
+        OrderBookMsg {
+            exchange: "okx".to_string(),
+            market_type: MarketType::Spot,
+            symbol: "BTC-USDT".to_string(),
+            pair: "BTC/USDT".to_string(),
+            msg_type: MessageType::L2Event,
+            timestamp: 1660000000000,
+            snapshot,
+            asks,
+            bids,
+            seq_id: None,
+            prev_seq_id: None,
+            json: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut book = L2Book::new(&orderbook(
+            true,
+            vec![order(101.0, 1.0), order(102.0, 2.0)],
+            vec![order(100.0, 1.0), order(99.0, 2.0)],
+        ));
+        assert_eq!(101.0, book.best_ask().unwrap().price);
+        assert_eq!(100.0, book.best_bid().unwrap().price);
+
+        // remove the best ask, add a new best bid and update a bid
+        book.apply(&orderbook(
+            false,
+            vec![order(101.0, 0.0)],
+            vec![order(100.5, 3.0), order(99.0, 4.0)],
+        ));
+        let msg = book.to_msg(None);
+        assert_eq!(MessageType::L2Snapshot, msg.msg_type);
+        assert!(msg.snapshot);
+        assert_eq!(
+            vec![102.0],
+            msg.asks.iter().map(|x| x.price).collect::<Vec<f64>>()
+        );
+        assert_eq!(
+            vec![(100.5, 3.0), (100.0, 1.0), (99.0, 4.0)],
+            msg.bids
+                .iter()
+                .map(|x| (x.price, x.quantity_base))
+                .collect::<Vec<(f64, f64)>>()
+        );
+
+        let msg = book.to_msg(Some(2));
+        assert_eq!(MessageType::L2TopK, msg.msg_type);
+        assert_eq!(
+            vec![100.5, 100.0],
+            msg.bids.iter().map(|x| x.price).collect::<Vec<f64>>()
+        );
+
+        // a snapshot replaces all levels
+        book.apply(&orderbook(true, vec![order(105.0, 1.0)], Vec::new()));
+        assert_eq!(105.0, book.best_ask().unwrap().price);
+        assert!(book.best_bid().is_none());
+    }
+}
@@ -0,0 +1,506 @@
+use std::{
+    collections::VecDeque,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crypto_market_type::MarketType;
+use crypto_message::OrderBookMsg;
+use crypto_msg_type::MessageType;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const EXCHANGE_NAME: &str = "binance";
+
+// Max number of diffs buffered while waiting for a snapshot
+const MAX_BUFFERED: usize = 4096;
+
+/// Events of a Binance level2 order book synchronizer.
+#[derive(Debug)]
+pub enum L2SyncEvent {
+    /// A full order book from the RESTful API, the following updates apply to it.
+    Snapshot(OrderBookMsg),
+    /// An incremental update which follows the previous update or snapshot without gaps.
+    Update(OrderBookMsg),
+    /// A gap was detected, the local order book must be discarded until the next snapshot.
+    ///
+    /// `expected` is the update ID which should come next, `received` is the one received.
+    Resync {
+        symbol: String,
+        expected: u64,
+        received: u64,
+    },
+}
+
+#[derive(Deserialize)]
+struct WebsocketMsg<T: Sized> {
+    data: T,
+}
+
+// Update IDs of a depthUpdate event.
+#[derive(Clone, Copy, Deserialize)]
+#[allow(non_snake_case)]
+struct UpdateIds {
+    U: u64,          // First update ID in event
+    u: u64,          // Final update ID in event
+    pu: Option<i64>, // Final update ID of the previous event, only in futures markets
+}
+
+#[derive(Deserialize)]
+#[allow(non_snake_case)]
+struct RawSnapshot {
+    lastUpdateId: u64,
+    E: Option<i64>,
+    T: Option<i64>,
+    bids: Vec<Value>,
+    asks: Vec<Value>,
+}
+
+/// Synchronizes a local level2 order book of one Binance symbol.
+///
+/// It follows the procedure documented by Binance:
+///
+/// 1. Buffer diffs from the `depth@100ms` stream
+/// 2. Fetch a snapshot from the RESTful API, and pass it to `on_snapshot()`
+/// 3. Drop buffered diffs older than the snapshot
+/// 4. Verify that every following diff continues the previous one, i.e., `pu` equals the
+///    previous `u` in futures markets and `U` equals the previous `u + 1` in the spot market
+///
+/// On a gap it emits `L2SyncEvent::Resync` and buffers diffs again until the next snapshot.
+pub struct BinanceL2Synchronizer {
+    market_type: MarketType,
+    symbol: String,
+    // `u` of the last applied diff, or `lastUpdateId` of the snapshot, `None` while waiting
+    // for a snapshot
+    last_update_id: Option<u64>,
+    // whether a diff was applied since the snapshot
+    applied: bool,
+    buffer: VecDeque<(UpdateIds, String)>,
+}
+
+impl BinanceL2Synchronizer {
+    pub fn new(market_type: MarketType, symbol: &str) -> Self {
+        BinanceL2Synchronizer {
+            market_type,
+            symbol: symbol.to_string(),
+            last_update_id: None,
+            applied: false,
+            buffer: VecDeque::new(),
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    /// Whether a snapshot is needed, which is true until the first snapshot and after a gap.
+    pub fn needs_snapshot(&self) -> bool {
+        self.last_update_id.is_none()
+    }
+
+    /// Processes a `depthUpdate` message of the websocket.
+    pub fn on_update(&mut self, msg: &str) -> Result<Vec<L2SyncEvent>, String> {
+        let ids = serde_json::from_str::<WebsocketMsg<UpdateIds>>(msg)
+            .map_err(|err| format!("Failed to parse {}, {}", msg, err))?
+            .data;
+        if self.last_update_id.is_none() {
+            if self.buffer.len() >= MAX_BUFFERED {
+                self.buffer.pop_front();
+            }
+            self.buffer.push_back((ids, msg.to_string()));
+            return Ok(Vec::new());
+        }
+        let mut events = Vec::new();
+        self.apply(ids, msg, &mut events)?;
+        Ok(events)
+    }
+
+    /// Processes a snapshot returned by `crypto_rest_client::fetch_l2_snapshot()`, then
+    /// applies buffered diffs.
+    pub fn on_snapshot(&mut self, json: &str) -> Result<Vec<L2SyncEvent>, String> {
+        let raw_snapshot = serde_json::from_str::<RawSnapshot>(json)
+            .map_err(|err| format!("Failed to parse {}, {}", json, err))?;
+        let mut events = vec![L2SyncEvent::Snapshot(
+            self.parse_snapshot(&raw_snapshot, json)?,
+        )];
+        self.last_update_id = Some(raw_snapshot.lastUpdateId);
+        self.applied = false;
+
+        let buffer = std::mem::take(&mut self.buffer);
+        for (ids, msg) in buffer {
+            if self.last_update_id.is_none() {
+                // a gap was detected, keep buffering for the next snapshot
+                self.buffer.push_back((ids, msg));
+            } else if !self.is_stale(&ids, raw_snapshot.lastUpdateId) {
+                self.apply(ids, &msg, &mut events)?;
+            }
+        }
+        Ok(events)
+    }
+
+    fn is_futures(&self) -> bool {
+        self.market_type != MarketType::Spot
+    }
+
+    // Whether a buffered diff is older than the snapshot.
+    fn is_stale(&self, ids: &UpdateIds, last_update_id: u64) -> bool {
+        if self.is_futures() {
+            ids.u < last_update_id
+        } else {
+            ids.u <= last_update_id
+        }
+    }
+
+    // Verifies the continuity of a diff and applies it, or emits a resync on a gap.
+    fn apply(
+        &mut self,
+        ids: UpdateIds,
+        msg: &str,
+        events: &mut Vec<L2SyncEvent>,
+    ) -> Result<(), String> {
+        let last_update_id = self.last_update_id.unwrap();
+        let (expected, received, continuous) = if !self.applied {
+            // the first diff must cover the snapshot
+            let expected = if self.is_futures() {
+                last_update_id
+            } else {
+                last_update_id + 1
+            };
+            (expected, ids.U, ids.U <= expected && expected <= ids.u)
+        } else if let Some(pu) = ids.pu.filter(|_| self.is_futures()) {
+            let received = pu.max(0) as u64;
+            (last_update_id, received, received == last_update_id)
+        } else {
+            (last_update_id + 1, ids.U, ids.U == last_update_id + 1)
+        };
+
+        if continuous {
+            let mut orderbooks =
+                crypto_msg_parser::parse_l2(EXCHANGE_NAME, self.market_type, msg, None)
+                    .map_err(|err| format!("Failed to parse {}, {}", msg, err))?;
+            self.last_update_id = Some(ids.u);
+            self.applied = true;
+            events.extend(orderbooks.drain(..).map(L2SyncEvent::Update));
+        } else {
+            self.last_update_id = None;
+            self.applied = false;
+            self.buffer.push_back((ids, msg.to_string()));
+            events.push(L2SyncEvent::Resync {
+                symbol: self.symbol.clone(),
+                expected,
+                received,
+            });
+        }
+        Ok(())
+    }
+
+    // Parses a snapshot by converting it to a depthUpdate message, so that quantities are
+    // calculated by `parse_l2()` the same way as diffs.
+    fn parse_snapshot(
+        &self,
+        raw_snapshot: &RawSnapshot,
+        json: &str,
+    ) -> Result<OrderBookMsg, String> {
+        let timestamp = raw_snapshot.E.or(raw_snapshot.T).unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64
+        });
+        let msg = json!({
+            "stream": format!("{}@depth", self.symbol.to_lowercase()),
+            "data": {
+                "e": "depthUpdate",
+                "E": timestamp,
+                "s": self.symbol,
+                "U": raw_snapshot.lastUpdateId,
+                "u": raw_snapshot.lastUpdateId,
+                "b": raw_snapshot.bids,
+                "a": raw_snapshot.asks,
+            }
+        })
+        .to_string();
+        let mut orderbook =
+            crypto_msg_parser::parse_l2(EXCHANGE_NAME, self.market_type, &msg, None)
+                .map_err(|err| format!("Failed to parse {}, {}", json, err))?
+                .pop()
+                .ok_or_else(|| format!("Failed to parse {}", json))?;
+        orderbook.msg_type = MessageType::L2Snapshot;
+        orderbook.snapshot = true;
+        orderbook.prev_seq_id = None;
+        orderbook.json = json.to_string();
+        Ok(orderbook)
+    }
+}
+
+// Whether an update is older than a snapshot.
+fn is_older(update: &OrderBookMsg, snapshot_seq_id: Option<u64>) -> bool {
+    match (update.seq_id, snapshot_seq_id) {
+        (Some(seq_id), Some(snapshot_seq_id)) => seq_id <= snapshot_seq_id,
+        _ => false,
+    }
+}
+
+/// Synchronizes a local level2 order book of one symbol from parsed messages, for exchanges
+/// whose websocket channel sends a snapshot on subscription.
+///
+/// Updates before the first snapshot are buffered, updates older than the snapshot are dropped,
+/// and every update with a `prev_seq_id` must continue the `seq_id` of the previous one.
+pub(crate) struct SequenceL2Synchronizer {
+    symbol: String,
+    synced: bool,
+    // `seq_id` of the last applied update or snapshot
+    last_seq_id: Option<u64>,
+    buffer: VecDeque<OrderBookMsg>,
+}
+
+impl SequenceL2Synchronizer {
+    pub fn new(symbol: &str) -> Self {
+        SequenceL2Synchronizer {
+            symbol: symbol.to_string(),
+            synced: false,
+            last_seq_id: None,
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// Whether a snapshot is needed, which is true until the first snapshot and after a gap.
+    pub fn needs_snapshot(&self) -> bool {
+        !self.synced
+    }
+
+    /// Number of updates buffered while waiting for a snapshot.
+    pub fn num_buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Processes a snapshot or an update parsed by `crypto_msg_parser::parse_l2()`.
+    pub fn on_message(&mut self, msg: OrderBookMsg) -> Vec<L2SyncEvent> {
+        let mut events = Vec::new();
+        if msg.snapshot {
+            self.synced = true;
+            self.last_seq_id = msg.seq_id;
+            let snapshot_seq_id = msg.seq_id;
+            events.push(L2SyncEvent::Snapshot(msg));
+
+            let buffer = std::mem::take(&mut self.buffer);
+            for update in buffer {
+                if !self.synced {
+                    // a gap was detected, keep buffering for the next snapshot
+                    self.buffer.push_back(update);
+                } else if !is_older(&update, snapshot_seq_id) {
+                    self.apply(update, &mut events);
+                }
+            }
+        } else if self.synced {
+            self.apply(msg, &mut events);
+        } else {
+            if self.buffer.len() >= MAX_BUFFERED {
+                self.buffer.pop_front();
+            }
+            self.buffer.push_back(msg);
+        }
+        events
+    }
+
+    fn apply(&mut self, msg: OrderBookMsg, events: &mut Vec<L2SyncEvent>) {
+        match (self.last_seq_id, msg.prev_seq_id, msg.seq_id) {
+            (Some(last_seq_id), Some(prev_seq_id), _) if prev_seq_id != last_seq_id => {
+                self.synced = false;
+                self.last_seq_id = None;
+                self.buffer.push_back(msg);
+                events.push(L2SyncEvent::Resync {
+                    symbol: self.symbol.clone(),
+                    expected: last_seq_id,
+                    received: prev_seq_id,
+                });
+            }
+            (Some(last_seq_id), None, Some(seq_id)) if seq_id <= last_seq_id => {
+                // outdated, without prev_seq_id gaps can't be detected
+            }
+            _ => {
+                self.last_seq_id = msg.seq_id.or(self.last_seq_id);
+                events.push(L2SyncEvent::Update(msg));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BinanceL2Synchronizer, L2SyncEvent, SequenceL2Synchronizer};
+    use crypto_market_type::MarketType;
+    use crypto_message::OrderBookMsg;
+    use crypto_msg_type::MessageType;
+
+    fn spot_update(first: u64, last: u64) -> String {
+        format!(
+            r#"{{"stream":"btcusdt@depth@100ms","data":{{"e":"depthUpdate","E":1660000000000,"s":"BTCUSDT","U":{},"u":{},"b":[["23950.01","0.5"]],"a":[]}}}}"#,
+            first, last
+        )
+    }
+
+    fn swap_update(first: u64, last: u64, prev: u64) -> String {
+        format!(
+            r#"{{"stream":"btcusdt@depth@100ms","data":{{"e":"depthUpdate","E":1660000000000,"T":1660000000000,"s":"BTCUSDT","U":{},"u":{},"pu":{},"b":[["23950.1","0.5"]],"a":[]}}}}"#,
+            first, last, prev
+        )
+    }
+
+    const SNAPSHOT: &str =
+        r#"{"lastUpdateId":100,"bids":[["23950.00","1.0"]],"asks":[["23950.02","2.0"]]}"#;
+
+    fn seq_ids(events: &[L2SyncEvent]) -> Vec<Option<u64>> {
+        events
+            .iter()
+            .map(|event| match event {
+                L2SyncEvent::Snapshot(orderbook) | L2SyncEvent::Update(orderbook) => {
+                    orderbook.seq_id
+                }
+                L2SyncEvent::Resync { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_spot() {
+        let mut synchronizer = BinanceL2Synchronizer::new(MarketType::Spot, "BTCUSDT");
+        assert!(synchronizer.needs_snapshot());
+        for (first, last) in [(90, 95), (96, 100), (101, 105), (106, 110)] {
+            let events = synchronizer.on_update(&spot_update(first, last)).unwrap();
+            assert!(events.is_empty());
+        }
+
+        // diffs up to the snapshot are dropped
+        let events = synchronizer.on_snapshot(SNAPSHOT).unwrap();
+        assert_eq!(vec![Some(100), Some(105), Some(110)], seq_ids(&events));
+        if let L2SyncEvent::Snapshot(orderbook) = &events[0] {
+            assert!(orderbook.snapshot);
+            assert_eq!(1, orderbook.bids.len());
+            assert_eq!(23950.02, orderbook.asks[0].price);
+        } else {
+            panic!("the first event must be the snapshot");
+        }
+        assert!(!synchronizer.needs_snapshot());
+
+        let events = synchronizer.on_update(&spot_update(111, 120)).unwrap();
+        assert_eq!(vec![Some(120)], seq_ids(&events));
+
+        // 121 is missing
+        let events = synchronizer.on_update(&spot_update(122, 130)).unwrap();
+        assert!(matches!(
+            events[..],
+            [L2SyncEvent::Resync {
+                expected: 121,
+                received: 122,
+                ..
+            }]
+        ));
+        assert!(synchronizer.needs_snapshot());
+    }
+
+    #[test]
+    fn test_swap() {
+        let mut synchronizer = BinanceL2Synchronizer::new(MarketType::LinearSwap, "BTCUSDT");
+        synchronizer.on_update(&swap_update(90, 99, 89)).unwrap();
+        synchronizer.on_update(&swap_update(100, 104, 99)).unwrap();
+        synchronizer.on_update(&swap_update(105, 108, 104)).unwrap();
+
+        // the first diff covers lastUpdateId
+        let events = synchronizer.on_snapshot(SNAPSHOT).unwrap();
+        assert_eq!(vec![Some(100), Some(104), Some(108)], seq_ids(&events));
+
+        // pu must be the previous u
+        let events = synchronizer.on_update(&swap_update(110, 112, 109)).unwrap();
+        assert!(matches!(
+            events[..],
+            [L2SyncEvent::Resync {
+                expected: 108,
+                received: 109,
+                ..
+            }]
+        ));
+
+        // resync with a newer snapshot
+        synchronizer.on_update(&swap_update(113, 115, 112)).unwrap();
+        let events = synchronizer
+            .on_snapshot(
+                r#"{"lastUpdateId":111,"E":1660000000100,"T":1660000000100,"bids":[],"asks":[]}"#,
+            )
+            .unwrap();
+        assert_eq!(vec![Some(111), Some(112), Some(115)], seq_ids(&events));
+    }
+
+    #[test]
+    fn test_outdated_snapshot() {
+        let mut synchronizer = BinanceL2Synchronizer::new(MarketType::Spot, "BTCUSDT");
+        synchronizer.on_update(&spot_update(105, 110)).unwrap();
+        // diffs from 101 to 104 were never received
+        let events = synchronizer.on_snapshot(SNAPSHOT).unwrap();
+        assert!(matches!(
+            events[..],
+            [
+                L2SyncEvent::Snapshot(_),
+                L2SyncEvent::Resync {
+                    expected: 101,
+                    received: 105,
+                    ..
+                }
+            ]
+        ));
+        assert!(synchronizer.needs_snapshot());
+    }
+
+    fn orderbook(snapshot: bool, seq_id: u64, prev_seq_id: Option<u64>) -> OrderBookMsg {
+        OrderBookMsg {
+            exchange: "okx".to_string(),
+            market_type: MarketType::Spot,
+            symbol: "BTC-USDT".to_string(),
+            pair: "BTC/USDT".to_string(),
+            msg_type: MessageType::L2Event,
+            timestamp: 1660000000000,
+            snapshot,
+            asks: Vec::new(),
+            bids: Vec::new(),
+            seq_id: Some(seq_id),
+            prev_seq_id,
+            json: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_sequence() {
+        let mut synchronizer = SequenceL2Synchronizer::new("BTC-USDT");
+        assert!(synchronizer
+            .on_message(orderbook(false, 9, Some(8)))
+            .is_empty());
+        assert!(synchronizer
+            .on_message(orderbook(false, 11, Some(10)))
+            .is_empty());
+        assert_eq!(2, synchronizer.num_buffered());
+
+        // the update older than the snapshot is dropped
+        let events = synchronizer.on_message(orderbook(true, 10, None));
+        assert_eq!(vec![Some(10), Some(11)], seq_ids(&events));
+        assert!(!synchronizer.needs_snapshot());
+
+        let events = synchronizer.on_message(orderbook(false, 12, Some(11)));
+        assert_eq!(vec![Some(12)], seq_ids(&events));
+
+        // 13 is missing
+        let events = synchronizer.on_message(orderbook(false, 14, Some(13)));
+        assert!(matches!(
+            events[..],
+            [L2SyncEvent::Resync {
+                expected: 12,
+                received: 13,
+                ..
+            }]
+        ));
+        assert!(synchronizer.needs_snapshot());
+
+        let events = synchronizer.on_message(orderbook(true, 20, None));
+        assert_eq!(vec![Some(20)], seq_ids(&events));
+        let events = synchronizer.on_message(orderbook(false, 21, Some(20)));
+        assert_eq!(vec![Some(21)], seq_ids(&events));
+    }
+}
@@ -44,6 +44,25 @@
 //! }
 //! ```
 //!
+//! ## Synchronize Binance level2 orderbooks
+//!
+//! ```rust,no_run
+//! use crypto_crawler::{sync_binance_l2, L2SyncEvent, MarketType};
+//!
+//! #[tokio::main(flavor = "multi_thread")]
+//! async fn main() {
+//!     let (tx, rx) = std::sync::mpsc::channel();
+//!     tokio::task::spawn(async move {
+//!         // Verify level2 updates of BTCUSDT against snapshots, resync on gaps
+//!         sync_binance_l2(MarketType::LinearSwap, &["BTCUSDT".to_string()], tx).await;
+//!     });
+//!
+//!     // The first event is always a snapshot
+//!     let event = rx.recv().unwrap();
+//!     assert!(matches!(event, L2SyncEvent::Snapshot(_)));
+//! }
+//! ```
+//!
 //! ## Crawl level2 orderbook full snapshots from RESTful API
 //!
 //! ```rust
@@ -85,6 +104,34 @@
 //! }
 //! ```
 //!
+//! ## Crawl consistent local level2 orderbooks
+//!
+//! Snapshots and incremental updates are merged in the order of sequence IDs, and a gap
+//! triggers a new snapshot.
+//!
+//! ```rust,no_run
+//! use crypto_crawler::{crawl_l2_book, L2BookOptions, MarketType, Message, OrderBookMsg};
+//!
+//! #[tokio::main(flavor = "multi_thread")]
+//! async fn main() {
+//!     let (tx, rx) = std::sync::mpsc::channel::<Message>();
+//!     tokio::task::spawn(async move {
+//!         for msg in rx {
+//!             let orderbook = serde_json::from_str::<OrderBookMsg>(&msg.json).unwrap();
+//!             println!("{} {:?} {:?}", orderbook.symbol, orderbook.bids.first(), orderbook.asks.first());
+//!         }
+//!     });
+//!
+//!     // Send the top 20 levels of local orderbooks every second
+//!     let options = L2BookOptions {
+//!         top_k: Some(20),
+//!         ..Default::default()
+//!     };
+//!     let symbols = vec!["BTC-USDT".to_string(), "ETH-USDT".to_string()];
+//!     crawl_l2_book("okx", MarketType::Spot, &symbols, options, tx).await;
+//! }
+//! ```
+//!
 //! ## Crawl realtime level3 orderbook incremental updates
 //!
 //! ```rust
@@ -213,28 +260,198 @@
 //!     assert!(!messages.is_empty());
 //! }
 //! ```
+//!
+//! ## Backfill historical trades
+//!
+//! Trades are sent in the same shape as realtime trades, and progress is checkpointed so that
+//! an interrupted backfill resumes without duplicates.
+//!
+//! ```rust,no_run
+//! use crypto_crawler::{backfill_trades, MarketType};
+//!
+//! fn main() {
+//!     let (tx, rx) = std::sync::mpsc::channel();
+//!     std::thread::spawn(move || {
+//!         for msg in rx {
+//!             println!("{}", msg);
+//!         }
+//!     });
+//!
+//!     // Backfill trades of an hour, resumes from the checkpoint if interrupted
+//!     let count = backfill_trades(
+//!         "binance",
+//!         MarketType::LinearSwap,
+//!         "BTCUSDT",
+//!         1660000000000,
+//!         1660003600000,
+//!         tx,
+//!     )
+//!     .unwrap();
+//!     println!("{} trades", count);
+//! }
+//! ```
+//!
+//! ## Backfill historical candlesticks
+//!
+//! Candlesticks of any supported interval are downloaded page by page and normalized into
+//! `CandlestickMsg`, resuming from the checkpoint if interrupted.
+//!
+//! ```rust,no_run
+//! use crypto_crawler::{backfill_candlesticks, CandlestickMsg, MarketType, Message};
+//!
+//! fn main() {
+//!     let (tx, rx) = std::sync::mpsc::channel::<Message>();
+//!     std::thread::spawn(move || {
+//!         for msg in rx {
+//!             let candlestick = serde_json::from_str::<CandlestickMsg>(&msg.json).unwrap();
+//!             println!("{} {} {}", candlestick.begin_time, candlestick.close, candlestick.volume);
+//!         }
+//!     });
+//!
+//!     // Backfill 1-hour candlesticks of a year, in Unix milliseconds
+//!     let count = backfill_candlesticks(
+//!         "okx",
+//!         MarketType::Spot,
+//!         "BTC-USDT",
+//!         3600,
+//!         1640995200000,
+//!         1672531200000,
+//!         tx,
+//!     )
+//!     .unwrap();
+//!     println!("{} candlesticks", count);
+//! }
+//! ```
+//!
+//! ## Write messages to a sink
+//!
+//! All `crawl_*` functions accept a `std::sync::mpsc::Sender<Message>`, or a `SinkSender`
+//! returned by `spawn_sink()`, which writes messages to a `Sink` in batches. Built-in sinks
+//! are `StdoutSink`, `RotatingFileSink`, `ChannelSink` and `LineSink`.
+//!
+//! ```rust,no_run
+//! use crypto_crawler::{
+//!     crawl_trade, spawn_sink, LineFormat, MarketType, RotatingFileSink, SinkOptions,
+//! };
+//!
+//! #[tokio::main(flavor = "multi_thread")]
+//! async fn main() {
+//!     // Rotate files every 64MB
+//!     let sink = RotatingFileSink::new("./data", "binance.trade", LineFormat::Json, 64 << 20)
+//!         .unwrap();
+//!     let (tx, handle) = spawn_sink(sink, SinkOptions::default());
+//!     crawl_trade("binance", MarketType::Spot, None, tx).await;
+//!     handle.join().unwrap();
+//! }
+//! ```
+//!
+//! ## Store messages in compressed files
+//!
+//! `FileWriter` is a sink which partitions messages by exchange, market type, message type
+//! and hour or day, and `FileReader` reads them back.
+//!
+//! ```rust,no_run
+//! use crypto_crawler::{
+//!     crawl_trade, spawn_sink, Compression, FileReader, FileWriter, LineFormat, MarketType,
+//!     Period, SinkOptions,
+//! };
+//!
+//! #[tokio::main(flavor = "multi_thread")]
+//! async fn main() {
+//!     let writer =
+//!         FileWriter::new("./data", LineFormat::Tsv, Compression::Zstd, Period::Hourly).unwrap();
+//!     let (tx, handle) = spawn_sink(writer, SinkOptions::default());
+//!     crawl_trade("binance", MarketType::Spot, None, tx).await;
+//!     handle.join().unwrap();
+//!
+//!     for msg in FileReader::new("./data").unwrap() {
+//!         println!("{}", msg.unwrap());
+//!     }
+//! }
+//! ```
+//!
+//! ## Monitor crawler status
+//!
+//! Crawlers recover from errors in-process instead of panicking, and report every error
+//! and recovery to the channel registered by `set_status_sender()`.
+//!
+//! ```rust,no_run
+//! use crypto_crawler::{crawl_trade, set_status_sender, MarketType};
+//!
+//! #[tokio::main(flavor = "multi_thread")]
+//! async fn main() {
+//!     let (status_tx, status_rx) = std::sync::mpsc::channel();
+//!     set_status_sender(status_tx);
+//!     std::thread::spawn(move || {
+//!         for status in status_rx {
+//!             println!("{:?}", status);
+//!         }
+//!     });
+//!
+//!     let (tx, rx) = std::sync::mpsc::channel();
+//!     tokio::task::spawn(async move {
+//!         crawl_trade("binance", MarketType::Spot, None, tx).await;
+//!     });
+//!     for msg in rx {
+//!         println!("{}", msg);
+//!     }
+//! }
+//! ```
+//!
+//! ## Share rate limits across processes
+//!
+//! Requests to the same endpoint are limited by an in-process token bucket, weighted by
+//! each exchange's rules. Processes started with the same `RATE_LIMITER_SOCKET` environment
+//! variable, or calling `set_rate_limiter_backend()` with the same socket, share one set of
+//! token buckets.
+//!
+//! ```rust,no_run
+//! use crypto_crawler::{set_rate_limiter_backend, RateLimiterBackend};
+//!
+//! set_rate_limiter_backend(RateLimiterBackend::UnixSocket("/tmp/crypto-crawler.sock".into()));
+//! ```
+mod backfill;
 mod crawlers;
+mod l2_book;
+mod l2_sync;
 mod msg;
+mod sink;
+mod status;
+mod storage;
 mod utils;
 
 use std::sync::mpsc::Sender;
 
-pub use crawlers::fetch_symbols_retry;
+pub use backfill::{backfill_candlesticks, backfill_trades};
+pub use crawlers::{fetch_symbols_retry, get_crawler_topics, CrawlerTopics};
 pub use crypto_market_type::MarketType;
+pub use crypto_message::{CandlestickMsg, Order, OrderBookMsg};
 pub use crypto_msg_type::MessageType;
+pub use l2_book::{L2Book, L2BookOptions};
+pub use l2_sync::{BinanceL2Synchronizer, L2SyncEvent};
 pub use msg::*;
-pub use utils::get_hot_spot_symbols;
+pub use sink::{
+    spawn_sink, ChannelSink, LineFormat, LineSink, RotatingFileSink, Sink, SinkHandle, SinkOptions,
+    SinkSender, StdoutSink,
+};
+pub use status::{set_status_sender, CrawlerStatus, StatusKind};
+pub use storage::{recover_files, Compression, FileReader, FileWriter, Period};
+pub use utils::{get_hot_spot_symbols, set_rate_limiter_backend, RateLimiterBackend};
 
 /// Crawl realtime trades.
 ///
 /// If `symbols` is None or empty, this API will crawl realtime trades for all symbols in the `market_type`
 /// market, and launch a thread to discover new symbols every hour. And so forth for all other APIs.
+///
+/// Delisted symbols are unsubscribed from the connections which own them, and topics of every
+/// connection can be inspected by `get_crawler_topics()`.
 pub async fn crawl_trade(
     exchange: &str,
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: impl Into<SinkSender>,
 ) {
+    let tx = tx.into();
     match exchange {
         "binance" => crawlers::binance::crawl_trade(market_type, symbols, tx).await,
         "bitmex" => crawlers::bitmex::crawl_trade(market_type, symbols, tx).await,
@@ -253,8 +470,9 @@ pub async fn crawl_l2_event(
     exchange: &str,
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: impl Into<SinkSender>,
 ) {
+    let tx = tx.into();
     match exchange {
         "bitmex" => crawlers::bitmex::crawl_l2_event(market_type, symbols, tx).await,
         "huobi" => crawlers::huobi::crawl_l2_event(market_type, symbols, tx).await,
@@ -270,13 +488,62 @@ pub async fn crawl_l2_event(
     }
 }
 
+/// Synchronize level2 orderbooks of Binance symbols.
+///
+/// Diffs of the websocket are verified against snapshots of the RESTful API following the
+/// procedure documented by Binance, see `BinanceL2Synchronizer`. When a gap is detected,
+/// `L2SyncEvent::Resync` is sent and a new snapshot is fetched.
+pub async fn sync_binance_l2(market_type: MarketType, symbols: &[String], tx: Sender<L2SyncEvent>) {
+    crawlers::binance::crawl_l2_sync(market_type, symbols, tx).await
+}
+
+/// Crawl consistent local level2 orderbooks, and send them periodically.
+///
+/// Every symbol starts from a snapshot, then updates are applied in the order of sequence IDs,
+/// and a gap triggers a new snapshot. Binance snapshots come from the RESTful API, see
+/// `sync_binance_l2()`. Other exchanges must send snapshots in their incremental level2
+/// websocket channels, which are bitfinex, bitget, bithumb, bitmex, bybit, coinbase_pro,
+/// deribit, dydx, ftx, gate, huobi except spot, kraken and okx. Other markets are reported to
+/// the status sender and nothing is crawled.
+///
+/// Every `options.interval` the full orderbook, or the best `options.top_k` levels, of every
+/// synchronized symbol is sent to `tx` as a JSON `OrderBookMsg`. Gaps and resynchronizations
+/// are reported to the status sender, see `set_status_sender()`.
+pub async fn crawl_l2_book(
+    exchange: &str,
+    market_type: MarketType,
+    symbols: &[String],
+    options: L2BookOptions,
+    tx: impl Into<SinkSender>,
+) {
+    if exchange != "binance" && !crawlers::l2_sync::has_websocket_snapshots(exchange, market_type) {
+        status::report(
+            exchange,
+            market_type,
+            Some(MessageType::L2Event),
+            StatusKind::Error,
+            "the incremental level2 websocket channel does NOT send snapshots".to_string(),
+        );
+        return;
+    }
+    let tx = tx.into();
+    let (events_tx, events_rx) = std::sync::mpsc::channel();
+    let handle = l2_book::spawn_book_thread(exchange, market_type, options, events_rx, tx);
+    match exchange {
+        "binance" => crawlers::binance::crawl_l2_sync(market_type, symbols, events_tx).await,
+        _ => crawlers::l2_sync::crawl_l2_sync(exchange, market_type, symbols, events_tx).await,
+    }
+    let _ = tokio::task::spawn_blocking(move || handle.join()).await;
+}
+
 /// Crawl level3 orderbook update events.
 pub async fn crawl_l3_event(
     exchange: &str,
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: impl Into<SinkSender>,
 ) {
+    let tx = tx.into();
     match exchange {
         "bitfinex" | "bitstamp" | "coinbase_pro" | "kucoin" => {
             crawlers::crawl_event(exchange, MessageType::L3Event, market_type, symbols, tx).await
@@ -293,8 +560,9 @@ pub fn crawl_l2_snapshot(
     exchange: &str,
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: impl Into<SinkSender>,
 ) {
+    let tx = tx.into();
     crawlers::crawl_snapshot(exchange, market_type, MessageType::L2Snapshot, symbols, tx);
 }
 
@@ -303,8 +571,9 @@ pub async fn crawl_bbo(
     exchange: &str,
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: impl Into<SinkSender>,
 ) {
+    let tx = tx.into();
     match exchange {
         "binance" => crawlers::binance::crawl_bbo(market_type, symbols, tx).await,
         "bitmex" => crawlers::bitmex::crawl_bbo(market_type, symbols, tx).await,
@@ -321,8 +590,9 @@ pub async fn crawl_l2_topk(
     exchange: &str,
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: impl Into<SinkSender>,
 ) {
+    let tx = tx.into();
     match exchange {
         "bitmex" => crawlers::bitmex::crawl_l2_topk(market_type, symbols, tx).await,
         "binance" | "bitget" | "bybit" | "bitstamp" | "deribit" | "gate" | "huobi" | "kucoin"
@@ -341,8 +611,9 @@ pub fn crawl_l3_snapshot(
     exchange: &str,
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: impl Into<SinkSender>,
 ) {
+    let tx = tx.into();
     crawlers::crawl_snapshot(exchange, market_type, MessageType::L3Snapshot, symbols, tx)
 }
 
@@ -354,8 +625,9 @@ pub async fn crawl_ticker(
     exchange: &str,
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: impl Into<SinkSender>,
 ) {
+    let tx = tx.into();
     match exchange {
         "binance" => crawlers::binance::crawl_ticker(market_type, symbols, tx).await,
         "bitfinex" | "bitget" | "bithumb" | "bitz" | "bybit" | "coinbase_pro" | "deribit"
@@ -373,8 +645,9 @@ pub async fn crawl_funding_rate(
     exchange: &str,
     market_type: MarketType,
     symbols: Option<&[String]>,
-    tx: Sender<Message>,
+    tx: impl Into<SinkSender>,
 ) {
+    let tx = tx.into();
     match exchange {
         "binance" => crawlers::binance::crawl_funding_rate(market_type, symbols, tx).await,
         "bitmex" => crawlers::bitmex::crawl_funding_rate(market_type, symbols, tx).await,
@@ -388,12 +661,15 @@ pub async fn crawl_funding_rate(
 ///
 /// If `symbol_interval_list` is None or empty, this API will crawl candlesticks from
 /// 10 seconds to 3 minutes(if available) for all symbols.
+///
+/// Candlesticks of delisted symbols are unsubscribed from the connections which own them.
 pub async fn crawl_candlestick(
     exchange: &str,
     market_type: MarketType,
     symbol_interval_list: Option<&[(String, usize)]>,
-    tx: Sender<Message>,
+    tx: impl Into<SinkSender>,
 ) {
+    let tx = tx.into();
     match exchange {
         "bitmex" => {
             crawlers::bitmex::crawl_candlestick(market_type, symbol_interval_list, tx).await
@@ -410,7 +686,8 @@ pub async fn crawl_candlestick(
 }
 
 /// Crawl all open interest.
-pub fn crawl_open_interest(exchange: &str, market_type: MarketType, tx: Sender<Message>) {
+pub fn crawl_open_interest(exchange: &str, market_type: MarketType, tx: impl Into<SinkSender>) {
+    let tx = tx.into();
     crawlers::crawl_open_interest(exchange, market_type, tx);
 }
 
@@ -426,7 +703,9 @@ pub async fn subscribe_symbol(
     msg_types: &[MessageType],
     tx: Sender<String>,
 ) {
-    let ws_client = crawlers::create_ws_client_symbol(exchange, market_type, tx).await;
+    let Some(ws_client) = crawlers::create_ws_client_symbol(exchange, market_type, tx).await else {
+        return;
+    };
     let symbols = vec![symbol.to_string()];
     let commands = crypto_msg_type::get_ws_commands(exchange, msg_types, &symbols, true, None);
     ws_client.send(&commands).await;
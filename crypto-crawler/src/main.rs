@@ -0,0 +1,701 @@
+use crypto_crawler::*;
+use log::*;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The configuration file, in TOML or YAML.
+///
+/// ```toml
+/// status_interval = 60
+/// rate_limiter_socket = "/tmp/crypto-crawler.sock"
+///
+/// [[sinks]]
+/// name = "files"
+/// type = "files"
+/// dir = "/data"
+/// format = "tsv"
+/// compression = "zstd"
+/// period = "hourly"
+///
+/// [[crawlers]]
+/// exchange = "binance"
+/// market_types = ["spot", "linear_swap"]
+/// msg_types = ["trade", "l2_event", "candlestick"]
+/// filter = { include = ["usdt"], exclude = ["busd"] }
+/// intervals = [60, 300]
+/// ```
+#[derive(Debug, Deserialize)]
+struct Config {
+    #[serde(default)]
+    sinks: Vec<SinkConfig>,
+    crawlers: Vec<CrawlerConfig>,
+    /// How often to log the status of every stream, in seconds.
+    #[serde(default = "default_status_interval")]
+    status_interval: u64,
+    /// A Unix socket to share rate limits with other crawler processes.
+    rate_limiter_socket: Option<PathBuf>,
+}
+
+fn default_status_interval() -> u64 {
+    60
+}
+
+fn default_format() -> LineFormat {
+    LineFormat::Json
+}
+
+#[derive(Debug, Deserialize)]
+struct SinkConfig {
+    name: String,
+    #[serde(flatten)]
+    kind: SinkKind,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum SinkKind {
+    Stdout {
+        #[serde(default = "default_format")]
+        format: LineFormat,
+    },
+    RotatingFile {
+        dir: PathBuf,
+        name: String,
+        #[serde(default = "default_format")]
+        format: LineFormat,
+        max_bytes: u64,
+    },
+    Files {
+        dir: PathBuf,
+        #[serde(default = "default_format")]
+        format: LineFormat,
+        compression: Compression,
+        period: Period,
+    },
+    Tcp {
+        addr: String,
+        #[serde(default = "default_format")]
+        format: LineFormat,
+    },
+    #[cfg(unix)]
+    Unix {
+        path: PathBuf,
+        #[serde(default = "default_format")]
+        format: LineFormat,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct CrawlerConfig {
+    exchange: String,
+    market_types: Vec<MarketType>,
+    msg_types: Vec<MessageType>,
+    /// Symbols to crawl, all symbols if both `symbols` and `filter` are absent.
+    symbols: Option<Vec<String>>,
+    /// Selects symbols out of all symbols of a market.
+    filter: Option<SymbolFilter>,
+    /// Candlestick intervals in seconds. If empty, it's 60 for selected symbols, or the
+    /// crawler's defaults for all symbols.
+    #[serde(default)]
+    intervals: Vec<usize>,
+    /// Name of the sink, optional if there is only one sink.
+    sink: Option<String>,
+}
+
+/// Case-insensitive substrings of exchange-specific symbols.
+#[derive(Debug, Default, Deserialize)]
+struct SymbolFilter {
+    /// A symbol must contain one of them, any symbol matches if empty.
+    #[serde(default)]
+    include: Vec<String>,
+    /// A symbol must contain none of them.
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+impl SymbolFilter {
+    fn matches(&self, symbol: &str) -> bool {
+        let symbol = symbol.to_lowercase();
+        (self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|s| symbol.contains(&s.to_lowercase())))
+            && !self
+                .exclude
+                .iter()
+                .any(|s| symbol.contains(&s.to_lowercase()))
+    }
+}
+
+fn parse_config(path: &Path, text: &str) -> Result<Config, String> {
+    let config: Config = match path.extension().and_then(|s| s.to_str()) {
+        Some("toml") => toml::from_str(text).map_err(|err| err.to_string())?,
+        Some("yaml") | Some("yml") => serde_yaml::from_str(text).map_err(|err| err.to_string())?,
+        _ => return Err(format!("Unknown config format: {}", path.display())),
+    };
+
+    for (i, sink) in config.sinks.iter().enumerate() {
+        if config.sinks[..i].iter().any(|x| x.name == sink.name) {
+            return Err(format!("Duplicated sink: {}", sink.name));
+        }
+    }
+    for crawler in config.crawlers.iter() {
+        match crawler.sink.as_ref() {
+            Some(name) => {
+                if !config.sinks.iter().any(|sink| &sink.name == name) {
+                    return Err(format!("Unknown sink: {}", name));
+                }
+            }
+            None => {
+                if config.sinks.len() > 1 {
+                    return Err(format!(
+                        "{} must specify a sink out of {} sinks",
+                        crawler.exchange,
+                        config.sinks.len()
+                    ));
+                }
+            }
+        }
+    }
+    Ok(config)
+}
+
+fn create_sink(
+    kind: &SinkKind,
+    stats: Arc<Mutex<HashMap<StreamKey, StreamStats>>>,
+) -> std::io::Result<(SinkSender, SinkHandle)> {
+    let inner: Box<dyn Sink> = match kind {
+        SinkKind::Stdout { format } => Box::new(StdoutSink::new(*format)),
+        SinkKind::RotatingFile {
+            dir,
+            name,
+            format,
+            max_bytes,
+        } => Box::new(RotatingFileSink::new(dir, name, *format, *max_bytes)?),
+        SinkKind::Files {
+            dir,
+            format,
+            compression,
+            period,
+        } => Box::new(FileWriter::new(dir, *format, *compression, *period)?),
+        SinkKind::Tcp { addr, format } => Box::new(LineSink::tcp(addr.as_str(), *format)?),
+        #[cfg(unix)]
+        SinkKind::Unix { path, format } => Box::new(LineSink::unix(path, *format)?),
+    };
+    Ok(spawn_sink(
+        CountingSink { inner, stats },
+        SinkOptions::default(),
+    ))
+}
+
+type StreamKey = (String, MarketType, MessageType);
+
+#[derive(Default)]
+struct StreamStats {
+    count: u64,
+    last_count: u64,
+    last_received_at: u64,
+    // errors and in-process recoveries reported by crawlers
+    errors: u64,
+    recoveries: u64,
+}
+
+// Counts messages per stream before writing them to the inner sink.
+struct CountingSink {
+    inner: Box<dyn Sink>,
+    stats: Arc<Mutex<HashMap<StreamKey, StreamStats>>>,
+}
+
+impl Sink for CountingSink {
+    fn write_batch(&mut self, batch: Vec<Message>) -> std::io::Result<()> {
+        {
+            let mut stats = self.stats.lock().unwrap();
+            for msg in batch.iter() {
+                let entry = stats
+                    .entry((msg.exchange.clone(), msg.market_type, msg.msg_type))
+                    .or_default();
+                entry.count += 1;
+                entry.last_received_at = msg.received_at;
+            }
+        }
+        self.inner.write_batch(batch)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Counts status events of crawlers per stream, events are logged by crawlers already.
+fn spawn_status_counter(stats: Arc<Mutex<HashMap<StreamKey, StreamStats>>>) {
+    let (tx, rx) = std::sync::mpsc::channel::<CrawlerStatus>();
+    set_status_sender(tx);
+    std::thread::spawn(move || {
+        for status in rx {
+            if let Some(msg_type) = status.msg_type {
+                let mut stats = stats.lock().unwrap();
+                let entry = stats
+                    .entry((status.exchange, status.market_type, msg_type))
+                    .or_default();
+                match status.kind {
+                    StatusKind::Error => entry.errors += 1,
+                    StatusKind::Recovered => entry.recoveries += 1,
+                }
+            }
+        }
+    });
+}
+
+struct Stream {
+    exchange: String,
+    market_type: MarketType,
+    msg_type: MessageType,
+    finished: Arc<AtomicBool>,
+}
+
+// Marks a stream as finished when its task returns or panics.
+struct FinishGuard(Arc<AtomicBool>);
+
+impl Drop for FinishGuard {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+fn resolve_symbols(crawler: &CrawlerConfig, market_type: MarketType) -> Option<Vec<String>> {
+    if crawler.symbols.is_none() && crawler.filter.is_none() {
+        return None;
+    }
+    let symbols = match crawler.symbols.as_ref() {
+        Some(symbols) => symbols.clone(),
+        None => match crypto_markets::fetch_symbols(&crawler.exchange, market_type) {
+            Ok(symbols) => symbols,
+            Err(err) => {
+                error!(
+                    "Failed to fetch symbols of {} {}, error: {}",
+                    crawler.exchange, market_type, err
+                );
+                Vec::new()
+            }
+        },
+    };
+    let filter = crawler.filter.as_ref();
+    Some(
+        symbols
+            .into_iter()
+            .filter(|symbol| filter.map(|f| f.matches(symbol)).unwrap_or(true))
+            .collect(),
+    )
+}
+
+// Pairs every symbol with every interval, None means all symbols with the default interval.
+fn resolve_symbol_intervals(
+    exchange: &str,
+    market_type: MarketType,
+    symbols: Option<&[String]>,
+    intervals: &[usize],
+) -> Result<Option<Vec<(String, usize)>>, String> {
+    if intervals.is_empty() {
+        return Ok(symbols.map(|symbols| {
+            symbols
+                .iter()
+                .map(|symbol| (symbol.clone(), 60))
+                .collect::<Vec<(String, usize)>>()
+        }));
+    }
+    let symbols = match symbols {
+        Some(symbols) => symbols.to_vec(),
+        None => crypto_markets::fetch_symbols(exchange, market_type)
+            .map_err(|err| format!("Failed to fetch symbols, error: {}", err))?,
+    };
+    Ok(Some(
+        symbols
+            .iter()
+            .flat_map(|symbol| {
+                intervals
+                    .iter()
+                    .map(move |interval| (symbol.clone(), *interval))
+            })
+            .collect::<Vec<(String, usize)>>(),
+    ))
+}
+
+fn spawn_stream(
+    exchange: String,
+    market_type: MarketType,
+    msg_type: MessageType,
+    symbols: Option<Vec<String>>,
+    intervals: Vec<usize>,
+    tx: SinkSender,
+) -> Result<Stream, String> {
+    let finished = Arc::new(AtomicBool::new(false));
+    let guard = FinishGuard(finished.clone());
+    let stream = Stream {
+        exchange: exchange.clone(),
+        market_type,
+        msg_type,
+        finished,
+    };
+    match msg_type {
+        MessageType::L2Snapshot | MessageType::L3Snapshot | MessageType::OpenInterest => {
+            tokio::task::spawn_blocking(move || {
+                let _guard = guard;
+                let symbols = symbols.as_deref();
+                match msg_type {
+                    MessageType::L2Snapshot => {
+                        crawl_l2_snapshot(&exchange, market_type, symbols, tx)
+                    }
+                    MessageType::L3Snapshot => {
+                        crawl_l3_snapshot(&exchange, market_type, symbols, tx)
+                    }
+                    _ => crawl_open_interest(&exchange, market_type, tx),
+                }
+            });
+        }
+        MessageType::Trade
+        | MessageType::L2Event
+        | MessageType::L3Event
+        | MessageType::BBO
+        | MessageType::L2TopK
+        | MessageType::Ticker
+        | MessageType::FundingRate
+        | MessageType::Candlestick => {
+            // crypto_markets blocks, so symbols are fetched before spawning the task
+            let symbol_interval_list = if msg_type == MessageType::Candlestick {
+                tokio::task::block_in_place(|| {
+                    resolve_symbol_intervals(&exchange, market_type, symbols.as_deref(), &intervals)
+                })?
+            } else {
+                None
+            };
+            tokio::task::spawn(async move {
+                let _guard = guard;
+                let symbols = symbols.as_deref();
+                match msg_type {
+                    MessageType::Trade => crawl_trade(&exchange, market_type, symbols, tx).await,
+                    MessageType::L2Event => {
+                        crawl_l2_event(&exchange, market_type, symbols, tx).await
+                    }
+                    MessageType::L3Event => {
+                        crawl_l3_event(&exchange, market_type, symbols, tx).await
+                    }
+                    MessageType::BBO => crawl_bbo(&exchange, market_type, symbols, tx).await,
+                    MessageType::L2TopK => crawl_l2_topk(&exchange, market_type, symbols, tx).await,
+                    MessageType::Ticker => crawl_ticker(&exchange, market_type, symbols, tx).await,
+                    MessageType::FundingRate => {
+                        crawl_funding_rate(&exchange, market_type, symbols, tx).await
+                    }
+                    _ => {
+                        crawl_candlestick(
+                            &exchange,
+                            market_type,
+                            symbol_interval_list.as_deref(),
+                            tx,
+                        )
+                        .await
+                    }
+                }
+            });
+        }
+        _ => return Err(format!("Unsupported message type: {}", msg_type)),
+    }
+    Ok(stream)
+}
+
+fn log_status(streams: &[Stream], stats: &Mutex<HashMap<StreamKey, StreamStats>>, interval: u64) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let crawler_topics = get_crawler_topics();
+    let mut stats = stats.lock().unwrap();
+    for stream in streams {
+        let key = (stream.exchange.clone(), stream.market_type, stream.msg_type);
+        let entry = stats.entry(key).or_default();
+        let (connections, topics) = crawler_topics
+            .iter()
+            .filter(|x| {
+                x.exchange == stream.exchange
+                    && x.market_type == stream.market_type
+                    && x.msg_type == stream.msg_type
+            })
+            .fold((0, 0), |(connections, topics), x| {
+                (
+                    connections + x.connections.len(),
+                    topics + x.topics_per_connection().iter().sum::<usize>(),
+                )
+            });
+        let last_message = if entry.last_received_at > 0 {
+            format!("{}s ago", now.saturating_sub(entry.last_received_at) / 1000)
+        } else {
+            "never".to_string()
+        };
+        let line = format!(
+            "{} {} {}: {} messages, {} in the last {}s, last message {}, {} connections, {} topics, {} errors, {} recoveries",
+            stream.exchange,
+            stream.market_type,
+            stream.msg_type,
+            entry.count,
+            entry.count - entry.last_count,
+            interval,
+            last_message,
+            connections,
+            topics,
+            entry.errors,
+            entry.recoveries,
+        );
+        if stream.finished.load(Ordering::Acquire) {
+            warn!("{}, stopped", line);
+        } else {
+            info!("{}", line);
+        }
+        entry.last_count = entry.count;
+    }
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        eprintln!("Usage: crypto-crawler <config.toml|config.yaml>");
+        std::process::exit(1);
+    }
+    let path = Path::new(&args[1]);
+    let config = match std::fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| parse_config(path, &text))
+    {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Invalid config {}, error: {}", path.display(), err);
+            std::process::exit(1);
+        }
+    };
+    if let Some(socket) = config.rate_limiter_socket.clone() {
+        #[cfg(unix)]
+        set_rate_limiter_backend(RateLimiterBackend::UnixSocket(socket));
+        #[cfg(not(unix))]
+        warn!("rate_limiter_socket {} requires Unix", socket.display());
+    }
+
+    let sink_configs = if config.sinks.is_empty() {
+        vec![SinkConfig {
+            name: "stdout".to_string(),
+            kind: SinkKind::Stdout {
+                format: LineFormat::Json,
+            },
+        }]
+    } else {
+        config.sinks
+    };
+    let stats = Arc::new(Mutex::new(HashMap::new()));
+    spawn_status_counter(stats.clone());
+    let mut senders = HashMap::new();
+    let mut sink_handles = Vec::new();
+    for sink_config in sink_configs.iter() {
+        match create_sink(&sink_config.kind, stats.clone()) {
+            Ok((tx, handle)) => {
+                senders.insert(sink_config.name.clone(), tx);
+                sink_handles.push((sink_config.name.clone(), handle));
+            }
+            Err(err) => {
+                eprintln!("Failed to create sink {}, error: {}", sink_config.name, err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // All crawlers run in this process, so they share the same rate limits
+    let mut streams = Vec::new();
+    for crawler in config.crawlers.iter() {
+        let sink_name = crawler
+            .sink
+            .clone()
+            .unwrap_or_else(|| sink_configs[0].name.clone());
+        for market_type in crawler.market_types.iter() {
+            let symbols = tokio::task::block_in_place(|| resolve_symbols(crawler, *market_type));
+            // An empty list means all symbols to crawl_* functions
+            if symbols.as_ref().map(|x| x.is_empty()).unwrap_or(false) {
+                error!(
+                    "No symbols selected for {} {}",
+                    crawler.exchange, market_type
+                );
+                continue;
+            }
+            for msg_type in crawler.msg_types.iter() {
+                match spawn_stream(
+                    crawler.exchange.clone(),
+                    *market_type,
+                    *msg_type,
+                    symbols.clone(),
+                    crawler.intervals.clone(),
+                    senders[&sink_name].clone(),
+                ) {
+                    Ok(stream) => streams.push(stream),
+                    Err(err) => error!("{} {}, {}", crawler.exchange, market_type, err),
+                }
+            }
+        }
+    }
+    drop(senders);
+
+    let interval = config.status_interval.max(1);
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+        log_status(&streams, &stats, interval);
+        if let Some((name, _)) = sink_handles.iter().find(|(_, handle)| handle.is_finished()) {
+            error!("Sink {} stopped, exiting", name);
+            break;
+        }
+        if streams
+            .iter()
+            .all(|stream| stream.finished.load(Ordering::Acquire))
+        {
+            info!("All crawlers stopped, exiting");
+            break;
+        }
+    }
+    let mut failed = false;
+    for (name, handle) in sink_handles {
+        if handle.is_finished() {
+            if let Err(err) = handle.join() {
+                error!("Sink {} failed, error: {}", name, err);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_toml() {
+        let text = r#"
+status_interval = 30
+rate_limiter_socket = "/tmp/crypto-crawler.sock"
+
+[[sinks]]
+name = "files"
+type = "files"
+dir = "/data"
+format = "tsv"
+compression = "zstd"
+period = "hourly"
+
+[[sinks]]
+name = "stdout"
+type = "stdout"
+
+[[crawlers]]
+exchange = "binance"
+market_types = ["spot", "linear_swap"]
+msg_types = ["trade", "candlestick"]
+filter = { include = ["usdt"], exclude = ["busd"] }
+intervals = [60, 300]
+sink = "files"
+
+[[crawlers]]
+exchange = "okx"
+market_types = ["spot"]
+msg_types = ["bbo"]
+symbols = ["BTC-USDT"]
+sink = "stdout"
+"#;
+        let config = parse_config(Path::new("crawler.toml"), text).unwrap();
+        assert_eq!(30, config.status_interval);
+        assert_eq!(
+            Some(PathBuf::from("/tmp/crypto-crawler.sock")),
+            config.rate_limiter_socket
+        );
+        assert_eq!(2, config.sinks.len());
+        assert!(matches!(
+            config.sinks[0].kind,
+            SinkKind::Files {
+                format: LineFormat::Tsv,
+                compression: Compression::Zstd,
+                period: Period::Hourly,
+                ..
+            }
+        ));
+        assert!(matches!(
+            config.sinks[1].kind,
+            SinkKind::Stdout {
+                format: LineFormat::Json
+            }
+        ));
+        let binance = &config.crawlers[0];
+        assert_eq!(
+            vec![MarketType::Spot, MarketType::LinearSwap],
+            binance.market_types
+        );
+        assert_eq!(
+            vec![MessageType::Trade, MessageType::Candlestick],
+            binance.msg_types
+        );
+        assert_eq!(vec![60, 300], binance.intervals);
+        let filter = binance.filter.as_ref().unwrap();
+        assert!(filter.matches("BTCUSDT"));
+        assert!(!filter.matches("BTCBUSD"));
+        assert!(!filter.matches("BTCUSDT_BUSD"));
+        assert_eq!(
+            Some(vec!["BTC-USDT".to_string()]),
+            config.crawlers[1].symbols
+        );
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let text = r#"
+sinks:
+  - name: socket
+    type: tcp
+    addr: 127.0.0.1:9000
+crawlers:
+  - exchange: bitmex
+    market_types: [unknown]
+    msg_types: [trade, funding_rate]
+"#;
+        let config = parse_config(Path::new("crawler.yaml"), text).unwrap();
+        assert_eq!(60, config.status_interval);
+        assert!(matches!(config.sinks[0].kind, SinkKind::Tcp { .. }));
+        assert_eq!(None, config.crawlers[0].sink);
+        assert!(config.crawlers[0].symbols.is_none());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let text = r#"
+[[sinks]]
+name = "a"
+type = "stdout"
+
+[[sinks]]
+name = "b"
+type = "stdout"
+
+[[crawlers]]
+exchange = "binance"
+market_types = ["spot"]
+msg_types = ["trade"]
+"#;
+        assert!(parse_config(Path::new("crawler.toml"), text).is_err());
+        assert!(parse_config(Path::new("crawler.json"), "{}").is_err());
+    }
+}
@@ -0,0 +1,503 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, RecvTimeoutError, SendError, Sender, SyncSender},
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use crate::msg::Message;
+
+/// A destination of crawled messages.
+///
+/// Sinks are driven by a dedicated thread started by `spawn_sink()`, which calls
+/// `write_batch()` with up to `SinkOptions::batch_size` messages and `flush()` every
+/// `SinkOptions::flush_interval`. An error stops the thread, and every crawler sending to it
+/// stops as well.
+pub trait Sink: Send {
+    /// Writes a batch of messages, which may stay buffered until `flush()`.
+    fn write_batch(&mut self, batch: Vec<Message>) -> io::Result<()>;
+
+    /// Flushes buffered messages, called periodically and before the sink is dropped.
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/// Options of the thread which drives a sink.
+#[derive(Clone, Debug)]
+pub struct SinkOptions {
+    /// Max number of messages queued for the sink, crawlers block when the queue is full.
+    pub capacity: usize,
+    /// Max number of messages per `write_batch()`.
+    pub batch_size: usize,
+    /// How often to call `flush()`.
+    pub flush_interval: Duration,
+}
+
+impl Default for SinkOptions {
+    fn default() -> Self {
+        SinkOptions {
+            capacity: 10000,
+            batch_size: 1024,
+            flush_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum SenderInner {
+    Unbounded(Sender<Message>),
+    Bounded(SyncSender<Message>),
+}
+
+/// The sending side of crawled messages, accepted by all `crawl_*` functions.
+///
+/// It's converted from a `std::sync::mpsc::Sender<Message>`, a `SyncSender<Message>`, or
+/// returned by `spawn_sink()`. Sending blocks while a bounded queue is full.
+#[derive(Clone)]
+pub struct SinkSender(SenderInner);
+
+impl SinkSender {
+    /// Sends a message, fails if the receiver or the sink has stopped.
+    pub fn send(&self, msg: Message) -> Result<(), SendError<Message>> {
+        match &self.0 {
+            SenderInner::Unbounded(tx) => tx.send(msg),
+            SenderInner::Bounded(tx) => tx.send(msg),
+        }
+    }
+}
+
+impl From<Sender<Message>> for SinkSender {
+    fn from(tx: Sender<Message>) -> Self {
+        SinkSender(SenderInner::Unbounded(tx))
+    }
+}
+
+impl From<SyncSender<Message>> for SinkSender {
+    fn from(tx: SyncSender<Message>) -> Self {
+        SinkSender(SenderInner::Bounded(tx))
+    }
+}
+
+/// A handle of the thread which drives a sink.
+pub struct SinkHandle {
+    thread: JoinHandle<io::Result<()>>,
+}
+
+impl SinkHandle {
+    /// Waits until all senders are dropped and remaining messages are flushed.
+    ///
+    /// Returns the error which stopped the sink, if any.
+    pub fn join(self) -> io::Result<()> {
+        self.thread
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("sink panicked")))
+    }
+
+    /// Whether the sink has stopped, because all senders are dropped or it failed.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+}
+
+/// Starts a thread which writes messages to `sink` in batches.
+pub fn spawn_sink<S: Sink + 'static>(sink: S, options: SinkOptions) -> (SinkSender, SinkHandle) {
+    let (tx, rx) = std::sync::mpsc::sync_channel(options.capacity);
+    let thread = std::thread::spawn(move || {
+        let result = drive_sink(sink, rx, &options);
+        if let Err(err) = result.as_ref() {
+            error!("Sink stopped, error: {}", err);
+        }
+        result
+    });
+    (tx.into(), SinkHandle { thread })
+}
+
+fn drive_sink<S: Sink>(
+    mut sink: S,
+    rx: Receiver<Message>,
+    options: &SinkOptions,
+) -> io::Result<()> {
+    let batch_size = options.batch_size.max(1);
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut last_flush = Instant::now();
+    loop {
+        let timeout = options.flush_interval.saturating_sub(last_flush.elapsed());
+        match rx.recv_timeout(timeout) {
+            Ok(msg) => {
+                batch.push(msg);
+                if batch.len() >= batch_size {
+                    sink.write_batch(std::mem::replace(
+                        &mut batch,
+                        Vec::with_capacity(batch_size),
+                    ))?;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                if !batch.is_empty() {
+                    sink.write_batch(batch)?;
+                }
+                return sink.flush();
+            }
+        }
+        if last_flush.elapsed() >= options.flush_interval {
+            if !batch.is_empty() {
+                sink.write_batch(std::mem::replace(
+                    &mut batch,
+                    Vec::with_capacity(batch_size),
+                ))?;
+            }
+            sink.flush()?;
+            last_flush = Instant::now();
+        }
+    }
+}
+
+/// Line formats of sinks which write text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineFormat {
+    /// One JSON object per line, see `Message`'s `Display`.
+    Json,
+    /// `Message::to_tsv_string()`, without exchange, market type and message type.
+    Tsv,
+}
+
+impl LineFormat {
+    pub(crate) fn format(&self, msg: &Message) -> String {
+        match self {
+            LineFormat::Json => msg.to_string(),
+            LineFormat::Tsv => msg.to_tsv_string(),
+        }
+    }
+
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            LineFormat::Json => "jsonl",
+            LineFormat::Tsv => "tsv",
+        }
+    }
+}
+
+// Writes messages as lines.
+fn write_lines<W: Write>(
+    writer: &mut W,
+    format: LineFormat,
+    batch: &[Message],
+) -> io::Result<usize> {
+    let mut written = 0;
+    for msg in batch {
+        let line = format.format(msg);
+        writer.write_all(line.as_bytes())?;
+        writer.write_all(b"\n")?;
+        written += line.len() + 1;
+    }
+    Ok(written)
+}
+
+/// Writes messages to stdout.
+pub struct StdoutSink {
+    format: LineFormat,
+    writer: BufWriter<io::Stdout>,
+}
+
+impl StdoutSink {
+    pub fn new(format: LineFormat) -> Self {
+        StdoutSink {
+            format,
+            writer: BufWriter::new(io::stdout()),
+        }
+    }
+}
+
+impl Sink for StdoutSink {
+    fn write_batch(&mut self, batch: Vec<Message>) -> io::Result<()> {
+        write_lines(&mut self.writer, self.format, &batch).map(|_| ())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Writes messages to files in a directory, and starts a new file once the current one
+/// exceeds `max_bytes`.
+///
+/// Files are named `{name}.{unix_millis}.{seq}.{jsonl|tsv}` after the time they were created,
+/// where `seq` increases with every file, so that files created within the same millisecond
+/// don't collide.
+pub struct RotatingFileSink {
+    dir: PathBuf,
+    name: String,
+    format: LineFormat,
+    max_bytes: u64,
+    // sequence number of the next file
+    seq: u64,
+    current: Option<(BufWriter<File>, u64)>,
+}
+
+impl RotatingFileSink {
+    /// Creates `dir` if it doesn't exist, files are created lazily.
+    pub fn new(
+        dir: impl AsRef<Path>,
+        name: &str,
+        format: LineFormat,
+        max_bytes: u64,
+    ) -> io::Result<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        Ok(RotatingFileSink {
+            dir: dir.as_ref().to_path_buf(),
+            name: name.to_string(),
+            format,
+            max_bytes,
+            seq: 0,
+            current: None,
+        })
+    }
+
+    // Creates a new file, never appends to an existing one, e.g., of another process.
+    fn open(&mut self) -> io::Result<BufWriter<File>> {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        loop {
+            let path = self.dir.join(format!(
+                "{}.{}.{}.{}",
+                self.name,
+                millis,
+                self.seq,
+                self.format.extension()
+            ));
+            self.seq += 1;
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)
+            {
+                Ok(file) => return Ok(BufWriter::new(file)),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl Sink for RotatingFileSink {
+    fn write_batch(&mut self, batch: Vec<Message>) -> io::Result<()> {
+        if self.current.is_none() {
+            self.current = Some((self.open()?, 0));
+        }
+        let (writer, size) = self.current.as_mut().unwrap();
+        *size += write_lines(writer, self.format, &batch)? as u64;
+        if *size >= self.max_bytes {
+            writer.flush()?;
+            self.current = None;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.current.as_mut() {
+            Some((writer, _)) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Forwards messages to a tokio channel, waiting while the channel is full.
+pub struct ChannelSink {
+    tx: tokio::sync::mpsc::Sender<Message>,
+}
+
+impl ChannelSink {
+    pub fn new(tx: tokio::sync::mpsc::Sender<Message>) -> Self {
+        ChannelSink { tx }
+    }
+}
+
+impl Sink for ChannelSink {
+    fn write_batch(&mut self, batch: Vec<Message>) -> io::Result<()> {
+        for msg in batch {
+            self.tx
+                .blocking_send(msg)
+                .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "receiver dropped"))?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes messages as lines to a TCP or Unix domain socket.
+pub struct LineSink {
+    format: LineFormat,
+    writer: BufWriter<Box<dyn Write + Send>>,
+}
+
+impl LineSink {
+    /// Connects to a TCP server, e.g., `127.0.0.1:9000`.
+    pub fn tcp(addr: impl std::net::ToSocketAddrs, format: LineFormat) -> io::Result<Self> {
+        let stream = std::net::TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Self::with_writer(Box::new(stream), format))
+    }
+
+    /// Connects to a Unix domain socket.
+    #[cfg(unix)]
+    pub fn unix(path: impl AsRef<Path>, format: LineFormat) -> io::Result<Self> {
+        let stream = std::os::unix::net::UnixStream::connect(path)?;
+        Ok(Self::with_writer(Box::new(stream), format))
+    }
+
+    fn with_writer(writer: Box<dyn Write + Send>, format: LineFormat) -> Self {
+        LineSink {
+            format,
+            writer: BufWriter::new(writer),
+        }
+    }
+}
+
+impl Sink for LineSink {
+    fn write_batch(&mut self, batch: Vec<Message>) -> io::Result<()> {
+        write_lines(&mut self.writer, self.format, &batch).map(|_| ())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, BufRead},
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{spawn_sink, LineFormat, LineSink, RotatingFileSink, Sink, SinkOptions};
+    use crate::{Message, MessageType};
+    use crypto_market_type::MarketType;
+
+    fn message(i: usize) -> Message {
+        Message::new(
+            "binance".to_string(),
+            MarketType::Spot,
+            MessageType::Trade,
+            format!(r#"{{"id":{}}}"#, i),
+        )
+    }
+
+    #[derive(Clone, Default)]
+    struct MemorySink {
+        batches: Arc<Mutex<Vec<usize>>>,
+        flushes: Arc<Mutex<usize>>,
+        fail: bool,
+    }
+
+    impl Sink for MemorySink {
+        fn write_batch(&mut self, batch: Vec<Message>) -> io::Result<()> {
+            if self.fail {
+                return Err(io::Error::other("disk full"));
+            }
+            self.batches.lock().unwrap().push(batch.len());
+            Ok(())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            *self.flushes.lock().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_batching() {
+        let sink = MemorySink::default();
+        let options = SinkOptions {
+            capacity: 16,
+            batch_size: 4,
+            flush_interval: Duration::from_secs(60),
+        };
+        let (tx, handle) = spawn_sink(sink.clone(), options);
+        for i in 0..10 {
+            tx.send(message(i)).unwrap();
+        }
+        drop(tx);
+        handle.join().unwrap();
+        assert_eq!(vec![4, 4, 2], *sink.batches.lock().unwrap());
+        assert_eq!(1, *sink.flushes.lock().unwrap());
+    }
+
+    #[test]
+    fn test_failure_stops_senders() {
+        let sink = MemorySink {
+            fail: true,
+            ..Default::default()
+        };
+        let options = SinkOptions {
+            capacity: 1,
+            batch_size: 1,
+            flush_interval: Duration::from_secs(60),
+        };
+        let (tx, handle) = spawn_sink(sink, options);
+        // the first sends succeed before the sink thread fails
+        while tx.send(message(0)).is_ok() {}
+        assert_eq!("disk full", handle.join().unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_rotating_file_sink() {
+        let dir = std::env::temp_dir().join(format!("sink-{}", rand::random::<u64>()));
+        let mut sink = RotatingFileSink::new(&dir, "binance.trade", LineFormat::Json, 1).unwrap();
+        sink.write_batch(vec![message(0)]).unwrap();
+        std::thread::sleep(Duration::from_millis(2));
+        sink.write_batch(vec![message(1), message(2)]).unwrap();
+        sink.flush().unwrap();
+
+        let mut paths = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(2, paths.len());
+        let lines = std::fs::read_to_string(&paths[1]).unwrap();
+        assert_eq!(2, lines.lines().count());
+        assert!(lines.lines().next().unwrap().contains(r#"{\"id\":1}"#));
+
+        // files rotated within the same millisecond are not appended to
+        for i in 3..6 {
+            sink.write_batch(vec![message(i)]).unwrap();
+        }
+        assert_eq!(5, std::fs::read_dir(&dir).unwrap().count());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_line_sink() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, handle) = spawn_sink(
+            LineSink::tcp(addr, LineFormat::Tsv).unwrap(),
+            SinkOptions::default(),
+        );
+        let (stream, _) = listener.accept().unwrap();
+        tx.send(message(0)).unwrap();
+        tx.send(message(1)).unwrap();
+        drop(tx);
+        handle.join().unwrap();
+
+        let lines = io::BufReader::new(stream)
+            .lines()
+            .map(|line| line.unwrap())
+            .collect::<Vec<String>>();
+        assert_eq!(2, lines.len());
+        assert!(lines[1].ends_with("\t\t{\"id\":1}"));
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::{mpsc::Sender, Mutex};
+
+use crypto_market_type::MarketType;
+use crypto_msg_type::MessageType;
+use log::*;
+use once_cell::sync::Lazy;
+
+/// Kinds of events in `CrawlerStatus`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusKind {
+    /// Something failed, and the crawler skipped it and keeps running.
+    Error,
+    /// The crawler recovered in-process from a condition that used to require a restart.
+    Recovered,
+}
+
+/// An event reported by crawlers to the sender registered by `set_status_sender()`.
+#[derive(Clone, Debug)]
+pub struct CrawlerStatus {
+    pub exchange: String,
+    pub market_type: MarketType,
+    /// None if the event is not specific to a message type, e.g., a rate limiter failure.
+    pub msg_type: Option<MessageType>,
+    pub kind: StatusKind,
+    pub detail: String,
+}
+
+static STATUS_SENDER: Lazy<Mutex<Option<Sender<CrawlerStatus>>>> = Lazy::new(|| Mutex::new(None));
+
+/// Registers a channel which receives status events of all crawlers in this process.
+///
+/// Events are logged regardless, and the previous sender is replaced.
+pub fn set_status_sender(tx: Sender<CrawlerStatus>) {
+    *STATUS_SENDER.lock().unwrap_or_else(|err| err.into_inner()) = Some(tx);
+}
+
+/// Logs an event and sends it to the registered status sender, if any.
+pub(crate) fn report(
+    exchange: &str,
+    market_type: MarketType,
+    msg_type: Option<MessageType>,
+    kind: StatusKind,
+    detail: String,
+) {
+    let msg_type_str = msg_type.map(|x| x.to_string()).unwrap_or_default();
+    match kind {
+        StatusKind::Error => error!("{} {} {} {}", exchange, market_type, msg_type_str, detail),
+        StatusKind::Recovered => warn!("{} {} {} {}", exchange, market_type, msg_type_str, detail),
+    }
+    let mut guard = STATUS_SENDER.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(tx) = guard.as_ref() {
+        let status = CrawlerStatus {
+            exchange: exchange.to_string(),
+            market_type,
+            msg_type,
+            kind,
+            detail,
+        };
+        if tx.send(status).is_err() {
+            // the receiver has been dropped
+            *guard = None;
+        }
+    }
+}
@@ -0,0 +1,689 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Utc};
+use crypto_market_type::MarketType;
+use crypto_msg_type::MessageType;
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    msg::Message,
+    sink::{LineFormat, Sink},
+};
+
+const TMP_EXTENSION: &str = "tmp";
+const RECOVER_EXTENSION: &str = "recover";
+
+/// The time range covered by a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    /// One file per UTC hour, labeled `%Y-%m-%d-%H`.
+    Hourly,
+    /// One file per UTC day, labeled `%Y-%m-%d`.
+    Daily,
+}
+
+impl Period {
+    fn label(&self, timestamp_millis: u64) -> String {
+        let time: DateTime<Utc> = (UNIX_EPOCH + Duration::from_millis(timestamp_millis)).into();
+        match self {
+            Period::Hourly => time.format("%Y-%m-%d-%H").to_string(),
+            Period::Daily => time.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// Compression algorithms of files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+        }
+    }
+}
+
+// Metadata parsed from a file name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct FileMeta {
+    exchange: String,
+    market_type: MarketType,
+    msg_type: MessageType,
+    period: String,
+    part: u32,
+    format: LineFormat,
+    compression: Compression,
+}
+
+impl FileMeta {
+    // Parses `{exchange}.{market_type}.{msg_type}.{period}[.{part}].{jsonl|tsv}.{gz|zst}`.
+    fn parse(file_name: &str) -> Option<Self> {
+        let v: Vec<&str> = file_name.split('.').collect();
+        if v.len() != 6 && v.len() != 7 {
+            return None;
+        }
+        let compression = match v[v.len() - 1] {
+            "gz" => Compression::Gzip,
+            "zst" => Compression::Zstd,
+            _ => return None,
+        };
+        let format = match v[v.len() - 2] {
+            "jsonl" => LineFormat::Json,
+            "tsv" => LineFormat::Tsv,
+            _ => return None,
+        };
+        let part = if v.len() == 7 {
+            v[4].parse::<u32>().ok()?
+        } else {
+            0
+        };
+        Some(FileMeta {
+            exchange: v[0].to_string(),
+            market_type: MarketType::from_str(v[1]).ok()?,
+            msg_type: MessageType::from_str(v[2]).ok()?,
+            period: v[3].to_string(),
+            part,
+            format,
+            compression,
+        })
+    }
+
+    fn file_name(&self) -> String {
+        let part = if self.part == 0 {
+            "".to_string()
+        } else {
+            format!(".{}", self.part)
+        };
+        format!(
+            "{}.{}.{}.{}{}.{}.{}",
+            self.exchange,
+            self.market_type,
+            self.msg_type,
+            self.period,
+            part,
+            self.format.extension(),
+            self.compression.extension()
+        )
+    }
+
+    fn parse_line(&self, line: &str) -> Result<Message, String> {
+        match self.format {
+            LineFormat::Json => {
+                serde_json::from_str::<Message>(line).map_err(|err| err.to_string())
+            }
+            LineFormat::Tsv => {
+                if line.split('\t').count() != 3 {
+                    return Err(format!("Expected 3 fields, line: {}", line));
+                }
+                if line.split('\t').next().unwrap().parse::<u64>().is_err() {
+                    return Err(format!("Invalid timestamp, line: {}", line));
+                }
+                Ok(Message::from_tsv_string(
+                    &self.exchange,
+                    &self.market_type.to_string(),
+                    &self.msg_type.to_string(),
+                    line,
+                ))
+            }
+        }
+    }
+}
+
+enum Encoder {
+    Gzip(GzEncoder<File>),
+    Zstd(zstd::Encoder<'static, File>),
+}
+
+impl Encoder {
+    fn new(file: File, compression: Compression) -> io::Result<Self> {
+        match compression {
+            Compression::Gzip => Ok(Encoder::Gzip(GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            ))),
+            Compression::Zstd => Ok(Encoder::Zstd(zstd::Encoder::new(file, 0)?)),
+        }
+    }
+
+    // Writes the trailer of the compressed stream and syncs the file to disk.
+    fn finish(self) -> io::Result<()> {
+        let file = match self {
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            Encoder::Zstd(encoder) => encoder.finish()?,
+        };
+        file.sync_all()
+    }
+}
+
+impl Write for Encoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+fn open_decoder(path: &Path, compression: Compression) -> io::Result<Box<dyn BufRead + Send>> {
+    let file = File::open(path)?;
+    match compression {
+        Compression::Gzip => Ok(Box::new(BufReader::new(MultiGzDecoder::new(file)))),
+        Compression::Zstd => Ok(Box::new(BufReader::new(zstd::Decoder::new(file)?))),
+    }
+}
+
+// Lists all files under `dir` recursively.
+fn list_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(list_files(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+}
+
+// Renames `from` to the first name of `meta` which doesn't exist, increasing `meta.part`.
+fn rename_to_final(from: &Path, dir: &Path, meta: &mut FileMeta) -> io::Result<PathBuf> {
+    loop {
+        let path = dir.join(meta.file_name());
+        if !path.exists() {
+            std::fs::rename(from, &path)?;
+            return Ok(path);
+        }
+        meta.part += 1;
+    }
+}
+
+// Salvages complete lines of a temporary file left by a crash into a final file.
+fn recover_file(tmp_path: &Path) -> io::Result<Option<PathBuf>> {
+    let dir = tmp_path.parent().unwrap_or_else(|| Path::new("."));
+    let mut meta = match FileMeta::parse(file_name(&tmp_path.with_extension(""))) {
+        Some(meta) => meta,
+        None => {
+            warn!("Unknown temporary file {}", tmp_path.display());
+            return Ok(None);
+        }
+    };
+
+    let mut reader = open_decoder(tmp_path, meta.compression)?;
+    let recover_path = tmp_path.with_extension(RECOVER_EXTENSION);
+    let mut encoder = Encoder::new(File::create(&recover_path)?, meta.compression)?;
+    let mut num_lines = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            // A truncated line is the last one written before the crash
+            Ok(n) if n > 0 && line.ends_with(b"\n") => {
+                encoder.write_all(&line)?;
+                num_lines += 1;
+            }
+            Ok(_) => break,
+            // The compressed stream is truncated
+            Err(_) => break,
+        }
+    }
+    encoder.finish()?;
+
+    let result = if num_lines > 0 {
+        let path = rename_to_final(&recover_path, dir, &mut meta)?;
+        info!(
+            "Recovered {} lines from {} to {}",
+            num_lines,
+            tmp_path.display(),
+            path.display()
+        );
+        Some(path)
+    } else {
+        std::fs::remove_file(&recover_path)?;
+        None
+    };
+    std::fs::remove_file(tmp_path)?;
+    Ok(result)
+}
+
+/// Recovers temporary files under `root` left by a crashed `FileWriter`.
+///
+/// Complete lines are moved to final files, and the truncated tail is dropped. It's called
+/// by `FileWriter::new()`, so it's only needed before reading a directory which is not
+/// going to be written again.
+pub fn recover_files(root: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    let files = list_files(root.as_ref())?;
+    // Leftovers of a previous recovery, the temporary files are still there
+    for path in files.iter() {
+        if path.extension().and_then(|s| s.to_str()) == Some(RECOVER_EXTENSION) {
+            std::fs::remove_file(path)?;
+        }
+    }
+    let mut recovered = Vec::new();
+    for path in files.iter() {
+        if path.extension().and_then(|s| s.to_str()) == Some(TMP_EXTENSION) {
+            if let Some(path) = recover_file(path)? {
+                recovered.push(path);
+            }
+        }
+    }
+    Ok(recovered)
+}
+
+struct OpenFile {
+    meta: FileMeta,
+    dir: PathBuf,
+    tmp_path: PathBuf,
+    encoder: Encoder,
+}
+
+impl OpenFile {
+    fn finish(self) -> io::Result<PathBuf> {
+        let OpenFile {
+            mut meta,
+            dir,
+            tmp_path,
+            encoder,
+        } = self;
+        encoder.finish()?;
+        rename_to_final(&tmp_path, &dir, &mut meta)
+    }
+}
+
+/// Writes messages to compressed files partitioned by exchange, market type, message type
+/// and period.
+///
+/// Files are stored as
+/// `{root}/{exchange}/{market_type}/{msg_type}/{exchange}.{market_type}.{msg_type}.{period}.{jsonl|tsv}.{gz|zst}`.
+/// A file is written to a temporary `.tmp` file first, and renamed once its period is over,
+/// so a file with the final name is always complete. If the final name exists, for example
+/// after a restart, a part number is appended to the period.
+///
+/// Temporary files left by a crash are recovered by `new()`.
+pub struct FileWriter {
+    root: PathBuf,
+    format: LineFormat,
+    compression: Compression,
+    period: Period,
+    files: HashMap<(String, MarketType, MessageType), OpenFile>,
+}
+
+impl FileWriter {
+    /// Creates `root` if it doesn't exist, and recovers temporary files left by a crash.
+    pub fn new(
+        root: impl AsRef<Path>,
+        format: LineFormat,
+        compression: Compression,
+        period: Period,
+    ) -> io::Result<Self> {
+        std::fs::create_dir_all(root.as_ref())?;
+        recover_files(root.as_ref())?;
+        Ok(FileWriter {
+            root: root.as_ref().to_path_buf(),
+            format,
+            compression,
+            period,
+            files: HashMap::new(),
+        })
+    }
+
+    fn open(&self, msg: &Message, period: String) -> io::Result<OpenFile> {
+        let dir = self
+            .root
+            .join(&msg.exchange)
+            .join(msg.market_type.to_string())
+            .join(msg.msg_type.to_string());
+        std::fs::create_dir_all(&dir)?;
+        let meta = FileMeta {
+            exchange: msg.exchange.clone(),
+            market_type: msg.market_type,
+            msg_type: msg.msg_type,
+            period,
+            part: 0,
+            format: self.format,
+            compression: self.compression,
+        };
+        let tmp_path = dir.join(format!("{}.{}", meta.file_name(), TMP_EXTENSION));
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+        Ok(OpenFile {
+            meta,
+            dir,
+            tmp_path,
+            encoder: Encoder::new(file, self.compression)?,
+        })
+    }
+
+    fn write(&mut self, msg: &Message) -> io::Result<()> {
+        let key = (msg.exchange.clone(), msg.market_type, msg.msg_type);
+        let period = self.period.label(msg.received_at);
+        if let Some(file) = self.files.get(&key) {
+            if file.meta.period != period {
+                self.files.remove(&key).unwrap().finish()?;
+            }
+        }
+        if !self.files.contains_key(&key) {
+            let file = self.open(msg, period)?;
+            self.files.insert(key.clone(), file);
+        }
+        let file = self.files.get_mut(&key).unwrap();
+        file.encoder.write_all(self.format.format(msg).as_bytes())?;
+        file.encoder.write_all(b"\n")
+    }
+
+    // Renames files whose period is over.
+    fn rotate(&mut self) -> io::Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let period = self.period.label(now);
+        let expired: Vec<(String, MarketType, MessageType)> = self
+            .files
+            .iter()
+            .filter(|(_, file)| file.meta.period < period)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired {
+            self.files.remove(&key).unwrap().finish()?;
+        }
+        Ok(())
+    }
+}
+
+impl Sink for FileWriter {
+    fn write_batch(&mut self, batch: Vec<Message>) -> io::Result<()> {
+        for msg in batch.iter() {
+            self.write(msg)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.rotate()?;
+        for file in self.files.values_mut() {
+            file.encoder.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FileWriter {
+    fn drop(&mut self) {
+        for (_, file) in self.files.drain() {
+            let tmp_path = file.tmp_path.clone();
+            if let Err(err) = file.finish() {
+                error!("Failed to finish {}, error: {}", tmp_path.display(), err);
+            }
+        }
+    }
+}
+
+/// Iterates messages stored by `FileWriter` under a directory.
+///
+/// Files are read partition by partition in the order of periods, temporary files are
+/// skipped.
+pub struct FileReader {
+    files: VecDeque<(PathBuf, FileMeta)>,
+    current: Option<(PathBuf, FileMeta, Box<dyn BufRead + Send>)>,
+}
+
+impl FileReader {
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        let mut files: Vec<(PathBuf, FileMeta)> = list_files(root.as_ref())?
+            .into_iter()
+            .filter_map(|path| FileMeta::parse(file_name(&path)).map(|meta| (path, meta)))
+            .collect();
+        files.sort_by(|(x, x_meta), (y, y_meta)| {
+            (x.parent(), &x_meta.period, x_meta.part).cmp(&(
+                y.parent(),
+                &y_meta.period,
+                y_meta.part,
+            ))
+        });
+        Ok(FileReader {
+            files: files.into(),
+            current: None,
+        })
+    }
+}
+
+impl Iterator for FileReader {
+    type Item = io::Result<Message>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.current.is_none() {
+                let (path, meta) = self.files.pop_front()?;
+                match open_decoder(&path, meta.compression) {
+                    Ok(reader) => self.current = Some((path, meta, reader)),
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+            let (path, meta, reader) = self.current.as_mut().unwrap();
+            let mut line = String::new();
+            match reader.read_line(&mut line) {
+                Ok(0) => self.current = None,
+                Ok(_) => {
+                    let line = line.trim_end_matches('\n');
+                    if line.is_empty() {
+                        continue;
+                    }
+                    return Some(meta.parse_line(line).map_err(|err| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("{}: {}", path.display(), err),
+                        )
+                    }));
+                }
+                Err(err) => {
+                    // Skip the rest of a corrupted file
+                    self.current = None;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("crypto-crawler-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn new_message(exchange: &str, msg_type: MessageType, received_at: u64) -> Message {
+        let mut msg = Message::new_with_symbol(
+            exchange.to_string(),
+            MarketType::Spot,
+            msg_type,
+            "BTCUSDT".to_string(),
+            format!(r#"{{"t":{}}}"#, received_at),
+        );
+        msg.received_at = received_at;
+        msg
+    }
+
+    #[test]
+    fn test_file_meta() {
+        let meta = FileMeta::parse("binance.spot.trade.2022-08-01-14.tsv.gz").unwrap();
+        assert_eq!("binance", meta.exchange);
+        assert_eq!(MarketType::Spot, meta.market_type);
+        assert_eq!(MessageType::Trade, meta.msg_type);
+        assert_eq!("2022-08-01-14", meta.period);
+        assert_eq!(0, meta.part);
+        assert_eq!(LineFormat::Tsv, meta.format);
+        assert_eq!(Compression::Gzip, meta.compression);
+
+        let meta =
+            FileMeta::parse("coinbase_pro.linear_swap.l2_event.2022-08-01.2.jsonl.zst").unwrap();
+        assert_eq!(2, meta.part);
+        assert_eq!(
+            "coinbase_pro.linear_swap.l2_event.2022-08-01.2.jsonl.zst",
+            meta.file_name()
+        );
+
+        assert!(FileMeta::parse("binance.spot.trade.2022-08-01-14.tsv.gz.tmp").is_none());
+        assert!(FileMeta::parse("binance.spot.trade.2022-08-01-14.csv.gz").is_none());
+    }
+
+    #[test]
+    fn test_period() {
+        // 2022-08-01T14:30:00Z
+        assert_eq!("2022-08-01-14", Period::Hourly.label(1659364200000));
+        assert_eq!("2022-08-01", Period::Daily.label(1659364200000));
+    }
+
+    fn test_round_trip(format: LineFormat, compression: Compression) {
+        let root = temp_dir();
+        let hour = 3600 * 1000;
+        let start = 1659364200000;
+        let messages = vec![
+            new_message("binance", MessageType::Trade, start),
+            new_message("okx", MessageType::Trade, start + 1),
+            new_message("binance", MessageType::Trade, start + hour),
+            new_message("binance", MessageType::L2Event, start + 2),
+        ];
+        {
+            let mut writer = FileWriter::new(&root, format, compression, Period::Hourly).unwrap();
+            writer.write_batch(messages).unwrap();
+            writer.flush().unwrap();
+        }
+
+        let ext = format!("{}.{}", format.extension(), compression.extension());
+        let binance_trade = root.join("binance/spot/trade");
+        assert!(binance_trade
+            .join(format!("binance.spot.trade.2022-08-01-14.{}", ext))
+            .exists());
+        assert!(binance_trade
+            .join(format!("binance.spot.trade.2022-08-01-15.{}", ext))
+            .exists());
+
+        let messages = FileReader::new(&root)
+            .unwrap()
+            .collect::<io::Result<Vec<Message>>>()
+            .unwrap();
+        let actual: Vec<(String, MessageType, u64)> = messages
+            .iter()
+            .map(|msg| (msg.exchange.clone(), msg.msg_type, msg.received_at))
+            .collect();
+        assert_eq!(
+            vec![
+                ("binance".to_string(), MessageType::L2Event, start + 2),
+                ("binance".to_string(), MessageType::Trade, start),
+                ("binance".to_string(), MessageType::Trade, start + hour),
+                ("okx".to_string(), MessageType::Trade, start + 1),
+            ],
+            actual
+        );
+        assert_eq!(Some("BTCUSDT".to_string()), messages[0].symbol);
+        assert_eq!(format!(r#"{{"t":{}}}"#, start + 2), messages[0].json);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_tsv_gzip() {
+        test_round_trip(LineFormat::Tsv, Compression::Gzip);
+    }
+
+    #[test]
+    fn test_jsonl_zstd() {
+        test_round_trip(LineFormat::Json, Compression::Zstd);
+    }
+
+    #[test]
+    fn test_recover() {
+        let root = temp_dir();
+        // Files of past periods are renamed by flush()
+        let start = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let period = Period::Daily.label(start);
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let mut writer =
+                FileWriter::new(&root, LineFormat::Tsv, compression, Period::Daily).unwrap();
+            writer
+                .write_batch(vec![
+                    new_message("binance", MessageType::Trade, start),
+                    new_message("binance", MessageType::Trade, start + 1),
+                ])
+                .unwrap();
+            writer.flush().unwrap();
+            // Simulate a crash, the temporary file is left behind
+            std::mem::forget(writer);
+        }
+
+        let dir = root.join("binance/spot/trade");
+        assert_eq!(2, list_files(&dir).unwrap().len());
+        // The same period is written again after the restart
+        {
+            let mut writer =
+                FileWriter::new(&root, LineFormat::Tsv, Compression::Gzip, Period::Daily).unwrap();
+            writer
+                .write_batch(vec![new_message("binance", MessageType::Trade, start + 2)])
+                .unwrap();
+        }
+
+        let mut names: Vec<String> = list_files(&dir)
+            .unwrap()
+            .iter()
+            .map(|path| file_name(path).to_string())
+            .collect();
+        names.sort();
+        assert_eq!(
+            vec![
+                format!("binance.spot.trade.{}.1.tsv.gz", period),
+                format!("binance.spot.trade.{}.tsv.gz", period),
+                format!("binance.spot.trade.{}.tsv.zst", period),
+            ],
+            names
+        );
+        let timestamps: Vec<u64> = FileReader::new(&root)
+            .unwrap()
+            .map(|msg| msg.unwrap().received_at)
+            .collect();
+        assert_eq!(5, timestamps.len());
+        assert_eq!(Some(&(start + 2)), timestamps.last());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,685 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError, RwLock},
+    time::{Duration, Instant},
+};
+
+use crypto_market_type::MarketType;
+use crypto_msg_type::MessageType;
+use once_cell::sync::Lazy;
+
+use crate::status::{report, StatusKind};
+
+/// How crawlers in different processes share rate limits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RateLimiterBackend {
+    /// Token buckets live in this process, other processes are not coordinated.
+    Local,
+    /// Processes share the token buckets of whichever process listens on this Unix socket.
+    ///
+    /// The first process to start becomes the server, and another one takes over when it exits.
+    #[cfg(unix)]
+    UnixSocket(std::path::PathBuf),
+}
+
+/// Sets the backend of the rate limiter for all crawlers in this process.
+///
+/// Defaults to `RateLimiterBackend::UnixSocket` if the `RATE_LIMITER_SOCKET` environment
+/// variable is set, otherwise `RateLimiterBackend::Local`.
+pub fn set_rate_limiter_backend(backend: RateLimiterBackend) {
+    *BACKEND.write().unwrap_or_else(PoisonError::into_inner) = Backend::new(backend);
+}
+
+/// Max total weight of requests per period.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct RateLimit {
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+impl RateLimit {
+    const fn new(capacity: u32, period: Duration) -> Self {
+        RateLimit { capacity, period }
+    }
+}
+
+struct TokenBucket {
+    capacity: f64,
+    tokens_per_sec: f64,
+    tokens: f64,
+    // tokens are refilled from this time on, which is in the future during a penalty
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        let capacity = limit.capacity.max(1) as f64;
+        TokenBucket {
+            capacity,
+            tokens_per_sec: capacity / limit.period.as_secs_f64().max(0.001),
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if now > self.updated {
+            let elapsed = (now - self.updated).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.tokens_per_sec).min(self.capacity);
+            self.updated = now;
+        }
+    }
+
+    /// Takes `weight` tokens and returns how long to wait before sending the request.
+    ///
+    /// Tokens can go negative, so that concurrent callers queue up behind each other.
+    fn reserve(&mut self, weight: u32, now: Instant) -> Duration {
+        self.refill(now);
+        self.tokens -= weight as f64;
+        let penalty = self.updated.saturating_duration_since(now);
+        if self.tokens >= 0.0 {
+            penalty
+        } else {
+            penalty + Duration::from_secs_f64(-self.tokens / self.tokens_per_sec)
+        }
+    }
+
+    /// Stops refilling for `duration`, e.g., after a `Retry-After` response.
+    fn penalize(&mut self, duration: Duration, now: Instant) {
+        self.refill(now);
+        self.tokens = self.tokens.min(0.0);
+        self.updated = self.updated.max(now + duration);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Command {
+    Reserve(u32),
+    Penalize(Duration),
+}
+
+// group -> bucket
+static BUCKETS: Lazy<Mutex<HashMap<String, TokenBucket>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn execute_locally(group: &str, limit: RateLimit, command: Command) -> Duration {
+    let now = Instant::now();
+    let mut buckets = BUCKETS.lock().unwrap_or_else(PoisonError::into_inner);
+    let bucket = buckets
+        .entry(group.to_string())
+        .or_insert_with(|| TokenBucket::new(limit, now));
+    match command {
+        Command::Reserve(weight) => bucket.reserve(weight, now),
+        Command::Penalize(duration) => {
+            bucket.penalize(duration, now);
+            Duration::ZERO
+        }
+    }
+}
+
+enum Backend {
+    Local,
+    #[cfg(unix)]
+    UnixSocket(socket::Client),
+}
+
+impl Backend {
+    fn new(backend: RateLimiterBackend) -> Self {
+        match backend {
+            RateLimiterBackend::Local => Backend::Local,
+            #[cfg(unix)]
+            RateLimiterBackend::UnixSocket(path) => Backend::UnixSocket(socket::Client::new(path)),
+        }
+    }
+
+    fn from_env() -> Self {
+        match std::env::var("RATE_LIMITER_SOCKET") {
+            #[cfg(unix)]
+            Ok(path) if !path.is_empty() => {
+                Backend::new(RateLimiterBackend::UnixSocket(path.into()))
+            }
+            _ => Backend::Local,
+        }
+    }
+
+    /// Fails if the shared backend is unavailable, then the caller limits locally.
+    fn execute(
+        &self,
+        group: &str,
+        limit: RateLimit,
+        command: Command,
+    ) -> std::io::Result<Duration> {
+        match self {
+            Backend::Local => Ok(execute_locally(group, limit, command)),
+            #[cfg(unix)]
+            Backend::UnixSocket(client) => client.execute(group, limit, command),
+        }
+    }
+}
+
+static BACKEND: Lazy<RwLock<Backend>> = Lazy::new(|| RwLock::new(Backend::from_env()));
+
+/// Requests to the same endpoint group share one token bucket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Endpoint {
+    exchange: String,
+    market_type: MarketType,
+    group: String,
+    limit: RateLimit,
+}
+
+impl Endpoint {
+    /// The RESTful endpoint group of a market.
+    pub fn rest(exchange: &str, market_type: MarketType) -> Self {
+        Endpoint {
+            exchange: exchange.to_string(),
+            market_type,
+            group: get_endpoint_group(exchange, market_type, "rest"),
+            limit: get_rest_limit(exchange, market_type),
+        }
+    }
+
+    /// The websocket endpoint group of a market, None if new connections are not limited.
+    pub fn ws(exchange: &str, market_type: MarketType) -> Option<Self> {
+        get_ws_connection_limit(exchange).map(|limit| Endpoint {
+            exchange: exchange.to_string(),
+            market_type,
+            group: get_endpoint_group(exchange, market_type, "ws"),
+            limit,
+        })
+    }
+
+    fn execute(&self, command: Command) -> Duration {
+        let result = BACKEND
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .execute(&self.group, self.limit, command);
+        result.unwrap_or_else(|err| {
+            report(
+                &self.exchange,
+                self.market_type,
+                None,
+                StatusKind::Error,
+                format!("{}, limiting locally", err),
+            );
+            execute_locally(&self.group, self.limit, command)
+        })
+    }
+
+    /// Blocks until a request of `weight` is allowed.
+    pub fn wait(&self, weight: u32) {
+        let delay = self.execute(Command::Reserve(weight));
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+
+    /// Waits until a request of `weight` is allowed.
+    pub async fn wait_async(&self, weight: u32) {
+        let endpoint = self.clone();
+        let delay = tokio::task::spawn_blocking(move || endpoint.execute(Command::Reserve(weight)))
+            .await
+            .unwrap_or_default();
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Holds off all requests to this endpoint group for `duration`.
+    pub fn penalize(&self, duration: Duration) {
+        self.execute(Command::Penalize(duration));
+    }
+}
+
+/// Markets with the same endpoint are in the same group.
+fn get_endpoint_group(exchange: &str, market_type: MarketType, prefix: &str) -> String {
+    let group = match exchange {
+        "binance" => match market_type {
+            MarketType::InverseSwap | MarketType::InverseFuture => Some("binance_inverse"),
+            MarketType::LinearSwap | MarketType::LinearFuture => Some("binance_linear"),
+            MarketType::Spot => Some("binance_spot"),
+            MarketType::EuropeanOption => Some("binance_option"),
+            _ => None,
+        },
+        "bitfinex" => Some("bitfinex"),
+        "bitget" => match market_type {
+            MarketType::InverseSwap | MarketType::LinearSwap => Some("bitget_swap"),
+            MarketType::Spot => Some("bitget_spot"),
+            _ => None,
+        },
+        "bitmex" => Some("bitmex"),
+        "bitz" => match market_type {
+            MarketType::InverseSwap | MarketType::LinearSwap => Some("bitz_swap"),
+            MarketType::Spot => Some("bitz_spot"),
+            _ => None,
+        },
+        "bybit" => {
+            if prefix == "rest" {
+                Some("bybit")
+            } else {
+                match market_type {
+                    MarketType::InverseSwap | MarketType::InverseFuture => Some("bybit_inverse"),
+                    MarketType::LinearSwap => Some("bybit_linear"),
+                    _ => None,
+                }
+            }
+        }
+        "deribit" => Some("deribit"),
+        "ftx" => Some("ftx"),
+        "gate" => match market_type {
+            MarketType::InverseSwap | MarketType::LinearSwap => Some("gate_swap"),
+            MarketType::InverseFuture | MarketType::LinearFuture => Some("gate_future"),
+            MarketType::Spot => Some("gate_spot"),
+            _ => None,
+        },
+        "kucoin" => {
+            if prefix == "ws" {
+                Some("kucoin")
+            } else {
+                match market_type {
+                    MarketType::InverseSwap
+                    | MarketType::LinearSwap
+                    | MarketType::InverseFuture => Some("kucoin_swap"),
+                    MarketType::Spot => Some("kucoin_spot"),
+                    _ => None,
+                }
+            }
+        }
+        "mexc" => match market_type {
+            MarketType::InverseSwap | MarketType::LinearSwap => Some("mexc_swap"),
+            MarketType::Spot => Some("mexc_spot"),
+            _ => None,
+        },
+        "okx" => Some("okx"),
+        "zb" => match market_type {
+            MarketType::LinearSwap => Some("zb_swap"),
+            MarketType::Spot => Some("zb_spot"),
+            _ => None,
+        },
+        "zbg" => match market_type {
+            MarketType::InverseSwap | MarketType::LinearSwap => Some("zbg_swap"),
+            MarketType::Spot => Some("zbg_spot"),
+            _ => None,
+        },
+        _ => None,
+    };
+    match group {
+        Some(group) => format!("{}.{}", prefix, group),
+        None => format!("{}.{}_{}", prefix, exchange, market_type),
+    }
+}
+
+fn get_rest_limit(exchange: &str, market_type: MarketType) -> RateLimit {
+    let (capacity, secs) = match exchange {
+        "binance" => match market_type {
+            MarketType::Spot => (1200, 60), // weight 1200 per minute
+            MarketType::EuropeanOption => (400, 60),
+            _ => (2400, 60), // weight 2400 per minute
+        },
+        "bitget" => (20, 2),       // 20 requests per 2 seconds
+        "bithumb" => (25, 2),      // 135 requests per second for public APIs, reduced by 10x
+        "bitmex" => (30, 60),      // 60 requests per minute, reduced to 30 when unauthenticated
+        "bitstamp" => (800, 600), // 8000 requests per 10 minutes, but bitstamp orderbook is too big, need to reduce its frequency
+        "bitz" => (30, 1),        // no more than 30 times within 1 second
+        "bybit" => (5, 1),        // 50 requests per second for 2 minutes, reduced by 10x
+        "coinbase_pro" => (10, 1), // 10 requests per second
+        "deribit" => (20, 1),     // 20 requests per second
+        "dydx" => (100, 10),      // 100 requests per 10 seconds
+        "gate" => (300, 1),       // 300 read operations per IP per second
+        "huobi" => (800, 1),      // 800 times/second for one IP
+        "kucoin" => match market_type {
+            MarketType::Spot => (10, 3), // 1/3 of the quota to avoid 429
+            _ => (30, 3),                // 30 times/3s
+        },
+        "mexc" => (20, 2), // 20 times per 2 seconds
+        "okx" => (20, 2),  // 20 requests per 2 seconds
+        _ => (10, 1),
+    };
+    RateLimit::new(capacity, Duration::from_secs(secs))
+}
+
+fn get_ws_connection_limit(exchange: &str) -> Option<RateLimit> {
+    match exchange {
+        "bitfinex" => Some(RateLimit::new(20, Duration::from_secs(60))), // you cannot open more than 20 connections per minute, see https://docs.bitfinex.com/docs/requirements-and-limitations#websocket-rate-limits
+        "bitz" => Some(RateLimit::new(1, Duration::from_millis(100))), // `cat crawler-trade-bitz-spot-error-12.log` has many "429 Too Many Requests"
+        "kucoin" => Some(RateLimit::new(30, Duration::from_secs(60))), //  Connection Limit: 30 per minute, see https://docs.kucoin.com/#connection-times
+        "okx" => Some(RateLimit::new(1, Duration::from_secs(1))), // Connection limit: 1 time per second, https://www.okx.com/docs-v5/en/#websocket-api-connect
+        _ => None,
+    }
+}
+
+/// The weight of a RESTful request, `MessageType::Other` means fetching the symbol list and
+/// `MessageType::Trade` means fetching a page of historical trades.
+pub(crate) fn get_request_weight(
+    exchange: &str,
+    market_type: MarketType,
+    msg_type: MessageType,
+) -> u32 {
+    match exchange {
+        "binance" => match (market_type, msg_type) {
+            (MarketType::Spot, MessageType::Other) => 10,
+            (MarketType::Spot, MessageType::L2Snapshot) => 10, // limit=1000
+            (MarketType::EuropeanOption, _) => 1,
+            (_, MessageType::L2Snapshot) => 20, // limit=1000
+            (MarketType::Spot, MessageType::Trade) => 1,
+            (_, MessageType::Trade) => 20, // aggTrades
+            (MarketType::Spot, MessageType::Candlestick) => 2,
+            (_, MessageType::Candlestick) => 5, // klines, limit=1000
+            _ => 1,
+        },
+        _ => 1,
+    }
+}
+
+#[cfg(unix)]
+mod socket {
+    use std::{
+        io::{self, BufRead, BufReader, Write},
+        os::unix::net::{UnixListener, UnixStream},
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Mutex, PoisonError,
+        },
+        time::Duration,
+    };
+
+    use fslock::LockFile;
+    use log::*;
+
+    use super::{execute_locally, Command, RateLimit};
+
+    /// Requests are lines of `{reserve|penalize} {group} {capacity} {period_ms} {arg}`,
+    /// and responses are lines of milliseconds to wait.
+    fn encode(group: &str, limit: RateLimit, command: Command) -> String {
+        let (name, arg) = match command {
+            Command::Reserve(weight) => ("reserve", weight as u128),
+            Command::Penalize(duration) => ("penalize", duration.as_millis()),
+        };
+        format!(
+            "{} {} {} {} {}\n",
+            name,
+            group,
+            limit.capacity,
+            limit.period.as_millis(),
+            arg
+        )
+    }
+
+    fn decode(line: &str) -> Option<(String, RateLimit, Command)> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 5 {
+            return None;
+        }
+        let capacity = fields[2].parse::<u32>().ok()?;
+        let period = Duration::from_millis(fields[3].parse::<u64>().ok()?);
+        let arg = fields[4].parse::<u64>().ok()?;
+        let command = match fields[0] {
+            "reserve" => Command::Reserve(u32::try_from(arg).ok()?),
+            "penalize" => Command::Penalize(Duration::from_millis(arg)),
+            _ => return None,
+        };
+        Some((
+            fields[1].to_string(),
+            RateLimit { capacity, period },
+            command,
+        ))
+    }
+
+    fn serve_client(stream: UnixStream) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            let delay = match decode(&line) {
+                Some((group, limit, command)) => execute_locally(&group, limit, command),
+                None => {
+                    warn!("Invalid rate limiter request {}", line);
+                    Duration::ZERO
+                }
+            };
+            writer.write_all(format!("{}\n", delay.as_millis()).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn serve(listener: UnixListener) {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    std::thread::spawn(move || {
+                        if let Err(err) = serve_client(stream) {
+                            debug!("Rate limiter client disconnected, error: {}", err);
+                        }
+                    });
+                }
+                Err(err) => error!("Failed to accept a rate limiter client, error: {}", err),
+            }
+        }
+    }
+
+    pub(super) struct Client {
+        path: PathBuf,
+        // true if this process serves the socket
+        is_server: AtomicBool,
+        stream: Mutex<Option<(UnixStream, BufReader<UnixStream>)>>,
+    }
+
+    impl Client {
+        pub fn new(path: PathBuf) -> Self {
+            Client {
+                path,
+                is_server: AtomicBool::new(false),
+                stream: Mutex::new(None),
+            }
+        }
+
+        fn connect(path: &Path) -> io::Result<(UnixStream, BufReader<UnixStream>)> {
+            let stream = UnixStream::connect(path)?;
+            let reader = BufReader::new(stream.try_clone()?);
+            Ok((stream, reader))
+        }
+
+        /// Connects to the server, or becomes the server if nobody listens on the socket.
+        fn connect_or_serve(&self) -> io::Result<Option<(UnixStream, BufReader<UnixStream>)>> {
+            if let Ok(conn) = Self::connect(&self.path) {
+                return Ok(Some(conn));
+            }
+            // Only one process at a time may replace a stale socket file
+            let mut lock = LockFile::open(&self.path.with_extension("lock"))?;
+            lock.lock()?;
+            let result = match Self::connect(&self.path) {
+                Ok(conn) => Ok(Some(conn)),
+                Err(_) => {
+                    let _ = std::fs::remove_file(&self.path);
+                    UnixListener::bind(&self.path).map(|listener| {
+                        info!("Serving the rate limiter on {}", self.path.display());
+                        std::thread::spawn(move || serve(listener));
+                        self.is_server.store(true, Ordering::SeqCst);
+                        None
+                    })
+                }
+            };
+            lock.unlock()?;
+            result
+        }
+
+        fn request(&self, line: &str) -> io::Result<Option<Duration>> {
+            let mut guard = self.stream.lock().unwrap_or_else(PoisonError::into_inner);
+            if guard.is_none() {
+                match self.connect_or_serve()? {
+                    Some(conn) => *guard = Some(conn),
+                    None => return Ok(None),
+                }
+            }
+            let (writer, reader) = guard.as_mut().unwrap();
+            let result = writer.write_all(line.as_bytes()).and_then(|_| {
+                let mut resp = String::new();
+                if reader.read_line(&mut resp)? == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "the rate limiter server exited",
+                    ));
+                }
+                resp.trim()
+                    .parse::<u64>()
+                    .map(Duration::from_millis)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+            });
+            if result.is_err() {
+                *guard = None;
+            }
+            result.map(Some)
+        }
+
+        pub fn execute(
+            &self,
+            group: &str,
+            limit: RateLimit,
+            command: Command,
+        ) -> io::Result<Duration> {
+            if !self.is_server.load(Ordering::SeqCst) {
+                let line = encode(group, limit, command);
+                // retry once, in case the server exited and another process took over
+                let mut last_err = None;
+                for _ in 0..2 {
+                    match self.request(&line) {
+                        Ok(Some(delay)) => return Ok(delay),
+                        Ok(None) => {
+                            // this process became the server
+                            last_err = None;
+                            break;
+                        }
+                        Err(err) => last_err = Some(err),
+                    }
+                }
+                if let Some(err) = last_err {
+                    return Err(io::Error::new(
+                        err.kind(),
+                        format!(
+                            "Rate limiter socket {} is unavailable, error: {}",
+                            self.path.display(),
+                            err
+                        ),
+                    ));
+                }
+            }
+            Ok(execute_locally(group, limit, command))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::time::Duration;
+
+        use super::{decode, encode, Client, Command, RateLimit};
+
+        #[test]
+        fn test_encode_decode() {
+            let limit = RateLimit {
+                capacity: 1200,
+                period: Duration::from_secs(60),
+            };
+            for command in [
+                Command::Reserve(10),
+                Command::Penalize(Duration::from_secs(3)),
+            ] {
+                let line = encode("rest.binance_spot", limit, command);
+                assert_eq!(
+                    Some(("rest.binance_spot".to_string(), limit, command)),
+                    decode(line.trim())
+                );
+            }
+            assert_eq!(None, decode("reserve rest.binance_spot 1200"));
+        }
+
+        #[test]
+        fn test_shared_bucket() {
+            let dir = std::env::temp_dir().join(format!("rate-limit-{}", rand::random::<u64>()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("limiter.sock");
+            let limit = RateLimit {
+                capacity: 2,
+                period: Duration::from_secs(10),
+            };
+
+            let server = Client::new(path.clone());
+            assert!(server
+                .execute("test.shared", limit, Command::Reserve(1))
+                .unwrap()
+                .is_zero());
+            assert!(server.is_server.load(std::sync::atomic::Ordering::SeqCst));
+
+            // Another client shares the bucket through the socket
+            let client = Client::new(path);
+            assert!(client
+                .execute("test.shared", limit, Command::Reserve(1))
+                .unwrap()
+                .is_zero());
+            assert!(!client.is_server.load(std::sync::atomic::Ordering::SeqCst));
+            let delay = client
+                .execute("test.shared", limit, Command::Reserve(1))
+                .unwrap();
+            assert!(delay > Duration::from_secs(4) && delay <= Duration::from_secs(5));
+
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crypto_market_type::MarketType;
+
+    use super::{get_endpoint_group, RateLimit, TokenBucket};
+
+    #[test]
+    fn test_reserve() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(RateLimit::new(4, Duration::from_secs(1)), now);
+        assert_eq!(Duration::ZERO, bucket.reserve(4, now));
+        // empty now, 1 token per 250 milliseconds
+        assert_eq!(Duration::from_millis(500), bucket.reserve(2, now));
+        assert_eq!(Duration::from_millis(750), bucket.reserve(1, now));
+        // refilled
+        let later = now + Duration::from_secs(2);
+        assert_eq!(Duration::ZERO, bucket.reserve(4, later));
+    }
+
+    #[test]
+    fn test_penalize() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(RateLimit::new(4, Duration::from_secs(1)), now);
+        bucket.penalize(Duration::from_secs(3), now);
+        assert_eq!(Duration::from_millis(3250), bucket.reserve(1, now));
+        let later = now + Duration::from_secs(4);
+        // refilled for 1 second after the penalty, minus the reserved token
+        assert_eq!(Duration::ZERO, bucket.reserve(3, later));
+        assert_eq!(Duration::from_millis(250), bucket.reserve(1, later));
+    }
+
+    #[test]
+    fn test_endpoint_group() {
+        assert_eq!(
+            get_endpoint_group("binance", MarketType::InverseFuture, "rest"),
+            get_endpoint_group("binance", MarketType::InverseSwap, "rest")
+        );
+        assert_ne!(
+            get_endpoint_group("binance", MarketType::Spot, "rest"),
+            get_endpoint_group("binance", MarketType::LinearSwap, "rest")
+        );
+        assert_eq!(
+            "rest.bybit",
+            get_endpoint_group("bybit", MarketType::LinearSwap, "rest")
+        );
+        assert_eq!(
+            "ws.bybit_linear",
+            get_endpoint_group("bybit", MarketType::LinearSwap, "ws")
+        );
+        assert_eq!(
+            "rest.kucoin_unknown",
+            get_endpoint_group("kucoin", MarketType::Unknown, "rest")
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{
+    error::{Error, Result},
+    exchanges,
+};
+use crypto_market_type::MarketType;
+use crypto_message::CandlestickMsg;
+use crypto_msg_type::MessageType;
+use serde_json::Value;
+
+/// A candlestick in a RESTful response, before normalization.
+pub(crate) struct RawCandle {
+    /// Begin time in Unix seconds.
+    pub begin_time: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// Base volume.
+    pub volume: f64,
+    /// Quote volume.
+    pub quote_volume: Option<f64>,
+    /// The candlestick in the response.
+    pub json: String,
+}
+
+// Parses a JSON number or a string of number.
+pub(crate) fn to_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(x) => x.as_f64(),
+        Value::String(x) => x.parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
+// Parses every candlestick in the JSON array `candles` with `parse`.
+pub(crate) fn parse_candles<F>(exchange: &str, candles: &Value, parse: F) -> Result<Vec<RawCandle>>
+where
+    F: Fn(&Value) -> Option<RawCandle>,
+{
+    candles
+        .as_array()
+        .ok_or_else(|| unexpected_response(exchange, &candles.to_string()))?
+        .iter()
+        .map(|candle| {
+            parse(candle).ok_or_else(|| unexpected_response(exchange, &candle.to_string()))
+        })
+        .collect()
+}
+
+pub(crate) fn unexpected_response(exchange: &str, text: &str) -> Error {
+    Error::new(format!("Unexpected {} candlesticks {}", exchange, text))
+}
+
+pub(crate) fn unsupported_interval(exchange: &str, interval: usize) -> Error {
+    Error::new(format!(
+        "{} does NOT have candlesticks of {} seconds",
+        exchange, interval
+    ))
+}
+
+pub(crate) fn unsupported_market(exchange: &str, market_type: MarketType) -> Error {
+    Error::new(format!(
+        "{} {} does NOT have historical candlesticks with base volume",
+        exchange, market_type
+    ))
+}
+
+// Max number of candlesticks in a response.
+fn page_size(exchange: &str) -> Result<u64> {
+    match exchange {
+        "binance" => Ok(1000),
+        "bitfinex" => Ok(10000),
+        "bybit" => Ok(200),
+        "gate" => Ok(1000),
+        "huobi" => Ok(2000),
+        "kucoin" => Ok(1500),
+        "okx" => Ok(100),
+        _ => Err(Error::new(format!(
+            "{} does NOT have historical candlesticks",
+            exchange
+        ))),
+    }
+}
+
+// Max seconds of a page.
+fn page_span(exchange: &str, market_type: MarketType, interval: usize) -> Result<u64> {
+    let span = page_size(exchange)? * interval as u64;
+    match (exchange, market_type) {
+        // Binance COIN-M klines are limited to 200 days per request
+        ("binance", MarketType::InverseFuture | MarketType::InverseSwap) => {
+            Ok(span.min(200 * 86400))
+        }
+        _ => Ok(span),
+    }
+}
+
+/// Converts an interval in seconds to a period, e.g., 1m, 4H, 1D.
+fn to_period(interval: usize) -> String {
+    const UNITS: [(usize, &str); 4] = [(604800, "W"), (86400, "D"), (3600, "H"), (60, "m")];
+    for (seconds, unit) in UNITS {
+        if interval.checked_rem(seconds) == Some(0) {
+            return format!("{}{}", interval / seconds, unit);
+        }
+    }
+    format!("{}s", interval)
+}
+
+fn fetch_raw_candles(
+    exchange: &str,
+    market_type: MarketType,
+    symbol: &str,
+    interval: usize,
+    start: u64,
+    end: u64,
+) -> Result<Vec<RawCandle>> {
+    match exchange {
+        "binance" => {
+            exchanges::binance::fetch_candlesticks(market_type, symbol, interval, start, end)
+        }
+        "bitfinex" => {
+            exchanges::bitfinex::fetch_candlesticks(market_type, symbol, interval, start, end)
+        }
+        "bybit" => exchanges::bybit::fetch_candlesticks(market_type, symbol, interval, start),
+        "gate" => exchanges::gate::fetch_candlesticks(market_type, symbol, interval, start, end),
+        "huobi" => exchanges::huobi::fetch_candlesticks(market_type, symbol, interval, start, end),
+        "kucoin" => {
+            exchanges::kucoin::fetch_candlesticks(market_type, symbol, interval, start, end)
+        }
+        "okx" => exchanges::okx::fetch_candlesticks(market_type, symbol, interval, start, end),
+        _ => Err(Error::new(format!(
+            "{} does NOT have historical candlesticks",
+            exchange
+        ))),
+    }
+}
+
+/// Check whether historical candlesticks of a market and an interval are supported.
+///
+/// No requests are sent, so it is cheap to call before a long backfill.
+pub fn check_candlesticks(exchange: &str, market_type: MarketType, interval: usize) -> Result<()> {
+    match exchange {
+        "binance" => exchanges::binance::check_candlesticks(market_type, interval),
+        "bitfinex" => exchanges::bitfinex::check_candlesticks(market_type, interval),
+        "bybit" => exchanges::bybit::check_candlesticks(market_type, interval),
+        "gate" => exchanges::gate::check_candlesticks(market_type, interval),
+        "huobi" => exchanges::huobi::check_candlesticks(market_type, interval),
+        "kucoin" => exchanges::kucoin::check_candlesticks(market_type, interval),
+        "okx" => exchanges::okx::check_candlesticks(interval),
+        _ => Err(Error::new(format!(
+            "{} does NOT have historical candlesticks",
+            exchange
+        ))),
+    }
+}
+
+/// Fetch a page of historical candlesticks.
+///
+/// Candlesticks which begin within `[start, end)` in Unix seconds are requested, and those
+/// within `[start, next)` are returned in ascending order, along with `next`, the start of the
+/// next page. `interval` is in seconds.
+///
+/// A page may be empty if there are no trades in it, the range is done once `next >= end`.
+pub fn fetch_candlestick_page(
+    exchange: &str,
+    market_type: MarketType,
+    symbol: &str,
+    interval: usize,
+    start: u64,
+    end: u64,
+) -> Result<(Vec<CandlestickMsg>, u64)> {
+    check_candlesticks(exchange, market_type, interval)?;
+    let next = end.min(start + page_span(exchange, market_type, interval)?);
+    let pair = crypto_pair::normalize_pair(symbol, exchange)
+        .ok_or_else(|| Error::new(format!("Failed to normalize {} {}", exchange, symbol)))?;
+    let period = to_period(interval);
+
+    let mut raw_candles = fetch_raw_candles(exchange, market_type, symbol, interval, start, next)?;
+    raw_candles.retain(|candle| start <= candle.begin_time && candle.begin_time < next);
+    raw_candles.sort_by_key(|candle| candle.begin_time);
+    raw_candles.dedup_by_key(|candle| candle.begin_time);
+
+    let candlesticks = raw_candles
+        .into_iter()
+        .map(|candle| CandlestickMsg {
+            exchange: exchange.to_string(),
+            market_type,
+            symbol: symbol.to_string(),
+            pair: pair.clone(),
+            msg_type: MessageType::Candlestick,
+            timestamp: (candle.begin_time * 1000) as i64,
+            begin_time: candle.begin_time as i64,
+            open: candle.open,
+            high: candle.high,
+            low: candle.low,
+            close: candle.close,
+            volume: candle.volume,
+            period: period.clone(),
+            quote_volume: candle.quote_volume,
+            json: candle.json,
+        })
+        .collect();
+    Ok((candlesticks, next))
+}
+
+/// Fetch historical candlesticks which begin within `[start, end)` in Unix seconds.
+///
+/// `interval` is in seconds, and it must be one of the intervals of the exchange.
+/// Pages are fetched one after another without rate limiting, use
+/// `crypto_crawler::backfill_candlesticks()` for long ranges.
+///
+/// Supported exchanges:
+///
+/// * binance, spot, inverse and linear markets
+/// * bitfinex, spot and swap markets
+/// * bybit, inverse and linear markets
+/// * gate, spot market
+/// * huobi, spot market of the latest 2000 candlesticks, earlier `start` is an error, future
+///   and swap markets
+/// * kucoin, spot market
+/// * okx, all markets
+///
+/// `volume` is always in base currency, and `quote_volume` is None if the exchange does NOT
+/// provide it.
+pub fn fetch_candlesticks(
+    exchange: &str,
+    market_type: MarketType,
+    symbol: &str,
+    interval: usize,
+    start: u64,
+    end: u64,
+) -> Result<Vec<CandlestickMsg>> {
+    let mut candlesticks = Vec::new();
+    let mut cursor = start;
+    while cursor < end {
+        let (page, next) =
+            fetch_candlestick_page(exchange, market_type, symbol, interval, cursor, end)?;
+        candlesticks.extend(page);
+        cursor = next;
+    }
+    Ok(candlesticks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_candlesticks, page_span, to_period};
+    use crate::exchanges;
+    use crypto_market_type::MarketType;
+
+    #[test]
+    fn test_to_period() {
+        assert_eq!("1m", to_period(60));
+        assert_eq!("15m", to_period(900));
+        assert_eq!("4H", to_period(14400));
+        assert_eq!("1D", to_period(86400));
+        assert_eq!("3D", to_period(259200));
+        assert_eq!("1W", to_period(604800));
+        assert_eq!("10s", to_period(10));
+    }
+
+    #[test]
+    fn test_page_span() {
+        assert_eq!(60000, page_span("binance", MarketType::Spot, 60).unwrap());
+        assert_eq!(
+            200 * 86400,
+            page_span("binance", MarketType::InverseSwap, 86400).unwrap()
+        );
+        assert_eq!(6000, page_span("okx", MarketType::Spot, 60).unwrap());
+        assert!(page_span("bitmex", MarketType::InverseSwap, 60).is_err());
+    }
+
+    #[test]
+    fn test_check_candlesticks() {
+        assert!(check_candlesticks("binance", MarketType::LinearSwap, 60).is_ok());
+        assert!(check_candlesticks("binance", MarketType::EuropeanOption, 60).is_err());
+        assert!(check_candlesticks("binance", MarketType::Spot, 0).is_err());
+        assert!(check_candlesticks("okx", MarketType::EuropeanOption, 300).is_ok());
+        assert!(check_candlesticks("gate", MarketType::LinearSwap, 60).is_err());
+        assert!(check_candlesticks("bitmex", MarketType::InverseSwap, 60).is_err());
+    }
+
+    #[test]
+    fn test_binance_inverse() {
+        let text = r#"[[1660000000000,"23950.1","23960.0","23940.0","23955.5","1200",1660000059999,"5.0102",300,"600","2.5051","0"]]"#;
+        let candles =
+            exchanges::binance::parse_candlesticks(MarketType::InverseSwap, text).unwrap();
+        assert_eq!(1660000000, candles[0].begin_time);
+        assert_eq!(23950.1, candles[0].open);
+        assert_eq!(23955.5, candles[0].close);
+        // base volume instead of contracts
+        assert_eq!(5.0102, candles[0].volume);
+        assert_eq!(None, candles[0].quote_volume);
+    }
+
+    #[test]
+    fn test_gate_spot() {
+        let text = r#"[["1660000000","1197.5","23955.5","23960","23940","23950.1","0.05","true"]]"#;
+        let candles = exchanges::gate::parse_candlesticks(text).unwrap();
+        assert_eq!(1660000000, candles[0].begin_time);
+        assert_eq!(23950.1, candles[0].open);
+        assert_eq!(23960.0, candles[0].high);
+        assert_eq!(23940.0, candles[0].low);
+        assert_eq!(23955.5, candles[0].close);
+        assert_eq!(0.05, candles[0].volume);
+        assert_eq!(Some(1197.5), candles[0].quote_volume);
+    }
+
+    #[test]
+    fn test_okx_error() {
+        let text = r#"{"code":"51000","msg":"Parameter bar error","data":[]}"#;
+        assert!(exchanges::okx::parse_candlesticks(MarketType::Spot, text).is_err());
+    }
+}
@@ -1,26 +1,51 @@
-use std::{error::Error as StdError, fmt};
+use std::{error::Error as StdError, fmt, time::Duration};
 
 pub(crate) type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
-pub struct Error(pub String);
+pub struct Error {
+    pub msg: String,
+    /// How long the server asked to wait before the next request, taken from the
+    /// `Retry-After` header of a 418 or 429 response.
+    pub retry_after: Option<Duration>,
+}
+
+impl Error {
+    pub fn new(msg: impl Into<String>) -> Self {
+        Error {
+            msg: msg.into(),
+            retry_after: None,
+        }
+    }
+
+    /// The error message, which was the `.0` field before 0.10.0.
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+}
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.msg)
     }
 }
 
 impl StdError for Error {}
 
+impl From<String> for Error {
+    fn from(msg: String) -> Self {
+        Error::new(msg)
+    }
+}
+
 impl From<reqwest::Error> for Error {
     fn from(err: reqwest::Error) -> Self {
-        Error(err.to_string())
+        Error::new(err.to_string())
     }
 }
 
 impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Self {
-        Error(err.to_string())
+        Error::new(err.to_string())
     }
 }
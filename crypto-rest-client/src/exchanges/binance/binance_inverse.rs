@@ -31,6 +31,7 @@ impl BinanceInverseRestClient {
     ///
     /// - <https://dapi.binance.com/dapi/v1/aggTrades?symbol=BTCUSD_PERP&limit=1000>
     /// - <https://dapi.binance.com/dapi/v1/aggTrades?symbol=BTCUSD_210625&limit=1000>
+    #[allow(non_snake_case)]
     pub fn fetch_agg_trades(
         symbol: &str,
         from_id: Option<u64>,
@@ -40,12 +41,49 @@ impl BinanceInverseRestClient {
         check_symbol(symbol);
         let symbol = Some(symbol);
         let limit = Some(1000);
+        let fromId = from_id;
+        let startTime = start_time;
+        let endTime = end_time;
         gen_api_binance!(
             "/dapi/v1/aggTrades",
             symbol,
-            from_id,
-            start_time,
-            end_time,
+            fromId,
+            startTime,
+            endTime,
+            limit
+        )
+    }
+
+    /// Get candlesticks.
+    ///
+    /// Equivalent to `/dapi/v1/klines` with `limit=1000`, `start_time` and `end_time` are
+    /// inclusive Unix milliseconds.
+    ///
+    /// For example:
+    ///
+    /// - <https://dapi.binance.com/dapi/v1/klines?symbol=BTCUSD_PERP&interval=1m&limit=1000>
+    /// - <https://dapi.binance.com/dapi/v1/klines?symbol=BTCUSD_221230&interval=1m&limit=1000>
+    ///
+    /// The time between `start_time` and `end_time` can not be longer than 200 days.
+    #[allow(non_snake_case)]
+    pub fn fetch_klines(
+        symbol: &str,
+        interval: &str,
+        start_time: Option<u64>,
+        end_time: Option<u64>,
+    ) -> Result<String> {
+        check_symbol(symbol);
+        let symbol = Some(symbol);
+        let interval = Some(interval);
+        let limit = Some(1000);
+        let startTime = start_time;
+        let endTime = end_time;
+        gen_api_binance!(
+            "/dapi/v1/klines",
+            symbol,
+            interval,
+            startTime,
+            endTime,
             limit
         )
     }
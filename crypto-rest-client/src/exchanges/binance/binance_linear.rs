@@ -31,6 +31,7 @@ impl BinanceLinearRestClient {
     ///
     /// - <https://fapi.binance.com/fapi/v1/aggTrades?symbol=BTCUSDT&limit=1000>
     /// - <https://fapi.binance.com/fapi/v1/aggTrades?symbol=BTCUSDT_210625&limit=1000>
+    #[allow(non_snake_case)]
     pub fn fetch_agg_trades(
         symbol: &str,
         from_id: Option<u64>,
@@ -40,12 +41,47 @@ impl BinanceLinearRestClient {
         check_symbol(symbol);
         let symbol = Some(symbol);
         let limit = Some(1000);
+        let fromId = from_id;
+        let startTime = start_time;
+        let endTime = end_time;
         gen_api_binance!(
             "/fapi/v1/aggTrades",
             symbol,
-            from_id,
-            start_time,
-            end_time,
+            fromId,
+            startTime,
+            endTime,
+            limit
+        )
+    }
+
+    /// Get candlesticks.
+    ///
+    /// Equivalent to `/fapi/v1/klines` with `limit=1000`, `start_time` and `end_time` are
+    /// inclusive Unix milliseconds.
+    ///
+    /// For example:
+    ///
+    /// - <https://fapi.binance.com/fapi/v1/klines?symbol=BTCUSDT&interval=1m&limit=1000>
+    /// - <https://fapi.binance.com/fapi/v1/klines?symbol=BTCUSDT_221230&interval=1m&limit=1000>
+    #[allow(non_snake_case)]
+    pub fn fetch_klines(
+        symbol: &str,
+        interval: &str,
+        start_time: Option<u64>,
+        end_time: Option<u64>,
+    ) -> Result<String> {
+        check_symbol(symbol);
+        let symbol = Some(symbol);
+        let interval = Some(interval);
+        let limit = Some(1000);
+        let startTime = start_time;
+        let endTime = end_time;
+        gen_api_binance!(
+            "/fapi/v1/klines",
+            symbol,
+            interval,
+            startTime,
+            endTime,
             limit
         )
     }
@@ -29,6 +29,7 @@ impl BinanceSpotRestClient {
     /// Equivalent to `/api/v3/aggTrades` with `limit=1000`
     ///
     /// For example: <https://api.binance.com/api/v3/aggTrades?symbol=BTCUSDT&limit=1000>
+    #[allow(non_snake_case)]
     pub fn fetch_agg_trades(
         symbol: &str,
         from_id: Option<u64>,
@@ -38,12 +39,44 @@ impl BinanceSpotRestClient {
         check_symbol(symbol);
         let symbol = Some(symbol);
         let limit = Some(1000);
+        let fromId = from_id;
+        let startTime = start_time;
+        let endTime = end_time;
         gen_api_binance!(
             "/api/v3/aggTrades",
             symbol,
-            from_id,
-            start_time,
-            end_time,
+            fromId,
+            startTime,
+            endTime,
+            limit
+        )
+    }
+
+    /// Get candlesticks.
+    ///
+    /// Equivalent to `/api/v3/klines` with `limit=1000`, `start_time` and `end_time` are
+    /// inclusive Unix milliseconds.
+    ///
+    /// For example: <https://api.binance.com/api/v3/klines?symbol=BTCUSDT&interval=1m&limit=1000>
+    #[allow(non_snake_case)]
+    pub fn fetch_klines(
+        symbol: &str,
+        interval: &str,
+        start_time: Option<u64>,
+        end_time: Option<u64>,
+    ) -> Result<String> {
+        check_symbol(symbol);
+        let symbol = Some(symbol);
+        let interval = Some(interval);
+        let limit = Some(1000);
+        let startTime = start_time;
+        let endTime = end_time;
+        gen_api_binance!(
+            "/api/v3/klines",
+            symbol,
+            interval,
+            startTime,
+            endTime,
             limit
         )
     }
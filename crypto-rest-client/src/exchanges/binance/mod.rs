@@ -6,8 +6,15 @@ pub(crate) mod binance_linear;
 pub(crate) mod binance_option;
 pub(crate) mod binance_spot;
 
-use crate::error::Result;
+use crate::{
+    candlestick::{
+        parse_candles, to_f64, unexpected_response, unsupported_interval, unsupported_market,
+        RawCandle,
+    },
+    error::Result,
+};
 use crypto_market_type::MarketType;
+use serde_json::Value;
 
 pub(crate) fn fetch_l2_snapshot(market_type: MarketType, symbol: &str) -> Result<String> {
     let func = match market_type {
@@ -37,3 +44,96 @@ pub(crate) fn fetch_open_interest(market_type: MarketType, symbol: &str) -> Resu
     };
     func(symbol)
 }
+
+fn to_kline_interval(interval: usize) -> Option<&'static str> {
+    match interval {
+        60 => Some("1m"),
+        180 => Some("3m"),
+        300 => Some("5m"),
+        900 => Some("15m"),
+        1800 => Some("30m"),
+        3600 => Some("1h"),
+        7200 => Some("2h"),
+        14400 => Some("4h"),
+        21600 => Some("6h"),
+        28800 => Some("8h"),
+        43200 => Some("12h"),
+        86400 => Some("1d"),
+        259200 => Some("3d"),
+        604800 => Some("1w"),
+        _ => None,
+    }
+}
+
+// Whether fetch_candlesticks() supports the market and the interval.
+pub(crate) fn check_candlesticks(market_type: MarketType, interval: usize) -> Result<()> {
+    if !matches!(
+        market_type,
+        MarketType::Spot
+            | MarketType::InverseFuture
+            | MarketType::InverseSwap
+            | MarketType::LinearFuture
+            | MarketType::LinearSwap
+    ) {
+        return Err(unsupported_market("binance", market_type));
+    }
+    to_kline_interval(interval)
+        .map(|_| ())
+        .ok_or_else(|| unsupported_interval("binance", interval))
+}
+
+pub(crate) fn fetch_candlesticks(
+    market_type: MarketType,
+    symbol: &str,
+    interval: usize,
+    start: u64,
+    end: u64,
+) -> Result<Vec<RawCandle>> {
+    let func = match market_type {
+        MarketType::Spot => binance_spot::BinanceSpotRestClient::fetch_klines,
+        MarketType::InverseFuture | MarketType::InverseSwap => {
+            binance_inverse::BinanceInverseRestClient::fetch_klines
+        }
+        MarketType::LinearFuture | MarketType::LinearSwap => {
+            binance_linear::BinanceLinearRestClient::fetch_klines
+        }
+        _ => return Err(unsupported_market("binance", market_type)),
+    };
+    let kline_interval =
+        to_kline_interval(interval).ok_or_else(|| unsupported_interval("binance", interval))?;
+    let text = func(
+        symbol,
+        kline_interval,
+        Some(start * 1000),
+        Some(end * 1000 - 1),
+    )?;
+    parse_candlesticks(market_type, &text)
+}
+
+// Klines are [open time, open, high, low, close, volume, close time, quote volume, ...], in which
+// volume of inverse markets is in contracts and index 7 is base volume instead.
+pub(crate) fn parse_candlesticks(market_type: MarketType, text: &str) -> Result<Vec<RawCandle>> {
+    let klines =
+        serde_json::from_str::<Value>(text).map_err(|_| unexpected_response("binance", text))?;
+    let inverse = matches!(
+        market_type,
+        MarketType::InverseFuture | MarketType::InverseSwap
+    );
+    parse_candles("binance", &klines, |kline| {
+        let (volume, quote_volume) = if inverse {
+            (to_f64(&kline[7])?, None)
+        } else {
+            (to_f64(&kline[5])?, Some(to_f64(&kline[7])?))
+        };
+        Some(RawCandle {
+            begin_time: kline[0].as_u64()? / 1000,
+            open: to_f64(&kline[1])?,
+            high: to_f64(&kline[2])?,
+            low: to_f64(&kline[3])?,
+            close: to_f64(&kline[4])?,
+            volume,
+            quote_volume,
+            json: kline.to_string(),
+        })
+    })
+}
@@ -26,7 +26,7 @@ pub(super) fn check_code_in_body(resp: String) -> Result<String> {
     match obj.unwrap().get("code") {
         Some(code) => {
             if code.as_i64().unwrap() != 0 {
-                Err(Error(resp))
+                Err(Error::new(resp))
             } else {
                 Ok(resp)
             }
@@ -1,5 +1,13 @@
 use super::utils::http_get;
-use crate::error::Result;
+use crate::{
+    candlestick::{
+        parse_candles, to_f64, unexpected_response, unsupported_interval, unsupported_market,
+        RawCandle,
+    },
+    error::Result,
+};
+use crypto_market_type::MarketType;
+use serde_json::Value;
 use std::collections::BTreeMap;
 
 const BASE_URL: &str = "https://api-pub.bitfinex.com";
@@ -40,6 +48,28 @@ impl BitfinexRestClient {
         )
     }
 
+    /// Get candlesticks between `start` and `end` in Unix milliseconds, inclusive.
+    ///
+    /// Equivalent to `/v2/candles/trade:TimeFrame:Symbol/hist` with `limit=10000&sort=1`, oldest first.
+    ///
+    /// For example: <https://api-pub.bitfinex.com/v2/candles/trade:1m:tBTCUSD/hist?limit=10000&sort=1>
+    pub fn fetch_candles(
+        symbol: &str,
+        time_frame: &str,
+        start: Option<u64>,
+        end: Option<u64>,
+    ) -> Result<String> {
+        let limit = Some(10000);
+        let sort = Some(1);
+        gen_api!(
+            format!("/v2/candles/trade:{}:{}/hist", time_frame, symbol),
+            limit,
+            start,
+            end,
+            sort
+        )
+    }
+
     /// Get a Level2 snapshot of orderbook.
     ///
     /// Equivalent to `/v2/book/Symbol/P0` with `len=100`
@@ -62,3 +92,66 @@ impl BitfinexRestClient {
         gen_api!(format!("/v2/book/{}/R0", symbol), len)
     }
 }
+
+fn to_time_frame(interval: usize) -> Option<&'static str> {
+    match interval {
+        60 => Some("1m"),
+        300 => Some("5m"),
+        900 => Some("15m"),
+        1800 => Some("30m"),
+        3600 => Some("1h"),
+        10800 => Some("3h"),
+        21600 => Some("6h"),
+        43200 => Some("12h"),
+        86400 => Some("1D"),
+        604800 => Some("1W"),
+        _ => None,
+    }
+}
+
+// Whether fetch_candlesticks() supports the market and the interval.
+pub(crate) fn check_candlesticks(market_type: MarketType, interval: usize) -> Result<()> {
+    if !matches!(market_type, MarketType::Spot | MarketType::LinearSwap) {
+        return Err(unsupported_market("bitfinex", market_type));
+    }
+    to_time_frame(interval)
+        .map(|_| ())
+        .ok_or_else(|| unsupported_interval("bitfinex", interval))
+}
+
+pub(crate) fn fetch_candlesticks(
+    market_type: MarketType,
+    symbol: &str,
+    interval: usize,
+    start: u64,
+    end: u64,
+) -> Result<Vec<RawCandle>> {
+    check_candlesticks(market_type, interval)?;
+    let time_frame =
+        to_time_frame(interval).ok_or_else(|| unsupported_interval("bitfinex", interval))?;
+    let text = BitfinexRestClient::fetch_candles(
+        symbol,
+        time_frame,
+        Some(start * 1000),
+        Some(end * 1000 - 1),
+    )?;
+    parse_candlesticks(&text)
+}
+
+// Candlesticks are [MTS, OPEN, CLOSE, HIGH, LOW, VOLUME], and errors are ["error", CODE, MESSAGE].
+pub(crate) fn parse_candlesticks(text: &str) -> Result<Vec<RawCandle>> {
+    let candles =
+        serde_json::from_str::<Value>(text).map_err(|_| unexpected_response("bitfinex", text))?;
+    parse_candles("bitfinex", &candles, |candle| {
+        Some(RawCandle {
+            begin_time: candle[0].as_u64()? / 1000,
+            open: to_f64(&candle[1])?,
+            high: to_f64(&candle[3])?,
+            low: to_f64(&candle[4])?,
+            close: to_f64(&candle[2])?,
+            volume: to_f64(&candle[5])?,
+            quote_volume: None,
+            json: candle.to_string(),
+        })
+    })
+}
@@ -34,8 +34,9 @@ impl BitmexRestClient {
     #[allow(non_snake_case)]
     pub fn fetch_trades(symbol: &str, start_time: Option<String>) -> Result<String> {
         let symbol = Some(symbol);
+        let count = Some(1000);
         let startTime = start_time;
-        gen_api!("/trade", symbol, startTime)
+        gen_api!("/trade", symbol, count, startTime)
     }
 
     /// Get a full Level2 snapshot of orderbook.
@@ -34,7 +34,7 @@ impl BitzSwapRestClient {
     pub fn fetch_l2_snapshot(symbol: &str) -> Result<String> {
         let symbol_id_map = get_symbol_id_map()?;
         if !symbol_id_map.contains_key(symbol) {
-            return Err(Error(format!(
+            return Err(Error::new(format!(
                 "Can NOT find contractId for the pair {}",
                 symbol
             )));
@@ -53,7 +53,7 @@ impl BitzSwapRestClient {
         if let Some(symbol) = symbol {
             let symbol_id_map = get_symbol_id_map()?;
             if !symbol_id_map.contains_key(symbol) {
-                return Err(Error(format!(
+                return Err(Error::new(format!(
                     "Can NOT find contractId for the pair {}",
                     symbol
                 )));
@@ -94,7 +94,7 @@ fn get_symbol_id_map() -> Result<HashMap<String, String>> {
     let txt = http_get("https://apiv2.bitz.com/Market/getContractCoin", &params)?;
     let resp = serde_json::from_str::<Response>(&txt)?;
     if resp.status != 200 {
-        return Err(Error(txt));
+        return Err(Error::new(txt));
     }
 
     let mut symbol_id_map = HashMap::<String, String>::new();
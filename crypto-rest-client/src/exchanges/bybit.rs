@@ -1,5 +1,13 @@
 use super::utils::http_get;
-use crate::error::Result;
+use crate::{
+    candlestick::{
+        parse_candles, to_f64, unexpected_response, unsupported_interval, unsupported_market,
+        RawCandle,
+    },
+    error::{Error, Result},
+};
+use crypto_market_type::MarketType;
+use serde_json::Value;
 use std::collections::BTreeMap;
 
 const BASE_URL: &str = "https://api.bybit.com/v2";
@@ -41,6 +49,26 @@ impl BybitRestClient {
         gen_api!(format!("/public/orderBook/L2?symbol={}", symbol))
     }
 
+    /// Get candlesticks.
+    ///
+    /// At most 200 candlesticks from `from` in Unix seconds are returned.
+    ///
+    /// For example:
+    ///
+    /// - <https://api.bybit.com/v2/public/kline/list?symbol=BTCUSD&interval=1&from=1660000000&limit=200>
+    /// - <https://api.bybit.com/public/linear/kline?symbol=BTCUSDT&interval=1&from=1660000000&limit=200>
+    pub fn fetch_klines(symbol: &str, interval: &str, from: u64) -> Result<String> {
+        let path = if symbol.ends_with("USDT") {
+            "https://api.bybit.com/public/linear/kline"
+        } else {
+            "/public/kline/list"
+        };
+        gen_api!(format!(
+            "{}?symbol={}&interval={}&from={}&limit=200",
+            path, symbol, interval, from
+        ))
+    }
+
     /// Get open interest.
     ///
     /// For example:
@@ -69,3 +97,75 @@ impl BybitRestClient {
         ))
     }
 }
+
+fn to_kline_interval(interval: usize) -> Option<&'static str> {
+    match interval {
+        60 => Some("1"),
+        180 => Some("3"),
+        300 => Some("5"),
+        900 => Some("15"),
+        1800 => Some("30"),
+        3600 => Some("60"),
+        7200 => Some("120"),
+        14400 => Some("240"),
+        21600 => Some("360"),
+        43200 => Some("720"),
+        86400 => Some("D"),
+        604800 => Some("W"),
+        _ => None,
+    }
+}
+
+// Whether fetch_candlesticks() supports the market and the interval.
+pub(crate) fn check_candlesticks(market_type: MarketType, interval: usize) -> Result<()> {
+    if !matches!(
+        market_type,
+        MarketType::InverseFuture | MarketType::InverseSwap | MarketType::LinearSwap
+    ) {
+        return Err(unsupported_market("bybit", market_type));
+    }
+    to_kline_interval(interval)
+        .map(|_| ())
+        .ok_or_else(|| unsupported_interval("bybit", interval))
+}
+
+pub(crate) fn fetch_candlesticks(
+    market_type: MarketType,
+    symbol: &str,
+    interval: usize,
+    start: u64,
+) -> Result<Vec<RawCandle>> {
+    check_candlesticks(market_type, interval)?;
+    let kline_interval =
+        to_kline_interval(interval).ok_or_else(|| unsupported_interval("bybit", interval))?;
+    let text = BybitRestClient::fetch_klines(symbol, kline_interval, start)?;
+    parse_candlesticks(market_type, &text)
+}
+
+// Volume of inverse markets is in USD and turnover is in base currency, while volume of linear
+// markets is in base currency and turnover is in USDT.
+pub(crate) fn parse_candlesticks(market_type: MarketType, text: &str) -> Result<Vec<RawCandle>> {
+    let obj =
+        serde_json::from_str::<Value>(text).map_err(|_| unexpected_response("bybit", text))?;
+    if obj["ret_code"].as_i64() != Some(0) {
+        return Err(Error::new(text.to_string()));
+    }
+    let linear = market_type == MarketType::LinearSwap;
+    parse_candles("bybit", &obj["result"], |kline| {
+        let (volume, quote_volume) = if linear {
+            (to_f64(&kline["volume"])?, to_f64(&kline["turnover"])?)
+        } else {
+            (to_f64(&kline["turnover"])?, to_f64(&kline["volume"])?)
+        };
+        Some(RawCandle {
+            begin_time: kline["open_time"].as_u64()?,
+            open: to_f64(&kline["open"])?,
+            high: to_f64(&kline["high"])?,
+            low: to_f64(&kline["low"])?,
+            close: to_f64(&kline["close"])?,
+            volume,
+            quote_volume: Some(quote_volume),
+            json: kline.to_string(),
+        })
+    })
+}
@@ -23,6 +23,18 @@ impl GateSpotRestClient {
         }
     }
 
+    /// Get candlesticks between `from` and `to` in Unix seconds, inclusive.
+    ///
+    /// At most 1000 candlesticks are returned.
+    ///
+    /// For example: <https://api.gateio.ws/api/v4/spot/candlesticks?currency_pair=BTC_USDT&interval=1m&from=1660000000&to=1660059940>
+    pub fn fetch_candlesticks(symbol: &str, interval: &str, from: u64, to: u64) -> Result<String> {
+        gen_api!(format!(
+            "/spot/candlesticks?currency_pair={}&interval={}&from={}&to={}",
+            symbol, interval, from, to
+        ))
+    }
+
     /// Get the latest Level2 snapshot of orderbook.
     ///
     /// Top 1000 asks and bids are returned.
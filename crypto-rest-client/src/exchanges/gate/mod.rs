@@ -6,8 +6,15 @@ pub use gate_future::GateFutureRestClient;
 pub use gate_spot::GateSpotRestClient;
 pub use gate_swap::GateSwapRestClient;
 
-use crate::error::Result;
+use crate::{
+    candlestick::{
+        parse_candles, to_f64, unexpected_response, unsupported_interval, unsupported_market,
+        RawCandle,
+    },
+    error::Result,
+};
 use crypto_market_type::MarketType;
+use serde_json::Value;
 
 pub(crate) fn fetch_l2_snapshot(market_type: MarketType, symbol: &str) -> Result<String> {
     let func = match market_type {
@@ -34,3 +41,67 @@ pub(crate) fn fetch_open_interest(market_type: MarketType, symbol: &str) -> Resu
 
     func(symbol)
 }
+
+fn to_candlestick_interval(interval: usize) -> Option<&'static str> {
+    match interval {
+        10 => Some("10s"),
+        60 => Some("1m"),
+        300 => Some("5m"),
+        900 => Some("15m"),
+        1800 => Some("30m"),
+        3600 => Some("1h"),
+        14400 => Some("4h"),
+        28800 => Some("8h"),
+        86400 => Some("1d"),
+        604800 => Some("7d"),
+        _ => None,
+    }
+}
+
+// Candlesticks of future and swap markets have only contract volume
+// Whether fetch_candlesticks() supports the market and the interval.
+pub(crate) fn check_candlesticks(market_type: MarketType, interval: usize) -> Result<()> {
+    if market_type != MarketType::Spot {
+        return Err(unsupported_market("gate", market_type));
+    }
+    to_candlestick_interval(interval)
+        .map(|_| ())
+        .ok_or_else(|| unsupported_interval("gate", interval))
+}
+
+pub(crate) fn fetch_candlesticks(
+    market_type: MarketType,
+    symbol: &str,
+    interval: usize,
+    start: u64,
+    end: u64,
+) -> Result<Vec<RawCandle>> {
+    check_candlesticks(market_type, interval)?;
+    let candlestick_interval =
+        to_candlestick_interval(interval).ok_or_else(|| unsupported_interval("gate", interval))?;
+    let text = gate_spot::GateSpotRestClient::fetch_candlesticks(
+        symbol,
+        candlestick_interval,
+        start,
+        end - 1,
+    )?;
+    parse_candlesticks(&text)
+}
+
+// Candlesticks are [time, quote volume, close, high, low, open, base volume, ...].
+pub(crate) fn parse_candlesticks(text: &str) -> Result<Vec<RawCandle>> {
+    let candles =
+        serde_json::from_str::<Value>(text).map_err(|_| unexpected_response("gate", text))?;
+    parse_candles("gate", &candles, |candle| {
+        Some(RawCandle {
+            begin_time: to_f64(&candle[0])? as u64,
+            open: to_f64(&candle[5])?,
+            high: to_f64(&candle[3])?,
+            low: to_f64(&candle[4])?,
+            close: to_f64(&candle[2])?,
+            volume: to_f64(&candle[6])?,
+            quote_volume: Some(to_f64(&candle[1])?),
+            json: candle.to_string(),
+        })
+    })
+}
@@ -18,6 +18,18 @@ pub struct HuobiFutureRestClient {
 impl_contract!(HuobiFutureRestClient);
 
 impl HuobiFutureRestClient {
+    /// Get candlesticks between `from` and `to` in Unix seconds, inclusive.
+    ///
+    /// At most 2000 candlesticks are returned.
+    ///
+    /// For example: <https://api.hbdm.com/market/history/kline?symbol=BTC_CQ&period=1min&from=1660000000&to=1660119940>
+    pub fn fetch_klines(symbol: &str, period: &str, from: u64, to: u64) -> Result<String> {
+        gen_api!(format!(
+            "/market/history/kline?symbol={}&period={}&from={}&to={}",
+            symbol, period, from, to
+        ))
+    }
+
     /// Get the latest Level2 orderbook snapshot.
     ///
     /// Top 150 bids and asks (aggregated) are returned.
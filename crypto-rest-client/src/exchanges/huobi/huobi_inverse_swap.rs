@@ -20,6 +20,18 @@ pub struct HuobiInverseSwapRestClient {
 impl_contract!(HuobiInverseSwapRestClient);
 
 impl HuobiInverseSwapRestClient {
+    /// Get candlesticks between `from` and `to` in Unix seconds, inclusive.
+    ///
+    /// At most 2000 candlesticks are returned.
+    ///
+    /// For example: <https://api.hbdm.com/swap-ex/market/history/kline?contract_code=BTC-USD&period=1min&from=1660000000&to=1660119940>
+    pub fn fetch_klines(symbol: &str, period: &str, from: u64, to: u64) -> Result<String> {
+        gen_api!(format!(
+            "/swap-ex/market/history/kline?contract_code={}&period={}&from={}&to={}",
+            symbol, period, from, to
+        ))
+    }
+
     /// Get the latest Level2 orderbook snapshot.
     ///
     /// Top 150 bids and asks (aggregated) are returned.
@@ -20,6 +20,18 @@ pub struct HuobiLinearSwapRestClient {
 impl_contract!(HuobiLinearSwapRestClient);
 
 impl HuobiLinearSwapRestClient {
+    /// Get candlesticks between `from` and `to` in Unix seconds, inclusive.
+    ///
+    /// At most 2000 candlesticks are returned.
+    ///
+    /// For example: <https://api.hbdm.com/linear-swap-ex/market/history/kline?contract_code=BTC-USDT&period=1min&from=1660000000&to=1660119940>
+    pub fn fetch_klines(symbol: &str, period: &str, from: u64, to: u64) -> Result<String> {
+        gen_api!(format!(
+            "/linear-swap-ex/market/history/kline?contract_code={}&period={}&from={}&to={}",
+            symbol, period, from, to
+        ))
+    }
+
     /// Get the latest Level2 orderbook snapshot.
     ///
     /// Top 150 bids and asks (aggregated) are returned.
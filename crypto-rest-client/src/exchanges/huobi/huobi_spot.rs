@@ -18,6 +18,16 @@ pub struct HuobiSpotRestClient {
 impl_contract!(HuobiSpotRestClient);
 
 impl HuobiSpotRestClient {
+    /// Get the latest 2000 candlesticks.
+    ///
+    /// For example: <https://api.huobi.pro/market/history/kline?symbol=btcusdt&period=1min&size=2000>
+    pub fn fetch_klines(symbol: &str, period: &str) -> Result<String> {
+        gen_api!(format!(
+            "/market/history/kline?symbol={}&period={}&size=2000",
+            symbol, period
+        ))
+    }
+
     /// Get the latest Level2 orderbook snapshot.
     ///
     /// Top 150 bids and asks (aggregated) are returned.
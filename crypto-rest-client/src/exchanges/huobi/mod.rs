@@ -7,8 +7,19 @@ pub(crate) mod huobi_linear_swap;
 pub(crate) mod huobi_option;
 pub(crate) mod huobi_spot;
 
-use crate::error::Result;
+use crate::{
+    candlestick::{
+        parse_candles, to_f64, unexpected_response, unsupported_interval, unsupported_market,
+        RawCandle,
+    },
+    error::{Error, Result},
+};
 use crypto_market_type::MarketType;
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Number of the latest candlesticks returned by the spot market
+const SPOT_MAX_CANDLES: u64 = 2000;
 
 pub(crate) fn fetch_l2_snapshot(market_type: MarketType, symbol: &str) -> Result<String> {
     let func = match market_type {
@@ -37,3 +48,127 @@ pub(crate) fn fetch_open_interest(market_type: MarketType, symbol: Option<&str>)
 
     func(symbol)
 }
+
+fn to_period(interval: usize) -> Option<&'static str> {
+    match interval {
+        60 => Some("1min"),
+        300 => Some("5min"),
+        900 => Some("15min"),
+        1800 => Some("30min"),
+        3600 => Some("60min"),
+        14400 => Some("4hour"),
+        86400 => Some("1day"),
+        604800 => Some("1week"),
+        _ => None,
+    }
+}
+
+// Candlesticks of the spot market before the latest 2000 are unavailable, requesting them is
+// an error rather than an empty page.
+fn check_spot_window(interval: usize, start: u64, now: u64) -> Result<()> {
+    let interval = interval as u64;
+    let earliest = (now / interval).saturating_sub(SPOT_MAX_CANDLES - 1) * interval;
+    if start < earliest {
+        Err(Error::new(format!(
+            "huobi spot returns only the latest {} candlesticks, which begin at {} or later, start {} is too early",
+            SPOT_MAX_CANDLES, earliest, start
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+// Whether fetch_candlesticks() supports the market and the interval.
+pub(crate) fn check_candlesticks(market_type: MarketType, interval: usize) -> Result<()> {
+    if !matches!(
+        market_type,
+        MarketType::Spot
+            | MarketType::InverseFuture
+            | MarketType::LinearSwap
+            | MarketType::InverseSwap
+    ) {
+        return Err(unsupported_market("huobi", market_type));
+    }
+    to_period(interval)
+        .map(|_| ())
+        .ok_or_else(|| unsupported_interval("huobi", interval))
+}
+
+pub(crate) fn fetch_candlesticks(
+    market_type: MarketType,
+    symbol: &str,
+    interval: usize,
+    start: u64,
+    end: u64,
+) -> Result<Vec<RawCandle>> {
+    let period = to_period(interval).ok_or_else(|| unsupported_interval("huobi", interval))?;
+    let text = match market_type {
+        MarketType::Spot => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            check_spot_window(interval, start, now)?;
+            huobi_spot::HuobiSpotRestClient::fetch_klines(symbol, period)
+        }
+        MarketType::InverseFuture => {
+            huobi_future::HuobiFutureRestClient::fetch_klines(symbol, period, start, end - 1)
+        }
+        MarketType::LinearSwap => huobi_linear_swap::HuobiLinearSwapRestClient::fetch_klines(
+            symbol,
+            period,
+            start,
+            end - 1,
+        ),
+        MarketType::InverseSwap => huobi_inverse_swap::HuobiInverseSwapRestClient::fetch_klines(
+            symbol,
+            period,
+            start,
+            end - 1,
+        ),
+        _ => return Err(unsupported_market("huobi", market_type)),
+    }?;
+    parse_candlesticks(market_type, &text)
+}
+
+// amount is in base currency, vol is in quote currency in the spot market and in contracts in
+// other markets, and trade_turnover of linear swap markets is in quote currency.
+pub(crate) fn parse_candlesticks(market_type: MarketType, text: &str) -> Result<Vec<RawCandle>> {
+    let obj =
+        serde_json::from_str::<Value>(text).map_err(|_| unexpected_response("huobi", text))?;
+    if obj["status"].as_str() != Some("ok") {
+        return Err(Error::new(text.to_string()));
+    }
+    parse_candles("huobi", &obj["data"], |kline| {
+        let quote_volume = match market_type {
+            MarketType::Spot => Some(to_f64(&kline["vol"])?),
+            MarketType::LinearSwap => Some(to_f64(&kline["trade_turnover"])?),
+            _ => None,
+        };
+        Some(RawCandle {
+            begin_time: kline["id"].as_u64()?,
+            open: to_f64(&kline["open"])?,
+            high: to_f64(&kline["high"])?,
+            low: to_f64(&kline["low"])?,
+            close: to_f64(&kline["close"])?,
+            volume: to_f64(&kline["amount"])?,
+            quote_volume,
+            json: kline.to_string(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_spot_window;
+
+    #[test]
+    fn test_spot_window() {
+        let now = 1660000000;
+        // the latest candlestick begins at 1659999600, the earliest one 1999 hours before
+        let earliest = 1659999600 - 1999 * 3600;
+        assert!(check_spot_window(3600, earliest, now).is_ok());
+        assert!(check_spot_window(3600, now, now).is_ok());
+        assert!(check_spot_window(3600, earliest - 1, now).is_err());
+    }
+}
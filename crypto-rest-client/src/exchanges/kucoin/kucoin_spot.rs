@@ -22,6 +22,23 @@ impl KuCoinSpotRestClient {
         }
     }
 
+    /// Get candlesticks between `start_at` and `end_at` in Unix seconds.
+    ///
+    /// At most 1500 candlesticks are returned, newest first.
+    ///
+    /// For example: <https://api.kucoin.com/api/v1/market/candles?symbol=BTC-USDT&type=1min&startAt=1660000000&endAt=1660089999>
+    pub fn fetch_candles(
+        symbol: &str,
+        candle_type: &str,
+        start_at: u64,
+        end_at: u64,
+    ) -> Result<String> {
+        gen_api!(format!(
+            "/api/v1/market/candles?symbol={}&type={}&startAt={}&endAt={}",
+            symbol, candle_type, start_at, end_at
+        ))
+    }
+
     /// Get the latest Level2 snapshot of orderbook.
     ///
     /// For example: <https://api.kucoin.com/api/v1/market/orderbook/level2_100?symbol=BTC-USDT>,
@@ -4,8 +4,15 @@ mod kucoin_swap;
 pub use kucoin_spot::KuCoinSpotRestClient;
 pub use kucoin_swap::KuCoinSwapRestClient;
 
-use crate::error::Result;
+use crate::{
+    candlestick::{
+        parse_candles, to_f64, unexpected_response, unsupported_interval, unsupported_market,
+        RawCandle,
+    },
+    error::{Error, Result},
+};
 use crypto_market_type::MarketType;
+use serde_json::Value;
 
 pub(crate) fn fetch_l2_snapshot(market_type: MarketType, symbol: &str) -> Result<String> {
     let func = match market_type {
@@ -39,3 +46,69 @@ pub(crate) fn fetch_open_interest(market_type: MarketType) -> Result<String> {
         _ => panic!("kucoin {} does not have open interest", market_type),
     }
 }
+
+fn to_candle_type(interval: usize) -> Option<&'static str> {
+    match interval {
+        60 => Some("1min"),
+        180 => Some("3min"),
+        300 => Some("5min"),
+        900 => Some("15min"),
+        1800 => Some("30min"),
+        3600 => Some("1hour"),
+        7200 => Some("2hour"),
+        14400 => Some("4hour"),
+        21600 => Some("6hour"),
+        28800 => Some("8hour"),
+        43200 => Some("12hour"),
+        86400 => Some("1day"),
+        604800 => Some("1week"),
+        _ => None,
+    }
+}
+
+// Candlesticks of swap markets have only contract volume
+// Whether fetch_candlesticks() supports the market and the interval.
+pub(crate) fn check_candlesticks(market_type: MarketType, interval: usize) -> Result<()> {
+    if market_type != MarketType::Spot {
+        return Err(unsupported_market("kucoin", market_type));
+    }
+    to_candle_type(interval)
+        .map(|_| ())
+        .ok_or_else(|| unsupported_interval("kucoin", interval))
+}
+
+pub(crate) fn fetch_candlesticks(
+    market_type: MarketType,
+    symbol: &str,
+    interval: usize,
+    start: u64,
+    end: u64,
+) -> Result<Vec<RawCandle>> {
+    check_candlesticks(market_type, interval)?;
+    let candle_type =
+        to_candle_type(interval).ok_or_else(|| unsupported_interval("kucoin", interval))?;
+    let text =
+        kucoin_spot::KuCoinSpotRestClient::fetch_candles(symbol, candle_type, start, end - 1)?;
+    parse_candlesticks(&text)
+}
+
+// Candlesticks are [time, open, close, high, low, volume, turnover].
+pub(crate) fn parse_candlesticks(text: &str) -> Result<Vec<RawCandle>> {
+    let obj =
+        serde_json::from_str::<Value>(text).map_err(|_| unexpected_response("kucoin", text))?;
+    if obj["code"].as_str() != Some("200000") {
+        return Err(Error::new(text.to_string()));
+    }
+    parse_candles("kucoin", &obj["data"], |candle| {
+        Some(RawCandle {
+            begin_time: to_f64(&candle[0])? as u64,
+            open: to_f64(&candle[1])?,
+            high: to_f64(&candle[3])?,
+            low: to_f64(&candle[4])?,
+            close: to_f64(&candle[2])?,
+            volume: to_f64(&candle[5])?,
+            quote_volume: Some(to_f64(&candle[6])?),
+            json: candle.to_string(),
+        })
+    })
+}
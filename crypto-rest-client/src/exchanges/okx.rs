@@ -1,5 +1,8 @@
 use super::utils::http_get;
-use crate::error::Result;
+use crate::{
+    candlestick::{parse_candles, to_f64, unexpected_response, unsupported_interval, RawCandle},
+    error::{Error, Result},
+};
 use crypto_market_type::MarketType;
 use serde_json::Value;
 use std::collections::{BTreeMap, HashMap};
@@ -38,6 +41,34 @@ impl OkxRestClient {
         gen_api!(format!("/api/v5/market/trades?instId={}&limit=500", symbol))
     }
 
+    /// Get history candlesticks.
+    ///
+    /// Equivalent to `/api/v5/market/history-candles` with `limit=100`, candlesticks earlier
+    /// than `after` and later than `before` in Unix milliseconds are returned, newest first.
+    ///
+    /// For example:
+    /// * <https://www.okx.com/api/v5/market/history-candles?instId=BTC-USDT&bar=1m&limit=100>
+    /// * <https://www.okx.com/api/v5/market/history-candles?instId=BTC-USDT-SWAP&bar=1m&limit=100>
+    #[allow(non_snake_case)]
+    pub fn fetch_history_candles(
+        symbol: &str,
+        bar: &str,
+        after: Option<u64>,
+        before: Option<u64>,
+    ) -> Result<String> {
+        let instId = Some(symbol);
+        let bar = Some(bar);
+        let limit = Some(100);
+        gen_api!(
+            "/api/v5/market/history-candles",
+            instId,
+            bar,
+            after,
+            before,
+            limit
+        )
+    }
+
     /// Get the latest Level2 snapshot of orderbook.
     ///
     /// Top 400 bids and asks are returned.
@@ -97,3 +128,76 @@ impl OkxRestClient {
         }
     }
 }
+
+// Bars of 6 hours and longer are aligned to UTC instead of Hong Kong time
+fn to_bar(interval: usize) -> Option<&'static str> {
+    match interval {
+        60 => Some("1m"),
+        180 => Some("3m"),
+        300 => Some("5m"),
+        900 => Some("15m"),
+        1800 => Some("30m"),
+        3600 => Some("1H"),
+        7200 => Some("2H"),
+        14400 => Some("4H"),
+        21600 => Some("6Hutc"),
+        43200 => Some("12Hutc"),
+        86400 => Some("1Dutc"),
+        604800 => Some("1Wutc"),
+        _ => None,
+    }
+}
+
+// Whether fetch_candlesticks() supports the interval, all markets are supported.
+pub(crate) fn check_candlesticks(interval: usize) -> Result<()> {
+    to_bar(interval)
+        .map(|_| ())
+        .ok_or_else(|| unsupported_interval("okx", interval))
+}
+
+pub(crate) fn fetch_candlesticks(
+    market_type: MarketType,
+    symbol: &str,
+    interval: usize,
+    start: u64,
+    end: u64,
+) -> Result<Vec<RawCandle>> {
+    let bar = to_bar(interval).ok_or_else(|| unsupported_interval("okx", interval))?;
+    let text = OkxRestClient::fetch_history_candles(
+        symbol,
+        bar,
+        Some(end * 1000),
+        (start * 1000).checked_sub(1),
+    )?;
+    parse_candlesticks(market_type, &text)
+}
+
+// Candlesticks are [ts, o, h, l, c, vol, volCcy, volCcyQuote, confirm], in which vol of
+// derivatives is in contracts and volCcy is in base currency.
+pub(crate) fn parse_candlesticks(market_type: MarketType, text: &str) -> Result<Vec<RawCandle>> {
+    let obj = serde_json::from_str::<HashMap<String, Value>>(text)
+        .map_err(|_| unexpected_response("okx", text))?;
+    if obj.get("code").and_then(|code| code.as_str()) != Some("0") {
+        return Err(Error::new(text.to_string()));
+    }
+    let candles = obj
+        .get("data")
+        .ok_or_else(|| unexpected_response("okx", text))?;
+    parse_candles("okx", candles, |candle| {
+        let (volume, quote_volume) = if market_type == MarketType::Spot {
+            (to_f64(&candle[5])?, Some(to_f64(&candle[6])?))
+        } else {
+            (to_f64(&candle[6])?, to_f64(&candle[7]))
+        };
+        Some(RawCandle {
+            begin_time: to_f64(&candle[0])? as u64 / 1000,
+            open: to_f64(&candle[1])?,
+            high: to_f64(&candle[2])?,
+            low: to_f64(&candle[3])?,
+            close: to_f64(&candle[4])?,
+            volume,
+            quote_volume,
+            json: candle.to_string(),
+        })
+    })
+}
@@ -1,7 +1,7 @@
 use reqwest::{blocking::Response, header};
 
 use crate::error::{Error, Result};
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, time::Duration};
 
 // Returns the raw response directly.
 pub(super) fn http_get_raw(url: &str, params: &BTreeMap<String, String>) -> Result<Response> {
@@ -35,10 +35,25 @@ pub(super) fn http_get_raw(url: &str, params: &BTreeMap<String, String>) -> Resu
 // Returns the text in response.
 pub(super) fn http_get(url: &str, params: &BTreeMap<String, String>) -> Result<String> {
     match http_get_raw(url, params) {
-        Ok(response) => match response.error_for_status() {
-            Ok(resp) => Ok(resp.text()?),
-            Err(error) => Err(Error::from(error)),
-        },
+        Ok(response) => {
+            // 418 and 429 responses may tell how many seconds to wait
+            let retry_after = if matches!(response.status().as_u16(), 418 | 429) {
+                response
+                    .headers()
+                    .get(header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+            } else {
+                None
+            };
+            match response.error_for_status() {
+                Ok(resp) => Ok(resp.text()?),
+                Err(error) => Err(Error {
+                    msg: error.to_string(),
+                    retry_after: retry_after.map(Duration::from_secs),
+                }),
+            }
+        }
         Err(err) => Err(err),
     }
 }
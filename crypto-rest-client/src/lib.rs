@@ -1,6 +1,8 @@
+mod candlestick;
 mod error;
 mod exchanges;
 
+pub use candlestick::{check_candlesticks, fetch_candlestick_page, fetch_candlesticks};
 pub use error::Error;
 pub use exchanges::{
     binance::{
@@ -216,7 +218,7 @@ fn retriable(
                     (backoff_factor * cooldown_time).as_millis()
                 );
                 std::thread::sleep(backoff_factor * cooldown_time);
-                if err.0.contains("429") {
+                if err.msg.contains("429") {
                     backoff_factor += 1;
                 } else {
                     // Handle 403, 418, etc.
@@ -225,7 +227,7 @@ fn retriable(
             }
         }
     }
-    Err(Error(format!(
+    Err(Error::new(format!(
         "Failed {} {} {} after retrying {} times",
         exchange, market_type, symbol, retry_count
     )))
@@ -1,7 +1,9 @@
 #[cfg(test)]
 mod inverse_swap {
     use crypto_market_type::MarketType;
-    use crypto_rest_client::{fetch_l2_snapshot, fetch_open_interest, BinanceInverseRestClient};
+    use crypto_rest_client::{
+        fetch_candlesticks, fetch_l2_snapshot, fetch_open_interest, BinanceInverseRestClient,
+    };
 
     #[test]
     fn test_agg_trades() {
@@ -23,6 +25,22 @@ mod inverse_swap {
             fetch_open_interest("binance", MarketType::InverseSwap, Some("BTCUSD_PERP")).unwrap();
         assert!(text.starts_with('{'));
     }
+
+    #[test]
+    fn test_candlesticks() {
+        let candlesticks = fetch_candlesticks(
+            "binance",
+            MarketType::InverseSwap,
+            "BTCUSD_PERP",
+            60,
+            1660003200,
+            1660012200,
+        )
+        .unwrap();
+        assert_eq!(150, candlesticks.len());
+        // volume in BTC instead of contracts
+        assert!(candlesticks[0].volume < 10000.0);
+    }
 }
 
 #[cfg(test)]
@@ -1,7 +1,9 @@
 #[cfg(test)]
 mod linear_swap {
     use crypto_market_type::MarketType;
-    use crypto_rest_client::{fetch_l2_snapshot, fetch_open_interest, BinanceLinearRestClient};
+    use crypto_rest_client::{
+        fetch_candlesticks, fetch_l2_snapshot, fetch_open_interest, BinanceLinearRestClient,
+    };
 
     #[test]
     fn test_agg_trades() {
@@ -21,6 +23,21 @@ mod linear_swap {
         let text = fetch_open_interest("binance", MarketType::LinearSwap, Some("BTCUSDT")).unwrap();
         assert!(text.starts_with('{'));
     }
+
+    #[test]
+    fn test_candlesticks() {
+        let candlesticks = fetch_candlesticks(
+            "binance",
+            MarketType::LinearSwap,
+            "BTCUSDT",
+            60,
+            1660003200,
+            1660012200,
+        )
+        .unwrap();
+        assert_eq!(150, candlesticks.len());
+        assert_eq!(1660003200, candlesticks[0].begin_time);
+    }
 }
 
 #[cfg(test)]
@@ -1,5 +1,5 @@
 use crypto_market_type::MarketType;
-use crypto_rest_client::{fetch_l2_snapshot, BinanceSpotRestClient};
+use crypto_rest_client::{fetch_candlesticks, fetch_l2_snapshot, BinanceSpotRestClient};
 
 #[test]
 fn test_agg_trades() {
@@ -12,3 +12,20 @@ fn test_l2_snapshot() {
     let text = fetch_l2_snapshot("binance", MarketType::Spot, "BTCUSDT", Some(3)).unwrap();
     assert!(text.starts_with('{'));
 }
+
+#[test]
+fn test_candlesticks() {
+    let candlesticks = fetch_candlesticks(
+        "binance",
+        MarketType::Spot,
+        "BTCUSDT",
+        60,
+        1660003200,
+        1660012200,
+    )
+    .unwrap();
+    assert_eq!(150, candlesticks.len());
+    assert_eq!(1660003200, candlesticks[0].begin_time);
+    assert_eq!("1m", candlesticks[0].period);
+    assert!(candlesticks[0].quote_volume.is_some());
+}
@@ -1,5 +1,7 @@
 use crypto_market_type::MarketType;
-use crypto_rest_client::{fetch_l2_snapshot, fetch_l3_snapshot, BitfinexRestClient};
+use crypto_rest_client::{
+    fetch_candlesticks, fetch_l2_snapshot, fetch_l3_snapshot, BitfinexRestClient,
+};
 
 #[test]
 fn test_trades() {
@@ -18,3 +20,20 @@ fn test_l3_snapshot() {
     let text = fetch_l3_snapshot("bitfinex", MarketType::Spot, "tBTCUSD", Some(3)).unwrap();
     assert!(text.starts_with("[["));
 }
+
+#[test]
+fn test_candlesticks() {
+    let candlesticks = fetch_candlesticks(
+        "bitfinex",
+        MarketType::Spot,
+        "tBTCUSD",
+        60,
+        1660003200,
+        1660012200,
+    )
+    .unwrap();
+    assert!(!candlesticks.is_empty());
+    assert!(candlesticks
+        .windows(2)
+        .all(|x| x[0].begin_time < x[1].begin_time));
+}
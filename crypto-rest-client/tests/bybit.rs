@@ -1,7 +1,9 @@
 use std::collections::HashMap;
 
 use crypto_market_type::MarketType;
-use crypto_rest_client::{fetch_l2_snapshot, fetch_long_short_ratio, fetch_open_interest};
+use crypto_rest_client::{
+    fetch_candlesticks, fetch_l2_snapshot, fetch_long_short_ratio, fetch_open_interest,
+};
 use serde_json::Value;
 use test_case::test_case;
 
@@ -41,3 +43,13 @@ fn test_long_short_ratio(market_type: MarketType, symbol: &str) {
 
     assert!(!result.is_empty());
 }
+
+#[test_case(MarketType::InverseSwap, "BTCUSD")]
+#[test_case(MarketType::LinearSwap, "BTCUSDT")]
+fn test_candlesticks(market_type: MarketType, symbol: &str) {
+    let candlesticks =
+        fetch_candlesticks("bybit", market_type, symbol, 60, 1660003200, 1660012200).unwrap();
+    assert_eq!(150, candlesticks.len());
+    assert_eq!(1660003200, candlesticks[0].begin_time);
+    assert!(candlesticks[0].quote_volume.is_some());
+}
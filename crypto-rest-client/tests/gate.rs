@@ -1,5 +1,5 @@
 use crypto_market_type::MarketType;
-use crypto_rest_client::{fetch_l2_snapshot, fetch_open_interest};
+use crypto_rest_client::{fetch_candlesticks, fetch_l2_snapshot, fetch_open_interest};
 use serde_json::Value;
 use std::collections::HashMap;
 use test_case::test_case;
@@ -27,3 +27,19 @@ fn test_open_interest(market_type: MarketType, symbol: &str) {
     let arr = serde_json::from_str::<Vec<Value>>(&text).unwrap();
     assert!(!arr.is_empty());
 }
+
+#[test]
+fn test_candlesticks() {
+    let candlesticks = fetch_candlesticks(
+        "gate",
+        MarketType::Spot,
+        "BTC_USDT",
+        3600,
+        1660003200,
+        1660089600,
+    )
+    .unwrap();
+    assert_eq!(24, candlesticks.len());
+    assert_eq!(1660003200, candlesticks[0].begin_time);
+    assert_eq!("1H", candlesticks[0].period);
+}
@@ -1,7 +1,10 @@
 use crypto_market_type::MarketType;
-use crypto_rest_client::{fetch_l2_snapshot, fetch_open_interest};
+use crypto_rest_client::{fetch_candlesticks, fetch_l2_snapshot, fetch_open_interest};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
 use test_case::test_case;
 
 #[test_case(MarketType::Spot, "btcusdt")]
@@ -49,6 +52,25 @@ fn test_open_interest(market_type: MarketType) {
     assert!(!arr.is_empty());
 }
 
+// The spot market has only the latest 2000 candlesticks
+#[test_case(MarketType::Spot, "btcusdt")]
+#[test_case(MarketType::InverseFuture, "BTC_CQ")]
+#[test_case(MarketType::InverseSwap, "BTC-USD")]
+#[test_case(MarketType::LinearSwap, "BTC-USDT")]
+fn test_candlesticks(market_type: MarketType, symbol: &str) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let end = now / 60 * 60;
+    let candlesticks =
+        fetch_candlesticks("huobi", market_type, symbol, 60, end - 6000, end).unwrap();
+    assert!(!candlesticks.is_empty());
+    assert!(candlesticks
+        .iter()
+        .all(|x| end - 6000 <= x.begin_time as u64 && (x.begin_time as u64) < end));
+}
+
 #[cfg(test)]
 mod huobi_spot {
     use crypto_rest_client::HuobiSpotRestClient;
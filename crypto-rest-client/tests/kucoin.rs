@@ -1,5 +1,7 @@
 use crypto_market_type::MarketType;
-use crypto_rest_client::{fetch_l2_snapshot, fetch_l3_snapshot, fetch_open_interest};
+use crypto_rest_client::{
+    fetch_candlesticks, fetch_l2_snapshot, fetch_l3_snapshot, fetch_open_interest,
+};
 use serde_json::Value;
 use std::collections::HashMap;
 use test_case::test_case;
@@ -50,3 +52,20 @@ fn test_open_interest(market_type: MarketType) {
     let arr = obj.get("data").unwrap().as_array().unwrap();
     assert!(!arr.is_empty());
 }
+
+#[test]
+fn test_candlesticks() {
+    let candlesticks = fetch_candlesticks(
+        "kucoin",
+        MarketType::Spot,
+        "BTC-USDT",
+        60,
+        1660003200,
+        1660012200,
+    )
+    .unwrap();
+    assert_eq!(150, candlesticks.len());
+    assert_eq!(1660003200, candlesticks[0].begin_time);
+    assert!(candlesticks[0].low <= candlesticks[0].open);
+    assert!(candlesticks[0].open <= candlesticks[0].high);
+}
@@ -1,5 +1,5 @@
 use crypto_market_type::MarketType;
-use crypto_rest_client::{fetch_l2_snapshot, fetch_open_interest};
+use crypto_rest_client::{fetch_candlesticks, fetch_l2_snapshot, fetch_open_interest};
 use serde_json::Value;
 use std::collections::HashMap;
 use test_case::test_case;
@@ -27,6 +27,18 @@ fn test_open_interest(market_type: MarketType, symbol: &str) {
     assert!(!arr.is_empty());
 }
 
+#[test_case(MarketType::Spot, "BTC-USDT")]
+#[test_case(MarketType::InverseSwap, "BTC-USD-SWAP")]
+#[test_case(MarketType::LinearSwap, "BTC-USDT-SWAP")]
+fn test_candlesticks(market_type: MarketType, symbol: &str) {
+    // two pages
+    let candlesticks =
+        fetch_candlesticks("okx", market_type, symbol, 60, 1660003200, 1660012200).unwrap();
+    assert_eq!(150, candlesticks.len());
+    assert_eq!(1660003200, candlesticks[0].begin_time);
+    assert_eq!(1660012140, candlesticks[149].begin_time);
+}
+
 #[cfg(test)]
 mod okex_swap {
     use std::collections::HashMap;
@@ -1,16 +1,25 @@
 use async_trait::async_trait;
 use nonzero_ext::nonzero;
-use std::{collections::HashMap, num::NonZeroU32};
+use std::{
+    collections::HashMap,
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+};
 use tokio_tungstenite::tungstenite::Message;
 
 use crate::{
     common::{
+        ack_tracker::PendingAck,
+        auth::{Authenticator, Credentials},
+        codec::Codec,
         command_translator::CommandTranslator,
         message_handler::{MessageHandler, MiscMessage},
+        message_sender::MessageSender,
+        options::WSClientOptions,
         utils::ensure_frame_size,
         ws_client_internal::WSClientInternal,
     },
-    WSClient,
+    MetricsSnapshot, WSClient, WsError,
 };
 use log::*;
 use serde_json::Value;
@@ -21,6 +30,17 @@ const SPOT_WEBSOCKET_URL: &str = "wss://stream.binance.com:9443/stream";
 const LINEAR_WEBSOCKET_URL: &str = "wss://fstream.binance.com/stream";
 const INVERSE_WEBSOCKET_URL: &str = "wss://dstream.binance.com/stream";
 
+// REST APIs which create and keep alive listen keys of user data streams
+const SPOT_REST_URL: &str = "https://api.binance.com";
+const LINEAR_REST_URL: &str = "https://fapi.binance.com";
+const INVERSE_REST_URL: &str = "https://dapi.binance.com";
+
+// A listen key expires after 60 minutes unless it is kept alive
+const LISTEN_KEY_KEEPALIVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30 * 60);
+
+// The id of the command which subscribes the user data stream
+const LISTEN_KEY_COMMAND_ID: u64 = 9526;
+
 // the websocket message size should not exceed 4096 bytes, otherwise
 // you'll get `code: 3001, reason: illegal request`
 const WS_FRAME_SIZE: usize = 4096;
@@ -35,9 +55,14 @@ const UPLINK_LIMIT: (NonZeroU32, std::time::Duration) =
     (nonzero!(10u32), std::time::Duration::from_secs(1));
 
 // Internal unified client
+//
+// With `WSClientOptions::credentials`, the client also receives the user data stream,
+// see <https://binance-docs.github.io/apidocs/spot/en/#user-data-streams>
 pub struct BinanceWSClient<const MARKET_TYPE: char> {
     client: WSClientInternal<BinanceMessageHandler>,
     translator: BinanceCommandTranslator,
+    // keeps the listen key of the user data stream alive
+    keepalive: Option<tokio::task::JoinHandle<()>>,
 }
 
 /// Binance Spot market.
@@ -59,7 +84,11 @@ pub type BinanceInverseWSClient = BinanceWSClient<'I'>;
 pub type BinanceLinearWSClient = BinanceWSClient<'L'>;
 
 impl<const MARKET_TYPE: char> BinanceWSClient<MARKET_TYPE> {
-    pub async fn new(tx: std::sync::mpsc::Sender<String>, url: Option<&str>) -> Self {
+    async fn connect(
+        tx: MessageSender,
+        url: Option<&str>,
+        options: WSClientOptions,
+    ) -> Result<Self, WsError> {
         let real_url = match url {
             Some(endpoint) => endpoint,
             None => {
@@ -74,22 +103,159 @@ impl<const MARKET_TYPE: char> BinanceWSClient<MARKET_TYPE> {
                 }
             }
         };
-        BinanceWSClient {
-            client: WSClientInternal::connect(
-                EXCHANGE_NAME,
-                real_url,
-                BinanceMessageHandler {},
-                Some(UPLINK_LIMIT),
-                tx,
-            )
-            .await,
+        let user_data_stream = options.credentials.clone().map(|credentials| {
+            Arc::new(UserDataStream {
+                url: Self::listen_key_url(options.rest_url.as_deref()),
+                credentials,
+                listen_key: Mutex::new(None),
+            })
+        });
+        let authenticator = user_data_stream
+            .clone()
+            .map(|stream| Box::new(UserDataStreamAuthenticator(stream)) as Box<dyn Authenticator>);
+        let client = WSClientInternal::connect_with_auth(
+            EXCHANGE_NAME,
+            real_url,
+            BinanceMessageHandler {},
+            Some(UPLINK_LIMIT),
+            tx,
+            options,
+            authenticator,
+        )
+        .await?;
+        let keepalive =
+            user_data_stream.map(|stream| tokio::task::spawn(keep_listen_key_alive(stream)));
+        Ok(BinanceWSClient {
+            client,
             translator: BinanceCommandTranslator {
                 market_type: MARKET_TYPE,
             },
+            keepalive,
+        })
+    }
+
+    fn listen_key_url(rest_url: Option<&str>) -> String {
+        let (default_rest_url, path) = match MARKET_TYPE {
+            'S' => (SPOT_REST_URL, "/api/v3/userDataStream"),
+            'I' => (INVERSE_REST_URL, "/dapi/v1/listenKey"),
+            'L' => (LINEAR_REST_URL, "/fapi/v1/listenKey"),
+            _ => panic!("Unknown market type {}", MARKET_TYPE),
+        };
+        format!("{}{}", rest_url.unwrap_or(default_rest_url), path)
+    }
+}
+
+impl<const MARKET_TYPE: char> Drop for BinanceWSClient<MARKET_TYPE> {
+    fn drop(&mut self) {
+        if let Some(keepalive) = self.keepalive.take() {
+            keepalive.abort();
+        }
+    }
+}
+
+// The user data stream, whose listen key is created for every connection.
+struct UserDataStream {
+    url: String,
+    credentials: Credentials,
+    // the listen key of the current connection
+    listen_key: Mutex<Option<String>>,
+}
+
+impl UserDataStream {
+    fn listen_key(&self) -> Option<String> {
+        self.listen_key.lock().unwrap().clone()
+    }
+}
+
+// Subscribes the user data stream as another stream of the combined streams.
+//
+// A new listen key is created on every connect, so that reconnects after the listen key
+// expired resume the user data stream.
+struct UserDataStreamAuthenticator(Arc<UserDataStream>);
+
+#[async_trait]
+impl Authenticator for UserDataStreamAuthenticator {
+    async fn prepare(&self) -> Result<(), WsError> {
+        let listen_key = create_listen_key(&self.0.url, &self.0.credentials).await?;
+        *self.0.listen_key.lock().unwrap() = Some(listen_key);
+        Ok(())
+    }
+
+    fn login_commands(&self) -> Vec<String> {
+        match self.0.listen_key() {
+            Some(listen_key) => vec![format!(
+                r#"{{"id":{},"method":"SUBSCRIBE","params":["{}"]}}"#,
+                LISTEN_KEY_COMMAND_ID, listen_key
+            )],
+            None => Vec::new(),
+        }
+    }
+
+    fn check_login_response(&self, msg: &str) -> Option<Result<(), String>> {
+        let obj = serde_json::from_str::<HashMap<String, Value>>(msg).ok()?;
+        if obj.get("id")?.as_u64()? != LISTEN_KEY_COMMAND_ID {
+            return None;
+        }
+        match obj.get("result") {
+            Some(Value::Null) => Some(Ok(())),
+            _ => Some(Err(msg.to_string())),
+        }
+    }
+}
+
+// See https://binance-docs.github.io/apidocs/spot/en/#listen-key-spot
+async fn create_listen_key(url: &str, credentials: &Credentials) -> Result<String, WsError> {
+    let client = reqwest::Client::new();
+    let txt = client
+        .post(url)
+        .header("X-MBX-APIKEY", credentials.api_key.as_str())
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|err| WsError::Auth(format!("Failed to create a listen key, {}", err)))?
+        .text()
+        .await
+        .map_err(|err| WsError::Auth(format!("Failed to create a listen key, {}", err)))?;
+    serde_json::from_str::<HashMap<String, Value>>(&txt)
+        .ok()
+        .and_then(|obj| obj.get("listenKey")?.as_str().map(|s| s.to_string()))
+        .ok_or_else(|| WsError::Auth(format!("Failed to create a listen key, {}", txt)))
+}
+
+// Keeps the listen key of the current connection alive.
+async fn keep_listen_key_alive(stream: Arc<UserDataStream>) {
+    let client = reqwest::Client::new();
+    let mut timer = tokio::time::interval_at(
+        tokio::time::Instant::now() + LISTEN_KEY_KEEPALIVE_INTERVAL,
+        LISTEN_KEY_KEEPALIVE_INTERVAL,
+    );
+    loop {
+        timer.tick().await;
+        let listen_key = match stream.listen_key() {
+            Some(listen_key) => listen_key,
+            None => continue,
+        };
+        let resp = client
+            .put(&stream.url)
+            .header("X-MBX-APIKEY", stream.credentials.api_key.as_str())
+            .query(&[("listenKey", listen_key.as_str())])
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status());
+        match resp {
+            Ok(_) => debug!("Kept the listen key {} alive", listen_key),
+            // the server sends listenKeyExpired if the listen key expires, then the client
+            // reconnects with a new one
+            Err(err) => error!(
+                "Failed to keep the listen key {} alive, {}",
+                listen_key, err
+            ),
         }
     }
 }
 
+impl_constructors!(BinanceWSClient<const MARKET_TYPE: char>);
+
 #[async_trait]
 impl<const URL: char> WSClient for BinanceWSClient<URL> {
     async fn subscribe_trade(&self, symbols: &[String]) {
@@ -140,20 +306,23 @@ impl<const URL: char> WSClient for BinanceWSClient<URL> {
     }
 
     async fn subscribe_candlestick(&self, symbol_interval_list: &[(String, usize)]) {
-        let commands = self
-            .translator
-            .translate_to_candlestick_commands(true, symbol_interval_list);
-        self.client.send(&commands).await;
+        self.client
+            .subscribe_candlestick(&self.translator, symbol_interval_list)
+            .await;
+    }
+
+    async fn unsubscribe_candlestick(&self, symbol_interval_list: &[(String, usize)]) {
+        self.client
+            .unsubscribe_candlestick(&self.translator, symbol_interval_list)
+            .await;
     }
 
     async fn subscribe(&self, topics: &[(String, String)]) {
-        let commands = self.translator.translate_to_commands(true, topics);
-        self.client.send(&commands).await;
+        self.client.subscribe(&self.translator, topics).await;
     }
 
     async fn unsubscribe(&self, topics: &[(String, String)]) {
-        let commands = self.translator.translate_to_commands(false, topics);
-        self.client.send(&commands).await;
+        self.client.unsubscribe(&self.translator, topics).await;
     }
 
     async fn send(&self, commands: &[String]) {
@@ -161,12 +330,28 @@ impl<const URL: char> WSClient for BinanceWSClient<URL> {
     }
 
     async fn run(&self) {
-        self.client.run().await;
+        self.client.run(&self.translator).await;
     }
 
     fn close(&self) {
         self.client.close();
     }
+
+    async fn shutdown(&self) {
+        self.client.shutdown().await;
+    }
+
+    fn metrics(&self) -> Vec<MetricsSnapshot> {
+        vec![self.client.metrics()]
+    }
+
+    fn topics(&self) -> Vec<(String, String)> {
+        self.client.topics()
+    }
+
+    fn candlesticks(&self) -> Vec<(String, usize)> {
+        self.client.candlesticks()
+    }
 }
 
 struct BinanceMessageHandler {}
@@ -224,16 +409,40 @@ impl MessageHandler for BinanceMessageHandler {
         }
         let obj = resp.unwrap();
 
-        if obj.contains_key("error") {
+        if obj.contains_key("stream") && obj.contains_key("data") {
+            // See https://binance-docs.github.io/apidocs/spot/en/#listen-key-spot
+            if obj["data"].get("e").and_then(|e| e.as_str()) == Some("listenKeyExpired") {
+                warn!("Received {} from {}, reconnecting", msg, EXCHANGE_NAME);
+                MiscMessage::Reconnect
+            } else {
+                MiscMessage::Normal
+            }
+        } else if let Some(id) = obj.get("id").filter(|_| !obj.contains_key("result")) {
+            // e.g., {"error":{"code":2,"msg":"Invalid request"},"id":9527} or {"code":2,"msg":"Invalid request","id":9527}
+            let error = obj.get("error").unwrap_or(&Value::Null);
+            let reason = error
+                .get("msg")
+                .or_else(|| obj.get("msg"))
+                .and_then(|x| x.as_str())
+                .unwrap_or(msg);
+            MiscMessage::Ack {
+                key: id.to_string(),
+                error: Some(reason.to_string()),
+            }
+        } else if obj.contains_key("error") {
             panic!("Received {} from {}", msg, EXCHANGE_NAME);
-        } else if obj.contains_key("stream") && obj.contains_key("data") {
-            MiscMessage::Normal
         } else {
             if let Some(result) = obj.get("result") {
                 if serde_json::Value::Null != *result {
                     panic!("Received {} from {}", msg, EXCHANGE_NAME);
                 } else {
                     info!("Received {} from {}", msg, EXCHANGE_NAME);
+                    if let Some(id) = obj.get("id") {
+                        return MiscMessage::Ack {
+                            key: id.to_string(),
+                            error: None,
+                        };
+                    }
                 }
             } else {
                 warn!("Received {} from {}", msg, EXCHANGE_NAME);
@@ -252,6 +461,10 @@ impl MessageHandler for BinanceMessageHandler {
         // Send unsolicited pong frames per 3 minutes
         Some((Message::Pong(Vec::new()), 180))
     }
+
+    fn codec(&self) -> Codec {
+        Codec::Gzip
+    }
 }
 
 impl CommandTranslator for BinanceCommandTranslator {
@@ -287,11 +500,42 @@ impl CommandTranslator for BinanceCommandTranslator {
             .collect::<Vec<(String, String)>>();
         self.translate_to_commands(subscribe, &topics)
     }
+
+    // Every command is acknowledged by {"result":null,"id":9527}, see
+    // https://binance-docs.github.io/apidocs/spot/en/#live-subscribing-unsubscribing-to-streams
+    fn pending_acks(&self, command: &str) -> Vec<PendingAck> {
+        let obj = match serde_json::from_str::<HashMap<String, Value>>(command) {
+            Ok(obj) => obj,
+            Err(_) => return Vec::new(),
+        };
+        match (obj.get("id"), obj.get("method").and_then(|x| x.as_str())) {
+            (Some(id), Some(method)) => {
+                let raw_topics = obj
+                    .get("params")
+                    .and_then(|x| x.as_array())
+                    .map(|arr| {
+                        arr.iter()
+                            .filter_map(|x| x.as_str().map(|s| s.to_string()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                vec![PendingAck {
+                    key: id.to_string(),
+                    raw_topics,
+                    subscribe: method == "SUBSCRIBE",
+                }]
+            }
+            _ => Vec::new(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::common::command_translator::CommandTranslator;
+    use crate::common::{
+        command_translator::CommandTranslator,
+        message_handler::{MessageHandler, MiscMessage},
+    };
 
     #[test]
     fn test_one_topic() {
@@ -323,4 +567,33 @@ mod tests {
             commands[0]
         );
     }
+
+    #[test]
+    fn test_pending_acks() {
+        let translator = super::BinanceCommandTranslator { market_type: 'S' };
+        let commands = translator
+            .translate_to_commands(true, &[("aggTrade".to_string(), "BTCUSDT".to_string())]);
+        let acks = translator.pending_acks(&commands[0]);
+        assert_eq!(1, acks.len());
+        assert_eq!("9527", acks[0].key);
+        assert_eq!(vec!["btcusdt@aggTrade".to_string()], acks[0].raw_topics);
+        assert!(acks[0].subscribe);
+    }
+
+    #[test]
+    fn test_ack() {
+        let mut handler = super::BinanceMessageHandler {};
+        assert!(matches!(
+            handler.handle_message(r#"{"result":null,"id":9527}"#),
+            MiscMessage::Ack { key, error: None } if key == "9527"
+        ));
+        assert!(matches!(
+            handler.handle_message(r#"{"error":{"code":2,"msg":"Invalid request"},"id":9527}"#),
+            MiscMessage::Ack { key, error: Some(reason) } if key == "9527" && reason == "Invalid request"
+        ));
+        assert!(matches!(
+            handler.handle_message(r#"{"code":2,"msg":"Invalid request","id":9527}"#),
+            MiscMessage::Ack { error: Some(_), .. }
+        ));
+    }
 }
@@ -7,6 +7,7 @@ use crate::{
         Candlestick, Level3OrderBook, OrderBook, OrderBookTopK, Ticker, Trade, BBO,
     },
     common::{
+        codec::Codec,
         command_translator::CommandTranslator,
         message_handler::{MessageHandler, MiscMessage},
         ws_client_internal::WSClientInternal,
@@ -126,6 +127,10 @@ impl MessageHandler for BinanceOptionMessageHandler {
         // connection will be disconnected. Unsolicited ping frames are allowed.
         Some((Message::Text(r#"{"event":"ping"}"#.to_string()), 120))
     }
+
+    fn codec(&self) -> Codec {
+        Codec::Gzip
+    }
 }
 
 impl CommandTranslator for BinanceOptionCommandTranslator {
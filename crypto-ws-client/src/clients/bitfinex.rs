@@ -55,32 +55,22 @@ panic_l2_topk!(BitfinexWSClient);
 #[async_trait]
 impl OrderBook for BitfinexWSClient {
     async fn subscribe_orderbook(&self, symbols: &[String]) {
-        let commands = symbols
+        let topics = symbols
             .iter()
-            .map(|symbol| {
-                format!(r#"{{"event": "subscribe","channel": "book","symbol": "{}","prec": "P0","frec": "F0","len":25}}"#,
-                    symbol,
-                )
-            })
-            .collect::<Vec<String>>();
-
-        self.send(&commands).await;
+            .map(|symbol| ("book".to_string(), symbol.to_string()))
+            .collect::<Vec<(String, String)>>();
+        self.subscribe(&topics).await;
     }
 }
 
 #[async_trait]
 impl Level3OrderBook for BitfinexWSClient {
     async fn subscribe_l3_orderbook(&self, symbols: &[String]) {
-        let commands = symbols
+        let topics = symbols
             .iter()
-            .map(|symbol| {
-                format!(r#"{{"event": "subscribe","channel": "book","symbol": "{}","prec": "R0","len": 250}}"#,
-                    symbol,
-                )
-            })
-            .collect::<Vec<String>>();
-
-        self.send(&commands).await;
+            .map(|symbol| ("raw_book".to_string(), symbol.to_string()))
+            .collect::<Vec<(String, String)>>();
+        self.subscribe(&topics).await;
     }
 }
 
@@ -92,17 +82,28 @@ struct BitfinexMessageHandler {
 struct BitfinexCommandTranslator {}
 
 impl BitfinexCommandTranslator {
+    // `book` is the level2 orderbook and `raw_book` is the level3 orderbook, see
+    // <https://docs.bitfinex.com/reference/ws-public-books>
     fn topic_to_command(channel: &str, symbol: &str, subscribe: bool) -> String {
-        format!(
-            r#"{{"event": "{}", "channel": "{}", "symbol": "{}"}}"#,
-            if subscribe {
-                "subscribe"
-            } else {
-                "unsubscribe"
-            },
-            channel,
-            symbol
-        )
+        let event = if subscribe {
+            "subscribe"
+        } else {
+            "unsubscribe"
+        };
+        match channel {
+            "book" => format!(
+                r#"{{"event": "{}","channel": "book","symbol": "{}","prec": "P0","frec": "F0","len":25}}"#,
+                event, symbol,
+            ),
+            "raw_book" => format!(
+                r#"{{"event": "{}","channel": "book","symbol": "{}","prec": "R0","len": 250}}"#,
+                event, symbol,
+            ),
+            _ => format!(
+                r#"{{"event": "{}", "channel": "{}", "symbol": "{}"}}"#,
+                event, channel, symbol
+            ),
+        }
     }
     fn to_candlestick_command(symbol: &str, interval: usize, subscribe: bool) -> String {
         let interval_str = match interval {
@@ -315,4 +316,26 @@ mod tests {
             commands[0]
         );
     }
+
+    #[test]
+    fn test_orderbook_command() {
+        let translator = super::BitfinexCommandTranslator {};
+        let commands = translator.translate_to_commands(
+            true,
+            &[
+                ("book".to_string(), "tBTCUSD".to_string()),
+                ("raw_book".to_string(), "tBTCUSD".to_string()),
+            ],
+        );
+
+        assert_eq!(2, commands.len());
+        assert_eq!(
+            r#"{"event": "subscribe","channel": "book","symbol": "tBTCUSD","prec": "P0","frec": "F0","len":25}"#,
+            commands[0]
+        );
+        assert_eq!(
+            r#"{"event": "subscribe","channel": "book","symbol": "tBTCUSD","prec": "R0","len": 250}"#,
+            commands[1]
+        );
+    }
 }
@@ -4,8 +4,11 @@ use crate::{
     clients::common_traits::{
         Candlestick, Level3OrderBook, OrderBook, OrderBookTopK, Ticker, Trade, BBO,
     },
-    common::{command_translator::CommandTranslator, ws_client_internal::WSClientInternal},
-    WSClient,
+    common::{
+        message_sender::MessageSender, options::WSClientOptions,
+        ws_client_internal::WSClientInternal,
+    },
+    WSClient, WsError,
 };
 
 use super::{
@@ -25,25 +28,32 @@ pub struct BitgetSpotWSClient {
 }
 
 impl BitgetSpotWSClient {
-    pub async fn new(tx: std::sync::mpsc::Sender<String>, url: Option<&str>) -> Self {
+    async fn connect(
+        tx: MessageSender,
+        url: Option<&str>,
+        options: WSClientOptions,
+    ) -> Result<Self, WsError> {
         let real_url = match url {
             Some(endpoint) => endpoint,
             None => WEBSOCKET_URL,
         };
-        BitgetSpotWSClient {
+        Ok(BitgetSpotWSClient {
             client: WSClientInternal::connect(
                 EXCHANGE_NAME,
                 real_url,
                 BitgetMessageHandler {},
                 Some(UPLINK_LIMIT),
                 tx,
+                options,
             )
-            .await,
+            .await?,
             translator: BitgetCommandTranslator::<'S'> {},
-        }
+        })
     }
 }
 
+impl_constructors!(BitgetSpotWSClient);
+
 impl_trait!(Trade, BitgetSpotWSClient, subscribe_trade, "trade");
 #[rustfmt::skip]
 impl_trait!(OrderBookTopK, BitgetSpotWSClient, subscribe_orderbook_topk, "books15");
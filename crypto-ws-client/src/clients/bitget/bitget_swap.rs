@@ -4,8 +4,11 @@ use crate::{
     clients::common_traits::{
         Candlestick, Level3OrderBook, OrderBook, OrderBookTopK, Ticker, Trade, BBO,
     },
-    common::{command_translator::CommandTranslator, ws_client_internal::WSClientInternal},
-    WSClient,
+    common::{
+        message_sender::MessageSender, options::WSClientOptions,
+        ws_client_internal::WSClientInternal,
+    },
+    WSClient, WsError,
 };
 
 use super::{
@@ -25,25 +28,32 @@ pub struct BitgetSwapWSClient {
 }
 
 impl BitgetSwapWSClient {
-    pub async fn new(tx: std::sync::mpsc::Sender<String>, url: Option<&str>) -> Self {
+    async fn connect(
+        tx: MessageSender,
+        url: Option<&str>,
+        options: WSClientOptions,
+    ) -> Result<Self, WsError> {
         let real_url = match url {
             Some(endpoint) => endpoint,
             None => WEBSOCKET_URL,
         };
-        BitgetSwapWSClient {
+        Ok(BitgetSwapWSClient {
             client: WSClientInternal::connect(
                 EXCHANGE_NAME,
                 real_url,
                 BitgetMessageHandler {},
                 Some(UPLINK_LIMIT),
                 tx,
+                options,
             )
-            .await,
+            .await?,
             translator: BitgetCommandTranslator::<'M'> {},
-        }
+        })
     }
 }
 
+impl_constructors!(BitgetSwapWSClient);
+
 impl_trait!(Trade, BitgetSwapWSClient, subscribe_trade, "trade");
 #[rustfmt::skip]
 impl_trait!(OrderBookTopK, BitgetSwapWSClient, subscribe_orderbook_topk, "books15");
@@ -9,6 +9,7 @@ use log::*;
 use serde_json::Value;
 
 use crate::common::{
+    codec::Codec,
     command_translator::CommandTranslator,
     message_handler::{MessageHandler, MiscMessage},
     utils::ensure_frame_size,
@@ -121,6 +122,10 @@ impl MessageHandler for BitgetMessageHandler {
         // https://bitgetlimited.github.io/apidoc/en/spot/#connect
         Some((Message::Text("ping".to_string()), 30))
     }
+
+    fn codec(&self) -> Codec {
+        Codec::Gzip
+    }
 }
 
 impl<const MARKET_TYPE: char> CommandTranslator for BitgetCommandTranslator<MARKET_TYPE> {
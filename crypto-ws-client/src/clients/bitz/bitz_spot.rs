@@ -10,6 +10,7 @@ use crate::{
         Candlestick, Level3OrderBook, OrderBook, OrderBookTopK, Ticker, Trade, BBO,
     },
     common::{
+        codec::Codec,
         command_translator::CommandTranslator,
         message_handler::{MessageHandler, MiscMessage},
         ws_client_internal::WSClientInternal,
@@ -103,6 +104,10 @@ impl MessageHandler for BitzMessageHandler {
         // See https://apidocv2.bitz.plus/en/#heartbeat-and-persistent-connection-strategy
         Some((Message::Text("ping".to_string()), 10))
     }
+
+    fn codec(&self) -> Codec {
+        Codec::Gzip
+    }
 }
 
 impl BitzCommandTranslator {
@@ -8,7 +8,7 @@ use crate::{
     WSClient,
 };
 
-use super::utils::{BybitMessageHandler, EXCHANGE_NAME};
+use super::utils::{BybitAuthenticator, BybitMessageHandler, EXCHANGE_NAME};
 
 const WEBSOCKET_URL: &str = "wss://stream.bybit.com/realtime";
 
@@ -31,7 +31,9 @@ impl_new_constructor!(
     EXCHANGE_NAME,
     WEBSOCKET_URL,
     BybitMessageHandler {},
-    BybitInverseCommandTranslator {}
+    BybitInverseCommandTranslator {},
+    BybitAuthenticator,
+    WEBSOCKET_URL
 );
 
 impl_trait!(Trade, BybitInverseWSClient, subscribe_trade, "trade");
@@ -8,9 +8,10 @@ use crate::{
     WSClient,
 };
 
-use super::utils::{BybitMessageHandler, EXCHANGE_NAME};
+use super::utils::{BybitAuthenticator, BybitMessageHandler, EXCHANGE_NAME};
 
 const WEBSOCKET_URL: &str = "wss://stream.bybit.com/realtime_public";
+const PRIVATE_WEBSOCKET_URL: &str = "wss://stream.bybit.com/realtime_private";
 
 /// Bybit LinearSwap market.
 ///
@@ -26,7 +27,9 @@ impl_new_constructor!(
     EXCHANGE_NAME,
     WEBSOCKET_URL,
     BybitMessageHandler {},
-    BybitLinearCommandTranslator {}
+    BybitLinearCommandTranslator {},
+    BybitAuthenticator,
+    PRIVATE_WEBSOCKET_URL
 );
 
 impl_trait!(Trade, BybitLinearSwapWSClient, subscribe_trade, "trade");
@@ -4,14 +4,27 @@ use log::*;
 use serde_json::Value;
 use tokio_tungstenite::tungstenite::Message;
 
-use crate::common::message_handler::{MessageHandler, MiscMessage};
+use crate::common::{
+    auth::{hmac_sha256, to_hex, unix_millis, Authenticator, Credentials},
+    message_handler::{MessageHandler, MiscMessage},
+};
 
 pub(super) const EXCHANGE_NAME: &str = "bybit";
 
+// How long a signature stays valid
+const AUTH_EXPIRES_MS: u128 = 10000;
+
 pub(super) fn topics_to_command(topics: &[(String, String)], subscribe: bool) -> String {
     let raw_channels = topics
         .iter()
-        .map(|(channel, symbol)| format!("{}.{}", channel, symbol))
+        .map(|(channel, symbol)| {
+            // private topics such as order and position have no symbol
+            if symbol.is_empty() {
+                channel.to_string()
+            } else {
+                format!("{}.{}", channel, symbol)
+            }
+        })
         .collect::<Vec<String>>();
     format!(
         r#"{{"op":"{}","args":{}}}"#,
@@ -26,6 +39,36 @@ pub(super) fn topics_to_command(topics: &[(String, String)], subscribe: bool) ->
 
 pub(super) struct BybitMessageHandler {}
 
+// See https://bybit-exchange.github.io/docs/inverse/#t-authentication
+pub(super) struct BybitAuthenticator(pub Credentials);
+
+impl Authenticator for BybitAuthenticator {
+    fn login_commands(&self) -> Vec<String> {
+        let expires = unix_millis() + AUTH_EXPIRES_MS;
+        let signature = to_hex(&hmac_sha256(
+            &self.0.api_secret,
+            &format!("GET/realtime{}", expires),
+        ));
+        vec![format!(
+            r#"{{"op":"auth","args":["{}",{},"{}"]}}"#,
+            self.0.api_key, expires, signature
+        )]
+    }
+
+    fn check_login_response(&self, msg: &str) -> Option<Result<(), String>> {
+        let obj = serde_json::from_str::<HashMap<String, Value>>(msg).ok()?;
+        let op = obj.get("request")?.get("op")?.as_str()?;
+        if op != "auth" {
+            return None;
+        }
+        if obj.get("success")?.as_bool()? {
+            Some(Ok(()))
+        } else {
+            Some(Err(msg.to_string()))
+        }
+    }
+}
+
 impl MessageHandler for BybitMessageHandler {
     fn handle_message(&mut self, msg: &str) -> MiscMessage {
         let obj = serde_json::from_str::<HashMap<String, Value>>(msg).unwrap();
@@ -69,6 +112,40 @@ mod tests {
         assert_eq!(r#"{"op":"subscribe","args":["trade.BTCUSD"]}"#, command);
     }
 
+    #[test]
+    fn test_private_channel() {
+        let command = super::topics_to_command(&[("order".to_string(), "".to_string())], true);
+        assert_eq!(r#"{"op":"subscribe","args":["order"]}"#, command);
+    }
+
+    #[test]
+    fn test_auth() {
+        use crate::common::auth::{Authenticator, Credentials};
+
+        let authenticator = super::BybitAuthenticator(Credentials::new("key", "secret"));
+        let commands = authenticator.login_commands();
+        assert_eq!(1, commands.len());
+        assert!(commands[0].starts_with(r#"{"op":"auth","args":["key","#));
+        assert_eq!(
+            Some(Ok(())),
+            authenticator.check_login_response(
+                r#"{"success":true,"ret_msg":"","conn_id":"abc","request":{"op":"auth","args":["key",1662350400000,"sig"]}}"#
+            )
+        );
+        assert!(matches!(
+            authenticator.check_login_response(
+                r#"{"success":false,"ret_msg":"error sign!","conn_id":"abc","request":{"op":"auth","args":["key",1662350400000,"sig"]}}"#
+            ),
+            Some(Err(_))
+        ));
+        assert_eq!(
+            None,
+            authenticator.check_login_response(
+                r#"{"success":true,"ret_msg":"pong","conn_id":"abc","request":{"op":"ping","args":null}}"#
+            )
+        );
+    }
+
     #[test]
     fn test_multiple_channels() {
         let command = super::topics_to_command(
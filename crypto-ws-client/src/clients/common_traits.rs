@@ -69,10 +69,9 @@ macro_rules! impl_candlestick {
         #[async_trait]
         impl Candlestick for $struct_name {
             async fn subscribe_candlestick(&self, symbol_interval_list: &[(String, usize)]) {
-                let commands = self
-                    .translator
-                    .translate_to_candlestick_commands(true, symbol_interval_list);
-                self.client.send(&commands).await;
+                self.client
+                    .subscribe_candlestick(&self.translator, symbol_interval_list)
+                    .await;
             }
         }
     };
@@ -159,28 +158,141 @@ macro_rules! panic_candlestick {
     };
 }
 
-/// Implement the new() constructor.
+/// Implement the new(), new_async() and new_stream() constructors on top of
+/// a private `connect(tx, url)` function.
+macro_rules! impl_constructors {
+    (@methods) => {
+        /// Creates a websocket client.
+        ///
+        /// # Arguments
+        ///
+        /// * `tx` - The sending part of a channel
+        /// * `url` - Optional server url, usually you don't need specify it
+        pub async fn new(
+            tx: std::sync::mpsc::Sender<String>,
+            url: Option<&str>,
+        ) -> Result<Self, $crate::WsError> {
+            Self::connect(tx.into(), url, $crate::WSClientOptions::default()).await
+        }
+
+        /// Creates a websocket client which sends messages to a tokio channel.
+        ///
+        /// If the channel is full, `run()` waits until the receiver catches up.
+        ///
+        /// # Arguments
+        ///
+        /// * `tx` - The sending part of a bounded tokio channel
+        /// * `url` - Optional server url, usually you don't need specify it
+        pub async fn new_async(
+            tx: tokio::sync::mpsc::Sender<String>,
+            url: Option<&str>,
+        ) -> Result<Self, $crate::WsError> {
+            Self::connect(tx.into(), url, $crate::WSClientOptions::default()).await
+        }
+
+        /// Creates a websocket client and a stream of its messages.
+        ///
+        /// # Arguments
+        ///
+        /// * `capacity` - Max number of messages buffered before `run()` waits for the consumer
+        /// * `url` - Optional server url, usually you don't need specify it
+        pub async fn new_stream(
+            capacity: usize,
+            url: Option<&str>,
+        ) -> Result<(Self, $crate::MessageStream), $crate::WsError> {
+            let (tx, rx) = tokio::sync::mpsc::channel(capacity);
+            let ws_client = Self::connect(tx.into(), url, $crate::WSClientOptions::default()).await?;
+            Ok((ws_client, $crate::MessageStream::new(rx)))
+        }
+
+        /// Creates a websocket client with options.
+        ///
+        /// # Arguments
+        ///
+        /// * `tx` - The sending part of a std or tokio channel
+        /// * `url` - Optional server url, overrides `WSClientOptions::url`
+        /// * `options` - Options such as thresholds of the stale-connection watchdog
+        pub async fn new_with_options(
+            tx: impl Into<$crate::MessageSender>,
+            url: Option<&str>,
+            options: $crate::WSClientOptions,
+        ) -> Result<Self, $crate::WsError> {
+            let url = url.map(|url| url.to_string()).or_else(|| options.url.clone());
+            Self::connect(tx.into(), url.as_deref(), options).await
+        }
+    };
+    ($struct_name:ident<const $param:ident: char>) => {
+        impl<const $param: char> $struct_name<$param> {
+            impl_constructors!(@methods);
+        }
+    };
+    ($struct_name:ident) => {
+        impl $struct_name {
+            impl_constructors!(@methods);
+        }
+    };
+}
+
+/// Implement the constructors with a default url.
 macro_rules! impl_new_constructor {
+    // With an authenticator constructed from `Credentials`, the client connects to
+    // `$private_url` by default and logs in when `WSClientOptions::credentials` is present.
+    ($struct_name:ident, $exchange:ident, $default_url:expr, $handler:expr, $translator:expr, $authenticator:path, $private_url:expr) => {
+        impl $struct_name {
+            async fn connect(
+                tx: $crate::common::message_sender::MessageSender,
+                url: Option<&str>,
+                options: $crate::WSClientOptions,
+            ) -> Result<Self, $crate::WsError> {
+                let authenticator = options.credentials.clone().map(|credentials| {
+                    Box::new($authenticator(credentials))
+                        as Box<dyn $crate::common::auth::Authenticator>
+                });
+                let real_url = match url {
+                    Some(endpoint) => endpoint,
+                    None if authenticator.is_some() => $private_url,
+                    None => $default_url,
+                };
+                Ok($struct_name {
+                    client: WSClientInternal::connect_with_auth(
+                        $exchange,
+                        real_url,
+                        $handler,
+                        None,
+                        tx,
+                        options,
+                        authenticator,
+                    )
+                    .await?,
+                    translator: $translator,
+                })
+            }
+        }
+
+        impl_constructors!($struct_name);
+    };
     ($struct_name:ident, $exchange:ident, $default_url:expr, $handler:expr, $translator:expr) => {
         impl $struct_name {
-            /// Creates a websocket client.
-            ///
-            /// # Arguments
-            ///
-            /// * `tx` - The sending part of a channel
-            /// * `url` - Optional server url, usually you don't need specify it
-            pub async fn new(tx: std::sync::mpsc::Sender<String>, url: Option<&str>) -> Self {
+            async fn connect(
+                tx: $crate::common::message_sender::MessageSender,
+                url: Option<&str>,
+                options: $crate::WSClientOptions,
+            ) -> Result<Self, $crate::WsError> {
                 let real_url = match url {
                     Some(endpoint) => endpoint,
                     None => $default_url,
                 };
-                $struct_name {
-                    client: WSClientInternal::connect($exchange, real_url, $handler, None, tx)
-                        .await,
+                Ok($struct_name {
+                    client: WSClientInternal::connect(
+                        $exchange, real_url, $handler, None, tx, options,
+                    )
+                    .await?,
                     translator: $translator,
-                }
+                })
             }
         }
+
+        impl_constructors!($struct_name);
     };
 }
 
@@ -218,14 +330,18 @@ macro_rules! impl_ws_client_trait {
                     .await
             }
 
+            async fn unsubscribe_candlestick(&self, symbol_interval_list: &[(String, usize)]) {
+                self.client
+                    .unsubscribe_candlestick(&self.translator, symbol_interval_list)
+                    .await;
+            }
+
             async fn subscribe(&self, topics: &[(String, String)]) {
-                let commands = self.translator.translate_to_commands(true, topics);
-                self.client.send(&commands).await;
+                self.client.subscribe(&self.translator, topics).await;
             }
 
             async fn unsubscribe(&self, topics: &[(String, String)]) {
-                let commands = self.translator.translate_to_commands(false, topics);
-                self.client.send(&commands).await;
+                self.client.unsubscribe(&self.translator, topics).await;
             }
 
             async fn send(&self, commands: &[String]) {
@@ -233,12 +349,28 @@ macro_rules! impl_ws_client_trait {
             }
 
             async fn run(&self) {
-                self.client.run().await;
+                self.client.run(&self.translator).await;
             }
 
             fn close(&self) {
                 self.client.close();
             }
+
+            async fn shutdown(&self) {
+                self.client.shutdown().await;
+            }
+
+            fn metrics(&self) -> Vec<$crate::common::metrics::MetricsSnapshot> {
+                vec![self.client.metrics()]
+            }
+
+            fn topics(&self) -> Vec<(String, String)> {
+                self.client.topics()
+            }
+
+            fn candlesticks(&self) -> Vec<(String, usize)> {
+                self.client.candlesticks()
+            }
         }
     };
 }
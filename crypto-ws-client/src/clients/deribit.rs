@@ -7,6 +7,7 @@ use crate::{
         Candlestick, Level3OrderBook, OrderBook, OrderBookTopK, Ticker, Trade, BBO,
     },
     common::{
+        auth::{Authenticator, Credentials},
         command_translator::CommandTranslator,
         message_handler::{MessageHandler, MiscMessage},
         utils::{ensure_frame_size, topic_to_raw_channel},
@@ -26,6 +27,9 @@ const WEBSOCKET_URL: &str = "wss://www.deribit.com/ws/api/v2/";
 /// single frame in websocket connection frame exceeds the limit (32 kB)
 const WS_FRAME_SIZE: usize = 32 * 1024;
 
+// The id of the public/auth request, to recognize its response
+const AUTH_REQUEST_ID: i64 = 9527;
+
 /// The WebSocket client for Deribit.
 ///
 /// Deribit has InverseFuture, InverseSwap and Option markets.
@@ -44,7 +48,9 @@ impl_new_constructor!(
     EXCHANGE_NAME,
     WEBSOCKET_URL,
     DeribitMessageHandler {},
-    DeribitCommandTranslator {}
+    DeribitCommandTranslator {},
+    DeribitAuthenticator,
+    WEBSOCKET_URL
 );
 
 #[rustfmt::skip]
@@ -65,15 +71,30 @@ impl_ws_client_trait!(DeribitWSClient);
 
 struct DeribitMessageHandler {}
 struct DeribitCommandTranslator {}
+struct DeribitAuthenticator(Credentials);
 
 impl DeribitCommandTranslator {
     fn topics_to_command(topics: &[(String, String)], subscribe: bool) -> String {
+        Self::topics_to_scoped_command("public", topics, subscribe)
+    }
+
+    // user.* channels require private/subscribe after logging in
+    fn topics_to_private_command(topics: &[(String, String)], subscribe: bool) -> String {
+        Self::topics_to_scoped_command("private", topics, subscribe)
+    }
+
+    fn topics_to_scoped_command(
+        scope: &str,
+        topics: &[(String, String)],
+        subscribe: bool,
+    ) -> String {
         let raw_channels = topics
             .iter()
             .map(topic_to_raw_channel)
             .collect::<Vec<String>>();
         format!(
-            r#"{{"method": "public/{}", "params": {{"channels": {}}}}}"#,
+            r#"{{"method": "{}/{}", "params": {{"channels": {}}}}}"#,
+            scope,
             if subscribe {
                 "subscribe"
             } else {
@@ -149,15 +170,50 @@ impl MessageHandler for DeribitMessageHandler {
     }
 }
 
+// See https://docs.deribit.com/?shell#public-auth
+impl Authenticator for DeribitAuthenticator {
+    fn login_commands(&self) -> Vec<String> {
+        vec![format!(
+            r#"{{"jsonrpc": "2.0", "id": {}, "method": "public/auth", "params": {{"grant_type": "client_credentials", "client_id": {}, "client_secret": {}}}}}"#,
+            AUTH_REQUEST_ID,
+            serde_json::to_string(&self.0.api_key).unwrap(),
+            serde_json::to_string(&self.0.api_secret).unwrap(),
+        )]
+    }
+
+    fn check_login_response(&self, msg: &str) -> Option<Result<(), String>> {
+        let obj = serde_json::from_str::<HashMap<String, Value>>(msg).ok()?;
+        if obj.get("id")?.as_i64()? != AUTH_REQUEST_ID {
+            return None;
+        }
+        if obj.contains_key("result") {
+            Some(Ok(()))
+        } else {
+            Some(Err(msg.to_string()))
+        }
+    }
+}
+
 impl CommandTranslator for DeribitCommandTranslator {
     fn translate_to_commands(&self, subscribe: bool, topics: &[(String, String)]) -> Vec<String> {
+        let (private_topics, public_topics): (Vec<_>, Vec<_>) = topics
+            .iter()
+            .cloned()
+            .partition(|(channel, _)| channel.starts_with("user."));
         let mut all_commands: Vec<String> = ensure_frame_size(
-            topics,
+            &public_topics,
             subscribe,
             Self::topics_to_command,
             WS_FRAME_SIZE,
             None,
         );
+        all_commands.extend(ensure_frame_size(
+            &private_topics,
+            subscribe,
+            Self::topics_to_private_command,
+            WS_FRAME_SIZE,
+            None,
+        ));
 
         all_commands
             .push(r#"{"method": "public/set_heartbeat", "params": {"interval": 10}}"#.to_string());
@@ -222,4 +278,61 @@ mod tests {
             commands[1]
         );
     }
+
+    #[test]
+    fn test_private_channel() {
+        let translator = super::DeribitCommandTranslator {};
+        let commands = translator.translate_to_commands(
+            true,
+            &[
+                (
+                    "trades.SYMBOL.100ms".to_string(),
+                    "BTC-PERPETUAL".to_string(),
+                ),
+                (
+                    "user.orders.SYMBOL.raw".to_string(),
+                    "BTC-PERPETUAL".to_string(),
+                ),
+            ],
+        );
+
+        assert_eq!(3, commands.len());
+        assert_eq!(
+            r#"{"method": "public/subscribe", "params": {"channels": ["trades.BTC-PERPETUAL.100ms"]}}"#,
+            commands[0]
+        );
+        assert_eq!(
+            r#"{"method": "private/subscribe", "params": {"channels": ["user.orders.BTC-PERPETUAL.raw"]}}"#,
+            commands[1]
+        );
+    }
+
+    #[test]
+    fn test_auth() {
+        use crate::common::auth::{Authenticator, Credentials};
+
+        let authenticator = super::DeribitAuthenticator(Credentials::new("id", "secret"));
+        assert_eq!(
+            vec![
+                r#"{"jsonrpc": "2.0", "id": 9527, "method": "public/auth", "params": {"grant_type": "client_credentials", "client_id": "id", "client_secret": "secret"}}"#
+            ],
+            authenticator.login_commands()
+        );
+        assert_eq!(
+            Some(Ok(())),
+            authenticator.check_login_response(
+                r#"{"jsonrpc":"2.0","id":9527,"result":{"access_token":"token","expires_in":31536000}}"#
+            )
+        );
+        assert!(matches!(
+            authenticator.check_login_response(
+                r#"{"jsonrpc":"2.0","id":9527,"error":{"message":"invalid_credentials","code":13004}}"#
+            ),
+            Some(Err(_))
+        ));
+        assert_eq!(
+            None,
+            authenticator.check_login_response(r#"{"jsonrpc":"2.0","id":1,"result":[]}"#)
+        );
+    }
 }
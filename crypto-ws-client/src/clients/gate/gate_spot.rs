@@ -5,7 +5,7 @@ use crate::{
     clients::common_traits::{
         Candlestick, Level3OrderBook, OrderBook, OrderBookTopK, Ticker, Trade, BBO,
     },
-    common::{command_translator::CommandTranslator, ws_client_internal::WSClientInternal},
+    common::ws_client_internal::WSClientInternal,
     WSClient,
 };
 
@@ -7,11 +7,14 @@ use tokio_tungstenite::tungstenite::Message;
 
 use crate::{
     common::{
+        codec::Codec,
         command_translator::CommandTranslator,
         message_handler::{MessageHandler, MiscMessage},
+        message_sender::MessageSender,
+        options::WSClientOptions,
         ws_client_internal::WSClientInternal,
     },
-    WSClient,
+    MetricsSnapshot, WSClient, WsError,
 };
 
 pub(crate) const EXCHANGE_NAME: &str = "huobi";
@@ -69,7 +72,11 @@ pub type HuobiLinearSwapWSClient = HuobiWSClient<'L'>;
 pub type HuobiOptionWSClient = HuobiWSClient<'O'>;
 
 impl<const URL: char> HuobiWSClient<URL> {
-    pub async fn new(tx: std::sync::mpsc::Sender<String>, url: Option<&str>) -> Self {
+    async fn connect(
+        tx: MessageSender,
+        url: Option<&str>,
+        options: WSClientOptions,
+    ) -> Result<Self, WsError> {
         let real_url = match url {
             Some(endpoint) => endpoint,
             None => {
@@ -88,20 +95,23 @@ impl<const URL: char> HuobiWSClient<URL> {
                 }
             }
         };
-        HuobiWSClient {
+        Ok(HuobiWSClient {
             client: WSClientInternal::connect(
                 EXCHANGE_NAME,
                 real_url,
                 HuobiMessageHandler {},
                 None,
                 tx,
+                options,
             )
-            .await,
+            .await?,
             translator: HuobiCommandTranslator {},
-        }
+        })
     }
 }
 
+impl_constructors!(HuobiWSClient<const URL: char>);
+
 #[async_trait]
 impl<const URL: char> WSClient for HuobiWSClient<URL> {
     async fn subscribe_trade(&self, symbols: &[String]) {
@@ -120,11 +130,11 @@ impl<const URL: char> WSClient for HuobiWSClient<URL> {
                 .collect::<Vec<(String, String)>>();
             self.subscribe(&topics).await;
         } else {
-            let commands = symbols
+            let topics = symbols
                 .iter()
-                .map(|symbol| format!(r#"{{"sub":"market.{}.depth.size_20.high_freq","data_type":"incremental","id": "crypto-ws-client"}}"#, symbol))
-                .collect::<Vec<String>>();
-            self.client.send(&commands).await;
+                .map(|symbol| ("depth.size_20.high_freq".to_string(), symbol.to_string()))
+                .collect::<Vec<(String, String)>>();
+            self.subscribe(&topics).await;
         }
     }
 
@@ -165,20 +175,23 @@ impl<const URL: char> WSClient for HuobiWSClient<URL> {
     }
 
     async fn subscribe_candlestick(&self, symbol_interval_list: &[(String, usize)]) {
-        let commands = self
-            .translator
-            .translate_to_candlestick_commands(true, symbol_interval_list);
-        self.client.send(&commands).await;
+        self.client
+            .subscribe_candlestick(&self.translator, symbol_interval_list)
+            .await;
+    }
+
+    async fn unsubscribe_candlestick(&self, symbol_interval_list: &[(String, usize)]) {
+        self.client
+            .unsubscribe_candlestick(&self.translator, symbol_interval_list)
+            .await;
     }
 
     async fn subscribe(&self, topics: &[(String, String)]) {
-        let commands = self.translator.translate_to_commands(true, topics);
-        self.client.send(&commands).await;
+        self.client.subscribe(&self.translator, topics).await;
     }
 
     async fn unsubscribe(&self, topics: &[(String, String)]) {
-        let commands = self.translator.translate_to_commands(false, topics);
-        self.client.send(&commands).await;
+        self.client.unsubscribe(&self.translator, topics).await;
     }
 
     async fn send(&self, commands: &[String]) {
@@ -186,12 +199,28 @@ impl<const URL: char> WSClient for HuobiWSClient<URL> {
     }
 
     async fn run(&self) {
-        self.client.run().await;
+        self.client.run(&self.translator).await;
     }
 
     fn close(&self) {
         self.client.close();
     }
+
+    async fn shutdown(&self) {
+        self.client.shutdown().await;
+    }
+
+    fn metrics(&self) -> Vec<MetricsSnapshot> {
+        vec![self.client.metrics()]
+    }
+
+    fn topics(&self) -> Vec<(String, String)> {
+        self.client.topics()
+    }
+
+    fn candlesticks(&self) -> Vec<(String, usize)> {
+        self.client.candlesticks()
+    }
 }
 
 struct HuobiMessageHandler {}
@@ -200,11 +229,20 @@ struct HuobiCommandTranslator {}
 impl HuobiCommandTranslator {
     fn topic_to_command(channel: &str, symbol: &str, subscribe: bool) -> String {
         let raw_channel = format!("market.{}.{}", symbol, channel);
-        format!(
-            r#"{{"{}":"{}","id":"crypto-ws-client"}}"#,
-            if subscribe { "sub" } else { "unsub" },
-            raw_channel
-        )
+        if channel.ends_with(".high_freq") {
+            // incremental orderbooks of contracts, see https://huobiapi.github.io/docs/usdt_swap/v1/en/#subscribe-incremental-market-depth-data
+            format!(
+                r#"{{"{}":"{}","data_type":"incremental","id":"crypto-ws-client"}}"#,
+                if subscribe { "sub" } else { "unsub" },
+                raw_channel
+            )
+        } else {
+            format!(
+                r#"{{"{}":"{}","id":"crypto-ws-client"}}"#,
+                if subscribe { "sub" } else { "unsub" },
+                raw_channel
+            )
+        }
     }
 
     // see https://huobiapi.github.io/docs/dm/v1/en/#subscribe-kline-data
@@ -298,6 +336,10 @@ impl MessageHandler for HuobiMessageHandler {
         // - Option <https://huobiapi.github.io/docs/option/v1/en/#market-heartbeat>
         None
     }
+
+    fn codec(&self) -> Codec {
+        Codec::Gzip
+    }
 }
 
 impl CommandTranslator for HuobiCommandTranslator {
@@ -364,4 +406,22 @@ mod tests {
             commands[1]
         );
     }
+
+    #[test]
+    fn test_incremental_orderbook() {
+        let translator = super::HuobiCommandTranslator {};
+        let commands = translator.translate_to_commands(
+            true,
+            &[(
+                "depth.size_20.high_freq".to_string(),
+                "BTC-USDT".to_string(),
+            )],
+        );
+
+        assert_eq!(1, commands.len());
+        assert_eq!(
+            r#"{"sub":"market.BTC-USDT.depth.size_20.high_freq","data_type":"incremental","id":"crypto-ws-client"}"#,
+            commands[0]
+        );
+    }
 }
@@ -1,53 +1,69 @@
-use super::utils::{fetch_ws_token, KucoinMessageHandler, EXCHANGE_NAME, UPLINK_LIMIT};
+use super::utils::{
+    fetch_private_ws_token, fetch_ws_token, KucoinMessageHandler, EXCHANGE_NAME, UPLINK_LIMIT,
+};
 use crate::{
     clients::common_traits::{
         Candlestick, Level3OrderBook, OrderBook, OrderBookTopK, Ticker, Trade, BBO,
     },
-    common::{command_translator::CommandTranslator, ws_client_internal::WSClientInternal},
-    WSClient,
+    common::{
+        command_translator::CommandTranslator, message_sender::MessageSender,
+        options::WSClientOptions, ws_client_internal::WSClientInternal,
+    },
+    WSClient, WsError,
 };
 use async_trait::async_trait;
-use std::sync::mpsc::Sender;
+
+const REST_URL: &str = "https://api-futures.kucoin.com";
 
 /// The WebSocket client for KuCoin Swap markets.
 ///
 /// * WebSocket API doc: <https://docs.kucoin.cc/futures/#websocket-2>
 /// * Trading at: <https://futures.kucoin.com/>
+///
+/// With `WSClientOptions::credentials`, the client connects with a private token, so that
+/// private channels such as `/contractMarket/tradeOrders` can be subscribed too.
 pub struct KuCoinSwapWSClient {
     client: WSClientInternal<KucoinMessageHandler>,
     translator: KucoinCommandTranslator,
 }
 
 impl KuCoinSwapWSClient {
-    /// Creates a KuCoinSwapWSClient websocket client.
-    ///
-    /// # Arguments
-    ///
-    /// * `tx` - The sending part of a channel
-    /// * `url` - Optional server url, usually you don't need specify it
-    pub async fn new(tx: Sender<String>, url: Option<&str>) -> Self {
+    async fn connect(
+        tx: MessageSender,
+        url: Option<&str>,
+        options: WSClientOptions,
+    ) -> Result<Self, WsError> {
         let real_url = match url {
             Some(endpoint) => endpoint.to_string(),
             None => {
-                let ws_token = fetch_ws_token().await;
+                let ws_token = match options.credentials.as_ref() {
+                    Some(credentials) => {
+                        let rest_url = options.rest_url.as_deref().unwrap_or(REST_URL);
+                        fetch_private_ws_token(rest_url, credentials).await?
+                    }
+                    None => fetch_ws_token().await?,
+                };
                 let ws_url = format!("{}?token={}", ws_token.endpoint, ws_token.token);
                 ws_url
             }
         };
-        KuCoinSwapWSClient {
+        Ok(KuCoinSwapWSClient {
             client: WSClientInternal::connect(
                 EXCHANGE_NAME,
                 &real_url,
                 KucoinMessageHandler {},
                 Some(UPLINK_LIMIT),
                 tx,
+                options,
             )
-            .await,
+            .await?,
             translator: KucoinCommandTranslator {},
-        }
+        })
     }
 }
 
+impl_constructors!(KuCoinSwapWSClient);
+
 #[rustfmt::skip]
 impl_trait!(Trade, KuCoinSwapWSClient, subscribe_trade, "/contractMarket/execution");
 #[rustfmt::skip]
@@ -9,7 +9,13 @@ use reqwest::{header, Result};
 use serde_json::Value;
 use tokio_tungstenite::tungstenite::Message;
 
-use crate::common::message_handler::{MessageHandler, MiscMessage};
+use crate::{
+    common::{
+        auth::{hmac_sha256, unix_millis, Credentials},
+        message_handler::{MessageHandler, MiscMessage},
+    },
+    WsError,
+};
 
 pub(super) const EXCHANGE_NAME: &str = "kucoin";
 
@@ -21,13 +27,23 @@ const MAX_TOPICS_PER_COMMAND: usize = 100;
 pub(super) const UPLINK_LIMIT: (NonZeroU32, std::time::Duration) =
     (nonzero!(100u32), std::time::Duration::from_secs(10));
 
+// Channels which require a private token, see https://docs.kucoin.com/#private-channels
+const PRIVATE_CHANNELS: &[&str] = &[
+    "/spotMarket/tradeOrders",
+    "/spotMarket/advancedOrders",
+    "/account/balance",
+    "/contractMarket/tradeOrders",
+    "/contractMarket/advancedOrders",
+    "/contractAccount/wallet",
+    "/contract/position",
+];
+
 pub(super) struct WebsocketToken {
     pub token: String,
     pub endpoint: String,
 }
 
-async fn http_post(url: &str) -> Result<String> {
-    let mut headers = header::HeaderMap::new();
+async fn http_post(url: &str, mut headers: header::HeaderMap) -> Result<String> {
     headers.insert(
         header::CONTENT_TYPE,
         header::HeaderValue::from_static("application/json"),
@@ -47,41 +63,85 @@ async fn http_post(url: &str) -> Result<String> {
 }
 
 // See <https://docs.kucoin.com/#apply-connect-token>
-pub(super) async fn fetch_ws_token() -> WebsocketToken {
-    let txt = http_post("https://openapi-v2.kucoin.com/api/v1/bullet-public")
-        .await
-        .unwrap();
-    let obj = serde_json::from_str::<HashMap<String, Value>>(&txt).unwrap();
-    let code = obj.get("code").unwrap().as_str().unwrap();
-    if code != "200000" {
-        panic!("Failed to get token, code is {}", code);
-    }
-    let data = obj.get("data").unwrap().as_object().unwrap();
-    let token = data.get("token").unwrap().as_str().unwrap();
-    let servers = data.get("instanceServers").unwrap().as_array().unwrap();
-    let server = servers[0].as_object().unwrap();
-
-    WebsocketToken {
-        token: token.to_string(),
-        endpoint: server
-            .get("endpoint")
-            .unwrap()
-            .as_str()
-            .unwrap()
-            .to_string(),
+pub(super) async fn fetch_ws_token() -> std::result::Result<WebsocketToken, WsError> {
+    let txt = http_post(
+        "https://openapi-v2.kucoin.com/api/v1/bullet-public",
+        header::HeaderMap::new(),
+    )
+    .await
+    .map_err(|err| WsError::Connection(format!("Failed to get token, {}", err)))?;
+    parse_ws_token(&txt)
+}
+
+// See <https://docs.kucoin.com/#authentication>
+pub(super) async fn fetch_private_ws_token(
+    rest_url: &str,
+    credentials: &Credentials,
+) -> std::result::Result<WebsocketToken, WsError> {
+    let endpoint = "/api/v1/bullet-private";
+    let timestamp = unix_millis().to_string();
+    let sign = base64::encode(hmac_sha256(
+        &credentials.api_secret,
+        &format!("{}POST{}", timestamp, endpoint),
+    ));
+    let passphrase = base64::encode(hmac_sha256(
+        &credentials.api_secret,
+        credentials.passphrase.as_deref().unwrap_or_default(),
+    ));
+    let mut headers = header::HeaderMap::new();
+    for (name, value) in [
+        ("KC-API-KEY", credentials.api_key.as_str()),
+        ("KC-API-SIGN", sign.as_str()),
+        ("KC-API-TIMESTAMP", timestamp.as_str()),
+        ("KC-API-PASSPHRASE", passphrase.as_str()),
+        ("KC-API-KEY-VERSION", "2"),
+    ] {
+        let value = header::HeaderValue::from_str(value)
+            .map_err(|err| WsError::Auth(format!("invalid {}, {}", name, err)))?;
+        headers.insert(name, value);
     }
+    let txt = http_post(&format!("{}{}", rest_url, endpoint), headers)
+        .await
+        .map_err(|err| WsError::Auth(format!("Failed to get private token, {}", err)))?;
+    parse_ws_token(&txt)
+}
+
+fn parse_ws_token(txt: &str) -> std::result::Result<WebsocketToken, WsError> {
+    let parse_token = || -> Option<WebsocketToken> {
+        let obj = serde_json::from_str::<HashMap<String, Value>>(txt).ok()?;
+        let code = obj.get("code")?.as_str()?;
+        if code != "200000" {
+            return None;
+        }
+        let data = obj.get("data")?.as_object()?;
+        let token = data.get("token")?.as_str()?;
+        let servers = data.get("instanceServers")?.as_array()?;
+        let server = servers.first()?.as_object()?;
+
+        Some(WebsocketToken {
+            token: token.to_string(),
+            endpoint: server.get("endpoint")?.as_str()?.to_string(),
+        })
+    };
+    parse_token().ok_or_else(|| WsError::Connection(format!("Failed to get token, {}", txt)))
 }
 
 fn channel_symbols_to_command(channel: &str, symbols: &[String], subscribe: bool) -> String {
+    // some private channels such as /account/balance have no symbol
+    let topic = if symbols.iter().all(|symbol| symbol.is_empty()) {
+        channel.to_string()
+    } else {
+        format!("{}:{}", channel, symbols.join(","))
+    };
     format!(
-        r#"{{"id":"crypto-ws-client","type":"{}","topic":"{}:{}","privateChannel":false,"response":true}}"#,
+        r#"{{"id":"crypto-ws-client","type":"{}","topic":"{}","privateChannel":{},"response":true}}"#,
         if subscribe {
             "subscribe"
         } else {
             "unsubscribe"
         },
-        channel,
-        symbols.join(",")
+        topic,
+        PRIVATE_CHANNELS.contains(&channel),
     )
 }
 
@@ -158,7 +218,7 @@ impl MessageHandler for KucoinMessageHandler {
 mod tests {
     #[tokio::test(flavor = "multi_thread")]
     async fn fetch_ws_token() {
-        let ws_token = super::fetch_ws_token().await;
+        let ws_token = super::fetch_ws_token().await.unwrap();
         assert!(!ws_token.token.is_empty())
     }
 
@@ -170,4 +230,18 @@ mod tests {
         );
         assert_eq!(1, commands.len());
     }
+
+    #[test]
+    fn test_private_channel() {
+        let commands = super::topics_to_commands(
+            &[("/spotMarket/tradeOrders".to_string(), "".to_string())],
+            true,
+        );
+        assert_eq!(
+            vec![
+                r#"{"id":"crypto-ws-client","type":"subscribe","topic":"/spotMarket/tradeOrders","privateChannel":true,"response":true}"#
+            ],
+            commands
+        );
+    }
 }
@@ -14,17 +14,26 @@ use crate::{
         Candlestick, Level3OrderBook, OrderBook, OrderBookTopK, Ticker, Trade, BBO,
     },
     common::{
+        ack_tracker::PendingAck,
+        auth::{hmac_sha256, unix_millis, Authenticator, Credentials},
+        codec::Codec,
         command_translator::CommandTranslator,
         message_handler::{MessageHandler, MiscMessage},
+        message_sender::MessageSender,
+        options::WSClientOptions,
         utils::ensure_frame_size,
         ws_client_internal::WSClientInternal,
     },
-    WSClient,
+    WSClient, WsError,
 };
 
 pub(crate) const EXCHANGE_NAME: &str = "okx";
 
 const WEBSOCKET_URL: &str = "wss://ws.okx.com:8443/ws/v5/public";
+const PRIVATE_WEBSOCKET_URL: &str = "wss://ws.okx.com:8443/ws/v5/private";
+
+// Instrument types, private channels such as orders take one of them instead of instId
+const INST_TYPES: &[&str] = &["SPOT", "MARGIN", "SWAP", "FUTURES", "OPTION", "ANY"];
 
 /// https://www.okx.com/docs-v5/en/#websocket-api-subscribe
 /// The total length of multiple channels cannot exceed 4096 bytes
@@ -45,31 +54,55 @@ const UPLINK_LIMIT: (NonZeroU32, std::time::Duration) =
 ///     * Future <https://www.okx.com/trade-futures>
 ///     * Swap <https://www.okx.com/trade-swap>
 ///     * Option <https://www.okx.com/trade-option>
+///
+/// With `WSClientOptions::credentials`, the client logs in to the private endpoint, and
+/// private channels are subscribed with an instrument type, e.g., `("orders", "ANY")`.
 pub struct OkxWSClient {
     client: WSClientInternal<OkxMessageHandler>,
     translator: OkxCommandTranslator,
 }
 
 impl OkxWSClient {
-    pub async fn new(tx: std::sync::mpsc::Sender<String>, url: Option<&str>) -> Self {
+    async fn connect(
+        tx: MessageSender,
+        url: Option<&str>,
+        options: WSClientOptions,
+    ) -> Result<Self, WsError> {
+        let authenticator = match options.credentials.clone() {
+            Some(credentials) => {
+                if credentials.passphrase.is_none() {
+                    return Err(WsError::Auth(format!(
+                        "{} requires a passphrase",
+                        EXCHANGE_NAME
+                    )));
+                }
+                Some(Box::new(OkxAuthenticator(credentials)) as Box<dyn Authenticator>)
+            }
+            None => None,
+        };
         let real_url = match url {
             Some(endpoint) => endpoint,
+            None if authenticator.is_some() => PRIVATE_WEBSOCKET_URL,
             None => WEBSOCKET_URL,
         };
-        OkxWSClient {
-            client: WSClientInternal::connect(
+        Ok(OkxWSClient {
+            client: WSClientInternal::connect_with_auth(
                 EXCHANGE_NAME,
                 real_url,
                 OkxMessageHandler {},
                 Some(UPLINK_LIMIT),
                 tx,
+                options,
+                authenticator,
             )
-            .await,
+            .await?,
             translator: OkxCommandTranslator {},
-        }
+        })
     }
 }
 
+impl_constructors!(OkxWSClient);
+
 impl_trait!(Trade, OkxWSClient, subscribe_trade, "trades");
 impl_trait!(Ticker, OkxWSClient, subscribe_ticker, "tickers");
 impl_trait!(BBO, OkxWSClient, subscribe_bbo, "bbo-tbt");
@@ -85,6 +118,7 @@ impl_ws_client_trait!(OkxWSClient);
 
 struct OkxMessageHandler {}
 struct OkxCommandTranslator {}
+struct OkxAuthenticator(Credentials);
 
 impl OkxCommandTranslator {
     fn topics_to_command(chunk: &[(String, String)], subscribe: bool) -> String {
@@ -94,7 +128,11 @@ impl OkxCommandTranslator {
                 let mut map = BTreeMap::new();
                 let (channel, symbol) = t;
                 map.insert("channel".to_string(), channel.to_string());
-                map.insert("instId".to_string(), symbol.to_string());
+                if INST_TYPES.contains(&symbol.as_str()) {
+                    map.insert("instType".to_string(), symbol.to_string());
+                } else {
+                    map.insert("instId".to_string(), symbol.to_string());
+                }
                 map
             })
             .collect::<Vec<BTreeMap<String, String>>>();
@@ -146,25 +184,28 @@ impl MessageHandler for OkxMessageHandler {
         let obj = resp.unwrap();
 
         if let Some(event) = obj.get("event") {
-            match event.as_str().unwrap() {
+            match event.as_str().unwrap_or_default() {
                 "error" => {
-                    let error_code = obj
-                        .get("code")
-                        .unwrap()
-                        .as_str()
-                        .unwrap()
-                        .parse::<i64>()
-                        .unwrap();
-                    match error_code {
-                        30040 => {
-                            // channel doesn't exist, ignore because some symbols don't exist in websocket while they exist in `/v3/instruments`
-                            error!("Received {} from {}", msg, EXCHANGE_NAME);
-                        }
-                        _ => panic!("Received {} from {}", msg, EXCHANGE_NAME),
+                    // e.g., {"event":"error","code":"60018","msg":"Wrong URL or channel:trades,instId:BTC-XXX doesn't exist."}
+                    let code = obj.get("code").and_then(|x| x.as_str()).unwrap_or_default();
+                    let reason = obj.get("msg").and_then(|x| x.as_str()).unwrap_or_default();
+                    if let Some(key) = parse_arg_key(reason) {
+                        return MiscMessage::Ack {
+                            key,
+                            error: Some(format!("{} {}", code, reason)),
+                        };
+                    }
+                    error!("Received {} from {}", msg, EXCHANGE_NAME);
+                }
+                "subscribe" | "unsubscribe" => {
+                    info!("Received {} from {}", msg, EXCHANGE_NAME);
+                    if let Some(arg) = obj.get("arg").and_then(|x| x.as_object()) {
+                        return MiscMessage::Ack {
+                            key: arg_to_key(arg),
+                            error: None,
+                        };
                     }
                 }
-                "subscribe" => info!("Received {} from {}", msg, EXCHANGE_NAME),
-                "unsubscribe" => info!("Received {} from {}", msg, EXCHANGE_NAME),
                 _ => warn!("Received {} from {}", msg, EXCHANGE_NAME),
             }
             MiscMessage::Other
@@ -180,6 +221,37 @@ impl MessageHandler for OkxMessageHandler {
         // https://www.okx.com/docs-v5/en/#websocket-api-connect
         Some((Message::Text("ping".to_string()), 30))
     }
+
+    fn codec(&self) -> Codec {
+        Codec::Deflate
+    }
+}
+
+// See https://www.okx.com/docs-v5/en/#websocket-api-login
+impl Authenticator for OkxAuthenticator {
+    fn login_commands(&self) -> Vec<String> {
+        let timestamp = (unix_millis() / 1000).to_string();
+        let sign = base64::encode(hmac_sha256(
+            &self.0.api_secret,
+            &format!("{}GET/users/self/verify", timestamp),
+        ));
+        let arg = serde_json::json!({
+            "apiKey": self.0.api_key,
+            "passphrase": self.0.passphrase.clone().unwrap_or_default(),
+            "timestamp": timestamp,
+            "sign": sign,
+        });
+        vec![format!(r#"{{"op":"login","args":[{}]}}"#, arg)]
+    }
+
+    fn check_login_response(&self, msg: &str) -> Option<Result<(), String>> {
+        let obj = serde_json::from_str::<HashMap<String, Value>>(msg).ok()?;
+        match obj.get("event")?.as_str()? {
+            "login" if obj.get("code").and_then(|code| code.as_str()) == Some("0") => Some(Ok(())),
+            "login" | "error" => Some(Err(msg.to_string())),
+            _ => None,
+        }
+    }
 }
 
 impl CommandTranslator for OkxCommandTranslator {
@@ -207,11 +279,80 @@ impl CommandTranslator for OkxCommandTranslator {
             .collect::<Vec<(String, String)>>();
         self.translate_to_commands(subscribe, &topics)
     }
+
+    // Every arg is acknowledged separately, e.g., {"event":"subscribe","arg":{"channel":"trades","instId":"BTC-USDT"}}
+    fn pending_acks(&self, command: &str) -> Vec<PendingAck> {
+        let obj = match serde_json::from_str::<HashMap<String, Value>>(command) {
+            Ok(obj) => obj,
+            Err(_) => return Vec::new(),
+        };
+        let subscribe = obj.get("op").and_then(|x| x.as_str()) == Some("subscribe");
+        obj.get("args")
+            .and_then(|x| x.as_array())
+            .map(|args| {
+                args.iter()
+                    .filter_map(|arg| arg.as_object())
+                    .map(|arg| {
+                        let key = arg_to_key(arg);
+                        PendingAck {
+                            key: key.clone(),
+                            raw_topics: vec![key],
+                            subscribe,
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+// Identifies an arg by its fields sorted by name, e.g., `channel:trades,instId:BTC-USDT`,
+// which is also how error messages refer to args.
+fn arg_to_key(arg: &serde_json::Map<String, Value>) -> String {
+    let mut fields: Vec<(&String, String)> = arg
+        .iter()
+        .map(|(name, value)| {
+            let value = match value.as_str() {
+                Some(s) => s.to_string(),
+                None => value.to_string(),
+            };
+            (name, value)
+        })
+        .collect();
+    fields.sort();
+    fields
+        .iter()
+        .map(|(name, value)| format!("{}:{}", name, value))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+// Extracts the key of the arg from an error message, e.g.,
+// `Wrong URL or channel:trades,instId:BTC-XXX doesn't exist.`
+fn parse_arg_key(msg: &str) -> Option<String> {
+    let start = msg.find("channel:")?;
+    let arg = msg[start..].split_whitespace().next()?;
+    let mut fields = arg
+        .trim_end_matches(['.', ','])
+        .split(',')
+        .map(|field| field.split_once(':'))
+        .collect::<Option<Vec<(&str, &str)>>>()?;
+    fields.sort();
+    Some(
+        fields
+            .iter()
+            .map(|(name, value)| format!("{}:{}", name, value))
+            .collect::<Vec<String>>()
+            .join(","),
+    )
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::common::command_translator::CommandTranslator;
+    use crate::common::{
+        command_translator::CommandTranslator,
+        message_handler::{MessageHandler, MiscMessage},
+    };
 
     #[test]
     fn test_one_topic() {
@@ -226,6 +367,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_private_channel() {
+        let translator = super::OkxCommandTranslator {};
+        let commands =
+            translator.translate_to_commands(true, &[("orders".to_string(), "ANY".to_string())]);
+
+        assert_eq!(1, commands.len());
+        assert_eq!(
+            r#"{"op":"subscribe","args":[{"channel":"orders","instType":"ANY"}]}"#,
+            commands[0]
+        );
+    }
+
+    #[test]
+    fn test_auth() {
+        use crate::common::auth::{Authenticator, Credentials};
+
+        let authenticator = super::OkxAuthenticator(
+            Credentials::new("key", "secret").with_passphrase("passphrase"),
+        );
+        let commands = authenticator.login_commands();
+        assert_eq!(1, commands.len());
+        assert!(commands[0].starts_with(r#"{"op":"login","args":[{"apiKey":"key","#));
+        assert!(commands[0].contains(r#""passphrase":"passphrase""#));
+        assert_eq!(
+            Some(Ok(())),
+            authenticator.check_login_response(r#"{"event":"login","code":"0","msg":""}"#)
+        );
+        assert!(matches!(
+            authenticator
+                .check_login_response(r#"{"event":"error","code":"60009","msg":"Login failed."}"#),
+            Some(Err(_))
+        ));
+        assert_eq!(
+            None,
+            authenticator.check_login_response(
+                r#"{"event":"subscribe","arg":{"channel":"orders","instType":"ANY"}}"#
+            )
+        );
+    }
+
     #[test]
     fn test_two_topics() {
         let translator = super::OkxCommandTranslator {};
@@ -243,4 +425,45 @@ mod tests {
             commands[0]
         );
     }
+
+    #[test]
+    fn test_pending_acks() {
+        let translator = super::OkxCommandTranslator {};
+        let commands = translator.translate_to_commands(
+            true,
+            &[
+                ("trades".to_string(), "BTC-USDT".to_string()),
+                ("orders".to_string(), "ANY".to_string()),
+            ],
+        );
+        let keys: Vec<String> = translator
+            .pending_acks(&commands[0])
+            .into_iter()
+            .map(|ack| ack.key)
+            .collect();
+        assert_eq!(
+            vec![
+                "channel:trades,instId:BTC-USDT",
+                "channel:orders,instType:ANY"
+            ],
+            keys
+        );
+    }
+
+    #[test]
+    fn test_ack() {
+        let mut handler = super::OkxMessageHandler {};
+        assert!(matches!(
+            handler.handle_message(r#"{"event":"subscribe","arg":{"channel":"trades","instId":"BTC-USDT"}}"#),
+            MiscMessage::Ack { key, error: None } if key == "channel:trades,instId:BTC-USDT"
+        ));
+        assert!(matches!(
+            handler.handle_message(r#"{"event":"error","code":"60018","msg":"Wrong URL or channel:trades,instId:BTC-XXX doesn't exist."}"#),
+            MiscMessage::Ack { key, error: Some(_) } if key == "channel:trades,instId:BTC-XXX"
+        ));
+        assert!(matches!(
+            handler.handle_message(r#"{"event":"error","code":"60012","msg":"Invalid request"}"#),
+            MiscMessage::Other
+        ));
+    }
 }
@@ -12,9 +12,11 @@ use crate::{
     common::{
         command_translator::CommandTranslator,
         message_handler::{MessageHandler, MiscMessage},
+        message_sender::MessageSender,
+        options::WSClientOptions,
         ws_client_internal::WSClientInternal,
     },
-    WSClient,
+    WSClient, WsError,
 };
 use log::*;
 
@@ -38,25 +40,32 @@ pub struct ZbSwapWSClient {
 }
 
 impl ZbSwapWSClient {
-    pub async fn new(tx: std::sync::mpsc::Sender<String>, url: Option<&str>) -> Self {
+    async fn connect(
+        tx: MessageSender,
+        url: Option<&str>,
+        options: WSClientOptions,
+    ) -> Result<Self, WsError> {
         let real_url = match url {
             Some(endpoint) => endpoint,
             None => WEBSOCKET_URL,
         };
-        ZbSwapWSClient {
+        Ok(ZbSwapWSClient {
             client: WSClientInternal::connect(
                 EXCHANGE_NAME,
                 real_url,
                 ZbMessageHandler {},
                 Some(UPLINK_LIMIT),
                 tx,
+                options,
             )
-            .await,
+            .await?,
             translator: ZbCommandTranslator {},
-        }
+        })
     }
 }
 
+impl_constructors!(ZbSwapWSClient);
+
 #[rustfmt::skip]
 impl_trait!(Trade, ZbSwapWSClient, subscribe_trade, "Trade");
 #[rustfmt::skip]
@@ -0,0 +1,103 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// A subscribe or unsubscribe command, or a part of it, which the exchange acknowledges separately.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PendingAck {
+    /// Identifies the ack, e.g., the request id on Binance
+    pub key: String,
+    /// Exchange-specific raw topics in the command, which are reported in events
+    pub raw_topics: Vec<String>,
+    pub subscribe: bool,
+}
+
+// Commands awaiting acks, in the order they were sent.
+//
+// Commands with the same key are acknowledged in order, which is true for exchanges
+// which reuse a constant request id, e.g., Binance.
+#[derive(Default)]
+pub(crate) struct AckTracker {
+    pending: HashMap<String, VecDeque<(PendingAck, Instant)>>,
+}
+
+impl AckTracker {
+    pub fn track(&mut self, acks: Vec<PendingAck>) {
+        let now = Instant::now();
+        for ack in acks {
+            self.pending
+                .entry(ack.key.clone())
+                .or_default()
+                .push_back((ack, now));
+        }
+    }
+
+    /// Removes the earliest command with `key`, returns None if there is none.
+    pub fn resolve(&mut self, key: &str) -> Option<PendingAck> {
+        let queue = self.pending.get_mut(key)?;
+        let ack = queue.pop_front().map(|(ack, _)| ack);
+        if queue.is_empty() {
+            self.pending.remove(key);
+        }
+        ack
+    }
+
+    /// Removes commands sent more than `timeout` ago.
+    pub fn expire(&mut self, timeout: Duration) -> Vec<PendingAck> {
+        let mut expired = Vec::new();
+        for queue in self.pending.values_mut() {
+            while let Some((_, sent_time)) = queue.front() {
+                if sent_time.elapsed() <= timeout {
+                    break;
+                }
+                expired.push(queue.pop_front().unwrap().0);
+            }
+        }
+        self.pending.retain(|_, queue| !queue.is_empty());
+        expired
+    }
+
+    /// Forgets all commands, e.g., they will never be acknowledged after the connection was lost.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AckTracker, PendingAck};
+    use std::time::Duration;
+
+    fn pending_ack(key: &str, raw_topic: &str) -> PendingAck {
+        PendingAck {
+            key: key.to_string(),
+            raw_topics: vec![raw_topic.to_string()],
+            subscribe: true,
+        }
+    }
+
+    #[test]
+    fn test_resolve_in_order() {
+        let mut tracker = AckTracker::default();
+        tracker.track(vec![pending_ack("1", "a"), pending_ack("1", "b")]);
+        tracker.track(vec![pending_ack("2", "c")]);
+        assert_eq!(Some(pending_ack("1", "a")), tracker.resolve("1"));
+        assert_eq!(Some(pending_ack("2", "c")), tracker.resolve("2"));
+        assert_eq!(Some(pending_ack("1", "b")), tracker.resolve("1"));
+        assert_eq!(None, tracker.resolve("1"));
+    }
+
+    #[test]
+    fn test_expire() {
+        let mut tracker = AckTracker::default();
+        tracker.track(vec![pending_ack("1", "a")]);
+        assert!(tracker.expire(Duration::from_secs(60)).is_empty());
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(
+            vec![pending_ack("1", "a")],
+            tracker.expire(Duration::from_millis(5))
+        );
+        assert_eq!(None, tracker.resolve("1"));
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use ring::hmac;
+
+use crate::error::WsError;
+
+/// API credentials to access private channels.
+#[derive(Clone)]
+pub struct Credentials {
+    pub api_key: String,
+    pub api_secret: String,
+    /// Required by OKX and KuCoin
+    pub passphrase: Option<String>,
+}
+
+impl Credentials {
+    pub fn new(api_key: &str, api_secret: &str) -> Self {
+        Credentials {
+            api_key: api_key.to_string(),
+            api_secret: api_secret.to_string(),
+            passphrase: None,
+        }
+    }
+
+    pub fn with_passphrase(mut self, passphrase: &str) -> Self {
+        self.passphrase = Some(passphrase.to_string());
+        self
+    }
+}
+
+// Never print secrets in logs
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("api_key", &self.api_key)
+            .field("api_secret", &"***")
+            .field("passphrase", &self.passphrase.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+/// Exchange-specific login over the websocket connection.
+#[async_trait]
+pub(crate) trait Authenticator: Send + Sync {
+    /// Called before `login_commands()` for every connection, e.g., to renew a token over
+    /// the REST API.
+    async fn prepare(&self) -> Result<(), WsError> {
+        Ok(())
+    }
+    /// Commands to log in, called for every connection because signatures expire.
+    fn login_commands(&self) -> Vec<String>;
+    /// Returns `Some(Ok)` if `msg` accepts the login, `Some(Err)` if it rejects the login,
+    /// and `None` if `msg` is not a response to the login.
+    fn check_login_response(&self, msg: &str) -> Option<Result<(), String>>;
+}
+
+pub(crate) fn hmac_sha256(secret: &str, msg: &str) -> Vec<u8> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hmac::sign(&key, msg.as_bytes()).as_ref().to_vec()
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn unix_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hmac_sha256, to_hex, Credentials};
+
+    #[test]
+    fn test_hmac_sha256() {
+        // test case 2 of RFC 4231
+        assert_eq!(
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            to_hex(&hmac_sha256("Jefe", "what do ya want for nothing?"))
+        );
+    }
+
+    #[test]
+    fn test_debug_hides_secret() {
+        let credentials = Credentials::new("key", "secret").with_passphrase("passphrase");
+        let txt = format!("{:?}", credentials);
+        assert!(txt.contains("key"));
+        assert!(!txt.contains("secret\""));
+        assert!(!txt.contains("passphrase\""));
+    }
+}
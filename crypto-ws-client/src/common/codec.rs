@@ -0,0 +1,67 @@
+use std::io::prelude::*;
+
+use flate2::read::{DeflateDecoder, GzDecoder};
+
+/// How an exchange encodes binary frames.
+///
+/// Every `MessageHandler` declares its codec, a fresh codec is created for every connection.
+pub(crate) enum Codec {
+    /// Binary frames are UTF-8 text without compression
+    Utf8,
+    /// Every frame is a gzip stream
+    Gzip,
+    /// Every frame is a raw deflate stream
+    Deflate,
+}
+
+impl Codec {
+    /// Decodes a binary frame into text.
+    pub fn decode(&mut self, binary: &[u8]) -> Result<String, String> {
+        let mut txt = String::new();
+        let resp = match self {
+            Codec::Utf8 => {
+                return String::from_utf8(binary.to_vec()).map_err(|err| err.to_string());
+            }
+            Codec::Gzip => GzDecoder::new(binary).read_to_string(&mut txt),
+            Codec::Deflate => DeflateDecoder::new(binary).read_to_string(&mut txt),
+        };
+        match resp {
+            Ok(_) => Ok(txt),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Codec;
+    use flate2::{
+        write::{DeflateEncoder, GzEncoder},
+        Compression,
+    };
+    use std::io::Write;
+
+    const MSG: &str = r#"{"ch":"market.btcusdt.trade.detail","ts":1616243199157}"#;
+
+    #[test]
+    fn test_gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(MSG.as_bytes()).unwrap();
+        let binary = encoder.finish().unwrap();
+        assert_eq!(MSG, Codec::Gzip.decode(&binary).unwrap());
+    }
+
+    #[test]
+    fn test_deflate() {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(MSG.as_bytes()).unwrap();
+        let binary = encoder.finish().unwrap();
+        assert_eq!(MSG, Codec::Deflate.decode(&binary).unwrap());
+    }
+
+    #[test]
+    fn test_decode_error() {
+        assert!(Codec::Gzip.decode(b"not gzip").is_err());
+        assert!(Codec::Utf8.decode(&[0xff, 0xfe]).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use super::ack_tracker::PendingAck;
+
 /// Translate to exchange-specific websocket subscribe/unsubscribe commands.
 ///
 /// topic = channel + symbol
@@ -11,4 +13,11 @@ pub(crate) trait CommandTranslator {
         subscribe: bool,
         symbol_interval_list: &[(String, usize)],
     ) -> Vec<String>;
+
+    /// Acks which the exchange will send in response to a command generated above.
+    ///
+    /// Empty if the exchange doesn't acknowledge subscriptions, or acks are not tracked yet.
+    fn pending_acks(&self, _command: &str) -> Vec<PendingAck> {
+        Vec::new()
+    }
 }
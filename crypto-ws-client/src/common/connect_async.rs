@@ -1,70 +1,133 @@
-use fast_socks5::client::{Config, Socks5Stream};
 use futures_util::{SinkExt, StreamExt};
 use governor::{Quota, RateLimiter};
 use log::*;
 use nonzero_ext::*;
 use reqwest::Url;
-use std::{env, num::NonZeroU32};
+use std::{num::NonZeroU32, sync::Arc, time::Duration};
 use tokio::{
     io::{AsyncRead, AsyncWrite},
     sync::mpsc::{Receiver, Sender},
 };
+use tokio_rustls::rustls::ClientConfig;
 use tokio_tungstenite::{
-    tungstenite::{Error, Message},
-    MaybeTlsStream, WebSocketStream,
+    tungstenite::{
+        client::IntoClientRequest,
+        handshake::client::Request,
+        http::{HeaderName, HeaderValue},
+        Message,
+    },
+    Connector, MaybeTlsStream, WebSocketStream,
 };
 
+use crate::{
+    common::{
+        proxy::{resolve_proxy, ProxyStream},
+        recording::Recorder,
+    },
+    error::{Result, WsError},
+};
+
+// How long to wait for the server to respond to a close frame
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Settings of the connections of a client, which don't change between reconnects.
+#[derive(Clone, Default)]
+pub(crate) struct ConnectConfig {
+    /// Max number of uplink messsages, for example, 100 per 10 seconds
+    pub uplink_limit: Option<(NonZeroU32, Duration)>,
+    /// An explicit proxy which overrides `https_proxy`, `http_proxy` and `no_proxy`,
+    /// an empty string means no proxy
+    pub proxy: Option<String>,
+    /// Records every frame received from the connection
+    pub recorder: Option<Recorder>,
+    /// TLS configuration of the websocket connection, the native root certificates by default
+    pub tls_config: Option<Arc<ClientConfig>>,
+    /// Extra HTTP headers of the handshake
+    pub headers: Vec<(String, String)>,
+    pub command_buffer: usize,
+    pub message_buffer: usize,
+}
+
 /// Wraps a websocket client inside an event loop, returns a message_rx to receive messages and
 /// a command_tx to send commands to the websocket server.
 ///
 /// To close the websocket connection, send a `Message::Close` message to the command_tx.
-///
-/// `limit`, max number of uplink messsages, for example, 100 per 10 seconds
 pub async fn connect_async(
     url: &str,
-    uplink_limit: Option<(NonZeroU32, std::time::Duration)>,
-) -> Result<(Receiver<Message>, Sender<Message>), Error> {
-    if let Ok(proxy_env) = env::var("https_proxy").or_else(|_| env::var("http_proxy")) {
-        let proxy_url = Url::parse(&proxy_env).unwrap();
-        let proxy_scheme = proxy_url.scheme().to_lowercase();
-        if proxy_scheme.as_str() != "socks5" {
-            panic!("Unsupported proxy scheme {}", proxy_scheme);
+    config: &ConnectConfig,
+) -> Result<(Receiver<Message>, Sender<Message>)> {
+    let connect_url =
+        Url::parse(url).map_err(|err| WsError::Connection(format!("{}, {}", url, err)))?;
+    let request = build_request(&connect_url, &config.headers)?;
+    let connector = config.tls_config.clone().map(Connector::Rustls);
+    if let Some(proxy) = resolve_proxy(&connect_url, config.proxy.as_deref())? {
+        let host = connect_url
+            .host_str()
+            .ok_or_else(|| WsError::Connection(format!("no host in {}", url)))?;
+        let port = connect_url
+            .port_or_known_default()
+            .ok_or_else(|| WsError::Connection(format!("no port in {}", url)))?;
+        match proxy.connect(host, port).await? {
+            ProxyStream::Http(stream) => handshake(url, request, stream, connector, config).await,
+            ProxyStream::Https(stream) => handshake(url, request, stream, connector, config).await,
+            ProxyStream::Socks5(stream) => handshake(url, request, stream, connector, config).await,
         }
-        let proxy_addr = format!(
-            "{}:{}",
-            proxy_url.host_str().unwrap(),
-            proxy_url.port_or_known_default().unwrap()
-        );
-        let connect_url = Url::parse(url).unwrap();
-        let proxy_stream = Socks5Stream::connect(
-            proxy_addr.to_string(),
-            connect_url.host_str().unwrap().to_string(),
-            connect_url.port_or_known_default().unwrap(),
-            Config::default(),
-        )
-        .await
-        .unwrap();
-        let (ws_stream, _) = tokio_tungstenite::client_async_tls(connect_url, proxy_stream).await?;
-        // replaced
-        // let ret = tokio_tungstenite::connect_async(url).await;
-        connect_async_internal(ws_stream, uplink_limit).await
     } else {
-        let (ws_stream, _) = tokio_tungstenite::connect_async(url).await?;
+        let (ws_stream, _) =
+            tokio_tungstenite::connect_async_tls_with_config(request, None, connector)
+                .await
+                .map_err(|err| WsError::from_tungstenite(url, err))?;
+
+        Ok(connect_async_internal(url, ws_stream, config))
+    }
+}
 
-        connect_async_internal(ws_stream, uplink_limit).await
+// Builds the handshake request with extra headers.
+fn build_request(url: &Url, headers: &[(String, String)]) -> Result<Request> {
+    let mut request = url
+        .as_str()
+        .into_client_request()
+        .map_err(|err| WsError::from_tungstenite(url.as_str(), err))?;
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|err| WsError::Connection(format!("invalid header {}, {}", name, err)))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|err| WsError::Connection(format!("invalid header {}, {}", name, err)))?;
+        request.headers_mut().insert(name, value);
     }
+    Ok(request)
+}
+
+// Performs the TLS and websocket handshakes over a tunnel established by a proxy.
+async fn handshake<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+    url: &str,
+    request: Request,
+    stream: S,
+    connector: Option<Connector>,
+    config: &ConnectConfig,
+) -> Result<(Receiver<Message>, Sender<Message>)> {
+    let (ws_stream, _) =
+        tokio_tungstenite::client_async_tls_with_config(request, stream, None, connector)
+            .await
+            .map_err(|err| WsError::from_tungstenite(url, err))?;
+    Ok(connect_async_internal(url, ws_stream, config))
 }
 
-async fn connect_async_internal<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+fn connect_async_internal<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+    url: &str,
     ws_stream: WebSocketStream<MaybeTlsStream<S>>,
-    uplink_limit: Option<(NonZeroU32, std::time::Duration)>,
-) -> Result<(Receiver<Message>, Sender<Message>), Error> {
-    let (command_tx, mut command_rx) = tokio::sync::mpsc::channel::<Message>(1);
-    let (message_tx, message_rx) = tokio::sync::mpsc::channel::<Message>(32);
+    config: &ConnectConfig,
+) -> (Receiver<Message>, Sender<Message>) {
+    let recorder = config.recorder.clone();
+    if let Some(recorder) = recorder.as_ref() {
+        recorder.record_connected(url);
+    }
+    let (command_tx, mut command_rx) = tokio::sync::mpsc::channel::<Message>(config.command_buffer);
+    let (message_tx, message_rx) = tokio::sync::mpsc::channel::<Message>(config.message_buffer);
 
     let (mut write, mut read) = ws_stream.split();
 
-    let limiter = if let Some((max_burst, duration)) = uplink_limit {
+    let limiter = if let Some((max_burst, duration)) = config.uplink_limit {
         let quota = Quota::with_period(duration).unwrap().allow_burst(max_burst);
         RateLimiter::direct(quota)
     } else {
@@ -79,7 +142,21 @@ async fn connect_async_internal<S: AsyncRead + AsyncWrite + Unpin + Send + 'stat
                   Some(command) => {
                     match command {
                       Message::Close(_) => {
-                        break; // close the connection and break the loop
+                        // send a close frame, then forward frames until the server responds
+                        if write.send(command).await.is_ok() {
+                          let drain = async {
+                            while let Some(Ok(msg)) = read.next().await {
+                              if let Some(recorder) = recorder.as_ref() {
+                                recorder.record(&msg);
+                              }
+                              let _ = message_tx.send(msg).await;
+                            }
+                          };
+                          if tokio::time::timeout(CLOSE_TIMEOUT, drain).await.is_err() {
+                            warn!("No response to the close frame in {} seconds", CLOSE_TIMEOUT.as_secs());
+                          }
+                        }
+                        return;
                       }
                       _ => {
                         limiter.until_ready().await;
@@ -97,6 +174,9 @@ async fn connect_async_internal<S: AsyncRead + AsyncWrite + Unpin + Send + 'stat
               }
               msg = read.next() => match msg {
                 Some(Ok(msg)) => {
+                  if let Some(recorder) = recorder.as_ref() {
+                    recorder.record(&msg);
+                  }
                   let _= message_tx.send(msg).await;
                 }
                 Some(Err(err)) => {
@@ -113,5 +193,5 @@ async fn connect_async_internal<S: AsyncRead + AsyncWrite + Unpin + Send + 'stat
         _ = write.send(Message::Close(None)).await;
     });
 
-    Ok((message_rx, command_tx))
+    (message_rx, command_tx)
 }
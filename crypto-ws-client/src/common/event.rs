@@ -0,0 +1,18 @@
+use crate::WsError;
+
+/// Events about a websocket client, sent to the channel in `WSClientOptions::events`.
+#[derive(Debug)]
+pub enum WSClientEvent {
+    /// A binary frame could not be decoded and was skipped, `payload` is the raw frame.
+    DecodeError { error: WsError, payload: Vec<u8> },
+    /// The exchange confirmed the subscription of exchange-specific raw topics, e.g.,
+    /// `btcusdt@aggTrade` on Binance and `channel:trades,instId:BTC-USDT` on OKX.
+    Subscribed { raw_topics: Vec<String> },
+    /// The exchange rejected the subscription, e.g., the symbol doesn't exist.
+    SubscribeFailed {
+        raw_topics: Vec<String>,
+        reason: String,
+    },
+    /// The exchange didn't respond to the subscription within `WSClientOptions::ack_timeout`.
+    SubscribeTimeout { raw_topics: Vec<String> },
+}
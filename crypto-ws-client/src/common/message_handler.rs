@@ -1,5 +1,7 @@
 use tokio_tungstenite::tungstenite::Message;
 
+use super::codec::Codec;
+
 #[derive(Debug)]
 pub(crate) enum MiscMessage {
     Normal,             // A normal websocket message which contains a JSON string
@@ -7,7 +9,9 @@ pub(crate) enum MiscMessage {
     WebSocket(Message), // WebSocket message that needs to be sent to the server
     Pong,               // Pong message from the server
     Reconnect,          // Needs to reconnect
-    Other,              // Other messages will be ignored
+    // Response to a subscribe or unsubscribe command, the key must match `PendingAck::key`
+    Ack { key: String, error: Option<String> },
+    Other, // Other messages will be ignored
 }
 
 /// Exchange-specific message handler.
@@ -18,4 +22,8 @@ pub(crate) trait MessageHandler {
     /// None means the client doesn't need to send ping, instead the server will
     /// send ping and the client just needs to reply a pong
     fn get_ping_msg_and_interval(&self) -> Option<(Message, u64)>;
+    /// How binary frames from the exchange are encoded, called once per connection.
+    fn codec(&self) -> Codec {
+        Codec::Utf8
+    }
 }
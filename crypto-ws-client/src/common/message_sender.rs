@@ -0,0 +1,77 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::stream::Stream;
+
+/// The sending side of the channel which receives messages from the websocket server.
+///
+/// Both std and tokio senders can be converted into it via `into()`.
+#[derive(Clone)]
+pub enum MessageSender {
+    Std(std::sync::mpsc::Sender<String>),
+    // Bounded, so that a slow consumer applies backpressure instead of growing memory unboundedly
+    Tokio(tokio::sync::mpsc::Sender<String>),
+}
+
+impl MessageSender {
+    /// Returns an error if the receiver has been dropped.
+    pub(crate) async fn send(&self, msg: String) -> Result<(), String> {
+        match self {
+            MessageSender::Std(tx) => tx.send(msg).map_err(|err| err.0),
+            MessageSender::Tokio(tx) => tx.send(msg).await.map_err(|err| err.0),
+        }
+    }
+}
+
+impl From<std::sync::mpsc::Sender<String>> for MessageSender {
+    fn from(tx: std::sync::mpsc::Sender<String>) -> Self {
+        MessageSender::Std(tx)
+    }
+}
+
+impl From<tokio::sync::mpsc::Sender<String>> for MessageSender {
+    fn from(tx: tokio::sync::mpsc::Sender<String>) -> Self {
+        MessageSender::Tokio(tx)
+    }
+}
+
+/// A stream of messages from the websocket server, returned by `new_stream()`.
+///
+/// Messages are produced only while `run()` is running, so `run()` should be spawned
+/// as a separate task before consuming this stream.
+pub struct MessageStream {
+    rx: tokio::sync::mpsc::Receiver<String>,
+}
+
+impl MessageStream {
+    pub(crate) fn new(rx: tokio::sync::mpsc::Receiver<String>) -> Self {
+        MessageStream { rx }
+    }
+}
+
+impl Stream for MessageStream {
+    type Item = String;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MessageSender, MessageStream};
+    use futures_util::StreamExt;
+
+    #[tokio::test]
+    async fn test_stream() {
+        let (tx, rx) = tokio::sync::mpsc::channel(2);
+        let sender: MessageSender = tx.into();
+        let mut stream = MessageStream::new(rx);
+        sender.send("hello".to_string()).await.unwrap();
+        drop(sender);
+        assert_eq!(Some("hello".to_string()), stream.next().await);
+        assert_eq!(None, stream.next().await);
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crypto_market_type::MarketType;
+
+/// Metrics of a websocket connection.
+///
+/// Rates and averages are measured over the last second.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricsSnapshot {
+    pub exchange: String,
+    pub market_type: MarketType,
+    pub url: String,
+    /// Total number of frames received
+    pub messages: u64,
+    /// Total number of bytes received, before decompression
+    pub bytes: u64,
+    pub messages_per_second: f64,
+    pub bytes_per_second: f64,
+    /// Average time spent decoding a binary frame
+    pub decode_time: Option<Duration>,
+    /// Round-trip time of the last ping answered by the exchange
+    pub ping_rtt: Option<Duration>,
+    /// Average milliseconds from the exchange timestamp to the local receive time, requires
+    /// the `latency` feature
+    pub latency_ms: Option<f64>,
+    /// Number of reconnects since the client was created
+    pub reconnects: u64,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Counters {
+    messages: u64,
+    bytes: u64,
+    decoded: u64,
+    decode_time: Duration,
+    latency_count: u64,
+    latency_sum_ms: i64,
+}
+
+struct State {
+    total: Counters,
+    // counters of the current window
+    window: Counters,
+    window_start: Instant,
+    // counters and length of the last complete window
+    last_window: (Counters, Duration),
+    ping_sent: Option<Instant>,
+    ping_rtt: Option<Duration>,
+    reconnects: u64,
+}
+
+// Collects metrics of the connections of a client, which reconnects in place.
+pub(crate) struct ConnectionMetrics {
+    exchange: &'static str,
+    market_type: MarketType,
+    url: String,
+    state: Mutex<State>,
+}
+
+impl ConnectionMetrics {
+    pub fn new(exchange: &'static str, market_type: MarketType, url: &str) -> Self {
+        ConnectionMetrics {
+            exchange,
+            market_type,
+            url: url.to_string(),
+            state: Mutex::new(State {
+                total: Counters::default(),
+                window: Counters::default(),
+                window_start: Instant::now(),
+                last_window: (Counters::default(), Duration::ZERO),
+                ping_sent: None,
+                ping_rtt: None,
+                reconnects: 0,
+            }),
+        }
+    }
+
+    pub fn on_frame(&self, len: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.window.messages += 1;
+        state.window.bytes += len as u64;
+    }
+
+    pub fn on_decoded(&self, elapsed: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.window.decoded += 1;
+        state.window.decode_time += elapsed;
+    }
+
+    // Only the first ping of several unanswered ones is timed.
+    pub fn on_ping_sent(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.ping_sent.is_none() {
+            state.ping_sent = Some(Instant::now());
+        }
+    }
+
+    pub fn on_pong(&self) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(sent) = state.ping_sent.take() {
+            state.ping_rtt = Some(sent.elapsed());
+        }
+    }
+
+    pub fn on_reconnect(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.reconnects += 1;
+        // a ping to the lost connection will never be answered
+        state.ping_sent = None;
+    }
+
+    /// Measures the latency of a message delivered to the consumer.
+    #[cfg(feature = "latency")]
+    pub fn on_message(&self, msg: &str) {
+        if let Ok(Some(timestamp)) =
+            crypto_msg_parser::extract_timestamp(self.exchange, self.market_type, msg)
+        {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64;
+            let mut state = self.state.lock().unwrap();
+            state.window.latency_count += 1;
+            state.window.latency_sum_ms += now - timestamp;
+        }
+    }
+
+    #[cfg(not(feature = "latency"))]
+    pub fn on_message(&self, _msg: &str) {}
+
+    /// Closes the current window if it's at least one second long, called periodically.
+    pub fn tick(&self) {
+        let mut state = self.state.lock().unwrap();
+        let elapsed = state.window_start.elapsed();
+        if elapsed < Duration::from_secs(1) {
+            return;
+        }
+        let window = std::mem::take(&mut state.window);
+        state.total.messages += window.messages;
+        state.total.bytes += window.bytes;
+        state.last_window = (window, elapsed);
+        state.window_start = Instant::now();
+        #[cfg(feature = "metrics")]
+        self.publish(&state);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.snapshot_of(&self.state.lock().unwrap())
+    }
+
+    fn snapshot_of(&self, state: &State) -> MetricsSnapshot {
+        let (window, elapsed) = state.last_window;
+        let per_second = |n: u64| {
+            if elapsed.is_zero() {
+                0.0
+            } else {
+                n as f64 / elapsed.as_secs_f64()
+            }
+        };
+        MetricsSnapshot {
+            exchange: self.exchange.to_string(),
+            market_type: self.market_type,
+            url: self.url.clone(),
+            messages: state.total.messages + state.window.messages,
+            bytes: state.total.bytes + state.window.bytes,
+            messages_per_second: per_second(window.messages),
+            bytes_per_second: per_second(window.bytes),
+            decode_time: if window.decoded > 0 {
+                Some(window.decode_time / window.decoded as u32)
+            } else {
+                None
+            },
+            ping_rtt: state.ping_rtt,
+            latency_ms: if window.latency_count > 0 {
+                Some(window.latency_sum_ms as f64 / window.latency_count as f64)
+            } else {
+                None
+            },
+            reconnects: state.reconnects,
+        }
+    }
+
+    // Publishes the last window to the `metrics` crate, labelled by exchange and market type.
+    #[cfg(feature = "metrics")]
+    fn publish(&self, state: &State) {
+        let (window, _) = state.last_window;
+        let snapshot = self.snapshot_of(state);
+        let labels = [
+            ("exchange", self.exchange.to_string()),
+            ("market_type", self.market_type.to_string()),
+        ];
+        metrics::counter!("crypto_ws_client_messages_total", window.messages, &labels);
+        metrics::counter!("crypto_ws_client_bytes_total", window.bytes, &labels);
+        metrics::gauge!(
+            "crypto_ws_client_messages_per_second",
+            snapshot.messages_per_second,
+            &labels
+        );
+        metrics::gauge!(
+            "crypto_ws_client_bytes_per_second",
+            snapshot.bytes_per_second,
+            &labels
+        );
+        if let Some(decode_time) = snapshot.decode_time {
+            metrics::gauge!(
+                "crypto_ws_client_decode_seconds",
+                decode_time.as_secs_f64(),
+                &labels
+            );
+        }
+        if let Some(ping_rtt) = snapshot.ping_rtt {
+            metrics::gauge!(
+                "crypto_ws_client_ping_rtt_seconds",
+                ping_rtt.as_secs_f64(),
+                &labels
+            );
+        }
+        if let Some(latency_ms) = snapshot.latency_ms {
+            metrics::gauge!(
+                "crypto_ws_client_latency_seconds",
+                latency_ms / 1000.0,
+                &labels
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConnectionMetrics;
+    use crypto_market_type::MarketType;
+    use std::time::Duration;
+
+    #[test]
+    fn test_snapshot() {
+        let metrics = ConnectionMetrics::new("binance", MarketType::Spot, "wss://example.com");
+        metrics.on_frame(100);
+        metrics.on_frame(300);
+        metrics.on_decoded(Duration::from_micros(10));
+        metrics.on_decoded(Duration::from_micros(30));
+        metrics.on_ping_sent();
+        metrics.on_pong();
+        metrics.on_reconnect();
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(2, snapshot.messages);
+        assert_eq!(400, snapshot.bytes);
+        // no complete window yet
+        assert_eq!(0.0, snapshot.messages_per_second);
+        assert_eq!(None, snapshot.decode_time);
+        assert!(snapshot.ping_rtt.is_some());
+        assert_eq!(1, snapshot.reconnects);
+
+        std::thread::sleep(Duration::from_millis(1000));
+        metrics.tick();
+        let snapshot = metrics.snapshot();
+        assert_eq!(2, snapshot.messages);
+        assert!(snapshot.messages_per_second > 1.0 && snapshot.messages_per_second <= 2.0);
+        assert!(snapshot.bytes_per_second > 200.0 && snapshot.bytes_per_second <= 400.0);
+        assert_eq!(Some(Duration::from_micros(20)), snapshot.decode_time);
+    }
+}
@@ -1,6 +1,16 @@
+pub(crate) mod ack_tracker;
+pub(crate) mod auth;
+pub(crate) mod codec;
 pub(crate) mod command_translator;
 pub(crate) mod connect_async;
+pub(crate) mod event;
 pub(crate) mod message_handler;
+pub(crate) mod message_sender;
+pub(crate) mod metrics;
+pub(crate) mod options;
+pub(crate) mod proxy;
+pub(crate) mod recording;
+pub(super) mod subscription_registry;
 pub(super) mod utils;
 pub(crate) mod ws_client;
 pub(super) mod ws_client_internal;
@@ -0,0 +1,363 @@
+use std::{
+    borrow::Cow,
+    fs::{File, OpenOptions},
+    io::{BufReader, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::*;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio_tungstenite::tungstenite::{
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Message,
+};
+
+// Written once at the beginning of every recording
+const MAGIC: &[u8; 8] = b"CWSREC1\n";
+
+// Record kinds, every record is `kind: u8, timestamp: u64, len: u32, payload` in little endian
+const KIND_CONNECTED: u8 = 0; // payload is the url
+const KIND_TEXT: u8 = 1;
+const KIND_BINARY: u8 = 2;
+const KIND_PING: u8 = 3;
+const KIND_PONG: u8 = 4;
+const KIND_CLOSE: u8 = 5; // payload is the code and reason, empty without a CloseFrame
+
+const HEADER_LEN: usize = 13;
+
+/// A frame in a recording.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Recorded {
+    /// A new connection to the url, frames after it belong to the connection
+    Connected(String),
+    Message(Message),
+}
+
+/// Records every frame received from exchanges into an append-only file.
+///
+/// Binary frames are recorded before decompression, together with pings, pongs and close
+/// frames, each with the receive timestamp in microseconds. A recording can be fed back to
+/// a client with `Replayer`.
+///
+/// Clones write to the same file, so every client should have its own recorder, otherwise
+/// connections of different clients are interleaved.
+#[derive(Clone)]
+pub struct Recorder {
+    path: PathBuf,
+    file: Arc<std::sync::Mutex<File>>,
+}
+
+impl Recorder {
+    /// Opens a recording for appending, creates it if it doesn't exist.
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(MAGIC)?;
+        }
+        Ok(Recorder {
+            path: path.as_ref().to_path_buf(),
+            file: Arc::new(std::sync::Mutex::new(file)),
+        })
+    }
+
+    pub(crate) fn record_connected(&self, url: &str) {
+        self.write(KIND_CONNECTED, url.as_bytes());
+    }
+
+    pub(crate) fn record(&self, msg: &Message) {
+        match msg {
+            Message::Text(txt) => self.write(KIND_TEXT, txt.as_bytes()),
+            Message::Binary(binary) => self.write(KIND_BINARY, binary),
+            Message::Ping(payload) => self.write(KIND_PING, payload),
+            Message::Pong(payload) => self.write(KIND_PONG, payload),
+            Message::Close(frame) => {
+                let mut payload = Vec::new();
+                if let Some(frame) = frame {
+                    payload.extend_from_slice(&u16::from(frame.code).to_le_bytes());
+                    payload.extend_from_slice(frame.reason.as_bytes());
+                }
+                self.write(KIND_CLOSE, &payload)
+            }
+            Message::Frame(_) => (),
+        }
+    }
+
+    // Writes a record with a single call, so that a record is never split by other writers.
+    fn write(&self, kind: u8, payload: &[u8]) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_micros() as u64;
+        let mut record = Vec::with_capacity(HEADER_LEN + payload.len());
+        record.push(kind);
+        record.extend_from_slice(&timestamp.to_le_bytes());
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(payload);
+        if let Err(err) = self.file.lock().unwrap().write_all(&record) {
+            error!("Failed to write to {}, error: {}", self.path.display(), err);
+        }
+    }
+}
+
+impl std::fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recorder")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+// Reads records one by one.
+pub(crate) struct RecordingReader<R: Read> {
+    reader: R,
+}
+
+impl RecordingReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "not a recording of crypto-ws-client",
+            ));
+        }
+        Ok(RecordingReader { reader })
+    }
+}
+
+impl<R: Read> RecordingReader<R> {
+    /// Returns the next frame and its timestamp, None at the end.
+    ///
+    /// A truncated record at the end, e.g., the recorder was killed while writing, is ignored.
+    pub fn next_record(&mut self) -> std::io::Result<Option<(u64, Recorded)>> {
+        let mut header = [0u8; HEADER_LEN];
+        match self.reader.read_exact(&mut header) {
+            Ok(()) => (),
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let kind = header[0];
+        let timestamp = u64::from_le_bytes(header[1..9].try_into().unwrap());
+        let len = u32::from_le_bytes(header[9..13].try_into().unwrap()) as usize;
+        let mut payload = vec![0u8; len];
+        match self.reader.read_exact(&mut payload) {
+            Ok(()) => (),
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+                warn!("Ignored a truncated record at the end of the recording");
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        }
+        let to_string = |payload: Vec<u8>| {
+            String::from_utf8(payload)
+                .map_err(|err| std::io::Error::new(ErrorKind::InvalidData, err))
+        };
+        let recorded = match kind {
+            KIND_CONNECTED => Recorded::Connected(to_string(payload)?),
+            KIND_TEXT => Recorded::Message(Message::Text(to_string(payload)?)),
+            KIND_BINARY => Recorded::Message(Message::Binary(payload)),
+            KIND_PING => Recorded::Message(Message::Ping(payload)),
+            KIND_PONG => Recorded::Message(Message::Pong(payload)),
+            KIND_CLOSE if payload.len() >= 2 => {
+                let code = u16::from_le_bytes([payload[0], payload[1]]);
+                Recorded::Message(Message::Close(Some(CloseFrame {
+                    code: CloseCode::from(code),
+                    reason: Cow::Owned(to_string(payload[2..].to_vec())?),
+                })))
+            }
+            KIND_CLOSE => Recorded::Message(Message::Close(None)),
+            _ => {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unknown record kind {}", kind),
+                ))
+            }
+        };
+        Ok(Some((timestamp, recorded)))
+    }
+}
+
+struct ReplayState {
+    reader: RecordingReader<BufReader<File>>,
+    // the Connected record which ended the previous connection
+    pending: Option<Recorded>,
+}
+
+/// Feeds a recording to a client instead of connecting to the exchange.
+///
+/// Frames go through the same decoding and `MessageHandler` path as live frames, as fast as
+/// the client consumes them. Every recorded connection is replayed as a connection, and
+/// `run()` returns after the last one.
+#[derive(Clone)]
+pub struct Replayer {
+    path: PathBuf,
+    state: Arc<tokio::sync::Mutex<ReplayState>>,
+    exhausted: Arc<AtomicBool>,
+}
+
+impl Replayer {
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Ok(Replayer {
+            path: path.as_ref().to_path_buf(),
+            state: Arc::new(tokio::sync::Mutex::new(ReplayState {
+                reader: RecordingReader::open(path)?,
+                pending: None,
+            })),
+            exhausted: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Whether all recorded connections have been replayed.
+    pub(crate) fn is_exhausted(&self) -> bool {
+        self.exhausted.load(Ordering::Acquire)
+    }
+
+    /// Replays the next recorded connection, works like `connect_async()`.
+    pub(crate) fn next_connection(&self) -> (Receiver<Message>, Sender<Message>) {
+        let (command_tx, mut command_rx) = tokio::sync::mpsc::channel::<Message>(32);
+        let (message_tx, message_rx) = tokio::sync::mpsc::channel::<Message>(32);
+        let state = self.state.clone();
+        let exhausted = self.exhausted.clone();
+        let path = self.path.clone();
+        tokio::task::spawn(async move {
+            let mut state = state.lock().await;
+            let mut started = false;
+            loop {
+                let recorded = match state.pending.take() {
+                    Some(recorded) => recorded,
+                    None => match state.reader.next_record() {
+                        Ok(Some((_, recorded))) => recorded,
+                        Ok(None) => {
+                            exhausted.store(true, Ordering::Release);
+                            return;
+                        }
+                        Err(err) => {
+                            error!("Failed to read {}, error: {}", path.display(), err);
+                            exhausted.store(true, Ordering::Release);
+                            return;
+                        }
+                    },
+                };
+                let msg = match recorded {
+                    Recorded::Connected(_) if started => {
+                        state.pending = Some(recorded);
+                        return;
+                    }
+                    Recorded::Connected(_) => {
+                        started = true;
+                        continue;
+                    }
+                    Recorded::Message(msg) => msg,
+                };
+                started = true;
+                // commands are dropped, except that close() stops the replay
+                loop {
+                    tokio::select! {
+                        command = command_rx.recv() => match command {
+                            Some(Message::Close(_)) | None => return,
+                            Some(_) => continue,
+                        },
+                        permit = message_tx.reserve() => match permit {
+                            Ok(permit) => {
+                                permit.send(msg);
+                                break;
+                            }
+                            Err(_) => return,
+                        }
+                    }
+                }
+            }
+        });
+        (message_rx, command_tx)
+    }
+}
+
+impl std::fmt::Debug for Replayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Replayer")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Recorded, Recorder, RecordingReader};
+    use std::borrow::Cow;
+    use tokio_tungstenite::tungstenite::{
+        protocol::{frame::coding::CloseCode, CloseFrame},
+        Message,
+    };
+
+    #[test]
+    fn test_round_trip() {
+        let path = std::env::temp_dir().join(format!("recording-{}.bin", rand::random::<u64>()));
+        let messages = vec![
+            Message::Text(r#"{"e":"aggTrade"}"#.to_string()),
+            Message::Binary(vec![0x1f, 0x8b, 0x08, 0x00]),
+            Message::Ping(b"ping".to_vec()),
+            Message::Pong(Vec::new()),
+            Message::Close(Some(CloseFrame {
+                code: CloseCode::Away,
+                reason: Cow::Borrowed("going away"),
+            })),
+            Message::Close(None),
+        ];
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.record_connected("wss://example.com/ws");
+        for msg in messages.iter() {
+            recorder.record(msg);
+        }
+        drop(recorder);
+        // appending doesn't write the magic again
+        Recorder::create(&path)
+            .unwrap()
+            .record_connected("wss://example.com/ws");
+
+        let mut reader = RecordingReader::open(&path).unwrap();
+        let mut expected = vec![Recorded::Connected("wss://example.com/ws".to_string())];
+        expected.extend(messages.into_iter().map(Recorded::Message));
+        expected.push(Recorded::Connected("wss://example.com/ws".to_string()));
+        for recorded in expected {
+            let (timestamp, actual) = reader.next_record().unwrap().unwrap();
+            assert!(timestamp > 0);
+            assert_eq!(recorded, actual);
+        }
+        assert_eq!(None, reader.next_record().unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_truncated() {
+        let path = std::env::temp_dir().join(format!("recording-{}.bin", rand::random::<u64>()));
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.record(&Message::Text("complete".to_string()));
+        recorder.record(&Message::Text("truncated".to_string()));
+        drop(recorder);
+        let len = std::fs::metadata(&path).unwrap().len();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+
+        let mut reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(
+            Recorded::Message(Message::Text("complete".to_string())),
+            reader.next_record().unwrap().unwrap().1
+        );
+        assert_eq!(None, reader.next_record().unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+use crate::common::command_translator::CommandTranslator;
+
+/// Topics and candlesticks subscribed by a client.
+///
+/// The registry is the single source of truth for what a client is subscribed to,
+/// so that all subscriptions can be replayed exactly after a reconnect.
+#[derive(Default)]
+pub(crate) struct SubscriptionRegistry {
+    topics: Vec<(String, String)>,
+    candlesticks: Vec<(String, usize)>,
+}
+
+impl SubscriptionRegistry {
+    pub fn add_topics(&mut self, topics: &[(String, String)]) {
+        for topic in topics {
+            if !self.topics.contains(topic) {
+                self.topics.push(topic.clone());
+            }
+        }
+    }
+
+    pub fn remove_topics(&mut self, topics: &[(String, String)]) {
+        self.topics.retain(|topic| !topics.contains(topic));
+    }
+
+    pub fn add_candlesticks(&mut self, symbol_interval_list: &[(String, usize)]) {
+        for symbol_interval in symbol_interval_list {
+            if !self.candlesticks.contains(symbol_interval) {
+                self.candlesticks.push(symbol_interval.clone());
+            }
+        }
+    }
+
+    pub fn remove_candlesticks(&mut self, symbol_interval_list: &[(String, usize)]) {
+        self.candlesticks
+            .retain(|symbol_interval| !symbol_interval_list.contains(symbol_interval));
+    }
+
+    pub fn topics(&self) -> &[(String, String)] {
+        &self.topics
+    }
+
+    pub fn candlesticks(&self) -> &[(String, usize)] {
+        &self.candlesticks
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.topics.is_empty() && self.candlesticks.is_empty()
+    }
+
+    /// Translate all subscriptions to subscribe commands, in the order they were added.
+    pub fn to_commands<T: CommandTranslator + ?Sized>(&self, translator: &T) -> Vec<String> {
+        let mut commands = Vec::new();
+        if !self.topics.is_empty() {
+            commands.extend(translator.translate_to_commands(true, &self.topics));
+        }
+        if !self.candlesticks.is_empty() {
+            commands.extend(translator.translate_to_candlestick_commands(true, &self.candlesticks));
+        }
+        commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriptionRegistry;
+    use crate::common::command_translator::CommandTranslator;
+
+    struct EchoCommandTranslator {}
+
+    impl CommandTranslator for EchoCommandTranslator {
+        fn translate_to_commands(
+            &self,
+            subscribe: bool,
+            topics: &[(String, String)],
+        ) -> Vec<String> {
+            topics
+                .iter()
+                .map(|(channel, symbol)| format!("{}:{}:{}", subscribe, channel, symbol))
+                .collect()
+        }
+
+        fn translate_to_candlestick_commands(
+            &self,
+            subscribe: bool,
+            symbol_interval_list: &[(String, usize)],
+        ) -> Vec<String> {
+            symbol_interval_list
+                .iter()
+                .map(|(symbol, interval)| format!("{}:kline:{}:{}", subscribe, symbol, interval))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_replay_in_order() {
+        let mut registry = SubscriptionRegistry::default();
+        registry.add_topics(&[
+            ("trade".to_string(), "BTCUSDT".to_string()),
+            ("trade".to_string(), "ETHUSDT".to_string()),
+        ]);
+        registry.add_candlesticks(&[("BTCUSDT".to_string(), 60)]);
+        // duplicates are ignored
+        registry.add_topics(&[("trade".to_string(), "BTCUSDT".to_string())]);
+
+        assert_eq!(
+            vec![
+                "true:trade:BTCUSDT".to_string(),
+                "true:trade:ETHUSDT".to_string(),
+                "true:kline:BTCUSDT:60".to_string(),
+            ],
+            registry.to_commands(&EchoCommandTranslator {})
+        );
+    }
+
+    #[test]
+    fn test_remove_topics() {
+        let mut registry = SubscriptionRegistry::default();
+        registry.add_topics(&[
+            ("trade".to_string(), "BTCUSDT".to_string()),
+            ("trade".to_string(), "ETHUSDT".to_string()),
+        ]);
+        registry.remove_topics(&[("trade".to_string(), "BTCUSDT".to_string())]);
+
+        assert_eq!(
+            vec!["true:trade:ETHUSDT".to_string()],
+            registry.to_commands(&EchoCommandTranslator {})
+        );
+    }
+
+    #[test]
+    fn test_remove_candlesticks() {
+        let mut registry = SubscriptionRegistry::default();
+        registry.add_candlesticks(&[("BTCUSDT".to_string(), 60), ("BTCUSDT".to_string(), 300)]);
+        registry.remove_candlesticks(&[("BTCUSDT".to_string(), 60)]);
+
+        assert_eq!(&[("BTCUSDT".to_string(), 300)], registry.candlesticks());
+        registry.remove_candlesticks(&[("BTCUSDT".to_string(), 300)]);
+        assert!(registry.is_empty());
+    }
+}
@@ -1,5 +1,7 @@
 use async_trait::async_trait;
 
+use super::metrics::MetricsSnapshot;
+
 /// The public interface of every WebSocket client.
 #[async_trait]
 pub trait WSClient {
@@ -93,6 +95,12 @@ pub trait WSClient {
     /// and CoinbasePro.
     async fn subscribe_candlestick(&self, symbol_interval_list: &[(String, usize)]);
 
+    /// Unsubscribes candlestick channels.
+    ///
+    /// `symbol_interval_list` is a list of symbols and intervals passed to
+    /// `subscribe_candlestick()`.
+    async fn unsubscribe_candlestick(&self, symbol_interval_list: &[(String, usize)]);
+
     /// Subscribe to multiple topics.
     ///
     /// topic = channel + symbol, a topic will be converted to an exchange-specific
@@ -108,6 +116,9 @@ pub trait WSClient {
     /// * Deribit: `vec![(trades.SYMBOL.100ms".to_string(),"BTC-PERPETUAL".to_string()),(trades.SYMBOL.100ms".to_string(),"ETH-PERPETUAL".to_string())]`
     /// * Huobi: `vec![("trade.detail".to_string(), "btcusdt".to_string()),("trade.detail".to_string(), "ethusdt".to_string())]`
     /// * OKX: `vec![("trades".to_string(), "BTC-USDT".to_string()),("trades".to_string(), "ETH-USDT".to_string())]`
+    ///
+    /// On Binance and OKX, whether the exchange accepted, rejected or ignored every subscription
+    /// is reported as a `WSClientEvent` to `WSClientOptions::events`.
     async fn subscribe(&self, topics: &[(String, String)]);
 
     /// Unsubscribes multiple topics.
@@ -121,8 +132,36 @@ pub trait WSClient {
     async fn send(&self, commands: &[String]);
 
     /// Starts the infinite event loop.
+    ///
+    /// If the connection is lost, the client reconnects with jittered exponential
+    /// backoff and replays all topics subscribed by `subscribe()` and
+    /// `subscribe_candlestick()`, messages keep flowing into the same `tx`.
+    ///
+    /// A connection is considered lost too if pings go unanswered or subscribed topics
+    /// stay silent for too long, see `WSClientOptions` for the thresholds.
     async fn run(&self);
 
     /// Close the connection and break the loop in Run().
+    ///
+    /// It returns immediately, use `shutdown()` to wait until everything has stopped.
     fn close(&self);
+
+    /// Closes the connection gracefully and waits until `run()` returns.
+    ///
+    /// The heartbeat stops, a close frame is sent to the exchange, and messages received
+    /// before the exchange responds are still sent to `tx`.
+    async fn shutdown(&self);
+
+    /// Snapshots of metrics, one per connection.
+    fn metrics(&self) -> Vec<MetricsSnapshot>;
+
+    /// Topics currently subscribed, in the order they were subscribed.
+    ///
+    /// It includes topics subscribed by high-level functions such as `subscribe_trade()`, but
+    /// not candlesticks, see `candlesticks()`.
+    fn topics(&self) -> Vec<(String, String)>;
+
+    /// Symbols and intervals of candlesticks currently subscribed, in the order they were
+    /// subscribed.
+    fn candlesticks(&self) -> Vec<(String, usize)>;
 }
@@ -1,34 +1,88 @@
 use std::{
-    io::prelude::*,
     num::NonZeroU32,
     sync::{
-        atomic::{AtomicIsize, Ordering},
+        atomic::{AtomicBool, AtomicIsize, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use flate2::read::{DeflateDecoder, GzDecoder};
+use crypto_market_type::MarketType;
 use log::*;
-use reqwest::StatusCode;
-use tokio_tungstenite::tungstenite::{Error, Message};
+use tokio_tungstenite::tungstenite::Message;
 
-use crate::common::message_handler::{MessageHandler, MiscMessage};
+use crate::{
+    common::{
+        ack_tracker::AckTracker,
+        auth::Authenticator,
+        codec::Codec,
+        command_translator::CommandTranslator,
+        connect_async::ConnectConfig,
+        event::WSClientEvent,
+        message_handler::{MessageHandler, MiscMessage},
+        message_sender::MessageSender,
+        metrics::{ConnectionMetrics, MetricsSnapshot},
+        options::{WSClientOptions, WatchdogConfig},
+        recording::Replayer,
+        subscription_registry::SubscriptionRegistry,
+    },
+    error::{Result, WsError},
+};
+
+// The first reconnect waits about 1 second, then doubles until 64 seconds
+const RECONNECT_BASE_DELAY_MS: u64 = 1000;
+const RECONNECT_MAX_DELAY_MS: u64 = 64000;
+
+// How often the watchdog checks whether the connection is stale
+const WATCHDOG_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// How long to wait for the response of a login
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The message handler, the receiver and decoder of the current connection and the sender of messages
+type RunParams<H> = (
+    H,
+    tokio::sync::mpsc::Receiver<Message>,
+    Codec,
+    MessageSender,
+);
+
+// Why the event loop of a connection exited.
+enum LoopExit {
+    Disconnected, // The connection was lost, needs to reconnect
+    Stopped,      // The receiver of messages was dropped
+    Stale,        // The watchdog found the connection stale, needs to reconnect
+}
 
 // `WSClientInternal` should be Sync + Send so that it can be put into Arc directly.
 pub(crate) struct WSClientInternal<H: MessageHandler> {
     exchange: &'static str, // Eexchange name
     pub(crate) url: String, // Websocket base url
+    connect_config: ConnectConfig,
+    // how long to wait after sending each command
+    send_interval: Option<Duration>,
     // pass parameters to run()
-    #[allow(clippy::type_complexity)]
-    params_rx: std::sync::Mutex<
-        tokio::sync::oneshot::Receiver<(
-            H,
-            tokio::sync::mpsc::Receiver<Message>,
-            std::sync::mpsc::Sender<String>,
-        )>,
-    >,
-    command_tx: tokio::sync::mpsc::Sender<Message>,
+    params_rx: std::sync::Mutex<tokio::sync::oneshot::Receiver<RunParams<H>>>,
+    // replaced by run() after every reconnect
+    command_tx: std::sync::RwLock<tokio::sync::mpsc::Sender<Message>>,
+    subscriptions: std::sync::Mutex<SubscriptionRegistry>,
+    closed: AtomicBool,
+    // notifies run() that close() was called
+    shutdown: tokio::sync::watch::Sender<bool>,
+    // held by run() until it returns
+    running: tokio::sync::Mutex<()>,
+    watchdog: WatchdogConfig,
+    // when data on subscribed topics was received last time
+    last_data_time: std::sync::Mutex<Instant>,
+    events: Option<tokio::sync::mpsc::Sender<WSClientEvent>>,
+    // logs in after every connect
+    authenticator: Option<Box<dyn Authenticator>>,
+    // commands awaiting acks of the current connection
+    acks: std::sync::Mutex<AckTracker>,
+    ack_timeout: Option<Duration>,
+    // replays a recording instead of connecting
+    replayer: Option<Replayer>,
+    metrics: Arc<ConnectionMetrics>,
 }
 
 impl<H: MessageHandler> WSClientInternal<H> {
@@ -37,130 +91,447 @@ impl<H: MessageHandler> WSClientInternal<H> {
         url: &str,
         handler: H,
         uplink_limit: Option<(NonZeroU32, std::time::Duration)>,
-        tx: std::sync::mpsc::Sender<String>,
-    ) -> Self {
+        tx: MessageSender,
+        options: WSClientOptions,
+    ) -> Result<Self> {
+        Self::connect_with_auth(exchange, url, handler, uplink_limit, tx, options, None).await
+    }
+
+    /// Connects and logs in with `authenticator`, which logs in again after every reconnect.
+    pub async fn connect_with_auth(
+        exchange: &'static str,
+        url: &str,
+        handler: H,
+        uplink_limit: Option<(NonZeroU32, std::time::Duration)>,
+        tx: MessageSender,
+        options: WSClientOptions,
+        authenticator: Option<Box<dyn Authenticator>>,
+    ) -> Result<Self> {
         // A channel to send parameters to run()
-        let (params_tx, params_rx) = tokio::sync::oneshot::channel::<(
-            H,
-            tokio::sync::mpsc::Receiver<Message>,
-            std::sync::mpsc::Sender<String>,
-        )>();
-
-        match super::connect_async::connect_async(url, uplink_limit).await {
-            Ok((message_rx, command_tx)) => {
-                let _ = params_tx.send((handler, message_rx, tx));
-
-                WSClientInternal {
-                    exchange,
-                    url: url.to_string(),
-                    params_rx: std::sync::Mutex::new(params_rx),
-                    command_tx,
-                }
-            }
-            Err(err) => match err {
-                Error::Http(resp) => {
-                    if resp.status() == StatusCode::TOO_MANY_REQUESTS {
-                        if let Some(retry_after) = resp.headers().get("retry-after") {
-                            let mut seconds = retry_after.to_str().unwrap().parse::<u64>().unwrap();
-                            seconds += rand::random::<u64>() % 9 + 1; // add random seconds to avoid concurrent requests
-                            error!(
-                                "The retry-after header value is {}, sleeping for {} seconds now",
-                                retry_after.to_str().unwrap(),
-                                seconds
-                            );
-                            tokio::time::sleep(Duration::from_secs(seconds)).await;
-                        }
-                    }
-                    panic!("Failed to connect to {} due to 429 too many requests", url)
+        let (params_tx, params_rx) = tokio::sync::oneshot::channel::<RunParams<H>>();
+        // replaced once connected
+        let (command_tx, _) = tokio::sync::mpsc::channel(1);
+
+        let replaying = options.replayer.is_some();
+        let client = WSClientInternal {
+            exchange,
+            url: url.to_string(),
+            connect_config: ConnectConfig {
+                uplink_limit: options.uplink_limit.or(uplink_limit),
+                proxy: options.proxy.clone(),
+                recorder: options.recorder.clone(),
+                tls_config: options.tls_config.clone(),
+                headers: options.headers.clone(),
+                command_buffer: options.command_buffer(),
+                message_buffer: options.message_buffer(),
+            },
+            send_interval: options.send_interval(exchange),
+            params_rx: std::sync::Mutex::new(params_rx),
+            command_tx: std::sync::RwLock::new(command_tx),
+            subscriptions: std::sync::Mutex::new(SubscriptionRegistry::default()),
+            closed: AtomicBool::new(false),
+            shutdown: tokio::sync::watch::channel(false).0,
+            running: tokio::sync::Mutex::new(()),
+            watchdog: if replaying {
+                // recorded frames arrive as fast as they are consumed
+                WatchdogConfig {
+                    ping_interval: None,
+                    max_unanswered_pings: None,
+                    idle_timeout: None,
                 }
-                _ => panic!("Failed to connect to {}, error: {}", url, err),
+            } else {
+                options.watchdog_config(exchange)
             },
-        }
+            last_data_time: std::sync::Mutex::new(Instant::now()),
+            ack_timeout: options.ack_timeout().filter(|_| !replaying),
+            events: options.events,
+            authenticator,
+            acks: std::sync::Mutex::new(AckTracker::default()),
+            replayer: options.replayer,
+            metrics: Arc::new(ConnectionMetrics::new(
+                exchange,
+                options.market_type.unwrap_or(MarketType::Unknown),
+                url,
+            )),
+        };
+        let mut message_rx = client.open_connection().await?;
+        let mut codec = handler.codec();
+        client.login(&mut message_rx, &mut codec).await?;
+        let _ = params_tx.send((handler, message_rx, codec, tx));
+        Ok(client)
     }
 
-    fn get_send_interval_ms(&self) -> Option<u64> {
-        match self.exchange {
-            "binance" => Some(100), // WebSocket connections have a limit of 10 incoming messages per second
-            "kucoin" => Some(100),  //  Message limit sent to the server: 100 per 10 seconds
-            _ => None,
-        }
+    // Connects to the exchange, or replays the next recorded connection, then replaces the
+    // command sender.
+    async fn open_connection(&self) -> Result<tokio::sync::mpsc::Receiver<Message>> {
+        let (message_rx, command_tx) = match self.replayer.as_ref() {
+            Some(replayer) => replayer.next_connection(),
+            None => super::connect_async::connect_async(&self.url, &self.connect_config).await?,
+        };
+        *self.command_tx.write().unwrap() = command_tx;
+        Ok(message_rx)
+    }
+
+    // The command sender of the current connection.
+    fn command_tx(&self) -> tokio::sync::mpsc::Sender<Message> {
+        self.command_tx.read().unwrap().clone()
     }
 
     pub async fn send(&self, commands: &[String]) {
+        let command_tx = self.command_tx();
         for command in commands {
             debug!("{}", command);
-            if self
-                .command_tx
+            if command_tx
                 .send(Message::Text(command.to_string()))
                 .await
                 .is_err()
             {
                 break; // break the loop if there is no receiver
             }
-            if let Some(interval) = self.get_send_interval_ms() {
-                std::thread::sleep(Duration::from_millis(interval));
+            if let Some(interval) = self.send_interval {
+                std::thread::sleep(interval);
             }
         }
     }
 
-    pub async fn run(&self) {
-        let (mut handler, mut message_rx, tx) = {
+    /// Subscribes to topics and remembers them so that they survive reconnects.
+    pub async fn subscribe<T: CommandTranslator + Sync>(
+        &self,
+        translator: &T,
+        topics: &[(String, String)],
+    ) {
+        self.subscriptions.lock().unwrap().add_topics(topics);
+        self.reset_idle_timer();
+        let commands = translator.translate_to_commands(true, topics);
+        self.track_acks(translator, &commands);
+        self.send(&commands).await;
+    }
+
+    /// Unsubscribes topics so that they will not be replayed after reconnects.
+    pub async fn unsubscribe<T: CommandTranslator + Sync>(
+        &self,
+        translator: &T,
+        topics: &[(String, String)],
+    ) {
+        self.subscriptions.lock().unwrap().remove_topics(topics);
+        let commands = translator.translate_to_commands(false, topics);
+        self.track_acks(translator, &commands);
+        self.send(&commands).await;
+    }
+
+    /// Subscribes to candlesticks and remembers them so that they survive reconnects.
+    pub async fn subscribe_candlestick<T: CommandTranslator + Sync>(
+        &self,
+        translator: &T,
+        symbol_interval_list: &[(String, usize)],
+    ) {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .add_candlesticks(symbol_interval_list);
+        self.reset_idle_timer();
+        let commands = translator.translate_to_candlestick_commands(true, symbol_interval_list);
+        self.track_acks(translator, &commands);
+        self.send(&commands).await;
+    }
+
+    /// Unsubscribes candlesticks so that they will not be replayed after reconnects.
+    pub async fn unsubscribe_candlestick<T: CommandTranslator + Sync>(
+        &self,
+        translator: &T,
+        symbol_interval_list: &[(String, usize)],
+    ) {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .remove_candlesticks(symbol_interval_list);
+        let commands = translator.translate_to_candlestick_commands(false, symbol_interval_list);
+        self.track_acks(translator, &commands);
+        self.send(&commands).await;
+    }
+
+    // Must be called before sending commands, otherwise acks may arrive before being tracked.
+    fn track_acks<T: CommandTranslator + Sync>(&self, translator: &T, commands: &[String]) {
+        let mut acks = self.acks.lock().unwrap();
+        for command in commands {
+            acks.track(translator.pending_acks(command));
+        }
+    }
+
+    fn on_ack(&self, key: &str, error: Option<String>) {
+        let ack = match self.acks.lock().unwrap().resolve(key) {
+            Some(ack) => ack,
+            None => {
+                debug!("Received an unexpected ack {} from {}", key, self.url);
+                return;
+            }
+        };
+        if !ack.subscribe {
+            return;
+        }
+        match error {
+            Some(reason) => {
+                error!(
+                    "Failed to subscribe {:?} on {}, {}",
+                    ack.raw_topics, self.url, reason
+                );
+                self.emit(WSClientEvent::SubscribeFailed {
+                    raw_topics: ack.raw_topics,
+                    reason,
+                });
+            }
+            None => self.emit(WSClientEvent::Subscribed {
+                raw_topics: ack.raw_topics,
+            }),
+        }
+    }
+
+    fn expire_acks(&self) {
+        let timeout = match self.ack_timeout {
+            Some(timeout) => timeout,
+            None => return,
+        };
+        let expired = self.acks.lock().unwrap().expire(timeout);
+        for ack in expired.into_iter().filter(|ack| ack.subscribe) {
+            warn!(
+                "Subscribing {:?} on {} was not acknowledged in {} seconds",
+                ack.raw_topics,
+                self.url,
+                timeout.as_secs()
+            );
+            self.emit(WSClientEvent::SubscribeTimeout {
+                raw_topics: ack.raw_topics,
+            });
+        }
+    }
+
+    // Subscribed topics may take a while to deliver the first message.
+    fn reset_idle_timer(&self) {
+        *self.last_data_time.lock().unwrap() = Instant::now();
+    }
+
+    /// Runs the event loop, reconnects and replays subscriptions whenever the connection is lost.
+    ///
+    /// `translator` is used to regenerate subscribe commands after reconnecting.
+    pub async fn run<T: CommandTranslator + Sync>(&self, translator: &T) {
+        let _running = self.running.lock().await;
+        if self.closed.load(Ordering::Acquire) {
+            return;
+        }
+        let (mut handler, mut message_rx, mut codec, tx) = {
             let mut guard = self.params_rx.lock().unwrap();
             guard.try_recv().unwrap()
         };
 
-        let num_unanswered_ping = Arc::new(AtomicIsize::new(0)); // for debug only
-        if let Some((msg, interval)) = handler.get_ping_msg_and_interval() {
-            // send heartbeat periodically
-            let command_tx_clone = self.command_tx.clone();
-            let num_unanswered_ping_clone = num_unanswered_ping.clone();
-            tokio::task::spawn(async move {
-                let mut timer = {
-                    let duration = Duration::from_secs(interval / 2 + 1);
-                    tokio::time::interval(duration)
-                };
-                loop {
-                    let now = timer.tick().await;
-                    debug!("{:?} sending ping {}", now, msg.to_text().unwrap());
-                    if let Err(err) = command_tx_clone.send(msg.clone()).await {
-                        error!("Error sending ping {}", err);
-                    } else {
-                        num_unanswered_ping_clone.fetch_add(1, Ordering::SeqCst);
+        loop {
+            let num_unanswered_ping = Arc::new(AtomicIsize::new(0));
+            let heartbeat = self.spawn_heartbeat(&handler, num_unanswered_ping.clone());
+            let exit = self
+                .run_connection(
+                    &mut handler,
+                    &mut message_rx,
+                    &mut codec,
+                    tx.clone(),
+                    num_unanswered_ping,
+                )
+                .await;
+            if let Some(heartbeat) = heartbeat {
+                heartbeat.abort();
+                _ = heartbeat.await;
+            }
+
+            match exit {
+                LoopExit::Stopped => break,
+                LoopExit::Disconnected | LoopExit::Stale => {
+                    if self.closed.load(Ordering::Acquire) {
+                        break;
+                    }
+                    match self.reconnect(&handler, translator).await {
+                        Some((rx, new_codec)) => {
+                            message_rx = rx;
+                            codec = new_codec;
+                        }
+                        None => break,
                     }
                 }
-            });
+            }
         }
+    }
 
-        while let Some(msg) = message_rx.recv().await {
-            let txt = match msg {
-                Message::Text(txt) => Some(txt),
-                Message::Binary(binary) => {
-                    let mut txt = String::new();
-                    let resp = match self.exchange {
-                        crate::clients::huobi::EXCHANGE_NAME
-                        | crate::clients::binance::EXCHANGE_NAME
-                        | "bitget"
-                        | "bitz" => {
-                            let mut decoder = GzDecoder::new(&binary[..]);
-                            decoder.read_to_string(&mut txt)
-                        }
-                        crate::clients::okx::EXCHANGE_NAME => {
-                            let mut decoder = DeflateDecoder::new(&binary[..]);
-                            decoder.read_to_string(&mut txt)
-                        }
-                        _ => {
-                            panic!("Unknown binary format from {}", self.url);
-                        }
-                    };
+    // Sends heartbeat periodically to the current connection.
+    fn spawn_heartbeat(
+        &self,
+        handler: &H,
+        num_unanswered_ping: Arc<AtomicIsize>,
+    ) -> Option<tokio::task::JoinHandle<()>> {
+        if self.replayer.is_some() {
+            return None;
+        }
+        let (msg, interval) = handler.get_ping_msg_and_interval()?;
+        let duration = self
+            .watchdog
+            .ping_interval
+            .unwrap_or_else(|| Duration::from_secs(interval / 2 + 1));
+        let command_tx = self.command_tx();
+        let metrics = self.metrics.clone();
+        Some(tokio::task::spawn(async move {
+            let mut timer = tokio::time::interval(duration);
+            loop {
+                let now = timer.tick().await;
+                debug!("{:?} sending ping {}", now, msg.to_text().unwrap());
+                if let Err(err) = command_tx.send(msg.clone()).await {
+                    error!("Error sending ping {}", err);
+                } else {
+                    num_unanswered_ping.fetch_add(1, Ordering::SeqCst);
+                    metrics.on_ping_sent();
+                }
+            }
+        }))
+    }
 
-                    match resp {
-                        Ok(_) => Some(txt),
-                        Err(err) => {
-                            error!("Decompression failed, {}", err);
-                            None
+    // Reconnects with jittered exponential backoff, logs in and replays all subscriptions.
+    //
+    // Returns None if the client was closed while reconnecting.
+    async fn reconnect<T: CommandTranslator + Sync>(
+        &self,
+        handler: &H,
+        translator: &T,
+    ) -> Option<(tokio::sync::mpsc::Receiver<Message>, Codec)> {
+        let mut attempt = 0;
+        loop {
+            if matches!(self.replayer.as_ref(), Some(replayer) if replayer.is_exhausted()) {
+                info!("Finished replaying the recording of {}", self.url);
+                return None;
+            }
+            let delay = if self.replayer.is_some() {
+                Duration::ZERO
+            } else {
+                reconnect_delay(attempt)
+            };
+            warn!(
+                "Reconnecting to {} in {} milliseconds, attempt {}",
+                self.url,
+                delay.as_millis(),
+                attempt + 1
+            );
+            let mut shutdown_rx = self.shutdown.subscribe();
+            if self.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => (),
+                _ = shutdown_rx.changed() => return None,
+            }
+
+            let connected = match self.open_connection().await {
+                Ok(mut message_rx) => {
+                    let mut codec = handler.codec();
+                    self.login(&mut message_rx, &mut codec)
+                        .await
+                        .map(|_| (message_rx, codec))
+                }
+                Err(err) => Err(err),
+            };
+            match connected {
+                Ok((message_rx, codec)) => {
+                    let commands = self.subscriptions.lock().unwrap().to_commands(translator);
+                    info!(
+                        "Reconnected to {}, replaying {} commands",
+                        self.url,
+                        commands.len()
+                    );
+                    self.metrics.on_reconnect();
+                    // commands sent to the lost connection will never be acknowledged
+                    self.acks.lock().unwrap().clear();
+                    self.track_acks(translator, &commands);
+                    self.send(&commands).await;
+                    return Some((message_rx, codec));
+                }
+                Err(err) => {
+                    error!("Failed to reconnect to {}, error: {}", self.url, err);
+                    if let WsError::RateLimited {
+                        retry_after: Some(seconds),
+                        ..
+                    } = err
+                    {
+                        // add random seconds to avoid concurrent requests
+                        let seconds = seconds + rand::random::<u64>() % 9 + 1;
+                        tokio::select! {
+                            _ = tokio::time::sleep(Duration::from_secs(seconds)) => (),
+                            _ = shutdown_rx.changed() => return None,
                         }
                     }
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    // The event loop of one connection.
+    async fn run_connection(
+        &self,
+        handler: &mut H,
+        message_rx: &mut tokio::sync::mpsc::Receiver<Message>,
+        codec: &mut Codec,
+        tx: MessageSender,
+        num_unanswered_ping: Arc<AtomicIsize>,
+    ) -> LoopExit {
+        self.reset_idle_timer();
+        let mut watchdog_timer = tokio::time::interval(WATCHDOG_CHECK_INTERVAL);
+        let mut shutdown_rx = self.shutdown.subscribe();
+        let mut closing = false;
+        loop {
+            if !closing && self.closed.load(Ordering::Acquire) {
+                // the connection sends a close frame and stops after draining frames
+                // received before the response of the server
+                closing = true;
+                _ = self.command_tx().send(Message::Close(None)).await;
+            }
+            let msg = tokio::select! {
+                msg = message_rx.recv() => match msg {
+                    Some(msg) => msg,
+                    // message_rx is closed, either the connection was lost or close() was called
+                    None => return LoopExit::Disconnected,
+                },
+                _ = shutdown_rx.changed(), if !closing => continue,
+                _ = watchdog_timer.tick(), if !closing => {
+                    self.metrics.tick();
+                    self.expire_acks();
+                    if self.is_stale(&num_unanswered_ping) {
+                        // close the stale connection before reconnecting
+                        _ = self.command_tx().try_send(Message::Close(None));
+                        return LoopExit::Stale;
+                    }
+                    continue;
                 }
+            };
+            // Any frame from the server proves that the connection is alive
+            num_unanswered_ping.store(0, Ordering::Release);
+            self.metrics.on_frame(frame_len(&msg));
+
+            let txt = match msg {
+                Message::Text(txt) => Some(txt),
+                Message::Binary(binary) => match self.decode(codec, &binary) {
+                    Ok(txt) => Some(txt),
+                    Err(reason) => {
+                        let error = if let Codec::Utf8 = *codec {
+                            WsError::UnsupportedBinaryEncoding {
+                                exchange: self.exchange.to_string(),
+                            }
+                        } else {
+                            WsError::Decode {
+                                exchange: self.exchange.to_string(),
+                                reason,
+                            }
+                        };
+                        error!("{}", error);
+                        self.emit(WSClientEvent::DecodeError {
+                            error,
+                            payload: binary,
+                        });
+                        None
+                    }
+                },
                 Message::Ping(resp) => {
                     // binance server will send a ping frame every 3 or 5 minutes
                     debug!(
@@ -171,21 +542,21 @@ impl<H: MessageHandler> WSClientInternal<H> {
                     if self.exchange == "binance" {
                         // send a pong frame
                         debug!("Sending a pong frame to {}", self.url);
-                        _ = self.command_tx.send(Message::Pong(Vec::new())).await;
+                        _ = self.command_tx().send(Message::Pong(Vec::new())).await;
                     }
                     None
                 }
                 Message::Pong(resp) => {
-                    num_unanswered_ping.store(0, Ordering::Release);
+                    self.metrics.on_pong();
                     debug!(
-                        "Received a pong frame: {} from {}, reset num_unanswered_ping to {}",
+                        "Received a pong frame: {} from {}",
                         std::str::from_utf8(&resp).unwrap(),
                         self.exchange,
-                        num_unanswered_ping.load(Ordering::Acquire)
                     );
                     None
                 }
                 Message::Frame(_) => todo!(),
+                Message::Close(_) if closing => return LoopExit::Disconnected,
                 Message::Close(resp) => {
                     match resp {
                         Some(frame) => {
@@ -196,8 +567,7 @@ impl<H: MessageHandler> WSClientInternal<H> {
                         }
                         None => warn!("Received a close message without CloseFrame"),
                     }
-                    // break;
-                    panic!("Received a CloseFrame"); //fail fast so that pm2 can restart the process
+                    return LoopExit::Disconnected;
                 }
             };
 
@@ -205,31 +575,187 @@ impl<H: MessageHandler> WSClientInternal<H> {
                 let txt = txt.as_str().trim().to_string();
                 match handler.handle_message(&txt) {
                     MiscMessage::Normal => {
+                        self.reset_idle_timer();
+                        self.metrics.on_message(&txt);
                         // the receiver might get dropped earlier than this loop
-                        if tx.send(txt).is_err() {
-                            break; // break the loop if there is no receiver
+                        if tx.send(txt).await.is_err() {
+                            return LoopExit::Stopped; // break the loop if there is no receiver
                         }
                     }
-                    MiscMessage::Mutated(txt) => _ = tx.send(txt),
-                    MiscMessage::WebSocket(ws_msg) => _ = self.command_tx.send(ws_msg).await,
+                    MiscMessage::Mutated(txt) => {
+                        self.reset_idle_timer();
+                        self.metrics.on_message(&txt);
+                        _ = tx.send(txt).await;
+                    }
+                    MiscMessage::WebSocket(ws_msg) => _ = self.command_tx().send(ws_msg).await,
                     MiscMessage::Pong => {
-                        num_unanswered_ping.store(0, Ordering::Release);
-                        debug!(
-                            "Received {} from {}, reset num_unanswered_ping to {}",
-                            txt,
-                            self.exchange,
-                            num_unanswered_ping.load(Ordering::Acquire)
-                        );
+                        self.metrics.on_pong();
+                        debug!("Received {} from {}", txt, self.exchange)
+                    }
+                    MiscMessage::Reconnect => return LoopExit::Disconnected,
+                    MiscMessage::Ack { key, error } => self.on_ack(&key, error),
+                    MiscMessage::Other => (), // ignore
+                }
+            }
+        }
+    }
+
+    // Decodes a binary frame and measures the time spent.
+    fn decode(&self, codec: &mut Codec, binary: &[u8]) -> std::result::Result<String, String> {
+        let start = Instant::now();
+        let txt = codec.decode(binary);
+        self.metrics.on_decoded(start.elapsed());
+        txt
+    }
+
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    pub fn topics(&self) -> Vec<(String, String)> {
+        self.subscriptions.lock().unwrap().topics().to_vec()
+    }
+
+    pub fn candlesticks(&self) -> Vec<(String, usize)> {
+        self.subscriptions.lock().unwrap().candlesticks().to_vec()
+    }
+
+    // Sends login commands and waits for the response, does nothing without an authenticator.
+    async fn login(
+        &self,
+        message_rx: &mut tokio::sync::mpsc::Receiver<Message>,
+        codec: &mut Codec,
+    ) -> Result<()> {
+        let authenticator = match self.authenticator.as_ref() {
+            Some(authenticator) => authenticator,
+            None => return Ok(()),
+        };
+        authenticator.prepare().await?;
+        let command_tx = self.command_tx();
+        for command in authenticator.login_commands() {
+            if command_tx.send(Message::Text(command)).await.is_err() {
+                return Err(WsError::Connection(format!(
+                    "{} closed while logging in",
+                    self.url
+                )));
+            }
+        }
+
+        let wait_for_response = async {
+            while let Some(msg) = message_rx.recv().await {
+                let txt = match msg {
+                    Message::Text(txt) => txt,
+                    Message::Binary(binary) => match codec.decode(&binary) {
+                        Ok(txt) => txt,
+                        Err(_) => continue,
+                    },
+                    _ => continue,
+                };
+                match authenticator.check_login_response(txt.trim()) {
+                    Some(Ok(())) => {
+                        info!("Logged in to {}", self.url);
+                        return Ok(());
                     }
-                    MiscMessage::Reconnect => break, // fail fast, pm2 will restart, restart is reconnect
-                    MiscMessage::Other => (),        // ignore
+                    Some(Err(reason)) => return Err(WsError::Auth(reason)),
+                    None => debug!("Received {} from {} while logging in", txt, self.url),
                 }
             }
+            Err(WsError::Connection(format!(
+                "{} closed while logging in",
+                self.url
+            )))
+        };
+        match tokio::time::timeout(LOGIN_TIMEOUT, wait_for_response).await {
+            Ok(result) => result,
+            Err(_) => Err(WsError::Auth(format!(
+                "no response to the login from {}",
+                self.url
+            ))),
+        }
+    }
+
+    // Sends an event to the events channel if there is one, drops it if the channel is full.
+    fn emit(&self, event: WSClientEvent) {
+        if let Some(events) = self.events.as_ref() {
+            if let Err(err) = events.try_send(event) {
+                debug!("Dropped an event of {}, {}", self.url, err);
+            }
+        }
+    }
+
+    // Whether too many pings were unanswered, or subscribed topics have been silent for too long.
+    fn is_stale(&self, num_unanswered_ping: &AtomicIsize) -> bool {
+        if let Some(max_unanswered_pings) = self.watchdog.max_unanswered_pings {
+            let num_unanswered_ping = num_unanswered_ping.load(Ordering::Acquire);
+            if num_unanswered_ping > max_unanswered_pings as isize {
+                warn!(
+                    "{} pings to {} were not answered, the connection is stale",
+                    num_unanswered_ping, self.url
+                );
+                return true;
+            }
+        }
+        if let Some(idle_timeout) = self.watchdog.idle_timeout {
+            let elapsed = self.last_data_time.lock().unwrap().elapsed();
+            if elapsed > idle_timeout && !self.subscriptions.lock().unwrap().is_empty() {
+                warn!(
+                    "No data from {} for {} seconds, the connection is stale",
+                    self.url,
+                    elapsed.as_secs()
+                );
+                return true;
+            }
         }
+        false
     }
 
     pub fn close(&self) {
-        // close the websocket connection and break the while loop in run()
-        _ = self.command_tx.send(Message::Close(None));
+        // run() closes the websocket connection and breaks the while loop
+        self.closed.store(true, Ordering::Release);
+        self.shutdown.send_replace(true);
+        // run() is not running, close the connection here, run() returns immediately if
+        // called later
+        if self.running.try_lock().is_ok() {
+            _ = self.command_tx().try_send(Message::Close(None));
+        }
+    }
+
+    /// Closes the connection gracefully and waits until run() returns.
+    ///
+    /// Messages received before the close frame are still sent to `tx`.
+    pub async fn shutdown(&self) {
+        self.close();
+        let _running = self.running.lock().await;
+    }
+}
+
+fn frame_len(msg: &Message) -> usize {
+    match msg {
+        Message::Text(txt) => txt.len(),
+        Message::Binary(payload) | Message::Ping(payload) | Message::Pong(payload) => payload.len(),
+        Message::Close(_) | Message::Frame(_) => 0,
+    }
+}
+
+// Exponential backoff with full jitter in the upper half, to avoid reconnecting in lockstep.
+fn reconnect_delay(attempt: u32) -> Duration {
+    let max_delay = RECONNECT_BASE_DELAY_MS
+        .saturating_mul(1 << attempt.min(16))
+        .min(RECONNECT_MAX_DELAY_MS);
+    let jitter = rand::random::<u64>() % (max_delay / 2 + 1);
+    Duration::from_millis(max_delay / 2 + jitter)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_reconnect_delay() {
+        for attempt in 0..32 {
+            let delay = super::reconnect_delay(attempt).as_millis() as u64;
+            let max_delay = (super::RECONNECT_BASE_DELAY_MS << attempt.min(16))
+                .min(super::RECONNECT_MAX_DELAY_MS);
+            assert!(delay >= max_delay / 2);
+            assert!(delay <= max_delay);
+        }
     }
 }
@@ -0,0 +1,140 @@
+use std::{error::Error as StdError, fmt};
+
+use crypto_market_type::MarketType;
+use reqwest::StatusCode;
+use tokio_tungstenite::tungstenite::Error as TungsteniteError;
+
+pub(crate) type Result<T> = std::result::Result<T, WsError>;
+
+/// Errors returned when connecting to a websocket server.
+#[derive(Debug)]
+pub enum WsError {
+    /// The server responded with 429 Too Many Requests.
+    ///
+    /// `retry_after` is the value of the `retry-after` header in seconds, if present.
+    RateLimited {
+        url: String,
+        retry_after: Option<u64>,
+    },
+    /// The TLS or websocket handshake failed.
+    Handshake { url: String, reason: String },
+    /// Failed to connect through the proxy.
+    Proxy(String),
+    /// The exchange rejected the credentials, or did not respond to the login.
+    Auth(String),
+    /// The exchange doesn't have the market type, or the exchange is unknown.
+    UnsupportedMarket {
+        exchange: String,
+        market_type: MarketType,
+    },
+    /// Received a binary frame which the exchange is not known to send.
+    UnsupportedBinaryEncoding { exchange: String },
+    /// Failed to decompress a binary frame.
+    Decode { exchange: String, reason: String },
+    /// Other errors, e.g., I/O errors, invalid URLs, failures fetching tokens, etc.
+    Connection(String),
+}
+
+impl WsError {
+    pub(crate) fn from_tungstenite(url: &str, err: TungsteniteError) -> Self {
+        match err {
+            TungsteniteError::Http(resp) => {
+                if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+                    let retry_after = resp
+                        .headers()
+                        .get("retry-after")
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| value.trim().parse::<u64>().ok());
+                    WsError::RateLimited {
+                        url: url.to_string(),
+                        retry_after,
+                    }
+                } else {
+                    WsError::Handshake {
+                        url: url.to_string(),
+                        reason: format!("HTTP status {}", resp.status()),
+                    }
+                }
+            }
+            TungsteniteError::Io(err) => WsError::Connection(format!("{}, {}", url, err)),
+            TungsteniteError::Url(err) => WsError::Connection(format!("{}, {}", url, err)),
+            _ => WsError::Handshake {
+                url: url.to_string(),
+                reason: err.to_string(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for WsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WsError::RateLimited { url, retry_after } => match retry_after {
+                Some(seconds) => write!(
+                    f,
+                    "Failed to connect to {} due to 429 too many requests, retry after {} seconds",
+                    url, seconds
+                ),
+                None => write!(
+                    f,
+                    "Failed to connect to {} due to 429 too many requests",
+                    url
+                ),
+            },
+            WsError::Handshake { url, reason } => {
+                write!(f, "Handshake with {} failed, {}", url, reason)
+            }
+            WsError::Proxy(reason) => write!(f, "Proxy error, {}", reason),
+            WsError::Auth(reason) => write!(f, "Authentication failed, {}", reason),
+            WsError::UnsupportedMarket {
+                exchange,
+                market_type,
+            } => write!(
+                f,
+                "{} does NOT have the {} market type",
+                exchange, market_type
+            ),
+            WsError::UnsupportedBinaryEncoding { exchange } => {
+                write!(f, "Unknown binary format from {}", exchange)
+            }
+            WsError::Decode { exchange, reason } => {
+                write!(
+                    f,
+                    "Failed to decode a binary frame from {}, {}",
+                    exchange, reason
+                )
+            }
+            WsError::Connection(reason) => write!(f, "Connection error, {}", reason),
+        }
+    }
+}
+
+impl StdError for WsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::WsError;
+    use tokio_tungstenite::tungstenite::{http::Response, Error};
+
+    #[test]
+    fn test_rate_limited() {
+        let resp = Response::builder()
+            .status(429)
+            .header("retry-after", "30")
+            .body(None)
+            .unwrap();
+        match WsError::from_tungstenite("wss://example.com", Error::Http(resp)) {
+            WsError::RateLimited { retry_after, .. } => assert_eq!(Some(30), retry_after),
+            err => panic!("Unexpected error {}", err),
+        }
+    }
+
+    #[test]
+    fn test_handshake() {
+        let resp = Response::builder().status(403).body(None).unwrap();
+        assert!(matches!(
+            WsError::from_tungstenite("wss://example.com", Error::Http(resp)),
+            WsError::Handshake { .. }
+        ));
+    }
+}
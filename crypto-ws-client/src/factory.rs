@@ -0,0 +1,308 @@
+use crypto_market_type::MarketType;
+
+use crate::{
+    clients::{
+        binance::*, binance_option::*, bitfinex::*, bitget::*, bithumb::*, bitmex::*, bitstamp::*,
+        bitz::*, bybit::*, coinbase_pro::*, deribit::*, dydx::*, ftx::*, gate::*, huobi::*,
+        kraken::*, kucoin::*, mexc::*, okx::*, zb::*, zbg::*,
+    },
+    common::{message_sender::MessageSender, options::WSClientOptions, ws_client::WSClient},
+    error::{Result, WsError},
+};
+
+// Market types that every exchange supports, must be consistent with create_ws_client()
+const SUPPORTED_MARKETS: &[(&str, &[MarketType])] = &[
+    (
+        "binance",
+        &[
+            MarketType::Spot,
+            MarketType::LinearFuture,
+            MarketType::InverseFuture,
+            MarketType::LinearSwap,
+            MarketType::InverseSwap,
+            MarketType::EuropeanOption,
+        ],
+    ),
+    ("bitfinex", &[MarketType::Spot, MarketType::LinearSwap]),
+    (
+        "bitget",
+        &[
+            MarketType::Spot,
+            MarketType::InverseSwap,
+            MarketType::LinearSwap,
+        ],
+    ),
+    ("bithumb", &[MarketType::Spot]),
+    (
+        "bitmex",
+        &[
+            MarketType::Unknown, // all symbols
+            MarketType::Spot,
+            MarketType::LinearSwap,
+            MarketType::InverseSwap,
+            MarketType::QuantoSwap,
+            MarketType::LinearFuture,
+            MarketType::InverseFuture,
+            MarketType::QuantoFuture,
+        ],
+    ),
+    ("bitstamp", &[MarketType::Spot]),
+    ("bitz", &[MarketType::Spot]),
+    (
+        "bybit",
+        &[
+            MarketType::InverseFuture,
+            MarketType::InverseSwap,
+            MarketType::LinearSwap,
+        ],
+    ),
+    ("coinbase_pro", &[MarketType::Spot]),
+    (
+        "deribit",
+        &[
+            MarketType::InverseFuture,
+            MarketType::InverseSwap,
+            MarketType::EuropeanOption,
+        ],
+    ),
+    ("dydx", &[MarketType::LinearSwap]),
+    (
+        "ftx",
+        &[
+            MarketType::Spot,
+            MarketType::LinearFuture,
+            MarketType::LinearSwap,
+            MarketType::Move,
+            MarketType::BVOL,
+        ],
+    ),
+    (
+        "gate",
+        &[
+            MarketType::Spot,
+            MarketType::InverseFuture,
+            MarketType::LinearFuture,
+            MarketType::InverseSwap,
+            MarketType::LinearSwap,
+        ],
+    ),
+    (
+        "huobi",
+        &[
+            MarketType::Spot,
+            MarketType::InverseFuture,
+            MarketType::LinearSwap,
+            MarketType::InverseSwap,
+            MarketType::EuropeanOption,
+        ],
+    ),
+    (
+        "kraken",
+        &[
+            MarketType::Spot,
+            MarketType::InverseFuture,
+            MarketType::InverseSwap,
+        ],
+    ),
+    (
+        "kucoin",
+        &[
+            MarketType::Spot,
+            MarketType::InverseFuture,
+            MarketType::LinearSwap,
+            MarketType::InverseSwap,
+        ],
+    ),
+    (
+        "mexc",
+        &[
+            MarketType::Spot,
+            MarketType::LinearSwap,
+            MarketType::InverseSwap,
+        ],
+    ),
+    (
+        "okx",
+        &[
+            MarketType::Spot,
+            MarketType::LinearFuture,
+            MarketType::InverseFuture,
+            MarketType::LinearSwap,
+            MarketType::InverseSwap,
+            MarketType::EuropeanOption,
+        ],
+    ),
+    ("zb", &[MarketType::Spot, MarketType::LinearSwap]),
+    (
+        "zbg",
+        &[
+            MarketType::Spot,
+            MarketType::InverseSwap,
+            MarketType::LinearSwap,
+        ],
+    ),
+];
+
+/// Lists all (exchange, market_type) pairs supported by `create_ws_client()`.
+pub fn supported_markets() -> Vec<(&'static str, MarketType)> {
+    SUPPORTED_MARKETS
+        .iter()
+        .flat_map(|(exchange, market_types)| {
+            market_types
+                .iter()
+                .map(move |market_type| (*exchange, *market_type))
+        })
+        .collect()
+}
+
+/// Returns true if `create_ws_client()` supports the market.
+pub fn is_supported(exchange: &str, market_type: MarketType) -> bool {
+    SUPPORTED_MARKETS
+        .iter()
+        .any(|(x, market_types)| *x == exchange && market_types.contains(&market_type))
+}
+
+/// Creates a websocket client by exchange name and market type.
+///
+/// Returns `WsError::UnsupportedMarket` if the exchange doesn't have the market type,
+/// see `supported_markets()`.
+pub async fn create_ws_client(
+    exchange: &str,
+    market_type: MarketType,
+    tx: impl Into<MessageSender>,
+    mut options: WSClientOptions,
+) -> Result<Box<dyn WSClient + Send + Sync>> {
+    if !is_supported(exchange, market_type) {
+        return Err(WsError::UnsupportedMarket {
+            exchange: exchange.to_string(),
+            market_type,
+        });
+    }
+    let tx = tx.into();
+    options.market_type.get_or_insert(market_type);
+    let ws_client: Box<dyn WSClient + Send + Sync> = match (exchange, market_type) {
+        ("binance", MarketType::Spot) => {
+            Box::new(BinanceSpotWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("binance", MarketType::InverseFuture | MarketType::InverseSwap) => {
+            Box::new(BinanceInverseWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("binance", MarketType::LinearFuture | MarketType::LinearSwap) => {
+            Box::new(BinanceLinearWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("binance", MarketType::EuropeanOption) => {
+            Box::new(BinanceOptionWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("bitfinex", _) => Box::new(BitfinexWSClient::new_with_options(tx, None, options).await?),
+        ("bitget", MarketType::Spot) => {
+            Box::new(BitgetSpotWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("bitget", _) => Box::new(BitgetSwapWSClient::new_with_options(tx, None, options).await?),
+        ("bithumb", _) => Box::new(BithumbWSClient::new_with_options(tx, None, options).await?),
+        ("bitmex", _) => Box::new(BitmexWSClient::new_with_options(tx, None, options).await?),
+        ("bitstamp", _) => Box::new(BitstampWSClient::new_with_options(tx, None, options).await?),
+        ("bitz", _) => Box::new(BitzSpotWSClient::new_with_options(tx, None, options).await?),
+        ("bybit", MarketType::LinearSwap) => {
+            Box::new(BybitLinearSwapWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("bybit", _) => Box::new(BybitInverseWSClient::new_with_options(tx, None, options).await?),
+        ("coinbase_pro", _) => {
+            Box::new(CoinbaseProWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("deribit", _) => Box::new(DeribitWSClient::new_with_options(tx, None, options).await?),
+        ("dydx", _) => Box::new(DydxSwapWSClient::new_with_options(tx, None, options).await?),
+        ("ftx", _) => Box::new(FtxWSClient::new_with_options(tx, None, options).await?),
+        ("gate", MarketType::Spot) => {
+            Box::new(GateSpotWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("gate", MarketType::InverseSwap) => {
+            Box::new(GateInverseSwapWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("gate", MarketType::LinearSwap) => {
+            Box::new(GateLinearSwapWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("gate", MarketType::InverseFuture) => {
+            Box::new(GateInverseFutureWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("gate", _) => {
+            Box::new(GateLinearFutureWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("huobi", MarketType::Spot) => {
+            Box::new(HuobiSpotWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("huobi", MarketType::InverseFuture) => {
+            Box::new(HuobiFutureWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("huobi", MarketType::LinearSwap) => {
+            Box::new(HuobiLinearSwapWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("huobi", MarketType::InverseSwap) => {
+            Box::new(HuobiInverseSwapWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("huobi", _) => Box::new(HuobiOptionWSClient::new_with_options(tx, None, options).await?),
+        ("kraken", MarketType::Spot) => {
+            Box::new(KrakenSpotWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("kraken", _) => {
+            Box::new(KrakenFuturesWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("kucoin", MarketType::Spot) => {
+            Box::new(KuCoinSpotWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("kucoin", _) => Box::new(KuCoinSwapWSClient::new_with_options(tx, None, options).await?),
+        ("mexc", MarketType::Spot) => {
+            Box::new(MexcSpotWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("mexc", _) => Box::new(MexcSwapWSClient::new_with_options(tx, None, options).await?),
+        ("okx", _) => Box::new(OkxWSClient::new_with_options(tx, None, options).await?),
+        ("zb", MarketType::Spot) => {
+            Box::new(ZbSpotWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("zb", _) => Box::new(ZbSwapWSClient::new_with_options(tx, None, options).await?),
+        ("zbg", MarketType::Spot) => {
+            Box::new(ZbgSpotWSClient::new_with_options(tx, None, options).await?)
+        }
+        ("zbg", _) => Box::new(ZbgSwapWSClient::new_with_options(tx, None, options).await?),
+        _ => unreachable!("{} {} is in SUPPORTED_MARKETS", exchange, market_type),
+    };
+    Ok(ws_client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create_ws_client, is_supported, supported_markets};
+    use crate::WsError;
+    use crypto_market_type::MarketType;
+
+    #[test]
+    fn test_supported_markets() {
+        let markets = supported_markets();
+        assert!(markets.contains(&("binance", MarketType::InverseSwap)));
+        assert!(markets.contains(&("okx", MarketType::EuropeanOption)));
+        assert!(!markets.contains(&("bithumb", MarketType::LinearSwap)));
+        assert!(markets
+            .iter()
+            .all(|(exchange, market_type)| is_supported(exchange, *market_type)));
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_market() {
+        let (tx, _rx) = std::sync::mpsc::channel();
+        for (exchange, market_type) in [
+            ("bithumb", MarketType::LinearSwap),
+            ("dydx", MarketType::Spot),
+            ("unknown", MarketType::Spot),
+        ] {
+            match create_ws_client(exchange, market_type, tx.clone(), Default::default()).await {
+                Err(WsError::UnsupportedMarket {
+                    exchange: x,
+                    market_type: m,
+                }) => {
+                    assert_eq!(exchange, x);
+                    assert_eq!(market_type, m);
+                }
+                _ => panic!("{} {} should be unsupported", exchange, market_type),
+            }
+        }
+    }
+}
@@ -0,0 +1,415 @@
+//! A local websocket server which speaks the protocols of exchanges, for offline tests.
+//!
+//! ```no_run
+//! use crypto_ws_client::{mock::{Frame, MockServer}, OkxWSClient, WSClient};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let frames = vec![Frame::Text(r#"{"arg":{"channel":"trades","instId":"BTC-USDT"},"data":[]}"#.to_string())];
+//!     let server = MockServer::start_with_frames("okx", frames).await.unwrap();
+//!
+//!     let (tx, rx) = std::sync::mpsc::channel();
+//!     let ws_client = OkxWSClient::new(tx, Some(server.url())).await.unwrap();
+//!     ws_client.subscribe_trade(&["BTC-USDT".to_string()]).await;
+//!     tokio::task::spawn(async move { ws_client.run().await });
+//!     println!("{}", rx.recv().unwrap());
+//!
+//!     // the client reconnects and receives the frames again
+//!     server.inject(Frame::Disconnect);
+//! }
+//! ```
+
+use std::{
+    borrow::Cow,
+    io::Write,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use flate2::{
+    write::{DeflateEncoder, GzEncoder},
+    Compression,
+};
+use futures_util::{SinkExt, StreamExt};
+use log::*;
+use serde_json::{json, Value};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::broadcast,
+    task::JoinHandle,
+};
+use tokio_tungstenite::{
+    tungstenite::{
+        protocol::{frame::coding::CloseCode, CloseFrame},
+        Message,
+    },
+    WebSocketStream,
+};
+
+/// A frame sent by `MockServer`, or an action on the connection.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Frame {
+    Text(String),
+    /// A text compressed by gzip and sent as a binary frame, e.g., Huobi
+    Gzip(String),
+    /// A text compressed by raw deflate and sent as a binary frame, e.g., OKX
+    Deflate(String),
+    Binary(Vec<u8>),
+    /// Sends a close frame with the code and reason, then drops the connection
+    Close(u16, String),
+    /// Drops the TCP connection without a close frame
+    Disconnect,
+}
+
+/// Loads recorded frames from a file, one text frame per line, empty lines are skipped.
+pub fn load_frames<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<Frame>> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| Frame::Text(line.to_string()))
+        .collect())
+}
+
+struct State {
+    exchange: String,
+    // replayed to every connection after its first subscribe command
+    frames: Vec<Frame>,
+    injections: broadcast::Sender<Frame>,
+    received: Mutex<Vec<String>>,
+    num_connections: AtomicUsize,
+}
+
+/// A websocket server on localhost which mimics an exchange.
+///
+/// Every connection gets the replies an exchange sends to subscribe commands and pings, and
+/// the recorded frames after its first subscribe command. Frames injected by `inject()` go
+/// to all live connections. The server stops when dropped.
+pub struct MockServer {
+    url: String,
+    state: Arc<State>,
+    handle: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Starts a server which only replies to commands.
+    pub async fn start(exchange: &str) -> std::io::Result<Self> {
+        Self::start_with_frames(exchange, Vec::new()).await
+    }
+
+    /// Starts a server which replays `frames` after the first subscribe command of every
+    /// connection.
+    pub async fn start_with_frames(exchange: &str, frames: Vec<Frame>) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("ws://{}", listener.local_addr()?);
+        let (injections, _) = broadcast::channel(64);
+        let state = Arc::new(State {
+            exchange: exchange.to_string(),
+            frames,
+            injections,
+            received: Mutex::new(Vec::new()),
+            num_connections: AtomicUsize::new(0),
+        });
+        let state_clone = state.clone();
+        let handle = tokio::task::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::task::spawn(serve_connection(stream, state_clone.clone()));
+            }
+        });
+        Ok(MockServer { url, state, handle })
+    }
+
+    /// The url to pass to the `url` parameter of clients.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Sends a frame to all live connections.
+    pub fn inject(&self, frame: Frame) {
+        let _ = self.state.injections.send(frame);
+    }
+
+    /// Text commands received from all connections so far, in order.
+    pub fn received(&self) -> Vec<String> {
+        self.state.received.lock().unwrap().clone()
+    }
+
+    /// Number of connections accepted so far.
+    pub fn num_connections(&self) -> usize {
+        self.state.num_connections.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn serve_connection(stream: TcpStream, state: Arc<State>) {
+    let mut ws_stream = match tokio_tungstenite::accept_async(stream).await {
+        Ok(ws_stream) => ws_stream,
+        Err(err) => {
+            warn!("Failed to accept a websocket connection, error: {}", err);
+            return;
+        }
+    };
+    state.num_connections.fetch_add(1, Ordering::AcqRel);
+    let mut injections = state.injections.subscribe();
+
+    for reply in on_connect(&state.exchange) {
+        let frame = encode_reply(&state.exchange, reply);
+        if !send_frame(&mut ws_stream, frame).await {
+            return;
+        }
+    }
+
+    let mut replayed = false;
+    loop {
+        tokio::select! {
+            msg = ws_stream.next() => {
+                let txt = match msg {
+                    Some(Ok(Message::Text(txt))) => txt,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    Some(Ok(_)) => continue,
+                };
+                state.received.lock().unwrap().push(txt.clone());
+                let (replies, is_subscribe) = reply(&state.exchange, &txt);
+                for reply in replies {
+                    let frame = encode_reply(&state.exchange, reply);
+                    if !send_frame(&mut ws_stream, frame).await {
+                        return;
+                    }
+                }
+                if is_subscribe && !replayed {
+                    replayed = true;
+                    for frame in state.frames.iter() {
+                        if !send_frame(&mut ws_stream, frame.clone()).await {
+                            return;
+                        }
+                    }
+                }
+            }
+            frame = injections.recv() => {
+                match frame {
+                    Ok(frame) => {
+                        if !send_frame(&mut ws_stream, frame).await {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        }
+    }
+}
+
+// Returns false if the connection is closed.
+async fn send_frame(ws_stream: &mut WebSocketStream<TcpStream>, frame: Frame) -> bool {
+    let msg = match frame {
+        Frame::Text(txt) => Message::Text(txt),
+        Frame::Gzip(txt) => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(txt.as_bytes()).unwrap();
+            Message::Binary(encoder.finish().unwrap())
+        }
+        Frame::Deflate(txt) => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(txt.as_bytes()).unwrap();
+            Message::Binary(encoder.finish().unwrap())
+        }
+        Frame::Binary(bytes) => Message::Binary(bytes),
+        Frame::Close(code, reason) => {
+            let _ = ws_stream
+                .close(Some(CloseFrame {
+                    code: CloseCode::from(code),
+                    reason: Cow::Owned(reason),
+                }))
+                .await;
+            return false;
+        }
+        Frame::Disconnect => return false,
+    };
+    ws_stream.send(msg).await.is_ok()
+}
+
+// Huobi compresses every frame by gzip.
+fn encode_reply(exchange: &str, reply: String) -> Frame {
+    if exchange == "huobi" {
+        Frame::Gzip(reply)
+    } else {
+        Frame::Text(reply)
+    }
+}
+
+// Frames sent by the exchange once connected.
+fn on_connect(exchange: &str) -> Vec<String> {
+    match exchange {
+        "kucoin" => vec![json!({"id": "mock", "type": "welcome"}).to_string()],
+        _ => Vec::new(),
+    }
+}
+
+// Returns the replies to a command, and whether it's a subscribe command.
+fn reply(exchange: &str, txt: &str) -> (Vec<String>, bool) {
+    if txt == "ping" {
+        return (vec!["pong".to_string()], false);
+    }
+    let obj = match serde_json::from_str::<Value>(txt) {
+        Ok(Value::Object(obj)) => obj,
+        // Assume the rest are subscribe commands of exchanges not modelled below
+        _ => return (Vec::new(), true),
+    };
+    let field = |name: &str| obj.get(name).and_then(|x| x.as_str()).unwrap_or_default();
+    match exchange {
+        "binance" => {
+            let id = obj.get("id").cloned().unwrap_or(Value::Null);
+            (
+                vec![json!({"result": null, "id": id}).to_string()],
+                field("method") == "SUBSCRIBE",
+            )
+        }
+        "bitmex" => {
+            let op = field("op");
+            let replies = obj
+                .get("args")
+                .and_then(|x| x.as_array())
+                .map(|args| {
+                    args.iter()
+                        .map(|arg| {
+                            let mut resp = json!({"success": true, "request": obj.clone()});
+                            resp[op] = arg.clone();
+                            resp.to_string()
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            (replies, op == "subscribe")
+        }
+        "bybit" => {
+            let op = field("op");
+            let ret_msg = if op == "ping" { "pong" } else { "" };
+            (
+                vec![json!({
+                    "success": true,
+                    "ret_msg": ret_msg,
+                    "conn_id": "mock",
+                    "request": obj.clone(),
+                })
+                .to_string()],
+                op == "subscribe",
+            )
+        }
+        "deribit" => {
+            let method = field("method");
+            let result = match method {
+                "public/subscribe"
+                | "private/subscribe"
+                | "public/unsubscribe"
+                | "private/unsubscribe" => obj
+                    .get("params")
+                    .and_then(|x| x.get("channels"))
+                    .cloned()
+                    .unwrap_or(Value::Null),
+                "public/auth" => json!({"access_token": "mock", "expires_in": 31536000}),
+                "public/test" => json!({"version": "mock"}),
+                _ => json!("ok"),
+            };
+            let mut resp = json!({"jsonrpc": "2.0", "result": result});
+            if let Some(id) = obj.get("id") {
+                resp["id"] = id.clone();
+            }
+            (vec![resp.to_string()], method.ends_with("/subscribe"))
+        }
+        "huobi" => {
+            if obj.contains_key("pong") {
+                (Vec::new(), false)
+            } else if obj.contains_key("sub") || obj.contains_key("unsub") {
+                let (key, topic) = if obj.contains_key("sub") {
+                    ("subbed", field("sub"))
+                } else {
+                    ("unsubbed", field("unsub"))
+                };
+                let mut resp = json!({"id": field("id"), "status": "ok", "ts": 0});
+                resp[key] = json!(topic);
+                (vec![resp.to_string()], key == "subbed")
+            } else {
+                (Vec::new(), false)
+            }
+        }
+        "kucoin" => {
+            let id = obj.get("id").cloned().unwrap_or(Value::Null);
+            match field("type") {
+                "ping" => (vec![json!({"id": id, "type": "pong"}).to_string()], false),
+                "subscribe" => (vec![json!({"id": id, "type": "ack"}).to_string()], true),
+                "unsubscribe" => (vec![json!({"id": id, "type": "ack"}).to_string()], false),
+                _ => (Vec::new(), false),
+            }
+        }
+        "okx" => match field("op") {
+            "subscribe" | "unsubscribe" => {
+                let event = field("op");
+                let replies = obj
+                    .get("args")
+                    .and_then(|x| x.as_array())
+                    .map(|args| {
+                        args.iter()
+                            .map(|arg| json!({"event": event, "arg": arg}).to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                (replies, event == "subscribe")
+            }
+            "login" => (
+                vec![json!({"event": "login", "code": "0", "msg": ""}).to_string()],
+                false,
+            ),
+            _ => (Vec::new(), false),
+        },
+        _ => (Vec::new(), true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::reply;
+
+    #[test]
+    fn test_reply_binance() {
+        let (replies, is_subscribe) = reply(
+            "binance",
+            r#"{"id":9527,"method":"SUBSCRIBE","params":["btcusdt@aggTrade"]}"#,
+        );
+        assert_eq!(vec![r#"{"id":9527,"result":null}"#], replies);
+        assert!(is_subscribe);
+    }
+
+    #[test]
+    fn test_reply_okx() {
+        let (replies, is_subscribe) = reply(
+            "okx",
+            r#"{"op":"subscribe","args":[{"channel":"trades","instId":"BTC-USDT"},{"channel":"trades","instId":"ETH-USDT"}]}"#,
+        );
+        assert_eq!(
+            vec![
+                r#"{"arg":{"channel":"trades","instId":"BTC-USDT"},"event":"subscribe"}"#,
+                r#"{"arg":{"channel":"trades","instId":"ETH-USDT"},"event":"subscribe"}"#,
+            ],
+            replies
+        );
+        assert!(is_subscribe);
+        assert_eq!((vec!["pong".to_string()], false), reply("okx", "ping"));
+    }
+
+    #[test]
+    fn test_reply_kucoin() {
+        let (replies, is_subscribe) = reply("kucoin", r#"{"type":"ping","id":"crypto-ws-client"}"#);
+        assert_eq!(vec![r#"{"id":"crypto-ws-client","type":"pong"}"#], replies);
+        assert!(!is_subscribe);
+    }
+}
@@ -0,0 +1,747 @@
+use std::{
+    collections::HashSet,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use crypto_market_type::MarketType;
+use log::*;
+use tokio::{sync::Notify, task::JoinHandle};
+
+use crate::{
+    common::{
+        message_sender::MessageSender, metrics::MetricsSnapshot, options::WSClientOptions,
+        ws_client::WSClient,
+    },
+    error::{Result, WsError},
+    factory::{create_ws_client, is_supported},
+};
+
+type Client = Arc<dyn WSClient + Send + Sync>;
+
+type Connector = Box<
+    dyn Fn() -> Pin<Box<dyn Future<Output = Result<Box<dyn WSClient + Send + Sync>>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// The maximum number of topics per connection of an exchange, `usize::MAX` means unlimited.
+pub fn get_max_topics_per_connection(exchange: &str, market_type: MarketType) -> usize {
+    match exchange {
+        // https://binance-docs.github.io/apidocs/spot/en/#websocket-limits
+        "binance" if market_type == MarketType::Spot => 1024,
+        // https://binance-docs.github.io/apidocs/futures/en/#websocket-market-streams
+        "binance" => 200,
+        // https://docs.bitfinex.com/docs/ws-general#subscribe-to-channels
+        "bitfinex" => 30,
+        // https://docs.kucoin.com/#topic-subscription-limit
+        "kucoin" => 300,
+        // okx spot l2_event throws many ResetWithoutClosingHandshake errors beyond this
+        "okx" => 256,
+        _ => usize::MAX, // usize::MAX means unlimited
+    }
+}
+
+// A topic subscribed via one of the subscribe functions of WSClient
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Topic {
+    Trade(String),
+    Bbo(String),
+    Orderbook(String),
+    OrderbookTopK(String),
+    L3Orderbook(String),
+    Ticker(String),
+    Candlestick(String, usize),
+    Raw(String, String),
+}
+
+struct Connection {
+    client: Client,
+    topics: HashSet<Topic>,
+}
+
+/// A pool of websocket connections to one market, which spreads topics across connections.
+///
+/// Each connection holds at most `get_max_topics_per_connection()` topics, new connections
+/// are opened as topics grow, and connections left without topics by `unsubscribe()` are
+/// closed. All connections send messages to the same `tx`.
+///
+/// Only topics subscribed by `subscribe()` and `subscribe_candlestick()` can be unsubscribed,
+/// because `WSClient` has no counterparts of other high-level subscribe functions such as
+/// `subscribe_trade()`.
+pub struct WSClientPool {
+    exchange: String,
+    market_type: MarketType,
+    max_topics_per_connection: usize,
+    connector: Connector,
+    // serializes subscribe() and unsubscribe(), which may open and close connections
+    op_lock: tokio::sync::Mutex<()>,
+    connections: Mutex<Vec<Connection>>,
+    handles: Mutex<Vec<JoinHandle<()>>>,
+    running: AtomicBool,
+    closed: AtomicBool,
+    close_notify: Notify,
+}
+
+impl WSClientPool {
+    /// Creates a pool, connections are opened lazily by subscribe functions.
+    ///
+    /// Returns `WsError::UnsupportedMarket` if `create_ws_client()` doesn't support the market.
+    pub fn new(
+        exchange: &str,
+        market_type: MarketType,
+        tx: impl Into<MessageSender>,
+        options: WSClientOptions,
+    ) -> Result<Self> {
+        if !is_supported(exchange, market_type) {
+            return Err(WsError::UnsupportedMarket {
+                exchange: exchange.to_string(),
+                market_type,
+            });
+        }
+        let tx = tx.into();
+        let exchange_clone = exchange.to_string();
+        let connector: Connector = Box::new(move || {
+            let exchange = exchange_clone.clone();
+            let tx = tx.clone();
+            let options = options.clone();
+            Box::pin(async move { create_ws_client(&exchange, market_type, tx, options).await })
+        });
+        Ok(Self::with_connector(exchange, market_type, connector))
+    }
+
+    fn with_connector(exchange: &str, market_type: MarketType, connector: Connector) -> Self {
+        WSClientPool {
+            exchange: exchange.to_string(),
+            market_type,
+            max_topics_per_connection: get_max_topics_per_connection(exchange, market_type),
+            connector,
+            op_lock: tokio::sync::Mutex::new(()),
+            connections: Mutex::new(Vec::new()),
+            handles: Mutex::new(Vec::new()),
+            running: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            close_notify: Notify::new(),
+        }
+    }
+
+    /// Overrides the maximum number of topics per connection, which must be positive.
+    pub fn with_max_topics_per_connection(mut self, max_topics: usize) -> Self {
+        assert!(max_topics > 0);
+        self.max_topics_per_connection = max_topics;
+        self
+    }
+
+    /// The number of open connections.
+    pub fn num_connections(&self) -> usize {
+        self.connections.lock().unwrap().len()
+    }
+
+    /// The number of topics of every open connection.
+    pub fn topics_per_connection(&self) -> Vec<usize> {
+        self.connections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|conn| conn.topics.len())
+            .collect()
+    }
+
+    // Retries until connected, returns None if the pool was closed.
+    async fn connect(&self) -> Option<Client> {
+        let mut backoff_factor = 1;
+        loop {
+            if self.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            match (self.connector)().await {
+                Ok(ws_client) => return Some(Arc::from(ws_client)),
+                Err(err) => {
+                    let seconds = match err {
+                        WsError::RateLimited {
+                            retry_after: Some(seconds),
+                            ..
+                        } => seconds,
+                        _ => backoff_factor,
+                    };
+                    error!(
+                        "{} {} {}, retrying in {} seconds",
+                        self.exchange, self.market_type, err, seconds
+                    );
+                    tokio::time::sleep(Duration::from_secs(seconds)).await;
+                    backoff_factor = std::cmp::min(backoff_factor * 2, 64);
+                }
+            }
+        }
+    }
+
+    // Must be called with the connections lock held, so that run() doesn't miss it.
+    fn spawn_run(&self, ws_client: Client) {
+        let handle = tokio::task::spawn(async move { ws_client.run().await });
+        self.handles.lock().unwrap().push(handle);
+    }
+
+    async fn subscribe_topics(&self, topics: Vec<Topic>) {
+        let _guard = self.op_lock.lock().await;
+        if self.closed.load(Ordering::Acquire) {
+            return;
+        }
+        let mut assignments: Vec<(Client, Vec<Topic>)> = Vec::new();
+        let mut pending: Vec<Topic> = Vec::new();
+        {
+            let mut connections = self.connections.lock().unwrap();
+            let mut seen: HashSet<Topic> = HashSet::new();
+            for topic in topics {
+                if !connections.iter().any(|conn| conn.topics.contains(&topic))
+                    && seen.insert(topic.clone())
+                {
+                    pending.push(topic);
+                }
+            }
+            // fill existing connections first
+            for conn in connections.iter_mut() {
+                let spare = self.max_topics_per_connection - conn.topics.len();
+                if pending.is_empty() {
+                    break;
+                } else if spare == 0 {
+                    continue;
+                }
+                let chunk: Vec<Topic> = pending
+                    .drain(..std::cmp::min(spare, pending.len()))
+                    .collect();
+                conn.topics.extend(chunk.iter().cloned());
+                assignments.push((conn.client.clone(), chunk));
+            }
+        }
+        for (ws_client, chunk) in assignments {
+            subscribe_to(ws_client.as_ref(), &chunk).await;
+        }
+
+        while !pending.is_empty() {
+            let ws_client = match self.connect().await {
+                Some(ws_client) => ws_client,
+                None => return,
+            };
+            let chunk: Vec<Topic> = pending
+                .drain(..std::cmp::min(self.max_topics_per_connection, pending.len()))
+                .collect();
+            subscribe_to(ws_client.as_ref(), &chunk).await;
+            {
+                let mut connections = self.connections.lock().unwrap();
+                connections.push(Connection {
+                    client: ws_client.clone(),
+                    topics: chunk.into_iter().collect(),
+                });
+                if self.running.load(Ordering::Acquire) {
+                    self.spawn_run(ws_client.clone());
+                }
+            }
+            debug!(
+                "{} {} opened connection #{}",
+                self.exchange,
+                self.market_type,
+                self.num_connections()
+            );
+            // close() may have missed this connection
+            if self.closed.load(Ordering::Acquire) {
+                ws_client.close();
+            }
+        }
+    }
+
+    async fn unsubscribe_topics(&self, topics: Vec<Topic>) {
+        let _guard = self.op_lock.lock().await;
+        let mut assignments: Vec<(Client, Vec<Topic>)> = Vec::new();
+        let mut empty_clients: Vec<Client> = Vec::new();
+        {
+            let mut connections = self.connections.lock().unwrap();
+            for conn in connections.iter_mut() {
+                let chunk: Vec<Topic> = topics
+                    .iter()
+                    .filter(|topic| conn.topics.remove(topic))
+                    .cloned()
+                    .collect();
+                if conn.topics.is_empty() {
+                    empty_clients.push(conn.client.clone());
+                } else if !chunk.is_empty() {
+                    assignments.push((conn.client.clone(), chunk));
+                }
+            }
+            connections.retain(|conn| !conn.topics.is_empty());
+        }
+        for (ws_client, chunk) in assignments {
+            unsubscribe_from(ws_client.as_ref(), &chunk).await;
+        }
+        for ws_client in empty_clients {
+            debug!(
+                "{} {} closed a connection without topics",
+                self.exchange, self.market_type
+            );
+            ws_client.close();
+        }
+    }
+}
+
+// Unsubscribes topics subscribed by `subscribe()` or `subscribe_candlestick()`.
+async fn unsubscribe_from(ws_client: &(dyn WSClient + Send + Sync), topics: &[Topic]) {
+    let raw_topics: Vec<(String, String)> = topics
+        .iter()
+        .filter_map(|topic| match topic {
+            Topic::Raw(channel, symbol) => Some((channel.clone(), symbol.clone())),
+            _ => None,
+        })
+        .collect();
+    let symbol_interval_list: Vec<(String, usize)> = topics
+        .iter()
+        .filter_map(|topic| match topic {
+            Topic::Candlestick(symbol, interval) => Some((symbol.clone(), *interval)),
+            _ => None,
+        })
+        .collect();
+    if !raw_topics.is_empty() {
+        ws_client.unsubscribe(&raw_topics).await;
+    }
+    if !symbol_interval_list.is_empty() {
+        ws_client
+            .unsubscribe_candlestick(&symbol_interval_list)
+            .await;
+    }
+}
+
+// `topics` are of the same kind
+async fn subscribe_to(ws_client: &(dyn WSClient + Send + Sync), topics: &[Topic]) {
+    let symbols: Vec<String> = topics
+        .iter()
+        .filter_map(|topic| match topic {
+            Topic::Trade(symbol)
+            | Topic::Bbo(symbol)
+            | Topic::Orderbook(symbol)
+            | Topic::OrderbookTopK(symbol)
+            | Topic::L3Orderbook(symbol)
+            | Topic::Ticker(symbol) => Some(symbol.clone()),
+            _ => None,
+        })
+        .collect();
+    match topics.first() {
+        Some(Topic::Trade(_)) => ws_client.subscribe_trade(&symbols).await,
+        Some(Topic::Bbo(_)) => ws_client.subscribe_bbo(&symbols).await,
+        Some(Topic::Orderbook(_)) => ws_client.subscribe_orderbook(&symbols).await,
+        Some(Topic::OrderbookTopK(_)) => ws_client.subscribe_orderbook_topk(&symbols).await,
+        Some(Topic::L3Orderbook(_)) => ws_client.subscribe_l3_orderbook(&symbols).await,
+        Some(Topic::Ticker(_)) => ws_client.subscribe_ticker(&symbols).await,
+        Some(Topic::Candlestick(..)) => {
+            let symbol_interval_list: Vec<(String, usize)> = topics
+                .iter()
+                .filter_map(|topic| match topic {
+                    Topic::Candlestick(symbol, interval) => Some((symbol.clone(), *interval)),
+                    _ => None,
+                })
+                .collect();
+            ws_client.subscribe_candlestick(&symbol_interval_list).await
+        }
+        Some(Topic::Raw(..)) => {
+            let raw_topics: Vec<(String, String)> = topics
+                .iter()
+                .filter_map(|topic| match topic {
+                    Topic::Raw(channel, symbol) => Some((channel.clone(), symbol.clone())),
+                    _ => None,
+                })
+                .collect();
+            ws_client.subscribe(&raw_topics).await
+        }
+        None => (),
+    }
+}
+
+fn to_topics(symbols: &[String], f: fn(String) -> Topic) -> Vec<Topic> {
+    symbols.iter().cloned().map(f).collect()
+}
+
+#[async_trait]
+impl WSClient for WSClientPool {
+    async fn subscribe_trade(&self, symbols: &[String]) {
+        self.subscribe_topics(to_topics(symbols, Topic::Trade))
+            .await
+    }
+
+    async fn subscribe_bbo(&self, symbols: &[String]) {
+        self.subscribe_topics(to_topics(symbols, Topic::Bbo)).await
+    }
+
+    async fn subscribe_orderbook(&self, symbols: &[String]) {
+        self.subscribe_topics(to_topics(symbols, Topic::Orderbook))
+            .await
+    }
+
+    async fn subscribe_orderbook_topk(&self, symbols: &[String]) {
+        self.subscribe_topics(to_topics(symbols, Topic::OrderbookTopK))
+            .await
+    }
+
+    async fn subscribe_l3_orderbook(&self, symbols: &[String]) {
+        self.subscribe_topics(to_topics(symbols, Topic::L3Orderbook))
+            .await
+    }
+
+    async fn subscribe_ticker(&self, symbols: &[String]) {
+        self.subscribe_topics(to_topics(symbols, Topic::Ticker))
+            .await
+    }
+
+    async fn subscribe_candlestick(&self, symbol_interval_list: &[(String, usize)]) {
+        let topics = symbol_interval_list
+            .iter()
+            .map(|(symbol, interval)| Topic::Candlestick(symbol.clone(), *interval))
+            .collect();
+        self.subscribe_topics(topics).await
+    }
+
+    async fn subscribe(&self, topics: &[(String, String)]) {
+        let topics = topics
+            .iter()
+            .map(|(channel, symbol)| Topic::Raw(channel.clone(), symbol.clone()))
+            .collect();
+        self.subscribe_topics(topics).await
+    }
+
+    async fn unsubscribe(&self, topics: &[(String, String)]) {
+        let topics = topics
+            .iter()
+            .map(|(channel, symbol)| Topic::Raw(channel.clone(), symbol.clone()))
+            .collect();
+        self.unsubscribe_topics(topics).await
+    }
+
+    async fn unsubscribe_candlestick(&self, symbol_interval_list: &[(String, usize)]) {
+        let topics = symbol_interval_list
+            .iter()
+            .map(|(symbol, interval)| Topic::Candlestick(symbol.clone(), *interval))
+            .collect();
+        self.unsubscribe_topics(topics).await
+    }
+
+    /// Sends commands via every connection.
+    async fn send(&self, commands: &[String]) {
+        let clients: Vec<Client> = self
+            .connections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|conn| conn.client.clone())
+            .collect();
+        for ws_client in clients {
+            ws_client.send(commands).await;
+        }
+    }
+
+    /// Runs all connections, including those opened later, until `close()` is called.
+    async fn run(&self) {
+        {
+            let connections = self.connections.lock().unwrap();
+            if self.running.swap(true, Ordering::AcqRel) {
+                error!(
+                    "{} {} pool is already running",
+                    self.exchange, self.market_type
+                );
+                return;
+            }
+            for conn in connections.iter() {
+                self.spawn_run(conn.client.clone());
+            }
+        }
+        if !self.closed.load(Ordering::Acquire) {
+            self.close_notify.notified().await;
+        }
+        let handles: Vec<JoinHandle<()>> = std::mem::take(&mut *self.handles.lock().unwrap());
+        for handle in handles {
+            let _ = handle.await;
+        }
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        for conn in self.connections.lock().unwrap().iter() {
+            conn.client.close();
+        }
+        self.close_notify.notify_one();
+    }
+
+    async fn shutdown(&self) {
+        // wait for subscribe functions which may be opening connections
+        let _guard = self.op_lock.lock().await;
+        self.closed.store(true, Ordering::Release);
+        let clients: Vec<Client> = self
+            .connections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|conn| conn.client.clone())
+            .collect();
+        futures_util::future::join_all(clients.iter().map(|ws_client| ws_client.shutdown())).await;
+        self.close_notify.notify_one();
+        let handles: Vec<JoinHandle<()>> = std::mem::take(&mut *self.handles.lock().unwrap());
+        for handle in handles {
+            let _ = handle.await;
+        }
+    }
+
+    fn metrics(&self) -> Vec<MetricsSnapshot> {
+        self.connections
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|conn| conn.client.metrics())
+            .collect()
+    }
+
+    fn topics(&self) -> Vec<(String, String)> {
+        self.connections
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|conn| conn.client.topics())
+            .collect()
+    }
+
+    fn candlesticks(&self) -> Vec<(String, usize)> {
+        self.connections
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|conn| conn.client.candlesticks())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Connector, WSClientPool};
+    use crate::{
+        common::{metrics::MetricsSnapshot, ws_client::WSClient},
+        error::WsError,
+    };
+    use async_trait::async_trait;
+    use crypto_market_type::MarketType;
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+    use tokio::sync::Notify;
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    // Records calls as "<connection id> <function> <args>"
+    struct FakeClient {
+        id: usize,
+        log: Log,
+        closed: AtomicBool,
+        close_notify: Notify,
+    }
+
+    impl FakeClient {
+        fn record(&self, call: &str, args: &[String]) {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{} {} {}", self.id, call, args.join(",")));
+        }
+    }
+
+    #[async_trait]
+    impl WSClient for FakeClient {
+        async fn subscribe_trade(&self, symbols: &[String]) {
+            self.record("trade", symbols);
+        }
+        async fn subscribe_bbo(&self, symbols: &[String]) {
+            self.record("bbo", symbols);
+        }
+        async fn subscribe_orderbook(&self, symbols: &[String]) {
+            self.record("orderbook", symbols);
+        }
+        async fn subscribe_orderbook_topk(&self, symbols: &[String]) {
+            self.record("orderbook_topk", symbols);
+        }
+        async fn subscribe_l3_orderbook(&self, symbols: &[String]) {
+            self.record("l3_orderbook", symbols);
+        }
+        async fn subscribe_ticker(&self, symbols: &[String]) {
+            self.record("ticker", symbols);
+        }
+        async fn subscribe_candlestick(&self, symbol_interval_list: &[(String, usize)]) {
+            let args: Vec<String> = symbol_interval_list
+                .iter()
+                .map(|(symbol, interval)| format!("{}:{}", symbol, interval))
+                .collect();
+            self.record("candlestick", &args);
+        }
+        async fn unsubscribe_candlestick(&self, symbol_interval_list: &[(String, usize)]) {
+            let args: Vec<String> = symbol_interval_list
+                .iter()
+                .map(|(symbol, interval)| format!("{}:{}", symbol, interval))
+                .collect();
+            self.record("unsubscribe_candlestick", &args);
+        }
+        async fn subscribe(&self, topics: &[(String, String)]) {
+            let args: Vec<String> = topics.iter().map(|t| format!("{}:{}", t.0, t.1)).collect();
+            self.record("subscribe", &args);
+        }
+        async fn unsubscribe(&self, topics: &[(String, String)]) {
+            let args: Vec<String> = topics.iter().map(|t| format!("{}:{}", t.0, t.1)).collect();
+            self.record("unsubscribe", &args);
+        }
+        async fn send(&self, commands: &[String]) {
+            self.record("send", commands);
+        }
+        async fn run(&self) {
+            if !self.closed.load(Ordering::Acquire) {
+                self.close_notify.notified().await;
+            }
+        }
+        fn close(&self) {
+            self.record("close", &[]);
+            self.closed.store(true, Ordering::Release);
+            self.close_notify.notify_one();
+        }
+        async fn shutdown(&self) {
+            self.close();
+        }
+        fn metrics(&self) -> Vec<MetricsSnapshot> {
+            Vec::new()
+        }
+        fn topics(&self) -> Vec<(String, String)> {
+            Vec::new()
+        }
+        fn candlesticks(&self) -> Vec<(String, usize)> {
+            Vec::new()
+        }
+    }
+
+    fn fake_pool(max_topics: usize) -> (WSClientPool, Log) {
+        let log: Log = Arc::new(Mutex::new(Vec::new()));
+        let log_clone = log.clone();
+        let next_id = Arc::new(AtomicUsize::new(0));
+        let connector: Connector = Box::new(move || {
+            let ws_client: Box<dyn WSClient + Send + Sync> = Box::new(FakeClient {
+                id: next_id.fetch_add(1, Ordering::SeqCst),
+                log: log_clone.clone(),
+                closed: AtomicBool::new(false),
+                close_notify: Notify::new(),
+            });
+            Box::pin(async move { Ok(ws_client) })
+        });
+        let pool = WSClientPool::with_connector("binance", MarketType::Spot, connector)
+            .with_max_topics_per_connection(max_topics);
+        (pool, log)
+    }
+
+    fn symbols(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn topics(list: &[&str]) -> Vec<(String, String)> {
+        list.iter()
+            .map(|s| ("trade".to_string(), s.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_shard_topics() {
+        let (pool, log) = fake_pool(2);
+        pool.subscribe_trade(&symbols(&["a", "b", "c"])).await;
+        assert_eq!(vec![2, 1], pool.topics_per_connection());
+        // duplicated topics are skipped, then the last connection is filled up
+        pool.subscribe_trade(&symbols(&["a", "d", "e"])).await;
+        assert_eq!(vec![2, 2, 1], pool.topics_per_connection());
+        // the same symbol of another channel is a different topic
+        pool.subscribe_bbo(&symbols(&["a"])).await;
+        assert_eq!(vec![2, 2, 2], pool.topics_per_connection());
+        assert_eq!(
+            vec![
+                "0 trade a,b",
+                "1 trade c",
+                "1 trade d",
+                "2 trade e",
+                "2 bbo a"
+            ],
+            *log.lock().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_close_empty_connections() {
+        let (pool, log) = fake_pool(2);
+        pool.subscribe(&topics(&["a", "b", "c"])).await;
+        assert_eq!(2, pool.num_connections());
+        pool.unsubscribe(&topics(&["a", "c"])).await;
+        assert_eq!(vec![1], pool.topics_per_connection());
+        assert_eq!(
+            vec![
+                "0 subscribe trade:a,trade:b",
+                "1 subscribe trade:c",
+                "0 unsubscribe trade:a",
+                "1 close ",
+            ],
+            *log.lock().unwrap()
+        );
+        // freed capacity is reused before opening new connections
+        pool.subscribe(&topics(&["d"])).await;
+        assert_eq!(vec![2], pool.topics_per_connection());
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_candlestick() {
+        let (pool, log) = fake_pool(2);
+        pool.subscribe_candlestick(&[("a".to_string(), 60), ("a".to_string(), 300)])
+            .await;
+        pool.subscribe(&topics(&["a"])).await;
+        // topics of another kind are not unsubscribed
+        pool.unsubscribe(&topics(&["b"])).await;
+        pool.unsubscribe_candlestick(&[("a".to_string(), 60)]).await;
+        assert_eq!(vec![1, 1], pool.topics_per_connection());
+        assert_eq!(
+            vec![
+                "0 candlestick a:60,a:300",
+                "1 subscribe trade:a",
+                "0 unsubscribe_candlestick a:60",
+            ],
+            *log.lock().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_and_close() {
+        let (pool, log) = fake_pool(1);
+        let pool = Arc::new(pool);
+        pool.subscribe_candlestick(&[("a".to_string(), 60)]).await;
+        let pool_clone = pool.clone();
+        let handle = tokio::task::spawn(async move { pool_clone.run().await });
+        // connections opened while running are run too
+        pool.subscribe_candlestick(&[("b".to_string(), 60)]).await;
+        pool.close();
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap();
+        let log = log.lock().unwrap();
+        assert!(log.contains(&"0 close ".to_string()));
+        assert!(log.contains(&"1 close ".to_string()));
+    }
+
+    #[test]
+    fn test_unsupported_market() {
+        let (tx, _rx) = std::sync::mpsc::channel();
+        assert!(matches!(
+            WSClientPool::new("dydx", MarketType::Spot, tx, Default::default()),
+            Err(WsError::UnsupportedMarket { .. })
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use crypto_ws_client::{
+    BinanceSpotWSClient, OkxWSClient, WSClient, WSClientEvent, WSClientOptions,
+};
+use futures_util::SinkExt;
+use tokio::sync::mpsc::Receiver;
+use tokio_tungstenite::tungstenite::Message;
+
+async fn next_event(events: &mut Receiver<WSClientEvent>) -> WSClientEvent {
+    tokio::time::timeout(TIMEOUT, events.recv())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn okx_subscription_status() {
+    let url = start_ws_server(|_, mut ws_stream| async move {
+        let _ = next_text(&mut ws_stream).await;
+        for resp in [
+            r#"{"event":"subscribe","arg":{"channel":"trades","instId":"BTC-USDT"}}"#,
+            r#"{"event":"error","code":"60018","msg":"Wrong URL or channel:trades,instId:BTC-XXX doesn't exist."}"#,
+        ] {
+            ws_stream.send(Message::Text(resp.to_string())).await.unwrap();
+        }
+        // never respond to ETH-USDT
+        tokio::time::sleep(TIMEOUT).await;
+    })
+    .await;
+
+    let (tx, _rx) = tokio::sync::mpsc::channel(16);
+    let (events_tx, mut events) = tokio::sync::mpsc::channel(16);
+    let options = WSClientOptions {
+        events: Some(events_tx),
+        ack_timeout: Some(Duration::from_secs(2)),
+        ..Default::default()
+    };
+    let ws_client = OkxWSClient::new_with_options(tx, Some(&url), options)
+        .await
+        .unwrap();
+    let topics: Vec<(String, String)> = ["BTC-USDT", "BTC-XXX", "ETH-USDT"]
+        .iter()
+        .map(|symbol| ("trades".to_string(), symbol.to_string()))
+        .collect();
+    let ws_client = run_client(ws_client, &topics).await;
+
+    match next_event(&mut events).await {
+        WSClientEvent::Subscribed { raw_topics } => {
+            assert_eq!(vec!["channel:trades,instId:BTC-USDT"], raw_topics)
+        }
+        event => panic!("Unexpected event {:?}", event),
+    }
+    match next_event(&mut events).await {
+        WSClientEvent::SubscribeFailed { raw_topics, reason } => {
+            assert_eq!(vec!["channel:trades,instId:BTC-XXX"], raw_topics);
+            assert!(reason.starts_with("60018"));
+        }
+        event => panic!("Unexpected event {:?}", event),
+    }
+    match next_event(&mut events).await {
+        WSClientEvent::SubscribeTimeout { raw_topics } => {
+            assert_eq!(vec!["channel:trades,instId:ETH-USDT"], raw_topics)
+        }
+        event => panic!("Unexpected event {:?}", event),
+    }
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn binance_subscription_status() {
+    let url = start_ws_server(|_, mut ws_stream| async move {
+        let _ = next_text(&mut ws_stream).await;
+        ws_stream
+            .send(Message::Text(r#"{"result":null,"id":9527}"#.to_string()))
+            .await
+            .unwrap();
+        let _ = next_text(&mut ws_stream).await;
+        ws_stream
+            .send(Message::Text(
+                r#"{"error":{"code":2,"msg":"Invalid request"},"id":9527}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        tokio::time::sleep(TIMEOUT).await;
+    })
+    .await;
+
+    let (tx, _rx) = tokio::sync::mpsc::channel(16);
+    let (events_tx, mut events) = tokio::sync::mpsc::channel(16);
+    let options = WSClientOptions {
+        events: Some(events_tx),
+        ..Default::default()
+    };
+    let ws_client = BinanceSpotWSClient::new_with_options(tx, Some(&url), options)
+        .await
+        .unwrap();
+    let ws_client = run_client(
+        ws_client,
+        &[("aggTrade".to_string(), "BTCUSDT".to_string())],
+    )
+    .await;
+    ws_client
+        .subscribe(&[("aggTrade".to_string(), "XXX".to_string())])
+        .await;
+
+    match next_event(&mut events).await {
+        WSClientEvent::Subscribed { raw_topics } => {
+            assert_eq!(vec!["btcusdt@aggTrade"], raw_topics)
+        }
+        event => panic!("Unexpected event {:?}", event),
+    }
+    match next_event(&mut events).await {
+        WSClientEvent::SubscribeFailed { raw_topics, reason } => {
+            assert_eq!(vec!["xxx@aggTrade"], raw_topics);
+            assert_eq!("Invalid request", reason);
+        }
+        event => panic!("Unexpected event {:?}", event),
+    }
+    ws_client.close();
+}
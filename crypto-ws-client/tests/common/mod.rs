@@ -0,0 +1,80 @@
+// Mock servers shared by integration tests
+#![allow(dead_code)]
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use crypto_ws_client::WSClient;
+use futures_util::StreamExt;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
+
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+// Starts a websocket server which calls `on_connection` with the index and stream of every
+// connection, returns its url.
+pub async fn start_ws_server<F, Fut>(on_connection: F) -> String
+where
+    F: Fn(usize, WebSocketStream<TcpStream>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::task::spawn(async move {
+        let mut index = 0;
+        while let Ok((stream, _)) = listener.accept().await {
+            let ws_stream = tokio_tungstenite::accept_async(stream).await.unwrap();
+            tokio::task::spawn(on_connection(index, ws_stream));
+            index += 1;
+        }
+    });
+    url
+}
+
+// Starts an HTTP server which responds `body` to every request, returns its url and
+// a receiver of requests.
+pub async fn start_http_server(body: String) -> (String, tokio::sync::mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (request_tx, request_rx) = tokio::sync::mpsc::channel(8);
+    tokio::task::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(stream.read_u8().await.unwrap());
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            let _ = request_tx
+                .send(String::from_utf8_lossy(&request).to_lowercase())
+                .await;
+        }
+    });
+    (url, request_rx)
+}
+
+pub async fn next_text(ws_stream: &mut WebSocketStream<TcpStream>) -> String {
+    loop {
+        match ws_stream.next().await.unwrap().unwrap() {
+            Message::Text(txt) => return txt,
+            _ => continue,
+        }
+    }
+}
+
+pub async fn run_client<C: WSClient + Send + Sync + 'static>(
+    ws_client: C,
+    topics: &[(String, String)],
+) -> Arc<C> {
+    let ws_client = Arc::new(ws_client);
+    ws_client.subscribe(topics).await;
+    let ws_client_clone = ws_client.clone();
+    tokio::task::spawn(async move { ws_client_clone.run().await });
+    ws_client
+}
@@ -46,7 +46,9 @@ mod huobi_spot {
     async fn subscribe_orderbook() {
         let (tx, rx) = std::sync::mpsc::channel();
         tokio::task::spawn(async move {
-            let ws_client = HuobiSpotWSClient::new(tx, Some("wss://api.huobi.pro/feed")).await;
+            let ws_client = HuobiSpotWSClient::new(tx, Some("wss://api.huobi.pro/feed"))
+                .await
+                .unwrap();
             ws_client
                 .subscribe_orderbook(&["btcusdt".to_string()])
                 .await;
@@ -212,7 +214,8 @@ mod huobi_linear_swap {
                 tx,
                 Some("wss://api.hbdm.com/linear-swap-notification"),
             )
-            .await;
+            .await
+            .unwrap();
             ws_client
                 .send(&[r#"{"topic":"public.BTC-USDT.funding_rate","op":"sub"}"#.to_string()])
                 .await;
@@ -234,7 +237,8 @@ mod huobi_linear_swap {
                 tx,
                 Some("wss://api.hbdm.com/linear-swap-notification"),
             )
-            .await;
+            .await
+            .unwrap();
             ws_client
                 .send(&[r#"{"topic":"public.*.funding_rate","op":"sub"}"#.to_string()])
                 .await;
@@ -322,7 +326,8 @@ mod huobi_inverse_swap {
         tokio::task::spawn(async move {
             let ws_client =
                 HuobiInverseSwapWSClient::new(tx, Some("wss://api.hbdm.com/swap-notification"))
-                    .await;
+                    .await
+                    .unwrap();
             ws_client
                 .send(&[r#"{"topic":"public.BTC-USD.funding_rate","op":"sub"}"#.to_string()])
                 .await;
@@ -342,7 +347,8 @@ mod huobi_inverse_swap {
         tokio::task::spawn(async move {
             let ws_client =
                 HuobiInverseSwapWSClient::new(tx, Some("wss://api.hbdm.com/swap-notification"))
-                    .await;
+                    .await
+                    .unwrap();
             ws_client
                 .send(&[r#"{"topic":"public.*.funding_rate","op":"sub"}"#.to_string()])
                 .await;
@@ -0,0 +1,334 @@
+mod common;
+
+use common::*;
+use crypto_market_type::MarketType;
+use crypto_ws_client::{
+    mock::{load_frames, Frame, MockServer},
+    BinanceSpotWSClient, BitmexWSClient, BybitInverseWSClient, DeribitWSClient, HuobiSpotWSClient,
+    KuCoinSpotWSClient, OkxWSClient, WSClient, WSClientOptions,
+};
+use tokio::sync::mpsc::Receiver;
+
+fn recorded_frames(exchange: &str) -> Vec<String> {
+    let path = format!(
+        "{}/tests/fixtures/{}.txt",
+        env!("CARGO_MANIFEST_DIR"),
+        exchange
+    );
+    load_frames(path)
+        .unwrap()
+        .into_iter()
+        .map(|frame| match frame {
+            Frame::Text(txt) => txt,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+// Starts a server which replays recorded frames of the exchange, encoded by `encode`.
+async fn start_server(exchange: &str, encode: fn(String) -> Frame) -> MockServer {
+    let frames = recorded_frames(exchange).into_iter().map(encode).collect();
+    MockServer::start_with_frames(exchange, frames)
+        .await
+        .unwrap()
+}
+
+async fn assert_replayed(exchange: &str, rx: &mut Receiver<String>) {
+    for expected in recorded_frames(exchange) {
+        let msg = tokio::time::timeout(TIMEOUT, rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(expected, msg);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn binance_spot() {
+    let server = start_server("binance", Frame::Text).await;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let ws_client = BinanceSpotWSClient::new_async(tx, Some(server.url()))
+        .await
+        .unwrap();
+    let ws_client = run_client(ws_client, &[]).await;
+    ws_client.subscribe_trade(&["BTCUSDT".to_string()]).await;
+    assert_replayed("binance", &mut rx).await;
+    assert_eq!(
+        vec![r#"{"id":9527,"method":"SUBSCRIBE","params":["btcusdt@aggTrade"]}"#],
+        server.received()
+    );
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn topics() {
+    let server = start_server("binance", Frame::Text).await;
+    let (tx, _rx) = tokio::sync::mpsc::channel(16);
+    let ws_client = BinanceSpotWSClient::new_async(tx, Some(server.url()))
+        .await
+        .unwrap();
+    ws_client
+        .subscribe_trade(&["BTCUSDT".to_string(), "ETHUSDT".to_string()])
+        .await;
+    assert_eq!(
+        vec![
+            ("aggTrade".to_string(), "BTCUSDT".to_string()),
+            ("aggTrade".to_string(), "ETHUSDT".to_string()),
+        ],
+        ws_client.topics()
+    );
+    ws_client
+        .unsubscribe(&[("aggTrade".to_string(), "BTCUSDT".to_string())])
+        .await;
+    assert_eq!(
+        vec![("aggTrade".to_string(), "ETHUSDT".to_string())],
+        ws_client.topics()
+    );
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn candlesticks() {
+    let server = start_server("binance", Frame::Text).await;
+    let (tx, _rx) = tokio::sync::mpsc::channel(16);
+    let ws_client = BinanceSpotWSClient::new_async(tx, Some(server.url()))
+        .await
+        .unwrap();
+    ws_client
+        .subscribe_candlestick(&[("BTCUSDT".to_string(), 60), ("BTCUSDT".to_string(), 300)])
+        .await;
+    ws_client
+        .unsubscribe_candlestick(&[("BTCUSDT".to_string(), 60)])
+        .await;
+    assert_eq!(vec![("BTCUSDT".to_string(), 300)], ws_client.candlesticks());
+    assert!(ws_client.topics().is_empty());
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bitmex() {
+    let server = start_server("bitmex", Frame::Text).await;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let ws_client = BitmexWSClient::new_async(tx, Some(server.url()))
+        .await
+        .unwrap();
+    let ws_client = run_client(ws_client, &[("trade".to_string(), "XBTUSD".to_string())]).await;
+    assert_replayed("bitmex", &mut rx).await;
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bybit_inverse() {
+    let server = start_server("bybit", Frame::Text).await;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let ws_client = BybitInverseWSClient::new_async(tx, Some(server.url()))
+        .await
+        .unwrap();
+    let ws_client = run_client(ws_client, &[("trade".to_string(), "BTCUSD".to_string())]).await;
+    assert_replayed("bybit", &mut rx).await;
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn deribit() {
+    let server = start_server("deribit", Frame::Text).await;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let ws_client = DeribitWSClient::new_async(tx, Some(server.url()))
+        .await
+        .unwrap();
+    let ws_client = run_client(
+        ws_client,
+        &[("trades".to_string(), "BTC-PERPETUAL.raw".to_string())],
+    )
+    .await;
+    assert_replayed("deribit", &mut rx).await;
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn huobi_spot_gzip() {
+    let server = start_server("huobi", Frame::Gzip).await;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let ws_client = HuobiSpotWSClient::new_async(tx, Some(server.url()))
+        .await
+        .unwrap();
+    let ws_client = run_client(
+        ws_client,
+        &[("trade.detail".to_string(), "btcusdt".to_string())],
+    )
+    .await;
+    assert_replayed("huobi", &mut rx).await;
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn kucoin_spot() {
+    let server = start_server("kucoin", Frame::Text).await;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let ws_client = KuCoinSpotWSClient::new_async(tx, Some(server.url()))
+        .await
+        .unwrap();
+    let ws_client = run_client(
+        ws_client,
+        &[("/market/match".to_string(), "BTC-USDT".to_string())],
+    )
+    .await;
+    assert_replayed("kucoin", &mut rx).await;
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn okx_deflate() {
+    let server = start_server("okx", Frame::Deflate).await;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let ws_client = OkxWSClient::new_async(tx, Some(server.url()))
+        .await
+        .unwrap();
+    let ws_client = run_client(ws_client, &[("trades".to_string(), "BTC-USDT".to_string())]).await;
+    assert_replayed("okx", &mut rx).await;
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconnect_after_disconnect() {
+    let server = start_server("okx", Frame::Text).await;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let ws_client = OkxWSClient::new_async(tx, Some(server.url()))
+        .await
+        .unwrap();
+    let ws_client = run_client(ws_client, &[("trades".to_string(), "BTC-USDT".to_string())]).await;
+    assert_replayed("okx", &mut rx).await;
+
+    server.inject(Frame::Disconnect);
+    // frames are replayed again after the client subscribes on the new connection
+    assert_replayed("okx", &mut rx).await;
+    assert_eq!(2, server.num_connections());
+    let received: Vec<String> = server
+        .received()
+        .into_iter()
+        .filter(|command| command != "ping")
+        .collect();
+    assert_eq!(2, received.len());
+    assert_eq!(received[0], received[1]);
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconnect_after_close_frame() {
+    let server = start_server("binance", Frame::Text).await;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let ws_client = BinanceSpotWSClient::new_async(tx, Some(server.url()))
+        .await
+        .unwrap();
+    let ws_client = run_client(
+        ws_client,
+        &[("aggTrade".to_string(), "BTCUSDT".to_string())],
+    )
+    .await;
+    assert_replayed("binance", &mut rx).await;
+
+    server.inject(Frame::Close(1001, "going away".to_string()));
+    assert_replayed("binance", &mut rx).await;
+    assert_eq!(2, server.num_connections());
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn options_builder() {
+    let server = start_server("binance", Frame::Text).await;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let options = WSClientOptions::builder()
+        .url(server.url())
+        .uplink_limit(
+            std::num::NonZeroU32::new(1).unwrap(),
+            std::time::Duration::from_millis(100),
+        )
+        .send_interval(std::time::Duration::ZERO)
+        .message_buffer(1)
+        .command_buffer(1)
+        .user_agent("crypto-ws-client-test")
+        .build();
+    // the url of options is used when the url argument is None
+    let ws_client = BinanceSpotWSClient::new_with_options(tx, None, options)
+        .await
+        .unwrap();
+    let ws_client = run_client(ws_client, &[]).await;
+    ws_client.subscribe_trade(&["BTCUSDT".to_string()]).await;
+    assert_replayed("binance", &mut rx).await;
+    assert_eq!(1, server.num_connections());
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn metrics() {
+    let server = start_server("okx", Frame::Deflate).await;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let options = WSClientOptions {
+        market_type: Some(MarketType::Spot),
+        ..Default::default()
+    };
+    let ws_client = OkxWSClient::new_with_options(tx, Some(server.url()), options)
+        .await
+        .unwrap();
+    let ws_client = run_client(ws_client, &[("trades".to_string(), "BTC-USDT".to_string())]).await;
+    assert_replayed("okx", &mut rx).await;
+    // rates are measured over complete windows of one second
+    tokio::time::sleep(std::time::Duration::from_millis(2500)).await;
+
+    let snapshots = ws_client.metrics();
+    assert_eq!(1, snapshots.len());
+    let snapshot = &snapshots[0];
+    assert_eq!("okx", snapshot.exchange);
+    assert_eq!(MarketType::Spot, snapshot.market_type);
+    // pong, the subscribe response and two trades
+    assert_eq!(4, snapshot.messages);
+    assert!(snapshot.bytes > 0);
+    assert!(snapshot.ping_rtt.is_some());
+    assert_eq!(0, snapshot.reconnects);
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shutdown() {
+    let server = start_server("okx", Frame::Text).await;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let ws_client = OkxWSClient::new_async(tx, Some(server.url()))
+        .await
+        .unwrap();
+    let ws_client = std::sync::Arc::new(ws_client);
+    ws_client
+        .subscribe(&[("trades".to_string(), "BTC-USDT".to_string())])
+        .await;
+    let ws_client_clone = ws_client.clone();
+    let handle = tokio::task::spawn(async move { ws_client_clone.run().await });
+    assert_replayed("okx", &mut rx).await;
+
+    tokio::time::timeout(TIMEOUT, ws_client.shutdown())
+        .await
+        .unwrap();
+    // run() has returned
+    tokio::time::timeout(std::time::Duration::from_millis(100), handle)
+        .await
+        .unwrap()
+        .unwrap();
+    // the client neither reconnects nor keeps tx alive
+    drop(ws_client);
+    assert_eq!(None, rx.recv().await);
+    assert_eq!(1, server.num_connections());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shutdown_before_run() {
+    let server = MockServer::start("okx").await.unwrap();
+    let (tx, _rx) = tokio::sync::mpsc::channel(16);
+    let ws_client = OkxWSClient::new_async(tx, Some(server.url()))
+        .await
+        .unwrap();
+    tokio::time::timeout(TIMEOUT, ws_client.shutdown())
+        .await
+        .unwrap();
+    // returns immediately
+    tokio::time::timeout(std::time::Duration::from_millis(100), ws_client.run())
+        .await
+        .unwrap();
+}
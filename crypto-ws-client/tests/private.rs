@@ -0,0 +1,276 @@
+mod common;
+
+use common::*;
+use crypto_ws_client::{
+    BinanceSpotWSClient, BybitInverseWSClient, Credentials, DeribitWSClient, KuCoinSpotWSClient,
+    OkxWSClient, WSClient, WSClientOptions, WsError,
+};
+use futures_util::SinkExt;
+use tokio_tungstenite::tungstenite::Message;
+
+fn options_with(credentials: Credentials) -> WSClientOptions {
+    WSClientOptions {
+        credentials: Some(credentials),
+        ..Default::default()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn okx_login_again_after_reconnect() {
+    let url = start_ws_server(|index, mut ws_stream| async move {
+        let login = next_text(&mut ws_stream).await;
+        assert!(login.starts_with(r#"{"op":"login","args":[{"apiKey":"key","#));
+        ws_stream
+            .send(Message::Text(
+                r#"{"event":"login","code":"0","msg":""}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        let subscribe = next_text(&mut ws_stream).await;
+        assert_eq!(
+            r#"{"op":"subscribe","args":[{"channel":"orders","instType":"ANY"}]}"#,
+            subscribe
+        );
+        let order = format!(
+            r#"{{"arg":{{"channel":"orders","instType":"ANY"}},"data":[{{"ordId":"{}"}}]}}"#,
+            index
+        );
+        ws_stream.send(Message::Text(order)).await.unwrap();
+        if index == 0 {
+            // force the client to reconnect
+            ws_stream.close(None).await.unwrap();
+        } else {
+            tokio::time::sleep(TIMEOUT).await;
+        }
+    })
+    .await;
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let credentials = Credentials::new("key", "secret").with_passphrase("passphrase");
+    let ws_client = OkxWSClient::new_with_options(tx, Some(&url), options_with(credentials))
+        .await
+        .unwrap();
+    let ws_client = run_client(ws_client, &[("orders".to_string(), "ANY".to_string())]).await;
+
+    for index in 0..2 {
+        let msg = tokio::time::timeout(TIMEOUT, rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(msg.contains(&format!(r#""ordId":"{}""#, index)));
+    }
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn okx_login_rejected() {
+    let url = start_ws_server(|_, mut ws_stream| async move {
+        let _ = next_text(&mut ws_stream).await;
+        ws_stream
+            .send(Message::Text(
+                r#"{"event":"error","code":"60009","msg":"Login failed."}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        tokio::time::sleep(TIMEOUT).await;
+    })
+    .await;
+
+    let (tx, _rx) = tokio::sync::mpsc::channel(16);
+    let credentials = Credentials::new("key", "secret").with_passphrase("passphrase");
+    let result = OkxWSClient::new_with_options(tx, Some(&url), options_with(credentials)).await;
+    assert!(matches!(result, Err(WsError::Auth(_))));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn okx_requires_passphrase() {
+    let (tx, _rx) = tokio::sync::mpsc::channel(16);
+    let credentials = Credentials::new("key", "secret");
+    let result = OkxWSClient::new_with_options(tx, None, options_with(credentials)).await;
+    assert!(matches!(result, Err(WsError::Auth(_))));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bybit_auth() {
+    let url = start_ws_server(|_, mut ws_stream| async move {
+        let auth = next_text(&mut ws_stream).await;
+        assert!(auth.starts_with(r#"{"op":"auth","args":["key","#));
+        ws_stream
+            .send(Message::Text(
+                r#"{"success":true,"ret_msg":"","conn_id":"c","request":{"op":"auth","args":[]}}"#
+                    .to_string(),
+            ))
+            .await
+            .unwrap();
+        let subscribe = next_text(&mut ws_stream).await;
+        assert_eq!(r#"{"op":"subscribe","args":["order"]}"#, subscribe);
+        ws_stream
+            .send(Message::Text(
+                r#"{"topic":"order","data":[{"order_id":"1"}]}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        tokio::time::sleep(TIMEOUT).await;
+    })
+    .await;
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let credentials = Credentials::new("key", "secret");
+    let ws_client =
+        BybitInverseWSClient::new_with_options(tx, Some(&url), options_with(credentials))
+            .await
+            .unwrap();
+    let ws_client = run_client(ws_client, &[("order".to_string(), "".to_string())]).await;
+
+    let msg = tokio::time::timeout(TIMEOUT, rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(msg.starts_with(r#"{"topic":"order""#));
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn deribit_auth() {
+    let url = start_ws_server(|_, mut ws_stream| async move {
+        let auth = next_text(&mut ws_stream).await;
+        assert!(auth.contains(r#""method": "public/auth""#));
+        assert!(auth.contains(r#""client_id": "key""#));
+        ws_stream
+            .send(Message::Text(
+                r#"{"jsonrpc":"2.0","id":9527,"result":{"access_token":"token"}}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        let subscribe = next_text(&mut ws_stream).await;
+        assert_eq!(
+            r#"{"method": "private/subscribe", "params": {"channels": ["user.orders.BTC-PERPETUAL.raw"]}}"#,
+            subscribe
+        );
+        ws_stream
+            .send(Message::Text(
+                r#"{"jsonrpc":"2.0","method":"subscription","params":{"channel":"user.orders.BTC-PERPETUAL.raw","data":{"order_id":"1"}}}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        tokio::time::sleep(TIMEOUT).await;
+    })
+    .await;
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let credentials = Credentials::new("key", "secret");
+    let ws_client = DeribitWSClient::new_with_options(tx, Some(&url), options_with(credentials))
+        .await
+        .unwrap();
+    let topics = vec![(
+        "user.orders.SYMBOL.raw".to_string(),
+        "BTC-PERPETUAL".to_string(),
+    )];
+    let ws_client = run_client(ws_client, &topics).await;
+
+    let msg = tokio::time::timeout(TIMEOUT, rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(msg.contains("user.orders.BTC-PERPETUAL.raw"));
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn binance_user_data_stream() {
+    let (rest_url, mut requests) = start_http_server(r#"{"listenKey":"abc"}"#.to_string()).await;
+    let url = start_ws_server(|index, mut ws_stream| async move {
+        let subscribe = next_text(&mut ws_stream).await;
+        assert_eq!(
+            r#"{"id":9526,"method":"SUBSCRIBE","params":["abc"]}"#,
+            subscribe
+        );
+        ws_stream
+            .send(Message::Text(r#"{"result":null,"id":9526}"#.to_string()))
+            .await
+            .unwrap();
+        let event = if index == 0 {
+            // the client reconnects with a new listen key
+            r#"{"stream":"abc","data":{"e":"listenKeyExpired","E":1576653824250}}"#
+        } else {
+            r#"{"stream":"abc","data":{"e":"executionReport","s":"BTCUSDT"}}"#
+        };
+        ws_stream
+            .send(Message::Text(event.to_string()))
+            .await
+            .unwrap();
+        tokio::time::sleep(TIMEOUT).await;
+    })
+    .await;
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let options = WSClientOptions {
+        rest_url: Some(rest_url),
+        ..options_with(Credentials::new("key", "secret"))
+    };
+    let ws_client = BinanceSpotWSClient::new_with_options(tx, Some(&url), options)
+        .await
+        .unwrap();
+    let request = requests.recv().await.unwrap();
+    assert!(request.starts_with("post /api/v3/userdatastream "));
+    assert!(request.contains("x-mbx-apikey: key"));
+
+    let ws_client = run_client(ws_client, &[]).await;
+    let msg = tokio::time::timeout(TIMEOUT, rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(msg.contains("executionReport"));
+    let request = requests.recv().await.unwrap();
+    assert!(request.starts_with("post /api/v3/userdatastream "));
+    ws_client.close();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn kucoin_private_token() {
+    let url = start_ws_server(|_, mut ws_stream| async move {
+        ws_stream
+            .send(Message::Text(
+                r#"{"id":"1","type":"welcome"}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        let subscribe = next_text(&mut ws_stream).await;
+        assert!(subscribe.contains(r#""topic":"/spotMarket/tradeOrders","privateChannel":true"#));
+        ws_stream
+            .send(Message::Text(
+                r#"{"type":"message","topic":"/spotMarket/tradeOrders","subject":"orderChange","data":{"orderId":"1"}}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        tokio::time::sleep(TIMEOUT).await;
+    })
+    .await;
+    let token = format!(
+        r#"{{"code":"200000","data":{{"token":"t","instanceServers":[{{"endpoint":"{}","protocol":"websocket","encrypt":false,"pingInterval":18000,"pingTimeout":10000}}]}}}}"#,
+        url
+    );
+    let (rest_url, mut requests) = start_http_server(token).await;
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let options = WSClientOptions {
+        rest_url: Some(rest_url),
+        ..options_with(Credentials::new("key", "secret").with_passphrase("passphrase"))
+    };
+    let ws_client = KuCoinSpotWSClient::new_with_options(tx, None, options)
+        .await
+        .unwrap();
+    let request = requests.recv().await.unwrap();
+    assert!(request.starts_with("post /api/v1/bullet-private "));
+    assert!(request.contains("kc-api-sign: "));
+    assert!(request.contains("kc-api-key-version: 2"));
+
+    let topics = vec![("/spotMarket/tradeOrders".to_string(), "".to_string())];
+    let ws_client = run_client(ws_client, &topics).await;
+    let msg = tokio::time::timeout(TIMEOUT, rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(msg.contains("orderChange"));
+    ws_client.close();
+}
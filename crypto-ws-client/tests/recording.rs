@@ -0,0 +1,80 @@
+mod common;
+
+use common::*;
+use crypto_ws_client::{
+    mock::{Frame, MockServer},
+    HuobiSpotWSClient, Recorder, Replayer, WSClient, WSClientOptions,
+};
+
+const FRAMES: [&str; 2] = [
+    r#"{"ch":"market.btcusdt.trade.detail","ts":1660000000123,"tick":{"id":160000000000,"ts":1660000000120,"data":[{"id":1600000000000000000000,"ts":1660000000120,"tradeId":100000000001,"amount":0.0042,"price":23950.01,"direction":"buy"}]}}"#,
+    r#"{"ch":"market.btcusdt.trade.detail","ts":1660000000456,"tick":{"id":160000000001,"ts":1660000000450,"data":[{"id":1600000000000000000001,"ts":1660000000450,"tradeId":100000000002,"amount":0.015,"price":23950.02,"direction":"sell"}]}}"#,
+];
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replay_produces_identical_output() {
+    let path = std::env::temp_dir().join(format!("huobi-{}.rec", rand::random::<u64>()));
+    let frames = FRAMES
+        .iter()
+        .map(|frame| Frame::Gzip(frame.to_string()))
+        .collect();
+    let server = MockServer::start_with_frames("huobi", frames)
+        .await
+        .unwrap();
+
+    // record two connections, the first one ends with a close frame
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let options = WSClientOptions {
+        recorder: Some(Recorder::create(&path).unwrap()),
+        ..Default::default()
+    };
+    let ws_client = HuobiSpotWSClient::new_with_options(tx, Some(server.url()), options)
+        .await
+        .unwrap();
+    let ws_client = run_client(
+        ws_client,
+        &[("trade.detail".to_string(), "btcusdt".to_string())],
+    )
+    .await;
+    let mut live = Vec::new();
+    for i in 0..FRAMES.len() * 2 {
+        if i == FRAMES.len() {
+            server.inject(Frame::Close(1001, "going away".to_string()));
+        }
+        live.push(
+            tokio::time::timeout(TIMEOUT, rx.recv())
+                .await
+                .unwrap()
+                .unwrap(),
+        );
+    }
+    ws_client.close();
+    assert_eq!(
+        FRAMES
+            .iter()
+            .chain(FRAMES.iter())
+            .map(|frame| frame.to_string())
+            .collect::<Vec<String>>(),
+        live
+    );
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let options = WSClientOptions {
+        replayer: Some(Replayer::open(&path).unwrap()),
+        ..Default::default()
+    };
+    let ws_client = HuobiSpotWSClient::new_with_options(tx, None, options)
+        .await
+        .unwrap();
+    // returns after replaying all connections
+    tokio::time::timeout(TIMEOUT, ws_client.run())
+        .await
+        .unwrap();
+    drop(ws_client);
+    let mut replayed = Vec::new();
+    while let Some(msg) = rx.recv().await {
+        replayed.push(msg);
+    }
+    assert_eq!(live, replayed);
+    std::fs::remove_file(path).unwrap();
+}
@@ -2,7 +2,7 @@ macro_rules! gen_test_code {
     ($client:ident, $func_name:ident, $symbols:expr) => {
         let (tx, rx) = std::sync::mpsc::channel();
         tokio::task::spawn(async move {
-            let ws_client = $client::new(tx, None).await;
+            let ws_client = $client::new(tx, None).await.unwrap();
             ws_client.$func_name($symbols).await;
             // run for 60 seconds at most
             let _ = tokio::time::timeout(std::time::Duration::from_secs(60), ws_client.run()).await;
@@ -23,7 +23,7 @@ macro_rules! gen_test_subscribe_candlestick {
     ($client:ident, $symbol_interval_list:expr) => {
         let (tx, rx) = std::sync::mpsc::channel();
         tokio::task::spawn(async move {
-            let ws_client = $client::new(tx, None).await;
+            let ws_client = $client::new(tx, None).await.unwrap();
             ws_client.subscribe_candlestick($symbol_interval_list).await;
             // run for 60 seconds at most
             let _ = tokio::time::timeout(std::time::Duration::from_secs(60), ws_client.run()).await;